@@ -0,0 +1,126 @@
+use crate::lint;
+
+/// Longest slug of a branch name, cut at a word boundary
+const MAX_SLUG_LENGTH: usize = 50;
+
+/// Slug used when the summary has no usable words
+const FALLBACK_SLUG: &str = "changes";
+
+/// Kebab-case slug of a summary, e.g. `add-oauth-login`
+pub fn slugify(text: &str) -> String {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect();
+
+    let mut slug = String::new();
+    for word in words {
+        let separator = usize::from(!slug.is_empty());
+        if !slug.is_empty() && slug.len() + separator + word.len() > MAX_SLUG_LENGTH {
+            break;
+        }
+        if separator == 1 {
+            slug.push('-');
+        }
+        slug.push_str(&word[..word.len().min(MAX_SLUG_LENGTH)]);
+    }
+    slug
+}
+
+/// Branch name for a `type: summary` header, e.g. `feat/PROJ-12-add-login`
+///
+/// The type is only used as a prefix with `with_type` and when the summary is
+/// a conventional commit header. The ticket keeps its case.
+pub fn branch_name(summary: &str, with_type: bool, ticket: Option<&str>) -> String {
+    let header = summary.lines().next().unwrap_or_default().trim();
+    let (commit_type, subject) = match lint::parse_header(header) {
+        Some(parsed) => (Some(parsed.commit_type), parsed.subject),
+        None => (None, header),
+    };
+
+    let mut parts = Vec::new();
+    if let Some(ticket) = ticket {
+        let ticket: String = ticket
+            .trim()
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+            .collect();
+        if !ticket.is_empty() {
+            parts.push(ticket);
+        }
+    }
+    let slug = slugify(subject);
+    if !slug.is_empty() || parts.is_empty() {
+        parts.push(if slug.is_empty() {
+            FALLBACK_SLUG.to_string()
+        } else {
+            slug
+        });
+    }
+
+    let name = parts.join("-");
+    match commit_type.filter(|_| with_type) {
+        Some(commit_type) => format!("{}/{name}", commit_type.to_ascii_lowercase()),
+        None => name,
+    }
+}
+
+/// `name`, or `name-2`, `name-3`, ... when it is taken
+pub fn unique_name(name: &str, exists: impl Fn(&str) -> bool) -> String {
+    if !exists(name) {
+        return name.to_string();
+    }
+    (2..)
+        .map(|suffix| format!("{name}-{suffix}"))
+        .find(|candidate| !exists(candidate))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Add OAuth login"), "add-oauth-login");
+        assert_eq!(slugify("  fix: the `--all` flag!  "), "fix-the-all-flag");
+        assert_eq!(slugify("Über café"), "ber-caf");
+        assert_eq!(slugify("..."), "");
+
+        let long = slugify(&"word ".repeat(20));
+        assert!(long.len() <= MAX_SLUG_LENGTH);
+        assert!(long.ends_with("word"));
+    }
+
+    #[test]
+    fn test_branch_name() {
+        assert_eq!(
+            branch_name("feat(auth): add OAuth login", false, None),
+            "add-oauth-login"
+        );
+        assert_eq!(
+            branch_name("feat(auth): add OAuth login\n\nBody", true, Some("PROJ-12")),
+            "feat/PROJ-12-add-oauth-login"
+        );
+        assert_eq!(
+            branch_name("Update the readme", true, None),
+            "update-the-readme"
+        );
+        assert_eq!(branch_name("fix: ...", false, Some(" #42 ")), "42");
+        assert_eq!(branch_name("", false, None), "changes");
+    }
+
+    #[test]
+    fn test_unique_name() {
+        let taken = ["add-login", "add-login-2"];
+        assert_eq!(
+            unique_name("add-login", |name| taken.contains(&name)),
+            "add-login-3"
+        );
+        assert_eq!(
+            unique_name("fix-typo", |name| taken.contains(&name)),
+            "fix-typo"
+        );
+    }
+}
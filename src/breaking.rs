@@ -0,0 +1,426 @@
+use crate::git;
+use crate::lint;
+use crate::symbols::{self, Declaration, FileSides};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+/// Directory names holding database migrations, e.g. `db/migrate` or `app/migrations`
+const MIGRATION_DIRS: &[&str] = &["migrations", "migrate"];
+
+/// Statements of SQL, Rails and Django migrations that drop or change
+/// existing schema, lowercase
+const DESTRUCTIVE_MIGRATION_STATEMENTS: &[&str] = &[
+    "drop table",
+    "drop column",
+    "drop view",
+    "rename column",
+    "rename to",
+    "alter column",
+    "remove_column",
+    "drop_table",
+    "rename_column",
+    "rename_table",
+    "change_column",
+    "removefield",
+    "deletemodel",
+    "renamefield",
+    "renamemodel",
+    "alterfield",
+];
+
+/// Characters of a migration statement shown in the footer
+const MAX_STATEMENT_LENGTH: usize = 60;
+
+/// How a public item changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiChangeKind {
+    Removed,
+    SignatureChanged,
+
+    /// A migration drops or alters existing schema, `item` is the statement
+    SchemaChanged,
+}
+
+/// A public item of a library crate that was removed or changed incompatibly
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiChange {
+    pub kind: ApiChangeKind,
+    pub item: String,
+    pub file: String,
+}
+
+impl fmt::Display for ApiChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ApiChangeKind::Removed => write!(f, "removed `{}` ({})", self.item, self.file),
+            ApiChangeKind::SignatureChanged => {
+                write!(
+                    f,
+                    "changed the signature of `{}` ({})",
+                    self.item, self.file
+                )
+            }
+            ApiChangeKind::SchemaChanged => {
+                write!(f, "changed the schema with `{}` ({})", self.item, self.file)
+            }
+        }
+    }
+}
+
+/// The items of a Rust file declared plain `pub`
+///
+/// Restricted visibility such as `pub(crate)` is not public API.
+fn public_items(path: &str, source: &str) -> Vec<Declaration> {
+    symbols::declarations(path, source)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|declaration| declaration.visibility.as_deref() == Some("pub"))
+        .collect()
+}
+
+/// Detect removed and signature-changed public items in the Rust files of a diff
+///
+/// Both sides of each file are parsed, see [`FileSides::read`] for where they
+/// come from with `blob`. Only files accepted by `is_library_file` are
+/// considered, so binaries and tests can change freely. An item removed from
+/// one file and added to another was moved, not removed.
+pub fn detect_api_changes(
+    diff: &str,
+    is_library_file: impl Fn(&str) -> bool,
+    blob: impl Fn(&str, &str) -> Option<String>,
+) -> Vec<ApiChange> {
+    let mut removed: Vec<(Declaration, String)> = Vec::new();
+    let mut added: BTreeMap<String, Vec<(String, String)>> = BTreeMap::new();
+
+    for file in git::split_diff_by_file(diff) {
+        if !(file.path.ends_with(".rs") && is_library_file(&file.path)) {
+            continue;
+        }
+
+        let sides = FileSides::read(&file, &blob);
+        let old_path = sides.old_path.as_deref().unwrap_or(&file.path);
+        for declaration in public_items(&file.path, &sides.new_source) {
+            added
+                .entry(declaration.key())
+                .or_default()
+                .push((file.path.clone(), declaration.signature));
+        }
+        for declaration in public_items(old_path, &sides.old_source) {
+            removed.push((declaration, file.path.clone()));
+        }
+    }
+
+    removed
+        .into_iter()
+        .filter_map(|(declaration, file)| {
+            let readded = added.get(&declaration.key());
+            // Compare with the same file first, another file means the item moved
+            let same_file = readded
+                .and_then(|entries| entries.iter().find(|(added_file, _)| *added_file == file));
+            let kind = match (same_file, readded) {
+                (Some((_, new_signature)), _) if *new_signature != declaration.signature => {
+                    ApiChangeKind::SignatureChanged
+                }
+                (None, None) => ApiChangeKind::Removed,
+                _ => return None,
+            };
+            Some(ApiChange {
+                kind,
+                item: declaration.name,
+                file,
+            })
+        })
+        .collect()
+}
+
+/// Detect migrations in a diff that drop, rename or alter existing schema
+///
+/// Only added lines of files under a `migrations` or `migrate` directory are
+/// considered, so creating tables and adding columns stays compatible.
+pub fn detect_schema_changes(diff: &str) -> Vec<ApiChange> {
+    let mut changes = Vec::new();
+
+    let mut current_file: Option<String> = None;
+    for line in diff.lines() {
+        if let Some(header) = line.strip_prefix("diff --git ") {
+            current_file = header
+                .split_once(" b/")
+                .map(|(_, path)| path.to_string())
+                .filter(|path| {
+                    path.split('/')
+                        .rev()
+                        .skip(1)
+                        .any(|dir| MIGRATION_DIRS.contains(&dir))
+                });
+            continue;
+        }
+        let Some(file) = &current_file else {
+            continue;
+        };
+        if line.starts_with("+++") {
+            continue;
+        }
+
+        let Some(statement) = line.strip_prefix('+').map(str::trim) else {
+            continue;
+        };
+        let lowercase = statement.to_lowercase();
+        if DESTRUCTIVE_MIGRATION_STATEMENTS
+            .iter()
+            .any(|destructive| lowercase.contains(destructive))
+        {
+            let statement = statement.trim_end_matches([';', ',']);
+            let item = match statement.char_indices().nth(MAX_STATEMENT_LENGTH) {
+                Some((end, _)) => format!("{}...", &statement[..end]),
+                None => statement.to_string(),
+            };
+            changes.push(ApiChange {
+                kind: ApiChangeKind::SchemaChanged,
+                item,
+                file: file.clone(),
+            });
+        }
+    }
+
+    changes
+}
+
+/// Whether a Rust file belongs to the public source of a library crate
+///
+/// The nearest `Cargo.toml` above the file must have a `src/lib.rs` next to
+/// it, and binaries, tests, examples, benchmarks and build scripts are excluded.
+pub fn is_library_file(repo_root: &Path, file: &str) -> bool {
+    let parts: Vec<&str> = file.split('/').collect();
+    if parts
+        .iter()
+        .any(|part| ["bin", "tests", "examples", "benches"].contains(part))
+        || file.ends_with("main.rs")
+        || file.ends_with("build.rs")
+    {
+        return false;
+    }
+
+    (0..parts.len())
+        .rev()
+        .map(|depth| repo_root.join(parts[..depth].join("/")))
+        .find(|dir| dir.join("Cargo.toml").is_file())
+        .is_some_and(|crate_dir| crate_dir.join("src").join("lib.rs").is_file())
+}
+
+/// Whether a message already marks a breaking change, with `!` or a footer
+pub fn has_breaking_marker(message: &str) -> bool {
+    let header = message.lines().next().unwrap_or_default();
+    let bang_header = header
+        .split_once(':')
+        .is_some_and(|(prefix, _)| prefix.ends_with('!'));
+
+    bang_header
+        || message.lines().any(|line| {
+            line.starts_with("BREAKING CHANGE:") || line.starts_with("BREAKING-CHANGE:")
+        })
+}
+
+/// A `BREAKING CHANGE:` footer describing the changes
+pub fn breaking_footer(changes: &[ApiChange]) -> String {
+    let descriptions: Vec<String> = changes.iter().map(ApiChange::to_string).collect();
+    format!("BREAKING CHANGE: {}", descriptions.join("; "))
+}
+
+/// Mark a message as breaking: `!` after the type and scope of a
+/// conventional header, and a `BREAKING CHANGE:` footer describing the changes
+pub fn mark_breaking(message: &str, changes: &[ApiChange]) -> String {
+    let message = message.trim();
+    let (header, body) = message.split_once('\n').unwrap_or((message, ""));
+    let header = match lint::parse_header(header) {
+        Some(parsed) if !parsed.breaking => {
+            let (prefix, subject) = header.split_once(": ").unwrap_or_default();
+            format!("{prefix}!: {subject}")
+        }
+        _ => header.to_string(),
+    };
+    let message = match body {
+        "" => header,
+        body => format!("{header}\n{body}"),
+    };
+    lint::append_footer(&message, &breaking_footer(changes))
+}
+
+/// Instructions for the model about changes that look breaking, asking it to
+/// mark the message when they are
+pub fn breaking_hint(changes: &[ApiChange]) -> Option<String> {
+    if changes.is_empty() {
+        return None;
+    }
+    let descriptions: Vec<String> = changes.iter().map(|change| format!("- {change}")).collect();
+    Some(format!(
+        "These changes look breaking:\n{}\n\
+         If they break existing users, add `!` after the type and scope \
+         (e.g. `feat(api)!: ...`) and end the message with a `BREAKING CHANGE:` \
+         footer explaining what breaks and how to migrate.",
+        descriptions.join("\n")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lint;
+    use tempfile::TempDir;
+
+    const DIFF: &str = "diff --git a/crates/core/src/config.rs b/crates/core/src/config.rs\n\
+        --- a/crates/core/src/config.rs\n\
+        +++ b/crates/core/src/config.rs\n\
+        @@ -1,5 +1,2 @@\n\
+        -pub fn load(path: &Path) -> Result<Config> {}\n\
+        -pub struct Legacy;\n\
+        -pub async fn save(&self) -> Result<()> {}\n\
+        -pub(crate) fn helper() {}\n\
+        -pub fn moved() {}\n\
+        +pub fn load(path: &Path, strict: bool) -> Result<Config> {}\n\
+        +pub async fn save(&self)  -> Result<()> {}\n\
+        diff --git a/crates/core/src/util.rs b/crates/core/src/util.rs\n\
+        --- /dev/null\n\
+        +++ b/crates/core/src/util.rs\n\
+        @@ -0,0 +1 @@\n\
+        +pub fn moved() {}\n\
+        diff --git a/crates/cli/src/main.rs b/crates/cli/src/main.rs\n\
+        --- a/crates/cli/src/main.rs\n\
+        +++ /dev/null\n\
+        @@ -1 +0,0 @@\n\
+        -pub fn run() {}\n";
+
+    // Every side is pieced together from the hunks
+    fn no_blob(_id: &str, _path: &str) -> Option<String> {
+        None
+    }
+
+    #[test]
+    fn test_public_items() {
+        let source = "pub const unsafe fn get(&self) -> u8 {}\n\
+            pub extern \"C\" fn init() {}\n\
+            pub struct Config<T> {\n    value: T,\n}\n\
+            pub const MAX: usize = 3;\n\
+            pub(crate) fn helper() {}\n\
+            pub use crate::config::Config;\n\
+            fn private() {}\n";
+        let items = public_items("src/lib.rs", source);
+        let keys: Vec<String> = items.iter().map(Declaration::key).collect();
+        assert_eq!(
+            keys,
+            vec!["fn get", "fn init", "struct Config", "const MAX"]
+        );
+        assert_eq!(items[0].signature, "pub const unsafe fn get(&self) -> u8");
+        assert_eq!(items[2].signature, "pub struct Config<T>");
+    }
+
+    #[test]
+    fn test_detect_api_changes() {
+        let changes = detect_api_changes(DIFF, |file| !file.ends_with("main.rs"), no_blob);
+
+        assert_eq!(
+            changes,
+            vec![
+                ApiChange {
+                    kind: ApiChangeKind::SignatureChanged,
+                    item: "load".to_string(),
+                    file: "crates/core/src/config.rs".to_string(),
+                },
+                ApiChange {
+                    kind: ApiChangeKind::Removed,
+                    item: "Legacy".to_string(),
+                    file: "crates/core/src/config.rs".to_string(),
+                },
+            ]
+        );
+        assert!(detect_api_changes(DIFF, |_| false, no_blob).is_empty());
+    }
+
+    #[test]
+    fn test_detect_schema_changes() {
+        let diff =
+            "diff --git a/db/migrate/20240101_drop_email.rb b/db/migrate/20240101_drop_email.rb\n\
+            +++ b/db/migrate/20240101_drop_email.rb\n\
+            +    remove_column :users, :email\n\
+            +    add_column :users, :login, :string\n\
+            diff --git a/migrations/0003.sql b/migrations/0003.sql\n\
+            +CREATE TABLE audit (id INT);\n\
+            +ALTER TABLE users DROP COLUMN legacy_id;\n\
+            diff --git a/src/drop_table.sql b/src/drop_table.sql\n\
+            +DROP TABLE sessions;\n";
+
+        assert_eq!(
+            detect_schema_changes(diff),
+            vec![
+                ApiChange {
+                    kind: ApiChangeKind::SchemaChanged,
+                    item: "remove_column :users, :email".to_string(),
+                    file: "db/migrate/20240101_drop_email.rb".to_string(),
+                },
+                ApiChange {
+                    kind: ApiChangeKind::SchemaChanged,
+                    item: "ALTER TABLE users DROP COLUMN legacy_id".to_string(),
+                    file: "migrations/0003.sql".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mark_breaking() {
+        let changes = detect_api_changes(DIFF, |file| file.ends_with("main.rs"), no_blob);
+        assert_eq!(
+            mark_breaking("feat(cli): drop run\n\nUse start instead.\n", &changes),
+            "feat(cli)!: drop run\n\nUse start instead.\n\n\
+             BREAKING CHANGE: removed `run` (crates/cli/src/main.rs)"
+        );
+        // Headers that aren't conventional only get the footer
+        assert_eq!(
+            mark_breaking("Drop run", &changes),
+            "Drop run\n\nBREAKING CHANGE: removed `run` (crates/cli/src/main.rs)"
+        );
+
+        assert!(breaking_hint(&[]).is_none());
+        let hint = breaking_hint(&changes).unwrap();
+        assert!(hint.contains("- removed `run` (crates/cli/src/main.rs)\n"));
+    }
+
+    #[test]
+    fn test_is_library_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for dir in ["crates/core/src", "crates/cli/src"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        std::fs::write(root.join("crates/core/Cargo.toml"), "").unwrap();
+        std::fs::write(root.join("crates/core/src/lib.rs"), "").unwrap();
+        std::fs::write(root.join("crates/cli/Cargo.toml"), "").unwrap();
+
+        assert!(is_library_file(root, "crates/core/src/config.rs"));
+        assert!(is_library_file(root, "crates/core/src/lib.rs"));
+        assert!(!is_library_file(root, "crates/core/src/bin/tool.rs"));
+        assert!(!is_library_file(root, "crates/core/tests/api.rs"));
+        // A binary-only crate has no public API
+        assert!(!is_library_file(root, "crates/cli/src/commands.rs"));
+    }
+
+    #[test]
+    fn test_breaking_marker_and_footer() {
+        assert!(has_breaking_marker("feat(api)!: drop v1"));
+        assert!(has_breaking_marker(
+            "feat: drop v1\n\nBREAKING CHANGE: v1 is gone"
+        ));
+        assert!(!has_breaking_marker(
+            "feat: add v2\n\nMentions breaking changes"
+        ));
+
+        let changes = detect_api_changes(DIFF, |_| true, no_blob);
+        let message = lint::append_footer("feat: add strict loading\n", &breaking_footer(&changes));
+        assert_eq!(
+            message,
+            "feat: add strict loading\n\n\
+             BREAKING CHANGE: changed the signature of `load` (crates/core/src/config.rs); \
+             removed `Legacy` (crates/core/src/config.rs); removed `run` (crates/cli/src/main.rs)"
+        );
+    }
+}
@@ -0,0 +1,330 @@
+use crate::breaking;
+use crate::lint;
+use regex::Regex;
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Manifests whose version `aic bump` updates, in the order they are checked
+pub const MANIFESTS: &[&str] = &["Cargo.toml", "package.json", "pyproject.toml"];
+
+/// Part of a semantic version a release increments
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Patch,
+    Minor,
+    Major,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Patch => write!(f, "patch"),
+            Self::Minor => write!(f, "minor"),
+            Self::Major => write!(f, "major"),
+        }
+    }
+}
+
+/// Release level a commit message calls for, `None` for changes users don't see
+///
+/// Breaking changes call for a major release, features for a minor one and
+/// fixes, performance improvements and reverts for a patch. Messages that are
+/// not conventional commits count as patches, so no change goes unreleased.
+pub fn level_for(message: &str) -> Option<Level> {
+    if breaking::has_breaking_marker(message) {
+        return Some(Level::Major);
+    }
+
+    let header = message.lines().next().unwrap_or_default().trim();
+    match lint::parse_header(header) {
+        Some(parsed) => match parsed.commit_type {
+            "feat" => Some(Level::Minor),
+            "fix" | "perf" | "revert" | "security" => Some(Level::Patch),
+            _ => None,
+        },
+        None => Some(Level::Patch),
+    }
+}
+
+/// A `major.minor.patch` version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    /// Parse `1.2.3` or a tag like `v1.2.3`, dropping pre-release and build suffixes
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let text = text.strip_prefix('v').unwrap_or(text);
+        let core = text.split(['-', '+']).next()?;
+        let mut parts = core.split('.').map(|part| part.parse::<u64>().ok());
+        let version = Self {
+            major: parts.next()??,
+            minor: parts.next()??,
+            patch: parts.next()??,
+        };
+        parts.next().is_none().then_some(version)
+    }
+
+    /// The next version for a release of `level`
+    ///
+    /// Before 1.0.0, breaking changes bump the minor version, as Cargo and
+    /// npm treat `0.x` versions.
+    pub fn bump(self, level: Level) -> Self {
+        match level {
+            Level::Major if self.major > 0 => Self {
+                major: self.major + 1,
+                minor: 0,
+                patch: 0,
+            },
+            Level::Major | Level::Minor => Self {
+                minor: self.minor + 1,
+                patch: 0,
+                ..self
+            },
+            Level::Patch => Self {
+                patch: self.patch + 1,
+                ..self
+            },
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// A file at the repository root and its contents with the new version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestUpdate {
+    pub path: PathBuf,
+    pub contents: String,
+}
+
+/// Updates of the manifests at `repo_root` whose version is `from`
+///
+/// `Cargo.lock` is updated along with `Cargo.toml`, so the release commit
+/// builds without touching the lock file again.
+pub fn manifest_updates(repo_root: &Path, from: &str, to: &str) -> Vec<ManifestUpdate> {
+    let mut updates = Vec::new();
+    for name in MANIFESTS {
+        let path = repo_root.join(name);
+        let Ok(contents) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let Some(updated) = set_manifest_version(name, &contents, from, to) else {
+            continue;
+        };
+
+        if *name == "Cargo.toml" {
+            let lock_path = repo_root.join("Cargo.lock");
+            let package = toml::from_str::<toml::Value>(&contents)
+                .ok()
+                .and_then(|cargo| {
+                    cargo
+                        .get("package")?
+                        .get("name")?
+                        .as_str()
+                        .map(str::to_string)
+                });
+            if let (Some(package), Ok(lock)) = (package, fs::read_to_string(&lock_path)) {
+                if let Some(lock) = set_lock_version(&lock, &package, from, to) {
+                    updates.push(ManifestUpdate {
+                        path: lock_path,
+                        contents: lock,
+                    });
+                }
+            }
+        }
+        updates.push(ManifestUpdate {
+            path,
+            contents: updated,
+        });
+    }
+    updates.sort_by(|a, b| a.path.cmp(&b.path));
+    updates
+}
+
+/// Replace the version `from` in a manifest's contents, keeping everything else as it is
+///
+/// Returns `None` when the manifest doesn't have version `from`.
+pub fn set_manifest_version(name: &str, contents: &str, from: &str, to: &str) -> Option<String> {
+    match name {
+        "Cargo.toml" => set_toml_version(contents, &["package", "workspace.package"], from, to),
+        "pyproject.toml" => set_toml_version(contents, &["project", "tool.poetry"], from, to),
+        "package.json" => {
+            let pattern =
+                Regex::new(&format!(r#"("version"\s*:\s*"){}(")"#, regex::escape(from))).ok()?;
+            pattern.is_match(contents).then(|| {
+                pattern
+                    .replacen(contents, 1, format!("${{1}}{to}${{2}}"))
+                    .into_owned()
+            })
+        }
+        _ => None,
+    }
+}
+
+// Replace `version = "from"` in the first of `sections` that has it
+fn set_toml_version(contents: &str, sections: &[&str], from: &str, to: &str) -> Option<String> {
+    let pattern = Regex::new(&format!(
+        r#"^(\s*version\s*=\s*["']){}(["'].*)$"#,
+        regex::escape(from)
+    ))
+    .ok()?;
+
+    let mut section = String::new();
+    let mut replaced = false;
+    let mut updated = String::with_capacity(contents.len());
+    for line in contents.split_inclusive('\n') {
+        let text = line.trim_end_matches(['\r', '\n']);
+        let trimmed = text.trim();
+        if trimmed.starts_with('[') {
+            section = trimmed.trim_matches(['[', ']']).trim().to_string();
+        } else if !replaced && sections.contains(&section.as_str()) && pattern.is_match(text) {
+            updated.push_str(&pattern.replace(text, format!("${{1}}{to}${{2}}")));
+            updated.push_str(&line[text.len()..]);
+            replaced = true;
+            continue;
+        }
+        updated.push_str(line);
+    }
+
+    replaced.then_some(updated)
+}
+
+// Replace the version of `package` in the contents of a Cargo.lock
+fn set_lock_version(contents: &str, package: &str, from: &str, to: &str) -> Option<String> {
+    let name_line = format!("name = \"{package}\"");
+    let version_line = format!("version = \"{from}\"");
+
+    let mut in_package = false;
+    let mut replaced = false;
+    let mut updated = String::with_capacity(contents.len());
+    for line in contents.split_inclusive('\n') {
+        let text = line.trim_end_matches(['\r', '\n']);
+        if text == "[[package]]" {
+            in_package = false;
+        } else if text == name_line {
+            in_package = true;
+        } else if in_package && !replaced && text == version_line {
+            updated.push_str(&format!("version = \"{to}\""));
+            updated.push_str(&line[text.len()..]);
+            replaced = true;
+            continue;
+        }
+        updated.push_str(line);
+    }
+
+    replaced.then_some(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_level_for() {
+        assert_eq!(level_for("feat(api): add search"), Some(Level::Minor));
+        assert_eq!(level_for("fix: handle empty input"), Some(Level::Patch));
+        assert_eq!(level_for("perf: cache lookups"), Some(Level::Patch));
+        assert_eq!(level_for("feat!: drop the v1 API"), Some(Level::Major));
+        assert_eq!(
+            level_for("refactor: split config\n\nBREAKING CHANGE: load() takes a path"),
+            Some(Level::Major)
+        );
+        assert_eq!(level_for("docs: fix typo"), None);
+        assert_eq!(level_for("chore(deps): bump serde"), None);
+        assert_eq!(level_for("Update the readme"), Some(Level::Patch));
+    }
+
+    #[test]
+    fn test_version() {
+        let version = Version::parse("v1.2.3").unwrap();
+        assert_eq!(version.to_string(), "1.2.3");
+        assert_eq!(version.bump(Level::Patch).to_string(), "1.2.4");
+        assert_eq!(version.bump(Level::Minor).to_string(), "1.3.0");
+        assert_eq!(version.bump(Level::Major).to_string(), "2.0.0");
+
+        // Breaking changes before 1.0.0 bump the minor version
+        let version = Version::parse("0.4.1-beta.2+build").unwrap();
+        assert_eq!(version.bump(Level::Major).to_string(), "0.5.0");
+
+        assert_eq!(Version::parse("1.2"), None);
+        assert_eq!(Version::parse("1.2.3.4"), None);
+        assert_eq!(Version::parse("latest"), None);
+    }
+
+    #[test]
+    fn test_set_manifest_version() {
+        let cargo = "[package]\nname = \"aic\"\nversion = \"0.1.0\" # keep\n\n\
+                     [dependencies]\nserde = { version = \"0.1.0\" }\nfoo = \"0.1.0\"\n";
+        assert_eq!(
+            set_manifest_version("Cargo.toml", cargo, "0.1.0", "0.2.0").unwrap(),
+            cargo.replacen("0.1.0", "0.2.0", 1)
+        );
+        assert_eq!(
+            set_manifest_version("Cargo.toml", cargo, "9.9.9", "0.2.0"),
+            None
+        );
+
+        let workspace =
+            "[workspace]\nmembers = [\"a\"]\n\n[workspace.package]\nversion = '1.0.0'\r\n";
+        assert_eq!(
+            set_manifest_version("Cargo.toml", workspace, "1.0.0", "1.1.0").unwrap(),
+            workspace.replace("1.0.0", "1.1.0")
+        );
+
+        let pyproject = "[tool.black]\nversion = \"1.0.0\"\n\n[tool.poetry]\nversion = \"1.0.0\"\n";
+        assert_eq!(
+            set_manifest_version("pyproject.toml", pyproject, "1.0.0", "1.0.1").unwrap(),
+            "[tool.black]\nversion = \"1.0.0\"\n\n[tool.poetry]\nversion = \"1.0.1\"\n"
+        );
+
+        let package =
+            "{\n  \"name\": \"web\",\n  \"version\" : \"2.0.0\",\n  \"dependencies\": {}\n}\n";
+        assert_eq!(
+            set_manifest_version("package.json", package, "2.0.0", "3.0.0").unwrap(),
+            package.replace("2.0.0", "3.0.0")
+        );
+    }
+
+    #[test]
+    fn test_manifest_updates() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(
+            root.join("Cargo.toml"),
+            "[package]\nname = \"aic\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("Cargo.lock"),
+            "[[package]]\nname = \"anyhow\"\nversion = \"0.1.0\"\n\n\
+             [[package]]\nname = \"aic\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        fs::write(root.join("package.json"), "{\"version\": \"5.0.0\"}").unwrap();
+
+        let updates = manifest_updates(root, "0.1.0", "0.2.0");
+        let paths: Vec<&Path> = updates.iter().map(|update| update.path.as_path()).collect();
+        assert_eq!(
+            paths,
+            vec![root.join("Cargo.lock"), root.join("Cargo.toml")]
+        );
+        assert_eq!(
+            updates[0].contents,
+            "[[package]]\nname = \"anyhow\"\nversion = \"0.1.0\"\n\n\
+             [[package]]\nname = \"aic\"\nversion = \"0.2.0\"\n"
+        );
+    }
+}
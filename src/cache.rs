@@ -0,0 +1,145 @@
+use crate::capabilities::CapabilityCache;
+use crate::git;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const RESPONSES_DIR: &str = "responses";
+
+/// Cached responses older than this are removed when a new one is stored
+const MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Generated messages for one request, stored as `<key>.json`
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct CachedResponse {
+    messages: Vec<String>,
+}
+
+/// Directory of the cached responses, `~/.cache/aic/responses` on Linux and macOS
+pub fn responses_dir() -> Result<PathBuf> {
+    let dir = CapabilityCache::cache_dir()?.join(RESPONSES_DIR);
+    if !dir.exists() {
+        fs::create_dir_all(&dir).context("Failed to create cache directory")?;
+    }
+    Ok(dir)
+}
+
+/// Cache key of a request, from everything that shapes its response
+pub fn key(parts: &[&str]) -> Result<String> {
+    // NUL can't appear in the prompts or the diff, so the parts can't run into each other
+    git::hash_object(&parts.join("\0"))
+}
+
+/// The messages cached under `key`, if any
+pub fn lookup_in(dir: &Path, key: &str) -> Option<Vec<String>> {
+    let contents = fs::read_to_string(dir.join(format!("{key}.json"))).ok()?;
+    let response: CachedResponse = serde_json::from_str(&contents).ok()?;
+    (!response.messages.is_empty()).then_some(response.messages)
+}
+
+/// Cache `messages` under `key`, removing expired responses
+pub fn store_in(dir: &Path, key: &str, messages: &[String]) -> Result<()> {
+    prune(dir, MAX_AGE);
+
+    let response = CachedResponse {
+        messages: messages.to_vec(),
+    };
+    let contents = serde_json::to_string(&response).context("Failed to serialize response")?;
+    fs::write(dir.join(format!("{key}.json")), contents).context("Failed to write response cache")
+}
+
+/// Remove every cached response, returning how many there were
+pub fn clear_in(dir: &Path) -> Result<usize> {
+    let mut removed = 0;
+    for path in cached_files(dir)? {
+        fs::remove_file(&path).context(format!("Failed to remove {}", path.display()))?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+pub fn lookup(key: &str) -> Option<Vec<String>> {
+    lookup_in(&responses_dir().ok()?, key)
+}
+
+pub fn store(key: &str, messages: &[String]) -> Result<()> {
+    store_in(&responses_dir()?, key, messages)
+}
+
+pub fn clear() -> Result<usize> {
+    clear_in(&responses_dir()?)
+}
+
+fn cached_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    Ok(fs::read_dir(dir)
+        .context("Could not read the response cache")?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .collect())
+}
+
+// Best effort: a response that can't be removed now is tried again next time
+fn prune(dir: &Path, max_age: Duration) {
+    let now = SystemTime::now();
+    for path in cached_files(dir).unwrap_or_default() {
+        let expired = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age > max_age);
+        if expired {
+            let _ = fs::remove_file(&path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_store_lookup_and_clear() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let messages = vec!["feat: add login".to_string(), "feat: log in".to_string()];
+
+        assert!(lookup_in(dir, "abc").is_none());
+        store_in(dir, "abc", &messages).unwrap();
+        assert_eq!(lookup_in(dir, "abc"), Some(messages));
+        assert!(lookup_in(dir, "def").is_none());
+
+        // Responses past the age limit are pruned
+        prune(dir, MAX_AGE);
+        assert!(lookup_in(dir, "abc").is_some());
+        std::thread::sleep(Duration::from_millis(10));
+        prune(dir, Duration::ZERO);
+        assert!(lookup_in(dir, "abc").is_none());
+
+        store_in(dir, "abc", &["fix: typo".to_string()]).unwrap();
+        fs::write(dir.join("notes.txt"), "not a response").unwrap();
+        assert_eq!(clear_in(dir).unwrap(), 1);
+        assert!(lookup_in(dir, "abc").is_none());
+        assert!(dir.join("notes.txt").exists());
+    }
+
+    #[test]
+    fn test_key() {
+        let temp_dir = TempDir::new().unwrap();
+        std::env::set_current_dir(temp_dir.path()).unwrap();
+
+        let key = key(&["gpt-4o", "system", "diff"]).unwrap();
+        assert_eq!(key.len(), 40);
+        assert_eq!(super::key(&["gpt-4o", "system", "diff"]).unwrap(), key);
+        assert_ne!(super::key(&["gpt-4o", "systemdiff", ""]).unwrap(), key);
+    }
+}
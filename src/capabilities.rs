@@ -0,0 +1,261 @@
+use crate::llm::LlmClient;
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const CAPABILITIES_FILENAME: &str = "capabilities.toml";
+
+/// Reasoning models, which take no sampling parameters and limit their
+/// output with `max_completion_tokens`
+const REASONING_MODEL_PREFIXES: &[&str] = &["o1", "o3", "o4"];
+
+/// Reasoning models that also reject messages with the `system` role
+const NO_SYSTEM_ROLE_PREFIXES: &[&str] = &["o1-mini", "o1-preview"];
+
+/// Optional request features a model may or may not accept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelCapabilities {
+    /// Accepts messages with the `system` role
+    pub system_role: bool,
+
+    /// Accepts `response_format: {"type": "json_object"}`
+    pub json_mode: bool,
+
+    /// Accepts `response_format: {"type": "json_schema"}`, unknown for
+    /// models probed before it was
+    #[serde(default)]
+    pub json_schema: bool,
+
+    /// Accepts `stream: true`
+    pub streaming: bool,
+
+    /// Accepts `temperature` and `top_p`
+    #[serde(default = "accepted")]
+    pub sampling: bool,
+
+    /// Takes `max_completion_tokens` in place of `max_tokens`
+    #[serde(default)]
+    pub max_completion_tokens: bool,
+}
+
+// Features missing from caches written before they were probed are assumed to work
+fn accepted() -> bool {
+    true
+}
+
+impl Default for ModelCapabilities {
+    // Assume the full OpenAI feature set until a probe says otherwise
+    fn default() -> Self {
+        Self {
+            system_role: true,
+            json_mode: true,
+            json_schema: true,
+            streaming: true,
+            sampling: true,
+            max_completion_tokens: false,
+        }
+    }
+}
+
+impl ModelCapabilities {
+    /// What a model is known to support before it is probed, going by its name
+    ///
+    /// OpenAI's reasoning models (o1, o3, o4-mini) reject sampling parameters
+    /// and `max_tokens`, and the first of them reject the `system` role too.
+    pub fn for_model(model: &str) -> Self {
+        // Routers like OpenRouter prefix the model with its provider, `openai/o1-mini`
+        let name = model.rsplit('/').next().unwrap_or(model);
+        if !REASONING_MODEL_PREFIXES
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+        {
+            return Self::default();
+        }
+
+        Self {
+            system_role: !NO_SYSTEM_ROLE_PREFIXES
+                .iter()
+                .any(|prefix| name.starts_with(prefix)),
+            sampling: false,
+            max_completion_tokens: true,
+            ..Self::default()
+        }
+    }
+}
+
+/// On-disk cache of probed capabilities, keyed by API base URL and model
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CapabilityCache {
+    #[serde(default)]
+    models: BTreeMap<String, ModelCapabilities>,
+}
+
+impl CapabilityCache {
+    pub fn cache_dir() -> Result<PathBuf> {
+        let home_dir = dirs::home_dir().context("Could not find home directory")?;
+        let cache_dir = if cfg!(target_os = "windows") {
+            home_dir
+                .join("AppData")
+                .join("Local")
+                .join("aic")
+                .join("cache")
+        } else {
+            home_dir.join(".cache").join("aic")
+        };
+        if !cache_dir.exists() {
+            fs::create_dir_all(&cache_dir).context("Failed to create cache directory")?;
+        }
+
+        Ok(cache_dir)
+    }
+
+    pub fn cache_path() -> Result<PathBuf> {
+        Ok(Self::cache_dir()?.join(CAPABILITIES_FILENAME))
+    }
+
+    fn key(api_base_url: &str, model: &str) -> String {
+        format!("{}::{model}", api_base_url.trim_end_matches('/'))
+    }
+
+    // Load the cache from a file, a missing file is an empty cache
+    fn load_from(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(path).context("Could not read capabilities cache")?;
+        toml::from_str(&contents).context("Failed to parse capabilities cache")
+    }
+
+    fn save_to(&self, path: &Path) -> Result<()> {
+        let toml_string =
+            toml::to_string_pretty(self).context("Failed to serialize capabilities cache")?;
+        fs::write(path, toml_string).context("Failed to write capabilities cache")
+    }
+
+    pub fn load() -> Result<Self> {
+        Self::load_from(&Self::cache_path()?)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        self.save_to(&Self::cache_path()?)
+    }
+
+    pub fn get(&self, api_base_url: &str, model: &str) -> Option<ModelCapabilities> {
+        self.models.get(&Self::key(api_base_url, model)).copied()
+    }
+
+    pub fn insert(&mut self, api_base_url: &str, model: &str, capabilities: ModelCapabilities) {
+        self.models
+            .insert(Self::key(api_base_url, model), capabilities);
+    }
+}
+
+/// Probe the model and store the result in the cache
+pub async fn refresh(client: &LlmClient) -> Result<ModelCapabilities> {
+    let capabilities = client.probe_capabilities().await?;
+
+    let mut cache = CapabilityCache::load().unwrap_or_default();
+    cache.insert(client.api_base_url(), client.model(), capabilities);
+    cache.save()?;
+
+    Ok(capabilities)
+}
+
+/// Look up the capabilities of the client's model without probing
+///
+/// For time-critical requests where a probe would cost more than it saves.
+pub fn cached(client: &LlmClient) -> ModelCapabilities {
+    CapabilityCache::load()
+        .ok()
+        .and_then(|cache| cache.get(client.api_base_url(), client.model()))
+        .unwrap_or_else(|| ModelCapabilities::for_model(client.model()))
+}
+
+/// Look up the capabilities of the client's model, probing on first use
+///
+/// Probing failures are not fatal: the defaults are used and nothing is
+/// cached, so the real request surfaces the underlying error.
+pub async fn resolve(client: &LlmClient) -> ModelCapabilities {
+    if let Some(capabilities) = CapabilityCache::load()
+        .ok()
+        .and_then(|cache| cache.get(client.api_base_url(), client.model()))
+    {
+        return capabilities;
+    }
+
+    println!("{}", "🔬 Probing model capabilities...".blue());
+    match refresh(client).await {
+        Ok(capabilities) => capabilities,
+        Err(_) => {
+            println!(
+                "{}",
+                "⚠️  Could not probe model capabilities, using defaults.".yellow()
+            );
+            ModelCapabilities::for_model(client.model())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_cache_roundtrip() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let cache_path = temp_dir.path().join(CAPABILITIES_FILENAME);
+
+        // A missing file is an empty cache
+        let mut cache = CapabilityCache::load_from(&cache_path).unwrap();
+        assert!(cache.get("https://api.openai.com/v1", "o1-mini").is_none());
+
+        let capabilities = ModelCapabilities {
+            system_role: false,
+            json_mode: false,
+            json_schema: false,
+            streaming: true,
+            sampling: false,
+            max_completion_tokens: true,
+        };
+        cache.insert("https://api.openai.com/v1/", "o1-mini", capabilities);
+        cache.save_to(&cache_path).unwrap();
+
+        // Trailing slashes on the base URL map to the same entry
+        let loaded = CapabilityCache::load_from(&cache_path).unwrap();
+        assert_eq!(
+            loaded.get("https://api.openai.com/v1", "o1-mini"),
+            Some(capabilities)
+        );
+        assert!(loaded.get("https://api.openai.com/v1", "gpt-4").is_none());
+
+        // Caches from before sampling was probed assume it works
+        let old: CapabilityCache = toml::from_str(
+            "[models]\n\"https://api.openai.com/v1::gpt-4o\" = \
+             { system_role = true, json_mode = true, streaming = true }\n",
+        )
+        .unwrap();
+        let capabilities = old.get("https://api.openai.com/v1", "gpt-4o").unwrap();
+        assert!(capabilities.sampling);
+        assert!(!capabilities.max_completion_tokens);
+        assert!(!capabilities.json_schema);
+    }
+
+    #[test]
+    fn test_for_model() {
+        assert_eq!(
+            ModelCapabilities::for_model("gpt-4o"),
+            ModelCapabilities::default()
+        );
+
+        let o1_mini = ModelCapabilities::for_model("o1-mini");
+        assert!(!o1_mini.system_role && !o1_mini.sampling && o1_mini.max_completion_tokens);
+
+        let o3 = ModelCapabilities::for_model("openai/o3-mini");
+        assert!(o3.system_role && !o3.sampling && o3.max_completion_tokens);
+    }
+}
@@ -0,0 +1,220 @@
+use crate::lint;
+use std::collections::BTreeMap;
+
+/// Header of a new `CHANGELOG.md` in Keep a Changelog format
+const CHANGELOG_HEADER: &str = "# Changelog\n\n\
+    All notable changes to this project will be documented in this file.\n\n\
+    The format is based on [Keep a Changelog](https://keepachangelog.com/en/1.1.0/),\n\
+    and this project adheres to [Semantic Versioning](https://semver.org/spec/v2.0.0.html).\n";
+
+/// Keep a Changelog sections, in the order they are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Section {
+    Added,
+    Changed,
+    Removed,
+    Fixed,
+    Security,
+}
+
+impl Section {
+    pub fn title(&self) -> &'static str {
+        match self {
+            Self::Added => "Added",
+            Self::Changed => "Changed",
+            Self::Removed => "Removed",
+            Self::Fixed => "Fixed",
+            Self::Security => "Security",
+        }
+    }
+
+    /// Section for a conventional commit type, `None` for changes users don't see
+    fn for_type(commit_type: &str) -> Option<Self> {
+        match commit_type {
+            "feat" => Some(Self::Added),
+            "fix" => Some(Self::Fixed),
+            "perf" | "refactor" => Some(Self::Changed),
+            "revert" => Some(Self::Removed),
+            "security" => Some(Self::Security),
+            _ => None,
+        }
+    }
+}
+
+/// Group commit headers into changelog sections
+///
+/// Breaking changes are listed under Changed whatever their type, and
+/// messages that are not conventional commits are kept under Changed so
+/// nothing user-facing is silently dropped. Docs, tests, CI and chores are left out.
+pub fn group_commits(messages: &[String]) -> BTreeMap<Section, Vec<String>> {
+    let mut sections: BTreeMap<Section, Vec<String>> = BTreeMap::new();
+    for message in messages {
+        let header = message.lines().next().unwrap_or_default().trim();
+        let breaking = message.contains("\nBREAKING CHANGE:");
+
+        let (section, entry) = match lint::parse_header(header) {
+            Some(parsed) if parsed.breaking || breaking => (
+                Some(Section::Changed),
+                format!("**Breaking:** {}", describe(parsed.scope, parsed.subject)),
+            ),
+            Some(parsed) => (
+                Section::for_type(parsed.commit_type),
+                describe(parsed.scope, parsed.subject),
+            ),
+            None => (Some(Section::Changed), header.to_string()),
+        };
+
+        if let Some(section) = section.filter(|_| !entry.is_empty()) {
+            sections.entry(section).or_default().push(entry);
+        }
+    }
+
+    sections
+}
+
+fn describe(scope: Option<&str>, subject: &str) -> String {
+    match scope {
+        Some(scope) => format!("{scope}: {subject}"),
+        None => subject.to_string(),
+    }
+}
+
+/// Render grouped entries as markdown, the input for the release notes prompt
+/// and the fallback when the model is unavailable
+pub fn render_sections(sections: &BTreeMap<Section, Vec<String>>) -> String {
+    sections
+        .iter()
+        .map(|(section, entries)| {
+            let bullets: Vec<String> = entries.iter().map(|entry| format!("- {entry}")).collect();
+            format!("### {}\n\n{}", section.title(), bullets.join("\n"))
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Heading of a release: `[Unreleased]`, or the version and date of a tag like `v1.3.0`
+pub fn release_heading(to: &str, date: &str) -> String {
+    if to == "HEAD" {
+        return "## [Unreleased]".to_string();
+    }
+
+    let version = to.strip_prefix('v').unwrap_or(to);
+    format!("## [{version}] - {date}")
+}
+
+/// Insert a release into an existing changelog, or create one
+///
+/// The release goes above the newest existing release. An existing
+/// `[Unreleased]` section is replaced when the new release is unreleased and
+/// kept above it otherwise.
+pub fn insert_release(changelog: Option<&str>, release: &str) -> String {
+    let changelog = changelog.unwrap_or(CHANGELOG_HEADER).trim_end();
+    let release = release.trim();
+    let replaces_unreleased = release.starts_with("## [Unreleased]");
+
+    // Byte offsets of every release heading
+    let mut headings = Vec::new();
+    let mut offset = 0;
+    for line in changelog.split_inclusive('\n') {
+        if line.starts_with("## [") {
+            headings.push((offset, line.starts_with("## [Unreleased]")));
+        }
+        offset += line.len();
+    }
+
+    let (start, end) = match headings.as_slice() {
+        [] => (changelog.len(), changelog.len()),
+        [(unreleased, true), rest @ ..] => {
+            let next = rest.first().map_or(changelog.len(), |(start, _)| *start);
+            if replaces_unreleased {
+                (*unreleased, next)
+            } else {
+                (next, next)
+            }
+        }
+        [(first, false), ..] => (*first, *first),
+    };
+
+    let before = changelog[..start].trim_end();
+    let after = changelog[end..].trim();
+    let mut updated = format!("{before}\n\n{release}\n");
+    if !after.is_empty() {
+        updated.push_str(&format!("\n{after}\n"));
+    }
+    updated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(messages: &[&str]) -> Vec<String> {
+        messages.iter().map(|message| message.to_string()).collect()
+    }
+
+    #[test]
+    fn test_group_commits() {
+        let sections = group_commits(&messages(&[
+            "feat(auth): add OAuth login",
+            "fix: handle empty diff",
+            "docs: update readme",
+            "chore(release): v1.2.0",
+            "feat!: drop the v1 API",
+            "refactor: split the client\n\nBREAKING CHANGE: Client::new takes a config",
+            "Update dependencies",
+        ]));
+
+        assert_eq!(sections[&Section::Added], vec!["auth: add OAuth login"]);
+        assert_eq!(sections[&Section::Fixed], vec!["handle empty diff"]);
+        assert_eq!(
+            sections[&Section::Changed],
+            vec![
+                "**Breaking:** drop the v1 API",
+                "**Breaking:** split the client",
+                "Update dependencies",
+            ]
+        );
+        assert_eq!(sections.len(), 3);
+    }
+
+    #[test]
+    fn test_render_sections() {
+        let sections = group_commits(&messages(&["fix: handle errors", "feat: add login"]));
+        assert_eq!(
+            render_sections(&sections),
+            "### Added\n\n- add login\n\n### Fixed\n\n- handle errors"
+        );
+    }
+
+    #[test]
+    fn test_release_heading() {
+        assert_eq!(release_heading("HEAD", "2025-03-01"), "## [Unreleased]");
+        assert_eq!(
+            release_heading("v1.3.0", "2025-03-01"),
+            "## [1.3.0] - 2025-03-01"
+        );
+    }
+
+    #[test]
+    fn test_insert_release() {
+        let created = insert_release(None, "## [1.0.0] - 2025-03-01\n\n### Added\n\n- login");
+        assert!(created.starts_with("# Changelog\n"));
+        assert!(created.ends_with("\n\n## [1.0.0] - 2025-03-01\n\n### Added\n\n- login\n"));
+
+        // Newer releases go on top
+        let updated = insert_release(Some(&created), "## [1.1.0] - 2025-04-01\n\n- more");
+        let new = updated.find("## [1.1.0]").unwrap();
+        let old = updated.find("## [1.0.0]").unwrap();
+        assert!(new < old);
+
+        // An unreleased section is replaced by a new one, and stays above releases
+        let unreleased = insert_release(Some(&updated), "## [Unreleased]\n\n- draft");
+        let unreleased = insert_release(Some(&unreleased), "## [Unreleased]\n\n- final");
+        assert!(!unreleased.contains("draft"));
+        assert!(unreleased.find("- final").unwrap() < unreleased.find("## [1.1.0]").unwrap());
+
+        let released = insert_release(Some(&unreleased), "## [1.2.0] - 2025-05-01\n\n- new");
+        assert!(released.find("## [Unreleased]").unwrap() < released.find("## [1.2.0]").unwrap());
+        assert!(released.find("## [1.2.0]").unwrap() < released.find("## [1.1.0]").unwrap());
+    }
+}
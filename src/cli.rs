@@ -1,4 +1,7 @@
-use clap::{Parser, Subcommand};
+use crate::ui::OutputFormat;
+use clap::{ArgAction, Parser, Subcommand};
+use std::path::PathBuf;
+use tracing::level_filters::LevelFilter;
 
 #[derive(Parser)]
 #[command(
@@ -17,6 +20,16 @@ pub struct Cli {
     )]
     pub auto_add: bool,
 
+    /// Generate from all changes in the working tree and stage them on commit
+    #[arg(
+        short = 'A',
+        long = "all",
+        conflicts_with = "auto_add",
+        help = "Generate from all changes in the working tree and stage them on commit",
+        long_help = "Generate the message from the staged and unstaged changes and untracked files that aren't ignored, without staging anything first. Exactly those files are staged when the commit is confirmed, and nothing is staged when it is cancelled."
+    )]
+    pub all: bool,
+
     /// Execute the git commit command automatically without confirmation
     #[arg(
         short = 'c',
@@ -31,10 +44,305 @@ pub struct Cli {
         short = 'p',
         long = "push",
         help = "Execute the git push command automatically after a successful commit",
-        long_help = "When provided, automatically execute 'git push' after a successful commit."
+        long_help = "When provided, automatically execute 'git push' after a successful commit. A branch without upstream is pushed to origin (or the only remote) and set up to track it, after confirmation."
     )]
     pub auto_push: bool,
 
+    /// Remote to push to instead of the upstream's
+    #[arg(
+        long,
+        value_name = "NAME",
+        requires = "auto_push",
+        help = "Remote to push to with -p instead of the upstream's"
+    )]
+    pub remote: Option<String>,
+
+    /// Remote branch to push to instead of the upstream
+    #[arg(
+        long,
+        value_name = "BRANCH",
+        requires = "auto_push",
+        help = "Remote branch to push HEAD to with -p instead of the upstream"
+    )]
+    pub push_branch: Option<String>,
+
+    /// Number of candidate commit messages to generate and choose from
+    #[arg(
+        short = 'n',
+        long = "candidates",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u8).range(1..=10),
+        help = "Number of candidate commit messages to generate and choose from",
+        long_help = "Generate N candidate commit messages (1-10) and pick one from a numbered list before committing."
+    )]
+    pub candidates: u8,
+
+    /// Simulate staging, committing and pushing without changing the repository
+    #[arg(
+        long,
+        help = "Simulate staging, committing and pushing without changing the repository",
+        long_help = "Run the whole flow, including the model request, but leave the repository untouched and print the git commands that would run instead. With -a, the message is generated from all changes in the working tree."
+    )]
+    pub sandbox: bool,
+
+    /// Build the message from the diff alone, without calling the API
+    #[arg(
+        long,
+        help = "Build the message from the diff alone, without calling the API",
+        long_help = "Guess the commit message from the diff: the type from the kind of files, the scope from their directories and the subject from file and function names. The same fallback is used automatically when the API can't be reached."
+    )]
+    pub offline: bool,
+
+    /// Ask the model again even if the same request was answered before
+    #[arg(
+        long,
+        help = "Ask the model again even if the same request was answered before",
+        long_help = "Generated messages are cached in the user cache directory, keyed on the diff, prompts, model and sampling settings, so running aic again on the same staged changes answers instantly without a second API call. This skips the cache and replaces the cached messages. Clear the cache with 'aic cache clear'."
+    )]
+    pub no_cache: bool,
+
+    /// Show the prompt and ask before sending it
+    #[arg(
+        long,
+        conflicts_with_all = ["print", "auto_commit"],
+        help = "Show the prompt and ask before sending it",
+        long_help = "Print the fully rendered system and user prompts, including the diff after exclusions, redaction and truncation, and ask before calling the API. Set confirm_send = true to always ask."
+    )]
+    pub show_prompt: bool,
+
+    /// Print only the generated message, for scripts and other tools
+    #[arg(
+        long,
+        visible_alias = "message-only",
+        conflicts_with_all = [
+            "auto_add",
+            "all",
+            "auto_commit",
+            "auto_push",
+            "candidates",
+            "sandbox",
+            "amend"
+        ],
+        help = "Print only the generated message, for scripts and other tools",
+        long_help = "Write exactly the generated commit message for the staged changes to stdout, without headers, colors or prompts, and commit nothing. Failures are reported through the exit code. Combine with --offline to skip the API."
+    )]
+    pub print: bool,
+
+    /// Write the generated message to a file instead of committing
+    #[arg(
+        short = 'o',
+        long,
+        value_name = "PATH",
+        num_args = 0..=1,
+        conflicts_with_all = [
+            "auto_add",
+            "all",
+            "auto_commit",
+            "auto_push",
+            "candidates",
+            "sandbox",
+            "amend"
+        ],
+        help = "Write the generated message to a file instead of committing, .git/COMMIT_EDITMSG without a path",
+        long_help = "Write the generated commit message to PATH and commit nothing, e.g. for 'git commit -F PATH' or other tools. Without a path, the message goes to the repository's COMMIT_EDITMSG. The message is also printed with --print, and the JSON result with --output json. Works with --stdin, --diff-ref and --range."
+    )]
+    pub output_file: Option<Option<PathBuf>>,
+
+    /// Read the diff from stdin instead of the staged changes
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "auto_add",
+            "all",
+            "auto_commit",
+            "auto_push",
+            "candidates",
+            "sandbox",
+            "amend"
+        ],
+        help = "Read the diff from stdin instead of the staged changes",
+        long_help = "Generate the message for a diff piped on stdin, e.g. 'git diff main...HEAD | aic --stdin' or the output of another VCS. The repository is not read and nothing is committed: the message is printed like with --print, or as JSON with --output json."
+    )]
+    pub stdin: bool,
+
+    /// Generate from the diff of the working tree against a revision
+    #[arg(
+        long,
+        value_name = "REV",
+        conflicts_with_all = [
+            "auto_add",
+            "all",
+            "auto_commit",
+            "auto_push",
+            "candidates",
+            "sandbox",
+            "amend",
+            "stdin",
+            "range"
+        ],
+        help = "Generate from the diff of the working tree against a revision",
+        long_help = "Generate the message from 'git diff <REV>' instead of the staged changes. Nothing is committed: the message is printed like with --print, or as JSON with --output json."
+    )]
+    pub diff_ref: Option<String>,
+
+    /// Generate from the diff of a revision range
+    #[arg(
+        long,
+        value_name = "A..B",
+        conflicts_with_all = [
+            "auto_add",
+            "all",
+            "auto_commit",
+            "auto_push",
+            "candidates",
+            "sandbox",
+            "amend",
+            "stdin"
+        ],
+        help = "Generate from the diff of a revision range, e.g. main..feature",
+        long_help = "Generate the message from 'git diff A..B' instead of the staged changes, e.g. for a squash merge or to review what a branch changed. Use A...B for the changes since the branches diverged. Nothing is committed: the message is printed like with --print, or as JSON with --output json."
+    )]
+    pub range: Option<String>,
+
+    /// Regenerate the message of the last commit and amend it
+    #[arg(
+        long,
+        help = "Regenerate the message of the last commit and amend it",
+        long_help = "Generate a message from the diff of HEAD plus any newly staged changes and run 'git commit --amend' after confirmation. Commits that were already pushed are refused unless --force-amend is given."
+    )]
+    pub amend: bool,
+
+    /// Amend even if the last commit was already pushed
+    #[arg(
+        long,
+        requires = "amend",
+        help = "Amend even if the last commit was already pushed"
+    )]
+    pub force_amend: bool,
+
+    /// Run even if another aic run holds the repository lock
+    #[arg(
+        long,
+        global = true,
+        help = "Run even if another aic run holds the repository lock",
+        long_help = "Runs that stage or commit lock the repository in .git/aic/run.lock, so a hook and a manual run don't interleave. Use this to take over the lock of a run that is stuck."
+    )]
+    pub ignore_lock: bool,
+
+    /// Log requests, retries and timing to stderr
+    #[arg(
+        short,
+        long,
+        global = true,
+        action = ArgAction::Count,
+        help = "Log requests, retries and timing to stderr, -vv for more detail",
+        long_help = "Log what aic does to stderr: -v shows the API endpoint, model, each request with its status and duration, and retries; -vv adds request sizes and the git commands that run; -vvv includes the HTTP client's own events. Without -v or --log-level, RUST_LOG picks what is logged. stdout stays clean, so this works with --print and --output json."
+    )]
+    pub verbose: u8,
+
+    /// Log level on stderr, instead of counting -v
+    #[arg(
+        long,
+        global = true,
+        value_name = "LEVEL",
+        conflicts_with = "verbose",
+        help = "Log level on stderr: off, error, warn, info, debug or trace"
+    )]
+    pub log_level: Option<LevelFilter>,
+
+    /// Output format of the generated message, ping and config show
+    #[arg(
+        long,
+        global = true,
+        default_value_t = OutputFormat::Text,
+        help = "Output format: text or json",
+        long_help = "With json, the generated message is printed as one JSON object with its subject, body, model, token usage and timing, and nothing is committed, like with --print. ping and config show print their results as JSON too."
+    )]
+    pub output: OutputFormat,
+
+    /// Leave files out of the diff, in addition to `exclude_patterns`
+    #[arg(
+        long,
+        global = true,
+        value_name = "PATTERN",
+        value_delimiter = ',',
+        help = "Leave files matching a git pathspec pattern out of the diff, e.g. package-lock.json",
+        long_help = "Leave the files matching these git pathspec patterns, relative to the repository root, out of the diff sent to the model, in addition to exclude_patterns from the config. Comma-separated or repeated, e.g. --exclude package-lock.json --exclude '*.min.js,target/**'. With -A, excluded files are not staged either."
+    )]
+    pub exclude: Vec<String>,
+
+    /// Sampling temperature, overrides `temperature`
+    #[arg(
+        long,
+        global = true,
+        help = "Sampling temperature from 0 to 2, overrides the temperature setting"
+    )]
+    pub temperature: Option<f64>,
+
+    /// Nucleus sampling probability mass, overrides `top_p`
+    #[arg(
+        long,
+        global = true,
+        help = "Nucleus sampling probability mass from 0 to 1, overrides the top_p setting"
+    )]
+    pub top_p: Option<f64>,
+
+    /// Upper bound on generated tokens, overrides `max_tokens`
+    #[arg(
+        long,
+        global = true,
+        help = "Upper bound on the tokens generated per message, overrides the max_tokens setting"
+    )]
+    pub max_tokens: Option<u32>,
+
+    /// Trailer appended to the message, overrides `trailers` with the same key
+    #[arg(
+        long,
+        global = true,
+        value_name = "KEY=VALUE",
+        help = "Trailer appended to the generated message, e.g. Co-authored-by='Jane <jane@example.com>'",
+        long_help = "Append a git trailer like Co-authored-by or Reviewed-by to the generated message, in addition to the trailers setting. Replaces a configured trailer with the same key, and an empty value removes it, e.g. --trailer AI-assisted-by=. Repeat the flag for several trailers."
+    )]
+    pub trailer: Vec<String>,
+
+    /// Pass `--signoff` to `git commit`, overrides `signoff`
+    #[arg(
+        long,
+        global = true,
+        help = "Add a Signed-off-by trailer with git commit --signoff"
+    )]
+    pub signoff: bool,
+
+    /// Pass `--gpg-sign[=<keyid>]` to `git commit`, overrides `gpg_sign` and `gpg_key_id`
+    #[arg(
+        long,
+        global = true,
+        value_name = "KEYID",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "",
+        help = "GPG-sign the commit, with the default key or KEYID",
+        long_help = "GPG-sign the commit with git commit --gpg-sign. Without a key id, uses gpg_key_id from the config or git's default signing key."
+    )]
+    pub gpg_sign: Option<String>,
+
+    /// Pass `--no-verify` to `git commit`, overrides `no_verify`
+    #[arg(
+        long,
+        global = true,
+        help = "Skip the pre-commit and commit-msg hooks with git commit --no-verify"
+    )]
+    pub no_verify: bool,
+
+    /// Named provider profile to use instead of `default_profile`
+    #[arg(
+        long,
+        global = true,
+        help = "Named provider profile to use instead of default_profile",
+        long_help = "Use the token, base URL and model of the named profile, defined under [profiles.<name>] in the config file."
+    )]
+    pub profile: Option<String>,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
@@ -46,9 +354,568 @@ pub enum Commands {
         This command will attempt to connect to the configured API endpoint and verify the token.")]
     Ping,
 
+    /// List the models the API offers
+    #[command(
+        long_about = "List the model ids the API offers at {api_base_url}/models.\n\
+        When the configured model is not among them, similar ids are suggested to catch typos."
+    )]
+    Models {
+        /// Only list models whose id contains this text
+        search: Option<String>,
+    },
+
     /// Manage configuration settings
     #[command(subcommand)]
     Config(ConfigCommands),
+
+    /// Manage the cache of generated messages
+    #[command(subcommand)]
+    Cache(CacheCommands),
+
+    /// Split the staged changes into several commits grouped by the model
+    #[command(
+        long_about = "Ask the model to group the staged files into logically separate commits, then\n\
+        commit the groups one by one: each is staged on its own, gets a generated message and is\n\
+        committed after confirmation. Declining a commit stops, leaving the rest staged.\n\
+        When the model can't be reached, the files are grouped by directory."
+    )]
+    Split,
+
+    /// Write a commented .aic.toml at the repository root to share settings with the team
+    Init {
+        /// Fill in the model, endpoint, prompts and excludes from the global config
+        #[arg(
+            long,
+            help = "Fill in the model, endpoint, prompts and excludes from the global config (never the API token)"
+        )]
+        from_global: bool,
+
+        /// Overwrite an existing .aic.toml
+        #[arg(short, long, help = "Overwrite an existing .aic.toml")]
+        force: bool,
+    },
+
+    /// Diagnose the installation
+    #[command(
+        long_about = "Check git and its version, the repository's state, where the config files are\n\
+        and whether one holding the token is readable by others, and whether the API endpoint\n\
+        is reachable, accepts the token and offers the model. Prints a pass/fail checklist and\n\
+        exits with an error when a check fails."
+    )]
+    Doctor {
+        /// Verify the running binary against the published release checksums
+        #[arg(
+            long,
+            help = "Verify the running binary against the published release checksums",
+            long_help = "Verify the running binary against the published release checksums, and that SHA256SUMS is signed with the release key built into release binaries. A matching checksum without a valid signature only shows the binary is what GitHub serves, so it is reported as unconfirmed."
+        )]
+        verify_binary: bool,
+
+        /// Write a redacted tarball to attach to bug reports
+        #[arg(
+            long,
+            value_name = "PATH",
+            num_args = 0..=1,
+            default_missing_value = "aic-bundle.tar.gz",
+            help = "Write a redacted tarball to attach to bug reports [default path: aic-bundle.tar.gz]"
+        )]
+        bundle: Option<PathBuf>,
+    },
+
+    /// Serve requests from editor integrations in a long-running process
+    #[command(
+        long_about = "Serve generate, lint and explain requests in a long-running process.\n\
+        With --stdio, every line on stdin is a JSON request such as\n\
+        {\"id\": 1, \"method\": \"generate\", \"params\": {\"diff\": \"...\"}}\n\
+        and every response is written as one JSON line on stdout.\n\
+        With --http, a server on 127.0.0.1 accepts the same requests on POST /rpc and streams\n\
+        generation as server-sent events on POST /generate/stream. Its URL and session token\n\
+        are printed as JSON on the first line of stdout."
+    )]
+    Serve {
+        /// Speak newline-delimited JSON over stdin and stdout
+        #[arg(
+            long,
+            conflicts_with = "http",
+            help = "Speak newline-delimited JSON over stdin and stdout"
+        )]
+        stdio: bool,
+
+        /// Listen for HTTP requests on the loopback interface
+        #[arg(
+            long,
+            help = "Listen for HTTP requests on 127.0.0.1, streaming generation as server-sent events"
+        )]
+        http: bool,
+
+        /// Port for --http, a free port is picked by default
+        #[arg(
+            long,
+            default_value_t = 0,
+            requires = "http",
+            help = "Port for --http (default: any free port)"
+        )]
+        port: u16,
+    },
+
+    /// Check existing commit messages against the commit rules
+    #[command(
+        long_about = "Check the last commit, or every non-merge commit in --range, against the\n\
+        conventional commit rules used by commit_validation. Exits with an error when a message\n\
+        breaks a rule, so it can run as a CI check. With --llm, the model also grades each\n\
+        message against its diff and suggests improvements."
+    )]
+    Lint {
+        /// Revision range to check instead of the last commit
+        #[arg(long, help = "Revision range to check, e.g. origin/main..HEAD")]
+        range: Option<String>,
+
+        /// Ask the model to grade each message and suggest improvements
+        #[arg(
+            long,
+            help = "Ask the model to grade each message and suggest improvements"
+        )]
+        llm: bool,
+    },
+
+    /// Report how well the commit history follows the commit rules
+    #[command(
+        long_about = "Analyze the non-merge commits of the current branch, or of --range, and report\n\
+        the share of conventional commits and of messages passing aic lint, the average\n\
+        subject length, the commit types used and the most active scopes. Comparing the\n\
+        history before and after a date, e.g. --until and --since the day aic was adopted,\n\
+        shows whether the messages improved."
+    )]
+    Stats {
+        /// Revision range to analyze instead of the whole branch
+        #[arg(long, help = "Revision range to analyze, e.g. v1.0.0..HEAD")]
+        range: Option<String>,
+
+        /// Only analyze commits made on or after this date
+        #[arg(
+            long,
+            help = "Only analyze commits made on or after this date, e.g. 2024-05-01"
+        )]
+        since: Option<String>,
+
+        /// Only analyze commits made before this date
+        #[arg(
+            long,
+            help = "Only analyze commits made before this date, e.g. 2024-05-01"
+        )]
+        until: Option<String>,
+    },
+
+    /// Generate a pull request title and description for the current branch
+    #[command(
+        long_about = "Generate a pull request title and markdown description from the commits and\n\
+        diff of the current branch against a base branch.\n\
+        The title and body are printed to stdout. With --body-file, only the title is printed\n\
+        and the body is written to the file, e.g. for\n\
+        gh pr create --title \"$(aic pr --body-file pr.md)\" --body-file pr.md"
+    )]
+    Pr {
+        /// Branch the pull request merges into
+        #[arg(
+            long,
+            help = "Branch the pull request merges into (default: the remote's default branch, or main)"
+        )]
+        base: Option<String>,
+
+        /// Write the body to this file and print only the title
+        #[arg(long, help = "Write the body to this file and print only the title")]
+        body_file: Option<PathBuf>,
+    },
+
+    /// Write release notes for the commits between two revisions
+    #[command(
+        long_about = "Group the commits between two revisions by conventional commit type and have the\n\
+        model write release notes in the Keep a Changelog format.\n\
+        The notes are printed to stdout, or added to CHANGELOG.md at the repository root with\n\
+        --update. Releasing up to a tag like v1.3.0 writes a `## [1.3.0] - date` section,\n\
+        releasing up to HEAD an `## [Unreleased]` one."
+    )]
+    Changelog {
+        /// Revision of the previous release, usually its tag
+        #[arg(long, help = "Revision of the previous release, e.g. v1.2.0")]
+        from: String,
+
+        /// Revision of the new release
+        #[arg(long, default_value = "HEAD", help = "Revision of the new release")]
+        to: String,
+
+        /// Add the release to CHANGELOG.md instead of printing it
+        #[arg(long, help = "Add the release to CHANGELOG.md instead of printing it")]
+        update: bool,
+    },
+
+    /// Write one commit message summarizing a whole branch for a squash merge
+    #[command(
+        long_about = "Gather the commits and the cumulative diff of a branch against its base and write a\n\
+        single commit message summarizing the whole branch, following the configured prompts.\n\
+        The message is printed to stdout, ready for GitHub's squash-merge box or e.g.\n\
+        git merge --squash feature && git commit -m \"$(aic squash --base main --branch feature)\""
+    )]
+    Squash {
+        /// Branch the squashed commit goes onto
+        #[arg(
+            long,
+            help = "Branch the squashed commit goes onto (default: the remote's default branch, or main)"
+        )]
+        base: Option<String>,
+
+        /// Branch to squash
+        #[arg(long, default_value = "HEAD", help = "Branch to squash")]
+        branch: String,
+    },
+
+    /// Review the staged changes for bugs, missing tests and style issues
+    #[command(
+        long_about = "Send the staged diff to the model with the review_prompt setting as instructions\n\
+        and print the potential bugs, missing tests and style issues it finds, before you commit.\n\
+        Nothing is committed."
+    )]
+    Review,
+
+    /// Learn the repository's commit style and add it to the project's system prompt
+    #[command(
+        long_about = "Send the messages of the latest commits to the model, which derives a style guide\n\
+        from them: the commit types and scopes in use, tense, capitalization, emoji usage and\n\
+        how bodies are written. After confirmation the guide is added to the system_prompt in\n\
+        .aic.toml, so every generated message follows it. Running it again replaces the guide."
+    )]
+    Learn {
+        /// Number of recent commits to learn from
+        #[arg(
+            long,
+            default_value_t = 50,
+            value_parser = clap::value_parser!(u32).range(1..),
+            help = "Number of recent commits to learn from"
+        )]
+        commits: u32,
+
+        /// Write the guide without asking for confirmation
+        #[arg(short, long, help = "Write the guide without asking for confirmation")]
+        yes: bool,
+    },
+
+    /// Explain what an existing commit does and why it might matter
+    #[command(
+        long_about = "Send the message and diff of a commit to the model and print a plain-English\n\
+        explanation of what it changes, why, and what it might affect, e.g. during code\n\
+        archaeology or reviews."
+    )]
+    Explain {
+        /// Commit to explain
+        #[arg(
+            default_value = "HEAD",
+            help = "Commit to explain, e.g. a1b2c3d or HEAD~2"
+        )]
+        rev: String,
+    },
+
+    /// Name a branch after the changes and switch to it
+    #[command(
+        long_about = "Generate a kebab-case branch name from the staged changes, or from the working\n\
+        tree when nothing is staged, and offer to create it with git checkout -b. The changes\n\
+        stay in the working tree and index. The name is printed to stdout; with --output json\n\
+        it is printed as JSON and no branch is created."
+    )]
+    Branch {
+        /// Prefix the name with the conventional commit type, e.g. feat/
+        #[arg(
+            long = "type",
+            help = "Prefix the name with the conventional commit type, e.g. feat/add-login"
+        )]
+        with_type: bool,
+
+        /// Ticket ID to put before the summary, e.g. PROJ-123
+        #[arg(
+            long,
+            help = "Ticket ID to put before the summary, e.g. PROJ-123-add-login"
+        )]
+        ticket: Option<String>,
+
+        /// Name the branch from the diff alone, without calling the API
+        #[arg(
+            long,
+            help = "Name the branch from the diff alone, without calling the API"
+        )]
+        offline: bool,
+
+        /// Create the branch without asking for confirmation
+        #[arg(
+            short,
+            long,
+            help = "Create the branch without asking for confirmation"
+        )]
+        yes: bool,
+    },
+
+    /// Suggest the next semantic version and optionally write it into the manifests
+    #[command(
+        long_about = "Decide whether the commits since the last tag call for a major, minor or patch\n\
+        release, following the conventional commit types: breaking changes are major, feat is\n\
+        minor and fix, perf and revert are patches. Before 1.0.0, breaking changes bump the\n\
+        minor version. With --apply, the version in Cargo.toml (and Cargo.lock), package.json or\n\
+        pyproject.toml is updated and committed as chore(release): X.Y.Z."
+    )]
+    Bump {
+        /// Decide from the staged change instead of the commits since the last tag
+        #[arg(
+            long,
+            help = "Decide from the staged change instead of the commits since the last tag; --apply then stages the new version with it"
+        )]
+        staged: bool,
+
+        /// Write the new version into the manifests and commit it
+        #[arg(long, help = "Write the new version into the manifests and commit it")]
+        apply: bool,
+
+        /// Apply without asking for confirmation
+        #[arg(
+            short,
+            long,
+            requires = "apply",
+            help = "Apply without asking for confirmation"
+        )]
+        yes: bool,
+    },
+
+    /// Create an annotated release tag with a generated message
+    #[command(
+        long_about = "Summarize the commits since the previous tag into an annotated tag message and\n\
+        run git tag -a on HEAD after confirmation."
+    )]
+    Tag {
+        /// Name of the new tag, e.g. v1.3.0
+        name: String,
+
+        /// Create the tag without asking for confirmation
+        #[arg(short, long, help = "Create the tag without asking for confirmation")]
+        yes: bool,
+    },
+
+    /// Regenerate the messages of existing commits and rewrite them
+    #[command(
+        long_about = "Generate new messages for a commit or a range of commits on the current branch\n\
+        from their diffs, show the old and new messages side by side and rewrite the history\n\
+        after confirmation. Trees and authors are kept, every commit from the oldest reworded\n\
+        one up to HEAD gets a new id. Commits that were already pushed are refused unless\n\
+        --force is given."
+    )]
+    Reword {
+        /// Commit or revision range to reword
+        #[arg(help = "Commit or revision range to reword, e.g. HEAD~2 or main..HEAD")]
+        revs: String,
+
+        /// Rewrite without asking for confirmation
+        #[arg(short, long, help = "Rewrite without asking for confirmation")]
+        yes: bool,
+
+        /// Reword even if the commits were already pushed
+        #[arg(long, help = "Reword even if the commits were already pushed")]
+        force: bool,
+    },
+
+    /// Translate a commit message and optionally reword the commit with it
+    #[command(
+        long_about = "Translate the message of a commit into another language, English by default, and\n\
+        print it. With --apply the commit is reworded with the translation after confirmation,\n\
+        like aic reword. With --pending the latest generated message that wasn't committed is\n\
+        translated instead, and offered for committing the staged changes."
+    )]
+    Translate {
+        /// Commit whose message to translate
+        #[arg(
+            default_value = "HEAD",
+            conflicts_with = "pending",
+            help = "Commit whose message to translate, e.g. a1b2c3d or HEAD~2"
+        )]
+        rev: String,
+
+        /// Language to translate into
+        #[arg(
+            long,
+            default_value = "English",
+            help = "Language to translate into, e.g. English, German or ja"
+        )]
+        to: String,
+
+        /// Translate the latest generated message that wasn't committed
+        #[arg(
+            long,
+            help = "Translate the latest generated message that wasn't committed and offer it for the staged changes"
+        )]
+        pending: bool,
+
+        /// Reword the commit with the translation
+        #[arg(
+            long,
+            conflicts_with = "pending",
+            help = "Reword the commit with the translation"
+        )]
+        apply: bool,
+
+        /// Reword without asking for confirmation
+        #[arg(
+            short,
+            long,
+            requires = "apply",
+            help = "Reword without asking for confirmation"
+        )]
+        yes: bool,
+
+        /// Reword even if the commit was already pushed
+        #[arg(
+            long,
+            requires = "apply",
+            help = "Reword even if the commit was already pushed"
+        )]
+        force: bool,
+    },
+
+    /// Find commits by what they did, in plain language
+    #[command(
+        long_about = "Find the commits whose messages best match a question, e.g. \"when did we change\n\
+        the retry logic?\". Commit messages are embedded with embedding_model and kept in an\n\
+        index in .git/aic, so only new commits are embedded on later searches. With\n\
+        embedding_model = \"local\" no API is called, at the cost of matching words rather than\n\
+        meaning."
+    )]
+    Search {
+        /// What to look for
+        #[arg(help = "What to look for, e.g. \"when did we change the retry logic?\"")]
+        query: String,
+
+        /// Number of commits to list
+        #[arg(long, default_value_t = 10, help = "Number of commits to list")]
+        limit: usize,
+
+        /// Embed every commit again instead of reusing the index
+        #[arg(long, help = "Embed every commit again instead of reusing the index")]
+        reindex: bool,
+    },
+
+    /// Evaluate prompt variants against past commits
+    #[command(subcommand)]
+    Experiment(ExperimentCommands),
+
+    /// Generate messages for plain `git commit` through a git hook
+    #[command(subcommand)]
+    Hook(HookCommands),
+
+    /// List and reuse previously generated messages
+    #[command(
+        args_conflicts_with_subcommands = true,
+        long_about = "List the messages generated in the current repository, newest first. Every\n\
+        generation is kept in ~/.local/share/aic/history.jsonl with its time, repository, diff\n\
+        hash and model, and whether it was committed, so a discarded message can be reused."
+    )]
+    History {
+        #[command(subcommand)]
+        command: Option<HistoryCommands>,
+
+        /// Only list messages containing this text
+        #[arg(long, help = "Only list messages containing this text, ignoring case")]
+        search: Option<String>,
+
+        /// Number of messages to list
+        #[arg(long, default_value_t = 20, help = "Number of messages to list")]
+        limit: usize,
+
+        /// List the messages of every repository
+        #[arg(long, help = "List the messages of every repository")]
+        all_repos: bool,
+    },
+
+    /// Show the tokens used and their estimated cost
+    #[command(
+        long_about = "Show the tokens used per model and their estimated cost. The usage the API\n\
+        reports for every request is kept in ~/.local/share/aic/usage.jsonl. Costs come from\n\
+        model_prices, or the list prices of well-known models, at the time of the request."
+    )]
+    Usage {
+        /// Only count the usage of the last days
+        #[arg(long, help = "Only count the usage of the last N days")]
+        days: Option<u32>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum HistoryCommands {
+    /// Commit the staged changes with a message from the history
+    #[command(
+        long_about = "Commit the staged changes with a previously generated message, after the usual\n\
+        confirmation that allows editing it first. Any unambiguous prefix of the id works."
+    )]
+    Reuse {
+        /// Id of the message, as listed by `aic history`
+        id: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum HookCommands {
+    /// Install the prepare-commit-msg hook in the current repository
+    #[command(
+        long_about = "Write a prepare-commit-msg hook that calls 'aic hook run', so plain 'git commit',\n\
+        IDEs and GUIs get a generated message in the editor. Commits with a message of their\n\
+        own (-m, -F, merges, squashes and amends) are left alone, and the commit goes ahead\n\
+        with the usual message when aic is missing or fails."
+    )]
+    Install {
+        /// Replace an existing hook that was not installed by aic
+        #[arg(long, help = "Replace an existing hook that was not installed by aic")]
+        force: bool,
+    },
+
+    /// Remove the hook installed by aic
+    Uninstall,
+
+    /// Generate the message from the staged diff, called by the hook
+    #[command(hide = true)]
+    Run {
+        /// File holding the commit message
+        message_file: PathBuf,
+
+        /// Where the message comes from: message, template, merge, squash or commit
+        source: Option<String>,
+
+        /// Commit the message is taken from, for amends
+        sha: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ExperimentCommands {
+    /// Replay historical diffs through prompt variants and compare the results
+    #[command(
+        long_about = "Generate a message for every commit in a revision range with each prompt variant,\n\
+        then report how many messages follow the commit rules and how close their headers are\n\
+        to the original ones. A variant is a TOML file with any of system_prompt, user_prompt\n\
+        and model; unset fields come from the active configuration."
+    )]
+    Run {
+        /// Comma-separated prompt variant files
+        #[arg(
+            long,
+            required = true,
+            value_delimiter = ',',
+            help = "Comma-separated prompt variant files, e.g. a.toml,b.toml"
+        )]
+        prompts: Vec<PathBuf>,
+
+        /// Commits to replay
+        #[arg(long, help = "Commits to replay, e.g. HEAD~20..HEAD or v0.1.0..main")]
+        revs: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum CacheCommands {
+    /// Remove every cached message
+    Clear,
 }
 
 #[derive(Subcommand)]
@@ -96,6 +963,60 @@ pub enum ConfigCommands {
 
     /// List all configuration values
     List,
+
+    /// Import settings from another commit message tool (opencommit, aicommits, cz)
+    ImportFrom {
+        /// Tool to import from: opencommit, aicommits or cz
+        tool: String,
+
+        /// Apply the settings without asking for confirmation
+        #[arg(
+            short,
+            long,
+            help = "Apply the settings without asking for confirmation"
+        )]
+        yes: bool,
+    },
+
+    /// Check the config files and the merged settings for problems
+    #[command(
+        long_about = "Check the config files for invalid TOML and unknown keys, and the merged\n\
+        settings for an ill-formed base URL, a user_prompt without the diff placeholder and\n\
+        a missing API token. The API is then asked for its models to check that it can be\n\
+        reached and accepts the token. Exits with an error when a problem is an error, so\n\
+        the command works as a CI check."
+    )]
+    Validate {
+        /// Skip the checks that contact the API
+        #[arg(long, help = "Skip the checks that contact the API")]
+        offline: bool,
+
+        /// Also fail on warnings
+        #[arg(long, help = "Also fail on warnings, such as unknown keys")]
+        strict: bool,
+    },
+
+    /// Open the config file in $EDITOR and check it when the editor exits
+    Edit {
+        /// Edit the project's .aic.toml instead of the global config
+        #[arg(
+            long,
+            help = "Edit the project's .aic.toml instead of the global config, creating it at the repository root if needed"
+        )]
+        project: bool,
+    },
+}
+
+impl Cli {
+    /// Whether the default flow only prints a message instead of committing
+    pub fn prints_only(&self) -> bool {
+        self.print
+            || self.output_file.is_some()
+            || self.stdin
+            || self.diff_ref.is_some()
+            || self.range.is_some()
+            || self.output == OutputFormat::Json
+    }
 }
 
 pub fn parse_args() -> Cli {
@@ -113,6 +1034,711 @@ mod tests {
         assert!(!args.auto_commit);
         assert!(!args.auto_add);
         assert!(!args.auto_push);
+        assert_eq!(args.candidates, 1);
+        assert!(args.profile.is_none());
+    }
+
+    #[test]
+    fn test_candidates() {
+        let args = Cli::parse_from(["program", "-n", "3"]);
+        assert_eq!(args.candidates, 3);
+
+        let args = Cli::parse_from(["program", "--candidates", "5"]);
+        assert_eq!(args.candidates, 5);
+
+        // Out of range values are rejected
+        assert!(Cli::try_parse_from(["program", "-n", "0"]).is_err());
+        assert!(Cli::try_parse_from(["program", "-n", "11"]).is_err());
+    }
+
+    #[test]
+    fn test_config_import_from() {
+        let args = Cli::parse_from(["program", "config", "import-from", "opencommit", "-y"]);
+        match args.command {
+            Some(Commands::Config(ConfigCommands::ImportFrom { tool, yes })) => {
+                assert_eq!(tool, "opencommit");
+                assert!(yes);
+            }
+            _ => panic!("Expected config import-from command"),
+        }
+    }
+
+    #[test]
+    fn test_config_validate() {
+        let args = Cli::parse_from(["program", "config", "validate", "--offline", "--strict"]);
+        match args.command {
+            Some(Commands::Config(ConfigCommands::Validate { offline, strict })) => {
+                assert!(offline);
+                assert!(strict);
+            }
+            _ => panic!("Expected config validate command"),
+        }
+    }
+
+    #[test]
+    fn test_config_edit() {
+        let args = Cli::parse_from(["program", "config", "edit", "--project"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Config(ConfigCommands::Edit { project: true }))
+        ));
+    }
+
+    #[test]
+    fn test_init() {
+        let args = Cli::parse_from(["program", "init", "--from-global", "-f"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Init {
+                from_global: true,
+                force: true
+            })
+        ));
+    }
+
+    #[test]
+    fn test_trailer() {
+        let args = Cli::parse_from([
+            "program",
+            "--trailer",
+            "Co-authored-by=Jane <jane@example.com>",
+            "--trailer",
+            "AI-assisted-by=",
+        ]);
+        assert_eq!(
+            args.trailer,
+            vec!["Co-authored-by=Jane <jane@example.com>", "AI-assisted-by="]
+        );
+
+        // Global, so subcommands accept it too
+        let args = Cli::parse_from(["program", "squash", "--trailer", "Refs=ABC-1"]);
+        assert_eq!(args.trailer, vec!["Refs=ABC-1"]);
+    }
+
+    #[test]
+    fn test_commit_flags() {
+        let args = Cli::parse_from(["program", "--signoff", "--gpg-sign", "--no-verify"]);
+        assert!(args.signoff);
+        assert_eq!(args.gpg_sign.as_deref(), Some(""));
+        assert!(args.no_verify);
+
+        let args = Cli::parse_from(["program", "squash", "--gpg-sign=ABC123"]);
+        assert_eq!(args.gpg_sign.as_deref(), Some("ABC123"));
+        assert!(!args.signoff);
+
+        let args = Cli::parse_from(["program"]);
+        assert_eq!(args.gpg_sign, None);
+    }
+
+    #[test]
+    fn test_sampling_overrides() {
+        let args = Cli::parse_from(["program", "--temperature", "0.2", "--max-tokens", "300"]);
+        assert_eq!(args.temperature, Some(0.2));
+        assert_eq!(args.top_p, None);
+        assert_eq!(args.max_tokens, Some(300));
+
+        // Global, so subcommands accept them too
+        let args = Cli::parse_from(["program", "pr", "--top-p", "0.9"]);
+        assert_eq!(args.top_p, Some(0.9));
+        assert!(Cli::try_parse_from(["program", "--max-tokens", "-1"]).is_err());
+    }
+
+    #[test]
+    fn test_models() {
+        let args = Cli::parse_from(["program", "models"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Models { search: None })
+        ));
+
+        let args = Cli::parse_from(["program", "models", "gpt-4"]);
+        match args.command {
+            Some(Commands::Models { search }) => assert_eq!(search.as_deref(), Some("gpt-4")),
+            _ => panic!("Expected models command"),
+        }
+    }
+
+    #[test]
+    fn test_cache() {
+        let args = Cli::parse_from(["program", "--no-cache", "--show-prompt"]);
+        assert!(args.no_cache);
+        assert!(args.show_prompt);
+        assert!(Cli::try_parse_from(["program", "--show-prompt", "--print"]).is_err());
+        assert!(!Cli::parse_from(["program"]).no_cache);
+
+        let args = Cli::parse_from(["program", "cache", "clear"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Cache(CacheCommands::Clear))
+        ));
+    }
+
+    #[test]
+    fn test_split() {
+        let args = Cli::parse_from(["program", "split"]);
+        assert!(matches!(args.command, Some(Commands::Split)));
+    }
+
+    #[test]
+    fn test_squash() {
+        let args = Cli::parse_from(["program", "squash", "--base", "main"]);
+        match args.command {
+            Some(Commands::Squash { base, branch }) => {
+                assert_eq!(base.as_deref(), Some("main"));
+                assert_eq!(branch, "HEAD");
+            }
+            _ => panic!("Expected squash command"),
+        }
+
+        let args = Cli::parse_from(["program", "squash", "--branch", "feature"]);
+        match args.command {
+            Some(Commands::Squash { base, branch }) => {
+                assert_eq!(base, None);
+                assert_eq!(branch, "feature");
+            }
+            _ => panic!("Expected squash command"),
+        }
+    }
+
+    #[test]
+    fn test_review() {
+        let args = Cli::parse_from(["program", "review"]);
+        assert!(matches!(args.command, Some(Commands::Review)));
+    }
+
+    #[test]
+    fn test_learn() {
+        let args = Cli::parse_from(["program", "learn"]);
+        match args.command {
+            Some(Commands::Learn { commits, yes }) => {
+                assert_eq!(commits, 50);
+                assert!(!yes);
+            }
+            _ => panic!("Expected learn command"),
+        }
+
+        let args = Cli::parse_from(["program", "learn", "--commits", "20", "-y"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Learn {
+                commits: 20,
+                yes: true
+            })
+        ));
+        assert!(Cli::try_parse_from(["program", "learn", "--commits", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_explain() {
+        let args = Cli::parse_from(["program", "explain"]);
+        match args.command {
+            Some(Commands::Explain { rev }) => assert_eq!(rev, "HEAD"),
+            _ => panic!("Expected explain command"),
+        }
+
+        let args = Cli::parse_from(["program", "explain", "a1b2c3d"]);
+        match args.command {
+            Some(Commands::Explain { rev }) => assert_eq!(rev, "a1b2c3d"),
+            _ => panic!("Expected explain command"),
+        }
+    }
+
+    #[test]
+    fn test_branch() {
+        let args = Cli::parse_from(["program", "branch"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Branch {
+                with_type: false,
+                ticket: None,
+                offline: false,
+                yes: false
+            })
+        ));
+
+        let args = Cli::parse_from(["program", "branch", "--type", "--ticket", "PROJ-1", "-y"]);
+        match args.command {
+            Some(Commands::Branch {
+                with_type,
+                ticket,
+                yes,
+                ..
+            }) => {
+                assert!(with_type && yes);
+                assert_eq!(ticket.as_deref(), Some("PROJ-1"));
+            }
+            _ => panic!("Expected branch command"),
+        }
+    }
+
+    #[test]
+    fn test_bump() {
+        let args = Cli::parse_from(["program", "bump"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Bump {
+                staged: false,
+                apply: false,
+                yes: false
+            })
+        ));
+
+        let args = Cli::parse_from(["program", "bump", "--staged", "--apply", "-y"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Bump {
+                staged: true,
+                apply: true,
+                yes: true
+            })
+        ));
+        assert!(Cli::try_parse_from(["program", "bump", "--yes"]).is_err());
+    }
+
+    #[test]
+    fn test_profile() {
+        let args = Cli::parse_from(["program", "--profile", "work"]);
+        assert_eq!(args.profile.as_deref(), Some("work"));
+
+        // The flag is global, so subcommands accept it too
+        let args = Cli::parse_from(["program", "ping", "--profile", "personal"]);
+        assert_eq!(args.profile.as_deref(), Some("personal"));
+    }
+
+    #[test]
+    fn test_serve() {
+        let args = Cli::parse_from(["program", "serve", "--stdio"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Serve {
+                stdio: true,
+                http: false,
+                port: 0
+            })
+        ));
+
+        let args = Cli::parse_from(["program", "serve", "--http", "--port", "7777"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Serve {
+                stdio: false,
+                http: true,
+                port: 7777
+            })
+        ));
+
+        // One transport at a time, and a port only makes sense for HTTP
+        assert!(Cli::try_parse_from(["program", "serve", "--stdio", "--http"]).is_err());
+        assert!(Cli::try_parse_from(["program", "serve", "--port", "7777"]).is_err());
+    }
+
+    #[test]
+    fn test_stats() {
+        let args = Cli::parse_from(["program", "stats", "--since", "2024-05-01"]);
+        match args.command {
+            Some(Commands::Stats {
+                range,
+                since,
+                until,
+            }) => {
+                assert_eq!(range, None);
+                assert_eq!(since.as_deref(), Some("2024-05-01"));
+                assert_eq!(until, None);
+            }
+            _ => panic!("Expected Stats command"),
+        }
+    }
+
+    #[test]
+    fn test_lint() {
+        let args = Cli::parse_from(["program", "lint"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Lint {
+                range: None,
+                llm: false
+            })
+        ));
+
+        let args = Cli::parse_from(["program", "lint", "--range", "main..HEAD", "--llm"]);
+        match args.command {
+            Some(Commands::Lint { range, llm }) => {
+                assert_eq!(range.as_deref(), Some("main..HEAD"));
+                assert!(llm);
+            }
+            _ => panic!("Expected Lint command"),
+        }
+    }
+
+    #[test]
+    fn test_pr() {
+        let args = Cli::parse_from(["program", "pr"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Pr {
+                base: None,
+                body_file: None
+            })
+        ));
+
+        let args = Cli::parse_from(["program", "pr", "--base", "develop", "--body-file", "pr.md"]);
+        match args.command {
+            Some(Commands::Pr { base, body_file }) => {
+                assert_eq!(base.as_deref(), Some("develop"));
+                assert_eq!(body_file, Some(PathBuf::from("pr.md")));
+            }
+            _ => panic!("Expected Pr command"),
+        }
+    }
+
+    #[test]
+    fn test_changelog() {
+        let args = Cli::parse_from(["program", "changelog", "--from", "v1.2.0"]);
+        match args.command {
+            Some(Commands::Changelog { from, to, update }) => {
+                assert_eq!(from, "v1.2.0");
+                assert_eq!(to, "HEAD");
+                assert!(!update);
+            }
+            _ => panic!("Expected Changelog command"),
+        }
+
+        let args = Cli::parse_from([
+            "program",
+            "changelog",
+            "--from",
+            "v1.2.0",
+            "--to",
+            "v1.3.0",
+            "--update",
+        ]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Changelog { to, update: true, .. }) if to == "v1.3.0"
+        ));
+    }
+
+    #[test]
+    fn test_tag() {
+        let args = Cli::parse_from(["program", "tag", "v1.3.0"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Tag { name, yes: false }) if name == "v1.3.0"
+        ));
+
+        let args = Cli::parse_from(["program", "tag", "v1.3.0", "-y"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Tag { yes: true, .. })
+        ));
+        assert!(Cli::try_parse_from(["program", "tag"]).is_err());
+    }
+
+    #[test]
+    fn test_output() {
+        assert_eq!(Cli::parse_from(["program"]).output, OutputFormat::Text);
+        assert_eq!(
+            Cli::parse_from(["program", "--output", "json"]).output,
+            OutputFormat::Json
+        );
+        assert_eq!(
+            Cli::parse_from(["program", "config", "show", "--output", "json"]).output,
+            OutputFormat::Json
+        );
+        assert!(Cli::try_parse_from(["program", "--output", "yaml"]).is_err());
+    }
+
+    #[test]
+    fn test_exclude() {
+        assert!(Cli::parse_from(["program"]).exclude.is_empty());
+        let args = Cli::parse_from([
+            "program",
+            "--exclude",
+            "package-lock.json",
+            "--exclude",
+            "*.min.js,target/**",
+        ]);
+        assert_eq!(
+            args.exclude,
+            vec!["package-lock.json", "*.min.js", "target/**"]
+        );
+        let args = Cli::parse_from(["program", "pr", "--exclude", "Cargo.lock"]);
+        assert_eq!(args.exclude, vec!["Cargo.lock"]);
+    }
+
+    #[test]
+    fn test_history() {
+        let args = Cli::parse_from(["program", "history"]);
+        match args.command {
+            Some(Commands::History {
+                command: None,
+                search: None,
+                limit: 20,
+                all_repos: false,
+            }) => {}
+            _ => panic!("Expected History command"),
+        }
+
+        let args = Cli::parse_from([
+            "program",
+            "history",
+            "--search",
+            "login",
+            "--limit",
+            "5",
+            "--all-repos",
+        ]);
+        match args.command {
+            Some(Commands::History {
+                search,
+                limit: 5,
+                all_repos: true,
+                ..
+            }) => assert_eq!(search.as_deref(), Some("login")),
+            _ => panic!("Expected History command"),
+        }
+
+        let args = Cli::parse_from(["program", "history", "reuse", "1a2b"]);
+        match args.command {
+            Some(Commands::History {
+                command: Some(HistoryCommands::Reuse { id }),
+                ..
+            }) => assert_eq!(id, "1a2b"),
+            _ => panic!("Expected History Reuse command"),
+        }
+        assert!(
+            Cli::try_parse_from(["program", "history", "--limit", "5", "reuse", "1a2b"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_usage() {
+        let args = Cli::parse_from(["program", "usage"]);
+        assert!(matches!(args.command, Some(Commands::Usage { days: None })));
+
+        let args = Cli::parse_from(["program", "usage", "--days", "7"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Usage { days: Some(7) })
+        ));
+        assert!(Cli::try_parse_from(["program", "usage", "--days", "-1"]).is_err());
+    }
+
+    #[test]
+    fn test_all() {
+        let args = Cli::parse_from(["program", "-A", "-c"]);
+        assert!(args.all && args.auto_commit);
+        assert!(Cli::parse_from(["program", "--all"]).all);
+        assert!(Cli::try_parse_from(["program", "-A", "-a"]).is_err());
+        assert!(Cli::try_parse_from(["program", "-A", "--print"]).is_err());
+    }
+
+    #[test]
+    fn test_diff_ref_and_range() {
+        let args = Cli::parse_from(["program", "--diff-ref", "HEAD~3"]);
+        assert_eq!(args.diff_ref.as_deref(), Some("HEAD~3"));
+        assert!(args.prints_only());
+
+        let args = Cli::parse_from(["program", "--range", "main..feature", "--output", "json"]);
+        assert_eq!(args.range.as_deref(), Some("main..feature"));
+
+        assert!(!Cli::parse_from(["program"]).prints_only());
+        assert!(Cli::try_parse_from(["program", "--range", "a..b", "--diff-ref", "c"]).is_err());
+        assert!(Cli::try_parse_from(["program", "--range", "a..b", "-c"]).is_err());
+        assert!(Cli::try_parse_from(["program", "--diff-ref", "main", "--stdin"]).is_err());
+    }
+
+    #[test]
+    fn test_stdin() {
+        let args = Cli::parse_from(["program", "--stdin", "--output", "json"]);
+        assert!(args.stdin);
+        assert!(!args.print);
+        assert!(Cli::try_parse_from(["program", "--stdin", "-c"]).is_err());
+        assert!(Cli::try_parse_from(["program", "--stdin", "--amend"]).is_err());
+    }
+
+    #[test]
+    fn test_print() {
+        let args = Cli::parse_from(["program", "--print", "--offline"]);
+        assert!(args.print && args.offline);
+        assert!(Cli::parse_from(["program", "--message-only"]).print);
+
+        // Nothing is staged, committed or picked in print mode
+        for flag in ["-a", "-c", "-p", "--amend", "--sandbox"] {
+            assert!(Cli::try_parse_from(["program", "--print", flag]).is_err());
+        }
+        assert!(Cli::try_parse_from(["program", "--print", "-n", "3"]).is_err());
+    }
+
+    #[test]
+    fn test_verbosity() {
+        assert_eq!(Cli::parse_from(["program"]).verbose, 0);
+        assert_eq!(Cli::parse_from(["program", "-vv", "--print"]).verbose, 2);
+        assert_eq!(Cli::parse_from(["program", "ping", "-v"]).verbose, 1);
+
+        let args = Cli::parse_from(["program", "--log-level", "debug"]);
+        assert_eq!(args.log_level, Some(LevelFilter::DEBUG));
+        assert!(Cli::try_parse_from(["program", "--log-level", "loud"]).is_err());
+        assert!(Cli::try_parse_from(["program", "-v", "--log-level", "info"]).is_err());
+    }
+
+    #[test]
+    fn test_output_file() {
+        let args = Cli::parse_from(["program", "-o", "msg.txt", "--print"]);
+        assert_eq!(args.output_file, Some(Some(PathBuf::from("msg.txt"))));
+        assert!(args.prints_only());
+
+        // Without a path, the message goes to COMMIT_EDITMSG
+        let args = Cli::parse_from(["program", "--output-file", "--offline"]);
+        assert_eq!(args.output_file, Some(None));
+        assert!(args.offline);
+
+        for flag in ["-a", "-c", "--amend", "--sandbox"] {
+            assert!(Cli::try_parse_from(["program", "-o", "msg.txt", flag]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_hook() {
+        let args = Cli::parse_from(["program", "hook", "install", "--force"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Hook(HookCommands::Install { force: true }))
+        ));
+
+        let args = Cli::parse_from(["program", "hook", "run", ".git/COMMIT_EDITMSG"]);
+        match args.command {
+            Some(Commands::Hook(HookCommands::Run {
+                message_file,
+                source,
+                sha,
+            })) => {
+                assert_eq!(message_file, PathBuf::from(".git/COMMIT_EDITMSG"));
+                assert_eq!((source, sha), (None, None));
+            }
+            _ => panic!("Expected Hook Run command"),
+        }
+
+        let args = Cli::parse_from([
+            "program",
+            "hook",
+            "run",
+            ".git/COMMIT_EDITMSG",
+            "commit",
+            "HEAD",
+        ]);
+        match args.command {
+            Some(Commands::Hook(HookCommands::Run { source, sha, .. })) => {
+                assert_eq!(source.as_deref(), Some("commit"));
+                assert_eq!(sha.as_deref(), Some("HEAD"));
+            }
+            _ => panic!("Expected Hook Run command"),
+        }
+    }
+
+    #[test]
+    fn test_reword() {
+        let args = Cli::parse_from(["program", "reword", "main..HEAD"]);
+        match args.command {
+            Some(Commands::Reword { revs, yes, force }) => {
+                assert_eq!(revs, "main..HEAD");
+                assert!(!yes && !force);
+            }
+            _ => panic!("Expected Reword command"),
+        }
+
+        let args = Cli::parse_from(["program", "reword", "HEAD~2", "-y", "--force"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Reword {
+                yes: true,
+                force: true,
+                ..
+            })
+        ));
+        assert!(Cli::try_parse_from(["program", "reword"]).is_err());
+    }
+
+    #[test]
+    fn test_translate() {
+        let args = Cli::parse_from(["program", "translate"]);
+        match args.command {
+            Some(Commands::Translate {
+                rev,
+                to,
+                pending,
+                apply,
+                ..
+            }) => {
+                assert_eq!((rev.as_str(), to.as_str()), ("HEAD", "English"));
+                assert!(!pending && !apply);
+            }
+            _ => panic!("Expected Translate command"),
+        }
+
+        let args = Cli::parse_from([
+            "program",
+            "translate",
+            "HEAD~1",
+            "--to",
+            "ja",
+            "--apply",
+            "-y",
+        ]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Translate {
+                apply: true,
+                yes: true,
+                force: false,
+                ..
+            })
+        ));
+        assert!(Cli::try_parse_from(["program", "translate", "--pending"]).is_ok());
+        assert!(Cli::try_parse_from(["program", "translate", "HEAD~1", "--pending"]).is_err());
+        assert!(Cli::try_parse_from(["program", "translate", "-y"]).is_err());
+    }
+
+    #[test]
+    fn test_search() {
+        let args = Cli::parse_from(["program", "search", "retry logic", "--limit", "3"]);
+        match args.command {
+            Some(Commands::Search {
+                query,
+                limit,
+                reindex,
+            }) => {
+                assert_eq!((query.as_str(), limit), ("retry logic", 3));
+                assert!(!reindex);
+            }
+            _ => panic!("Expected Search command"),
+        }
+        assert!(Cli::try_parse_from(["program", "search"]).is_err());
+    }
+
+    #[test]
+    fn test_experiment_run() {
+        let args = Cli::parse_from([
+            "program",
+            "experiment",
+            "run",
+            "--prompts",
+            "a.toml,b.toml",
+            "--revs",
+            "HEAD~20..HEAD",
+        ]);
+        match args.command {
+            Some(Commands::Experiment(ExperimentCommands::Run { prompts, revs })) => {
+                assert_eq!(
+                    prompts,
+                    vec![PathBuf::from("a.toml"), PathBuf::from("b.toml")]
+                );
+                assert_eq!(revs, "HEAD~20..HEAD");
+            }
+            _ => panic!("Expected Experiment Run command"),
+        }
+
+        assert!(Cli::try_parse_from(["program", "experiment", "run", "--revs", "HEAD"]).is_err());
     }
 
     #[test]
@@ -121,6 +1747,41 @@ mod tests {
         assert!(args.auto_add);
         assert!(args.auto_commit);
         assert!(args.auto_push);
+        assert!(!args.sandbox);
+
+        let args = Cli::parse_from(["program", "-a", "--sandbox"]);
+        assert!(args.auto_add);
+        assert!(args.sandbox);
+        assert!(!args.ignore_lock);
+        assert!(!args.offline);
+        assert!(Cli::parse_from(["program", "--offline"]).offline);
+
+        assert!(Cli::parse_from(["program", "--ignore-lock"]).ignore_lock);
+    }
+
+    #[test]
+    fn test_push_flags() {
+        let args = Cli::parse_from(["program", "-p", "--remote", "fork", "--push-branch", "wip"]);
+        assert_eq!(args.remote.as_deref(), Some("fork"));
+        assert_eq!(args.push_branch.as_deref(), Some("wip"));
+        assert_eq!(Cli::parse_from(["program", "-p"]).remote, None);
+
+        // They only make sense with a push
+        assert!(Cli::try_parse_from(["program", "--remote", "fork"]).is_err());
+        assert!(Cli::try_parse_from(["program", "--push-branch", "wip"]).is_err());
+    }
+
+    #[test]
+    fn test_amend_flags() {
+        let args = Cli::parse_from(["program", "--amend"]);
+        assert!(args.amend);
+        assert!(!args.force_amend);
+
+        let args = Cli::parse_from(["program", "--amend", "--force-amend", "-c"]);
+        assert!(args.amend && args.force_amend && args.auto_commit);
+
+        // Forcing only makes sense when amending
+        assert!(Cli::try_parse_from(["program", "--force-amend"]).is_err());
     }
 
     #[test]
@@ -154,6 +1815,36 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_doctor() {
+        let args = Cli::parse_from(["program", "doctor", "--verify-binary"]);
+        match args.command {
+            Some(Commands::Doctor {
+                verify_binary,
+                bundle,
+            }) => {
+                assert!(verify_binary);
+                assert_eq!(bundle, None);
+            }
+            _ => panic!("Expected Doctor command"),
+        }
+
+        for (args, expected) in [
+            (vec!["program", "doctor", "--bundle"], "aic-bundle.tar.gz"),
+            (
+                vec!["program", "doctor", "--bundle", "report.tar.gz"],
+                "report.tar.gz",
+            ),
+        ] {
+            match Cli::parse_from(args).command {
+                Some(Commands::Doctor { bundle, .. }) => {
+                    assert_eq!(bundle, Some(PathBuf::from(expected)))
+                }
+                _ => panic!("Expected Doctor command"),
+            }
+        }
+    }
+
     #[test]
     fn test_config_setup() {
         let args = Cli::parse_from([
@@ -0,0 +1,240 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// commitlint configuration files, in the order commitlint looks for them
+pub const CONFIG_FILES: &[&str] = &[
+    ".commitlintrc",
+    ".commitlintrc.json",
+    ".commitlintrc.yaml",
+    ".commitlintrc.yml",
+    ".commitlintrc.js",
+    ".commitlintrc.cjs",
+    ".commitlintrc.mjs",
+    ".commitlintrc.ts",
+    "commitlint.config.js",
+    "commitlint.config.cjs",
+    "commitlint.config.mjs",
+    "commitlint.config.ts",
+];
+
+/// The commitlint rules aic understands, `None` for rules that aren't set
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommitlintRules {
+    pub types: Option<Vec<String>>,
+    pub scopes: Option<Vec<String>>,
+    pub header_max_length: Option<usize>,
+}
+
+impl CommitlintRules {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// The first commitlint configuration file in `dir`
+pub fn find_config(dir: &Path) -> Option<PathBuf> {
+    CONFIG_FILES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// Read the rules of a commitlint configuration file
+pub fn load(path: &Path) -> Result<CommitlintRules> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+    Ok(parse(&contents))
+}
+
+/// Read `type-enum`, `scope-enum` and `header-max-length` from a commitlint
+/// configuration in JSON, YAML or JavaScript
+///
+/// The configuration isn't evaluated: rules are only read when their values
+/// are written out, and rules that are disabled or use `never` are skipped.
+pub fn parse(contents: &str) -> CommitlintRules {
+    let list = |rule: &str| {
+        rule_values(contents, rule, true)
+            .filter(|values| !values.is_empty())
+            .map(|values| values.into_iter().map(str::to_string).collect())
+    };
+    CommitlintRules {
+        types: list("type-enum"),
+        scopes: list("scope-enum"),
+        header_max_length: rule_values(contents, "header-max-length", false)
+            .and_then(|values| values.first()?.parse().ok()),
+    }
+}
+
+// The values of an enabled `always` rule, after its level and applicability
+fn rule_values<'a>(contents: &'a str, rule: &str, list: bool) -> Option<Vec<&'a str>> {
+    let key = Regex::new(&format!(
+        r#"(?m)^([ \t]*)(?:.*[{{,][ \t]*)?["'`]?{rule}["'`]?[ \t]*:"#
+    ))
+    .expect("rule names are valid in patterns");
+    let captures = key.captures(contents)?;
+    let indent = captures[1].len();
+    let rest = &contents[captures.get(0)?.end()..];
+
+    // `[2, 'always', [...]]` inline, or a YAML block sequence on the next lines
+    let inline = rest.trim_start_matches([' ', '\t']);
+    let value = if inline.starts_with('[') {
+        let value = &inline[..bracket_end(inline)?];
+        // A variable in place of the list can't be read without running the file
+        if list && !value[1..].contains('[') {
+            return None;
+        }
+        value
+    } else if inline.starts_with(['\n', '\r']) {
+        let lines = inline.trim_start_matches(['\n', '\r']);
+        let end = lines
+            .split_inclusive('\n')
+            .take_while(|line| {
+                line.trim().is_empty()
+                    || (line.len() - line.trim_start().len() > indent
+                        && line.trim_start().starts_with('-'))
+            })
+            .map(str::len)
+            .sum();
+        &lines[..end]
+    } else {
+        return None;
+    };
+
+    let scalar = Regex::new(r#"'([^']*)'|"([^"]*)"|`([^`]*)`|([A-Za-z0-9_@.][A-Za-z0-9_@./-]*)"#)
+        .expect("the scalar pattern is valid");
+    let mut values = scalar.captures_iter(value).filter_map(|captures| {
+        (1..=4)
+            .find_map(|group| captures.get(group))
+            .map(|value| value.as_str())
+    });
+    let level = values.next()?;
+    let applicability = values.next()?;
+    if level == "0" || level.ends_with("Disabled") || applicability != "always" {
+        return None;
+    }
+    Some(values.collect())
+}
+
+// Length of the bracketed value at the start of `text`, skipping quoted brackets
+fn bracket_end(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut quote = None;
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '[') => depth += 1,
+            (None, ']') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_json() {
+        let rules = parse(
+            r#"{
+              "extends": ["@commitlint/config-conventional"],
+              "rules": {
+                "type-enum": [2, "always", ["feat", "fix", "wip", "deps"]],
+                "scope-enum": [0, "always", ["api"]],
+                "header-max-length": [2, "always", 100]
+              }
+            }"#,
+        );
+        assert_eq!(
+            rules,
+            CommitlintRules {
+                types: Some(vec![
+                    "feat".to_string(),
+                    "fix".to_string(),
+                    "wip".to_string(),
+                    "deps".to_string()
+                ]),
+                scopes: None,
+                header_max_length: Some(100),
+            }
+        );
+        assert!(parse(r#"{"extends": ["@commitlint/config-conventional"]}"#).is_empty());
+    }
+
+    #[test]
+    fn test_parse_javascript() {
+        let rules = parse(
+            "const types = ['feat', 'fix'];\n\
+             module.exports = {\n  \
+               extends: ['@commitlint/config-conventional'],\n  \
+               rules: {\n    \
+                 'type-enum': [2, 'always', types],\n    \
+                 'scope-enum': [RuleConfigSeverity.Error, 'always', ['api', 'web-app']],\n    \
+                 'header-max-length': [2, 'never', 50],\n  \
+               },\n\
+             };\n",
+        );
+        // Variables and `never` rules are left alone
+        assert_eq!(rules.types, None);
+        assert_eq!(
+            rules.scopes,
+            Some(vec!["api".to_string(), "web-app".to_string()])
+        );
+        assert_eq!(rules.header_max_length, None);
+
+        let rules = parse("export default { rules: { 'header-max-length': [2, 'always', 88] } };");
+        assert_eq!(rules.header_max_length, Some(88));
+    }
+
+    #[test]
+    fn test_parse_yaml() {
+        let rules = parse(
+            "extends:\n  - '@commitlint/config-conventional'\n\
+             rules:\n  \
+               type-enum:\n    \
+                 - 2\n    \
+                 - always\n    \
+                 - - feat\n      \
+                   - fix\n      \
+                   - infra\n  \
+               scope-enum: [2, always, [core, cli]]\n  \
+               header-max-length: [1, always, 80]\n",
+        );
+        assert_eq!(
+            rules,
+            CommitlintRules {
+                types: Some(vec![
+                    "feat".to_string(),
+                    "fix".to_string(),
+                    "infra".to_string()
+                ]),
+                scopes: Some(vec!["core".to_string(), "cli".to_string()]),
+                header_max_length: Some(80),
+            }
+        );
+    }
+
+    #[test]
+    fn test_find_config() {
+        let temp_dir = TempDir::new().unwrap();
+        assert_eq!(find_config(temp_dir.path()), None);
+
+        fs::write(temp_dir.path().join("commitlint.config.js"), "").unwrap();
+        fs::write(temp_dir.path().join(".commitlintrc.json"), "").unwrap();
+        assert_eq!(
+            find_config(temp_dir.path()),
+            Some(temp_dir.path().join(".commitlintrc.json"))
+        );
+    }
+}
@@ -1,9 +1,28 @@
+use crate::commitlint;
+use crate::context::DEFAULT_DATE_FORMAT;
+use crate::generated;
+use crate::glob;
+use crate::lint::{self, CommitValidation, LintRules};
+use crate::llm::{self, ModelPricing, RetryPolicy, Sampling, DEFAULT_REQUEST_TIMEOUT};
+use crate::priority::{DiffPriority, FileRole};
+use crate::redact;
+use crate::secrets;
+use crate::spell::Language;
+use crate::ticket::{
+    DEFAULT_TICKET_FOOTER, DEFAULT_TICKET_PATTERN, GITHUB_TICKET_FOOTER, GITHUB_TICKET_PATTERN,
+};
+use crate::tracker::TrackerKind;
 use anyhow::{Context, Result};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::env;
+use std::fmt;
 use std::fs::{self, File};
 use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
 
 const DEFAULT_SYSTEM_PROMPT: &str = "You are an expert at writing clear and concise commit messages. \
     Follow these rules strictly:\n\n\
@@ -34,8 +53,227 @@ const DEFAULT_USER_PROMPT: &str =
     the appropriate type and scope:\n\n\
     ```diff\n{}\n```";
 
-const PROJECT_CONFIG_FILENAME: &str = ".aic.toml";
+const DEFAULT_REVIEW_PROMPT: &str = "You are a careful code reviewer. Review the staged changes \
+    before they are committed and point out:\n\n\
+    1. Potential bugs: logic errors, unhandled edge cases and errors, security problems\n\
+    2. Missing or outdated tests for the changed behavior\n\
+    3. Style and readability issues that matter, not nitpicks\n\n\
+    Group the findings under `## Bugs`, `## Tests` and `## Style`, one bullet per finding that \
+    names the file and the line or symbol. Leave out empty sections, answer `No issues found.` \
+    when there is nothing worth changing, and do not restate the diff.";
+
+pub const PROJECT_CONFIG_FILENAME: &str = ".aic.toml";
+
+/// Gitignore-style list of files left out of the diff sent to the model
+const IGNORE_FILENAME: &str = ".aicignore";
+
+/// Version-controlled prompts at the repository root, `system.md` and `user.md`
+const PROMPTS_DIR: &str = ".aic/prompts";
+
+/// Environment variable checked for the issue tracker token before `ticket_api_token`
+const TICKET_API_TOKEN_ENV_VAR: &str = "AIC_TICKET_API_TOKEN";
+
+/// Environment variables checked for the API token, in order of precedence
+const API_TOKEN_ENV_VARS: &[&str] = &["AIC_API_TOKEN", "OPENAI_API_KEY"];
+
+/// Files treated as test snapshots (jest `__snapshots__`, insta `.snap`)
+const DEFAULT_SNAPSHOT_PATTERNS: &[&str] =
+    &["__snapshots__/", "*.snap", "*.snap.new", "*.pending-snap"];
+
+/// Regenerations of a message that breaks the commit rules before falling back to editing
+const DEFAULT_MAX_REPAIR_ATTEMPTS: usize = 2;
+
+/// Diffs larger than this (1 MiB) need confirmation before they are sent
+const DEFAULT_MAX_DIFF_BYTES: usize = 1024 * 1024;
+
+/// Embedding model of `aic search`
+const DEFAULT_EMBEDDING_MODEL: &str = "text-embedding-3-small";
+
+/// Keys accepted in a config file, `redact`, `templates`, `model_prices`,
+/// `diff_priority` and `profiles` hold tables
+pub const KEYS: &[&str] = &[
+    "api_token",
+    "api_base_url",
+    "model",
+    "system_prompt",
+    "user_prompt",
+    "max_input_tokens",
+    "large_diff_strategy",
+    "max_diff_bytes",
+    "max_retries",
+    "retry_base_delay_ms",
+    "request_timeout_secs",
+    "requests_per_minute",
+    "snapshot_patterns",
+    "commit_validation",
+    "max_repair_attempts",
+    "max_generation_seconds",
+    "fallback_model",
+    "default_profile",
+    "protected_branches",
+    "scopes",
+    "ticket_pattern",
+    "ticket_footer",
+    "date_format",
+    "milestone",
+    "cost_confirmation_threshold",
+    "confirm_send",
+    "exclude_patterns",
+    "system_prompt_file",
+    "user_prompt_file",
+    "temperature",
+    "top_p",
+    "max_tokens",
+    "review_prompt",
+    "trailers",
+    "signoff",
+    "gpg_sign",
+    "gpg_key_id",
+    "no_verify",
+    "generated_patterns",
+    "secret_scan",
+    "secret_patterns",
+    "history_context",
+    "symbol_summary",
+    "embedding_model",
+    "ticket_tracker",
+    "ticket_api_base_url",
+    "ticket_api_token",
+    "ticket_api_user",
+    "commit_types",
+    "max_header_length",
+    "max_rate_limit_wait_secs",
+    "structured_output",
+    "spell_check",
+    "redact",
+    "templates",
+    "model_prices",
+    "diff_priority",
+    "profiles",
+];
+
+/// Keys accepted in a `[profiles.<name>]` table
+pub const PROFILE_KEYS: &[&str] = &["api_token", "api_base_url", "model"];
+
+/// How to handle a diff that does not fit the model's context window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LargeDiffStrategy {
+    /// Summarize the diff per file first, then generate from the summaries
+    Summarize,
+
+    /// Cut the diff off at the token budget
+    Truncate,
+}
+
+impl FromStr for LargeDiffStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "summarize" => Ok(Self::Summarize),
+            "truncate" => Ok(Self::Truncate),
+            _ => Err(anyhow::anyhow!(
+                "Invalid value for large_diff_strategy: expected 'summarize' or 'truncate', got '{}'",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for LargeDiffStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Summarize => write!(f, "summarize"),
+            Self::Truncate => write!(f, "truncate"),
+        }
+    }
+}
+
+/// What to do when the diff seems to contain credentials
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretScan {
+    /// List what was found and ask before sending the diff
+    Confirm,
+
+    /// Replace what was found with a placeholder and send the rest
+    Redact,
+
+    /// Send the diff as it is
+    Off,
+}
+
+impl FromStr for SecretScan {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "confirm" => Ok(Self::Confirm),
+            "redact" => Ok(Self::Redact),
+            "off" => Ok(Self::Off),
+            _ => Err(anyhow::anyhow!(
+                "Invalid value for secret_scan: expected 'confirm', 'redact' or 'off', got '{}'",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SecretScan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Confirm => write!(f, "confirm"),
+            Self::Redact => write!(f, "redact"),
+            Self::Off => write!(f, "off"),
+        }
+    }
+}
 
+/// A named set of provider settings, selected with `--profile` or `default_profile`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Profile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_token: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_base_url: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+impl Profile {
+    fn set(&mut self, key: &str, value: Option<String>) -> Result<()> {
+        match key {
+            "api_token" => self.api_token = value,
+            "api_base_url" => self.api_base_url = value,
+            "model" => self.model = value,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Unknown profile key: {} (expected api_token, api_base_url or model)",
+                    key
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        match key {
+            "api_token" => self.api_token.clone(),
+            "api_base_url" => self.api_base_url.clone(),
+            "model" => self.model.clone(),
+            _ => None,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// Settings of aic, merged from the global config file, `.aic.toml` and the environment
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
     // Skip serializing None values to keep the config file clean
@@ -53,6 +291,176 @@ pub struct Config {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub user_prompt: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_input_tokens: Option<usize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub large_diff_strategy: Option<LargeDiffStrategy>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_diff_bytes: Option<usize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_retries: Option<usize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_base_delay_ms: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_timeout_secs: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot_patterns: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit_validation: Option<CommitValidation>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_repair_attempts: Option<usize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_generation_seconds: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback_model: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_profile: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protected_branches: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ticket_pattern: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ticket_footer: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date_format: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub milestone: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_confirmation_threshold: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confirm_send: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude_patterns: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_prompt_file: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_prompt_file: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub review_prompt: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trailers: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signoff: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gpg_sign: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gpg_key_id: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub no_verify: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generated_patterns: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret_scan: Option<SecretScan>,
+
+    /// Regular expressions of credentials besides the built-in ones
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret_patterns: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history_context: Option<usize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol_summary: Option<bool>,
+
+    /// Model embedding commit messages for `aic search`, `local` for none
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embedding_model: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ticket_tracker: Option<TrackerKind>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ticket_api_base_url: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ticket_api_token: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ticket_api_user: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit_types: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_header_length: Option<usize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_rate_limit_wait_secs: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub structured_output: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spell_check: Option<Language>,
+
+    // Tables come last so TOML can serialize the plain values before them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redact: Option<redact::Rules>,
+
+    /// Body templates by commit type, e.g. `fix = "Root cause: ..."`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub templates: Option<BTreeMap<String, String>>,
+
+    /// Prices in USD per million tokens by model name prefix
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_prices: Option<BTreeMap<String, ModelPricing>>,
+
+    /// Weights of the kinds of files kept first when the diff is truncated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff_priority: Option<BTreeMap<FileRole, u32>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profiles: Option<BTreeMap<String, Profile>>,
+
+    /// Token of the profile `with_profile` applied, which wins over the
+    /// environment so a key meant for another host never goes to the profile's
+    #[serde(skip)]
+    pub profile_api_token: Option<String>,
 }
 
 impl Default for Config {
@@ -63,18 +471,165 @@ impl Default for Config {
             model: Some("gpt-3.5-turbo".to_string()),
             system_prompt: Some(DEFAULT_SYSTEM_PROMPT.to_string()),
             user_prompt: Some(DEFAULT_USER_PROMPT.to_string()),
+            max_input_tokens: None,
+            large_diff_strategy: None,
+            max_diff_bytes: None,
+            max_retries: None,
+            retry_base_delay_ms: None,
+            request_timeout_secs: None,
+            requests_per_minute: None,
+            snapshot_patterns: None,
+            commit_validation: None,
+            max_repair_attempts: None,
+            max_generation_seconds: None,
+            fallback_model: None,
+            default_profile: None,
+            protected_branches: None,
+            scopes: None,
+            ticket_pattern: None,
+            ticket_footer: None,
+            date_format: None,
+            milestone: None,
+            cost_confirmation_threshold: None,
+            confirm_send: None,
+            exclude_patterns: None,
+            system_prompt_file: None,
+            user_prompt_file: None,
+            temperature: None,
+            top_p: None,
+            max_tokens: None,
+            review_prompt: None,
+            trailers: None,
+            signoff: None,
+            gpg_sign: None,
+            gpg_key_id: None,
+            no_verify: None,
+            generated_patterns: None,
+            secret_scan: None,
+            secret_patterns: None,
+            history_context: None,
+            symbol_summary: None,
+            embedding_model: None,
+            ticket_tracker: None,
+            ticket_api_base_url: None,
+            ticket_api_token: None,
+            ticket_api_user: None,
+            commit_types: None,
+            max_header_length: None,
+            max_rate_limit_wait_secs: None,
+            structured_output: None,
+            spell_check: None,
+            redact: None,
+            templates: None,
+            model_prices: None,
+            diff_priority: None,
+            profiles: None,
+            profile_api_token: None,
         }
     }
 }
 
+/// Move a config directory left by an older version to `config_dir`, unless
+/// both are the same or `config_dir` already has a config. Entries already in
+/// `config_dir` are kept. Returns whether anything was moved
+fn migrate_config_dir(legacy_dir: &Path, config_dir: &Path) -> Result<bool> {
+    if !legacy_dir.join("config.toml").exists() || config_dir.join("config.toml").exists() {
+        return Ok(false);
+    }
+    if let (Ok(legacy), Ok(current)) = (legacy_dir.canonicalize(), config_dir.canonicalize()) {
+        if legacy == current {
+            return Ok(false);
+        }
+    }
+
+    fs::create_dir_all(config_dir).context("Failed to create config directory")?;
+    let entries = fs::read_dir(legacy_dir).context("Could not read the legacy config directory")?;
+    for entry in entries {
+        let entry = entry.context("Could not read the legacy config directory")?;
+        let target = config_dir.join(entry.file_name());
+        if target.exists() {
+            continue;
+        }
+        // Renaming fails across file systems, copy single files instead
+        if fs::rename(entry.path(), &target).is_err() {
+            fs::copy(entry.path(), &target).with_context(|| {
+                format!(
+                    "Failed to move {} to {}",
+                    entry.path().display(),
+                    target.display()
+                )
+            })?;
+            fs::remove_file(entry.path()).ok();
+        }
+    }
+    // Only removed once empty, anything left behind stays where it was
+    fs::remove_dir(legacy_dir).ok();
+
+    Ok(true)
+}
+
+// Tests point the global config at a temporary directory through this rather
+// than HOME or XDG_CONFIG_HOME, so they never touch the developer's config and
+// don't race on process-wide environment variables
+#[cfg(test)]
+thread_local! {
+    static TEST_CONFIG_BASE: std::cell::RefCell<Option<PathBuf>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Keep the global config of the tests on this thread in `base/aic`
+#[cfg(test)]
+pub(crate) fn use_test_config_base(base: &Path) {
+    TEST_CONFIG_BASE.with(|current| *current.borrow_mut() = Some(base.to_path_buf()));
+}
+
 impl Config {
+    /// Directory for the global config, `$XDG_CONFIG_HOME/aic` (`~/.config/aic`
+    /// by default) on Linux, `~/Library/Application Support/aic` on macOS and
+    /// `%APPDATA%\aic` on Windows
     pub fn config_dir() -> Result<PathBuf> {
-        let home_dir = dirs::home_dir().context("Could not find home directory")?;
-        let config_dir = if cfg!(target_os = "windows") {
-            home_dir.join("AppData").join("Roaming").join("aic")
-        } else {
-            home_dir.join(".config").join("aic")
-        };
+        Self::config_dir_in(&Self::config_base()?)
+    }
+
+    /// The platform's config directory, after moving a config left in the
+    /// legacy location to it
+    #[cfg(not(test))]
+    fn config_base() -> Result<PathBuf> {
+        use colored::Colorize;
+
+        let base = dirs::config_dir().context("Could not find the config directory")?;
+        let config_dir = base.join("aic");
+        if let Some(legacy_dir) = Self::legacy_config_dir() {
+            if migrate_config_dir(&legacy_dir, &config_dir)? {
+                eprintln!(
+                    "{}",
+                    format!(
+                        "📦 Moved the config from {} to {}",
+                        legacy_dir.display(),
+                        config_dir.display()
+                    )
+                    .blue()
+                );
+            }
+        }
+        Ok(base)
+    }
+
+    /// The directory of `use_test_config_base`, or a temporary one, so tests
+    /// never reach the real config
+    #[cfg(test)]
+    fn config_base() -> Result<PathBuf> {
+        Ok(TEST_CONFIG_BASE.with(|base| {
+            base.borrow().clone().unwrap_or_else(|| {
+                env::temp_dir().join(format!("aic-test-config-{}", std::process::id()))
+            })
+        }))
+    }
+
+    /// The `aic` directory under a platform config directory such as
+    /// `~/.config`, created on demand
+    pub fn config_dir_in(base: &Path) -> Result<PathBuf> {
+        let config_dir = base.join("aic");
         if !config_dir.exists() {
             fs::create_dir_all(&config_dir).context("Failed to create config directory")?;
         }
@@ -82,6 +637,18 @@ impl Config {
         Ok(config_dir)
     }
 
+    /// Where older versions kept the global config, regardless of
+    /// `XDG_CONFIG_HOME` and the platform's conventions
+    #[cfg_attr(test, allow(dead_code))]
+    fn legacy_config_dir() -> Option<PathBuf> {
+        let home_dir = dirs::home_dir()?;
+        Some(if cfg!(target_os = "windows") {
+            home_dir.join("AppData").join("Roaming").join("aic")
+        } else {
+            home_dir.join(".config").join("aic")
+        })
+    }
+
     pub fn config_path() -> Result<PathBuf> {
         let config_dir = Self::config_dir()?;
         Ok(config_dir.join("config.toml"))
@@ -120,6 +687,48 @@ impl Config {
         Ok(None)
     }
 
+    /// The project config file to edit: the existing `.aic.toml`, or a new one
+    /// at the root of the git repository
+    pub fn project_config_path() -> Result<PathBuf> {
+        if let Some(path) = Self::find_project_config()? {
+            return Ok(path);
+        }
+        Self::find_repo_root()
+            .map(|root| root.join(PROJECT_CONFIG_FILENAME))
+            .context("Not in a git repository, the project config lives at its root")
+    }
+
+    // Find the root of the git repository containing the current directory
+    fn find_repo_root() -> Option<PathBuf> {
+        let current_dir = env::current_dir().ok()?;
+        current_dir
+            .ancestors()
+            .find(|dir| dir.join(".git").exists())
+            .map(Path::to_path_buf)
+    }
+
+    // Find the .aicignore file at the root of the git repository containing the current directory
+    pub fn find_ignore_file() -> Option<PathBuf> {
+        Some(Self::find_repo_root()?.join(IGNORE_FILENAME)).filter(|path| path.is_file())
+    }
+
+    // Find the .aic/prompts directory at the root of the git repository
+    pub fn find_prompts_dir() -> Option<PathBuf> {
+        Some(Self::find_repo_root()?.join(PROMPTS_DIR)).filter(|path| path.is_dir())
+    }
+
+    /// Patterns of the repository's .aicignore, empty when there is none
+    pub fn load_ignore_patterns() -> Result<Vec<String>> {
+        match Self::find_ignore_file() {
+            Some(path) => {
+                let contents = fs::read_to_string(&path)
+                    .with_context(|| format!("Could not read {}", path.display()))?;
+                Ok(parse_ignore_patterns(&contents))
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+
     // Load a config from a TOML file (now works for both global and project config)
     fn load_toml_config(path: &PathBuf) -> Result<Self> {
         let mut file = File::open(path).context("Could not open TOML config file")?;
@@ -133,7 +742,7 @@ impl Config {
     }
 
     // Load the global config from TOML
-    fn load_global_config() -> Result<Self> {
+    pub fn load_global_config() -> Result<Self> {
         let config_path = Self::config_path()?;
 
         if !config_path.exists() {
@@ -153,24 +762,340 @@ impl Config {
             model: override_config.model.or(base.model),
             system_prompt: override_config.system_prompt.or(base.system_prompt),
             user_prompt: override_config.user_prompt.or(base.user_prompt),
+            max_input_tokens: override_config.max_input_tokens.or(base.max_input_tokens),
+            large_diff_strategy: override_config
+                .large_diff_strategy
+                .or(base.large_diff_strategy),
+            max_diff_bytes: override_config.max_diff_bytes.or(base.max_diff_bytes),
+            max_retries: override_config.max_retries.or(base.max_retries),
+            retry_base_delay_ms: override_config
+                .retry_base_delay_ms
+                .or(base.retry_base_delay_ms),
+            request_timeout_secs: override_config
+                .request_timeout_secs
+                .or(base.request_timeout_secs),
+            requests_per_minute: override_config
+                .requests_per_minute
+                .or(base.requests_per_minute),
+            snapshot_patterns: override_config.snapshot_patterns.or(base.snapshot_patterns),
+            commit_validation: override_config.commit_validation.or(base.commit_validation),
+            max_repair_attempts: override_config
+                .max_repair_attempts
+                .or(base.max_repair_attempts),
+            max_generation_seconds: override_config
+                .max_generation_seconds
+                .or(base.max_generation_seconds),
+            fallback_model: override_config.fallback_model.or(base.fallback_model),
+            default_profile: override_config.default_profile.or(base.default_profile),
+            protected_branches: override_config
+                .protected_branches
+                .or(base.protected_branches),
+            scopes: override_config.scopes.or(base.scopes),
+            ticket_pattern: override_config.ticket_pattern.or(base.ticket_pattern),
+            ticket_footer: override_config.ticket_footer.or(base.ticket_footer),
+            date_format: override_config.date_format.or(base.date_format),
+            milestone: override_config.milestone.or(base.milestone),
+            cost_confirmation_threshold: override_config
+                .cost_confirmation_threshold
+                .or(base.cost_confirmation_threshold),
+            confirm_send: override_config.confirm_send.or(base.confirm_send),
+            exclude_patterns: override_config.exclude_patterns.or(base.exclude_patterns),
+            system_prompt_file: override_config
+                .system_prompt_file
+                .or(base.system_prompt_file),
+            user_prompt_file: override_config.user_prompt_file.or(base.user_prompt_file),
+            temperature: override_config.temperature.or(base.temperature),
+            top_p: override_config.top_p.or(base.top_p),
+            max_tokens: override_config.max_tokens.or(base.max_tokens),
+            review_prompt: override_config.review_prompt.or(base.review_prompt),
+            trailers: override_config.trailers.or(base.trailers),
+            signoff: override_config.signoff.or(base.signoff),
+            gpg_sign: override_config.gpg_sign.or(base.gpg_sign),
+            gpg_key_id: override_config.gpg_key_id.or(base.gpg_key_id),
+            no_verify: override_config.no_verify.or(base.no_verify),
+            generated_patterns: override_config
+                .generated_patterns
+                .or(base.generated_patterns),
+            secret_scan: override_config.secret_scan.or(base.secret_scan),
+            secret_patterns: override_config.secret_patterns.or(base.secret_patterns),
+            history_context: override_config.history_context.or(base.history_context),
+            symbol_summary: override_config.symbol_summary.or(base.symbol_summary),
+            embedding_model: override_config.embedding_model.or(base.embedding_model),
+            ticket_tracker: override_config.ticket_tracker.or(base.ticket_tracker),
+            ticket_api_base_url: override_config
+                .ticket_api_base_url
+                .or(base.ticket_api_base_url),
+            ticket_api_token: override_config.ticket_api_token.or(base.ticket_api_token),
+            ticket_api_user: override_config.ticket_api_user.or(base.ticket_api_user),
+            commit_types: override_config.commit_types.or(base.commit_types),
+            max_header_length: override_config.max_header_length.or(base.max_header_length),
+            max_rate_limit_wait_secs: override_config
+                .max_rate_limit_wait_secs
+                .or(base.max_rate_limit_wait_secs),
+            structured_output: override_config.structured_output.or(base.structured_output),
+            spell_check: override_config.spell_check.or(base.spell_check),
+            // Redaction rules are merged one by one
+            redact: match (base.redact, override_config.redact) {
+                (Some(base_rules), Some(override_rules)) => Some(override_rules.or(base_rules)),
+                (base_rules, override_rules) => override_rules.or(base_rules),
+            },
+            // Templates are merged by type, a project template replaces a global one
+            templates: match (base.templates, override_config.templates) {
+                (Some(mut base_templates), Some(override_templates)) => {
+                    base_templates.extend(override_templates);
+                    Some(base_templates)
+                }
+                (base_templates, override_templates) => override_templates.or(base_templates),
+            },
+            // Prices are merged by model, a project price replaces a global one
+            model_prices: match (base.model_prices, override_config.model_prices) {
+                (Some(mut base_prices), Some(override_prices)) => {
+                    base_prices.extend(override_prices);
+                    Some(base_prices)
+                }
+                (base_prices, override_prices) => override_prices.or(base_prices),
+            },
+            // Weights are merged by kind of file
+            diff_priority: match (base.diff_priority, override_config.diff_priority) {
+                (Some(mut base_weights), Some(override_weights)) => {
+                    base_weights.extend(override_weights);
+                    Some(base_weights)
+                }
+                (base_weights, override_weights) => override_weights.or(base_weights),
+            },
+            // Profiles are merged by name, a project profile replaces a global one
+            profiles: match (base.profiles, override_config.profiles) {
+                (Some(mut base_profiles), Some(override_profiles)) => {
+                    base_profiles.extend(override_profiles);
+                    Some(base_profiles)
+                }
+                (base_profiles, override_profiles) => override_profiles.or(base_profiles),
+            },
+            profile_api_token: override_config.profile_api_token.or(base.profile_api_token),
         }
     }
 
+    /// Apply the named profile, or `default_profile` when no name is given
+    ///
+    /// Settings of the profile take precedence over the top-level ones, and
+    /// its `api_token` over the token environment variables too.
+    pub fn with_profile(mut self, name: Option<&str>) -> Result<Self> {
+        let Some(name) = name.map(str::to_string).or(self.default_profile.clone()) else {
+            return Ok(self);
+        };
+
+        let profile = self
+            .profiles
+            .as_ref()
+            .and_then(|profiles| profiles.get(&name))
+            .cloned()
+            .with_context(|| {
+                format!(
+                    "Unknown profile '{}'. Available profiles: {}",
+                    name,
+                    self.profile_names().join(", ")
+                )
+            })?;
+
+        self.profile_api_token = profile.api_token.clone();
+        self.api_token = profile.api_token.or(self.api_token);
+        self.api_base_url = profile.api_base_url.or(self.api_base_url);
+        self.model = profile.model.or(self.model);
+        Ok(self)
+    }
+
+    /// Apply the `--temperature`, `--top-p` and `--max-tokens` flags
+    pub fn with_sampling_overrides(
+        mut self,
+        temperature: Option<f64>,
+        top_p: Option<f64>,
+        max_tokens: Option<u32>,
+    ) -> Result<Self> {
+        let overrides = [
+            ("temperature", temperature.map(|v| v.to_string())),
+            ("top_p", top_p.map(|v| v.to_string())),
+            ("max_tokens", max_tokens.map(|v| v.to_string())),
+        ];
+        for (key, value) in overrides {
+            if value.is_some() {
+                self.set_value(key, value)?;
+            }
+        }
+        Ok(self)
+    }
+
+    /// Leave out the files matching `patterns` as well, e.g. from `--exclude`
+    pub fn with_exclude_patterns(mut self, patterns: &[String]) -> Self {
+        if !patterns.is_empty() {
+            let mut exclude_patterns = self.get_exclude_patterns();
+            exclude_patterns.extend(patterns.iter().cloned());
+            self.exclude_patterns = Some(exclude_patterns);
+        }
+        self
+    }
+
+    /// Apply `--trailer key=value` overrides: a trailer replaces the configured
+    /// ones with the same key, and an empty value removes them
+    pub fn with_trailer_overrides(mut self, overrides: &[String]) -> Result<Self> {
+        if overrides.is_empty() {
+            return Ok(self);
+        }
+
+        let mut trailers = self.get_trailers();
+        for entry in overrides {
+            let (key, value) = entry
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .filter(|(key, _)| lint::is_footer_line(&format!("{key}: value")))
+                .with_context(|| format!("Invalid --trailer '{entry}', expected key=value"))?;
+            trailers.retain(|trailer| {
+                lint::parse_trailer(trailer)
+                    .is_none_or(|(other, _)| !other.eq_ignore_ascii_case(key))
+            });
+            if !value.is_empty() {
+                trailers.push(format!("{key}: {value}"));
+            }
+        }
+        self.trailers = Some(trailers);
+        Ok(self)
+    }
+
+    pub fn profile_names(&self) -> Vec<String> {
+        self.profiles
+            .as_ref()
+            .map(|profiles| profiles.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// The effective configuration: the config files with the `AIC_<KEY>`
+    /// environment variables on top
     pub fn load() -> Result<Self> {
-        // First load the global config
-        let global_config = Self::load_global_config()?;
+        Self::load_files()?.with_env_overrides()
+    }
+
+    /// The global and project config files merged, without environment overrides
+    pub fn load_files() -> Result<Self> {
+        // First load the global config, with prompt files relative to its directory
+        let global_config = Self::load_global_config()?.with_prompt_files(&Self::config_dir()?)?;
+
+        // Try to find and load project config, with prompt files relative to the .aic.toml
+        let project_config = match Self::find_project_config()? {
+            Some(project_config_path) => {
+                let project_dir = project_config_path.parent().unwrap_or(Path::new("."));
+                let project_config = Self::load_toml_config(&project_config_path)?;
+                Some(project_config.with_prompt_files(project_dir)?)
+            }
+            None => None,
+        };
+
+        // Prompts in .aic/prompts count as project settings
+        let project_config = match (project_config, Self::find_prompts_dir()) {
+            (project_config, Some(prompts_dir)) => {
+                let project_config = match project_config {
+                    Some(project_config) => project_config,
+                    None => toml::from_str("").context("Failed to create an empty config")?,
+                };
+                Some(project_config.with_project_prompts(&prompts_dir)?)
+            }
+            (project_config, None) => project_config,
+        };
 
-        // Try to find and load project config
-        if let Some(project_config_path) = Self::find_project_config()? {
-            // If project config exists, load it and merge with global config
-            let project_config = Self::load_toml_config(&project_config_path)?;
+        // The repository's commitlint rules beat the global config, not .aic.toml
+        let global_config = match Self::load_commitlint_config()? {
+            Some(commitlint_config) => Self::merge(global_config, commitlint_config),
+            None => global_config,
+        };
 
+        match project_config {
             // Merge configs, with project config taking precedence
-            Ok(Self::merge(global_config, project_config))
-        } else {
+            Some(project_config) => Ok(Self::merge(global_config, project_config)),
             // No project config, just use global config
-            Ok(global_config)
+            None => Ok(global_config),
+        }
+    }
+
+    /// The commitlint configuration file at the root of the repository
+    pub fn find_commitlint_config() -> Option<PathBuf> {
+        commitlint::find_config(&Self::find_repo_root()?)
+    }
+
+    // `commit_types`, `scopes` and `max_header_length` from the repository's
+    // commitlint configuration, `None` when there is none
+    fn load_commitlint_config() -> Result<Option<Self>> {
+        let Some(path) = Self::find_commitlint_config() else {
+            return Ok(None);
+        };
+        let rules = commitlint::load(&path)?;
+        if rules.is_empty() {
+            return Ok(None);
+        }
+
+        let mut config: Self = toml::from_str("").context("Failed to create an empty config")?;
+        config.commit_types = rules.types;
+        config.scopes = rules.scopes;
+        config.max_header_length = rules.header_max_length;
+        Ok(Some(config))
+    }
+
+    // Apply the `AIC_<KEY>` environment variables. Prompt files named there are
+    // resolved against the current directory
+    fn with_env_overrides(mut self) -> Result<Self> {
+        for (var, key, value) in env_overrides() {
+            match key {
+                "system_prompt_file" | "user_prompt_file" => {
+                    let path = env::current_dir()
+                        .context("Failed to get current directory")?
+                        .join(&value);
+                    let prompt = read_prompt_file(&var, &path)?;
+                    if key == "system_prompt_file" {
+                        self.system_prompt = Some(prompt);
+                    } else {
+                        self.user_prompt = Some(prompt);
+                    }
+                    self.set_value(key, Some(path.display().to_string()))?;
+                }
+                _ => {
+                    self.set_value(key, Some(value))
+                        .context(format!("Invalid value in {var}"))?;
+                    // An inline prompt from the environment beats a prompt file from a config file
+                    match key {
+                        "system_prompt" => self.system_prompt_file = None,
+                        "user_prompt" => self.user_prompt_file = None,
+                        _ => {}
+                    }
+                }
+            }
         }
+        Ok(self)
+    }
+
+    // Inline `system_prompt_file` and `user_prompt_file`, which take precedence over
+    // `system_prompt` and `user_prompt`. Relative paths are resolved against `dir`
+    fn with_prompt_files(mut self, dir: &Path) -> Result<Self> {
+        if let Some(file) = &self.system_prompt_file {
+            let path = dir.join(file);
+            self.system_prompt = Some(read_prompt_file("system_prompt_file", &path)?);
+            self.system_prompt_file = Some(path.display().to_string());
+        }
+        if let Some(file) = &self.user_prompt_file {
+            let path = dir.join(file);
+            self.user_prompt = Some(read_prompt_file("user_prompt_file", &path)?);
+            self.user_prompt_file = Some(path.display().to_string());
+        }
+        Ok(self)
+    }
+
+    // Use `system.md` and `user.md` from the prompts directory for the prompts not set yet
+    fn with_project_prompts(mut self, prompts_dir: &Path) -> Result<Self> {
+        let system_path = prompts_dir.join("system.md");
+        if self.system_prompt.is_none() && system_path.is_file() {
+            self.system_prompt = Some(read_prompt_file(PROMPTS_DIR, &system_path)?);
+        }
+        let user_path = prompts_dir.join("user.md");
+        if self.user_prompt.is_none() && user_path.is_file() {
+            self.user_prompt = Some(read_prompt_file(PROMPTS_DIR, &user_path)?);
+        }
+        Ok(self)
     }
 
     pub fn save(&self) -> Result<()> {
@@ -190,38 +1115,298 @@ impl Config {
     // Set a configuration value by key name
     #[allow(dead_code)] // Used by CLI command handlers
     pub fn set(&mut self, key: &str, value: Option<String>) -> Result<()> {
+        // Profile settings use dotted keys, e.g. `profiles.work.model`
+        if let Some((name, profile_key)) = parse_profile_key(key) {
+            let profiles = self.profiles.get_or_insert_with(BTreeMap::new);
+            let profile = profiles.entry(name.to_string()).or_default();
+            profile.set(profile_key, value)?;
+            if profile.is_empty() {
+                profiles.remove(name);
+            }
+            if profiles.is_empty() {
+                self.profiles = None;
+            }
+
+            self.save()?;
+            return Ok(());
+        }
+
+        self.set_value(key, value)?;
+        self.save()?;
+        Ok(())
+    }
+
+    // Set a configuration value by key name without saving it
+    fn set_value(&mut self, key: &str, value: Option<String>) -> Result<()> {
+        // Redaction rules use dotted keys, e.g. `redact.paths`
+        if let Some(rule) = key.strip_prefix("redact.") {
+            let rules = self.redact.get_or_insert_with(redact::Rules::default);
+            rules.set(rule, value)?;
+            if rules.is_empty() {
+                self.redact = None;
+            }
+            return Ok(());
+        }
+        // Templates are set per type, e.g. `templates.fix`
+        if let Some(commit_type) = key.strip_prefix("templates.") {
+            if commit_type.trim().is_empty() {
+                anyhow::bail!("Missing commit type in '{}', e.g. templates.fix", key);
+            }
+            let templates = self.templates.get_or_insert_with(BTreeMap::new);
+            match value {
+                Some(template) => templates.insert(commit_type.to_string(), template),
+                None => templates.remove(commit_type),
+            };
+            if templates.is_empty() {
+                self.templates = None;
+            }
+            return Ok(());
+        }
+
         match key {
             "api_token" => self.api_token = value,
             "api_base_url" => self.api_base_url = value,
             "model" => self.model = value,
             "system_prompt" => self.system_prompt = value,
             "user_prompt" => self.user_prompt = value,
+            "max_input_tokens" => {
+                self.max_input_tokens = value.map(|v| parse_positive(key, &v)).transpose()?
+            }
+            "large_diff_strategy" => {
+                self.large_diff_strategy = value.map(|v| v.parse()).transpose()?
+            }
+            "max_diff_bytes" => {
+                self.max_diff_bytes = value.map(|v| parse_count(key, &v)).transpose()?
+            }
+            "max_retries" => self.max_retries = value.map(|v| parse_count(key, &v)).transpose()?,
+            "retry_base_delay_ms" => {
+                self.retry_base_delay_ms = value
+                    .map(|v| parse_positive(key, &v).map(|ms| ms as u64))
+                    .transpose()?
+            }
+            "request_timeout_secs" => {
+                self.request_timeout_secs = value
+                    .map(|v| parse_positive(key, &v).map(|secs| secs as u64))
+                    .transpose()?
+            }
+            "requests_per_minute" => {
+                self.requests_per_minute = value
+                    .map(|v| parse_positive(key, &v).map(|rpm| rpm as u32))
+                    .transpose()?
+            }
+            "snapshot_patterns" => self.snapshot_patterns = value.map(|v| parse_list(&v)),
+            "commit_validation" => self.commit_validation = value.map(|v| v.parse()).transpose()?,
+            "max_repair_attempts" => {
+                self.max_repair_attempts = value.map(|v| parse_count(key, &v)).transpose()?
+            }
+            "max_generation_seconds" => {
+                self.max_generation_seconds = value
+                    .map(|v| parse_positive(key, &v).map(|secs| secs as u64))
+                    .transpose()?
+            }
+            "fallback_model" => self.fallback_model = value,
+            "default_profile" => self.default_profile = value,
+            "protected_branches" => self.protected_branches = value.map(|v| parse_list(&v)),
+            "scopes" => self.scopes = value.map(|v| parse_list(&v)),
+            "ticket_pattern" => {
+                if let Some(pattern) = value.as_deref().filter(|pattern| !pattern.is_empty()) {
+                    Regex::new(pattern).context(format!("Invalid ticket_pattern: {pattern}"))?;
+                }
+                self.ticket_pattern = value
+            }
+            "ticket_footer" => self.ticket_footer = value,
+            "date_format" => self.date_format = value,
+            "milestone" => self.milestone = value,
+            "cost_confirmation_threshold" => {
+                self.cost_confirmation_threshold =
+                    value.map(|v| parse_amount(key, &v)).transpose()?
+            }
+            "confirm_send" => self.confirm_send = value.map(|v| parse_bool(key, &v)).transpose()?,
+            "exclude_patterns" => self.exclude_patterns = value.map(|v| parse_list(&v)),
+            "system_prompt_file" => self.system_prompt_file = value,
+            "user_prompt_file" => self.user_prompt_file = value,
+            "temperature" => {
+                self.temperature = value
+                    .map(|v| parse_in_range(key, &v, 0.0, 2.0))
+                    .transpose()?
+            }
+            "top_p" => {
+                self.top_p = value
+                    .map(|v| parse_in_range(key, &v, 0.0, 1.0))
+                    .transpose()?
+            }
+            "max_tokens" => {
+                self.max_tokens = value
+                    .map(|v| parse_positive(key, &v).map(|tokens| tokens as u32))
+                    .transpose()?
+            }
+            "review_prompt" => self.review_prompt = value,
+            "trailers" => self.trailers = value.map(|v| parse_list(&v)),
+            "signoff" => self.signoff = value.map(|v| parse_bool(key, &v)).transpose()?,
+            "gpg_sign" => self.gpg_sign = value.map(|v| parse_bool(key, &v)).transpose()?,
+            "gpg_key_id" => self.gpg_key_id = value,
+            "no_verify" => self.no_verify = value.map(|v| parse_bool(key, &v)).transpose()?,
+            "generated_patterns" => self.generated_patterns = value.map(|v| parse_list(&v)),
+            "secret_scan" => self.secret_scan = value.map(|v| v.parse()).transpose()?,
+            "secret_patterns" => {
+                let patterns = value.map(|v| parse_list(&v));
+                secrets::Scanner::new(patterns.as_deref().unwrap_or_default())?;
+                self.secret_patterns = patterns
+            }
+            "history_context" => {
+                self.history_context = value.map(|v| parse_count(key, &v)).transpose()?
+            }
+            "symbol_summary" => {
+                self.symbol_summary = value.map(|v| parse_bool(key, &v)).transpose()?
+            }
+            "embedding_model" => self.embedding_model = value,
+            "ticket_tracker" => self.ticket_tracker = value.map(|v| v.parse()).transpose()?,
+            "ticket_api_base_url" => self.ticket_api_base_url = value,
+            "ticket_api_token" => self.ticket_api_token = value,
+            "ticket_api_user" => self.ticket_api_user = value,
+            "commit_types" => self.commit_types = value.map(|v| parse_list(&v)),
+            "max_header_length" => {
+                self.max_header_length = value.map(|v| parse_count(key, &v)).transpose()?
+            }
+            "model_prices" => {
+                self.model_prices = value.map(|v| parse_prices(key, &v)).transpose()?
+            }
+            "diff_priority" => {
+                self.diff_priority = value.map(|v| parse_weights(key, &v)).transpose()?
+            }
+            "max_rate_limit_wait_secs" => {
+                self.max_rate_limit_wait_secs = value
+                    .map(|v| parse_count(key, &v).map(|secs| secs as u64))
+                    .transpose()?
+            }
+            "structured_output" => {
+                self.structured_output = value.map(|v| parse_bool(key, &v)).transpose()?
+            }
+            "spell_check" => self.spell_check = value.map(|v| v.parse()).transpose()?,
             _ => return Err(anyhow::anyhow!("Unknown configuration key: {}", key)),
         }
 
-        self.save()?;
         Ok(())
     }
 
     // Get a configuration value by key name
     #[allow(dead_code)] // Used by CLI command handlers
-    pub fn get(&self, key: &str) -> Option<&String> {
+    pub fn get(&self, key: &str) -> Option<String> {
+        if let Some((name, profile_key)) = parse_profile_key(key) {
+            return self.profiles.as_ref()?.get(name)?.get(profile_key);
+        }
+        if let Some(rule) = key.strip_prefix("redact.") {
+            return self.redact.as_ref()?.get(rule);
+        }
+        if let Some(commit_type) = key.strip_prefix("templates.") {
+            return self.templates.as_ref()?.get(commit_type).cloned();
+        }
+
         match key {
-            "api_token" => self.api_token.as_ref(),
-            "api_base_url" => self.api_base_url.as_ref(),
-            "model" => self.model.as_ref(),
-            "system_prompt" => self.system_prompt.as_ref(),
-            "user_prompt" => self.user_prompt.as_ref(),
+            "api_token" => self.api_token.clone(),
+            "api_base_url" => self.api_base_url.clone(),
+            "model" => self.model.clone(),
+            "system_prompt" => self.system_prompt.clone(),
+            "user_prompt" => self.user_prompt.clone(),
+            "max_input_tokens" => self.max_input_tokens.map(|v| v.to_string()),
+            "large_diff_strategy" => self.large_diff_strategy.map(|v| v.to_string()),
+            "max_diff_bytes" => self.max_diff_bytes.map(|v| v.to_string()),
+            "max_retries" => self.max_retries.map(|v| v.to_string()),
+            "retry_base_delay_ms" => self.retry_base_delay_ms.map(|v| v.to_string()),
+            "request_timeout_secs" => self.request_timeout_secs.map(|v| v.to_string()),
+            "requests_per_minute" => self.requests_per_minute.map(|v| v.to_string()),
+            "snapshot_patterns" => self.snapshot_patterns.as_ref().map(|v| v.join(", ")),
+            "commit_validation" => self.commit_validation.map(|v| v.to_string()),
+            "max_repair_attempts" => self.max_repair_attempts.map(|v| v.to_string()),
+            "max_generation_seconds" => self.max_generation_seconds.map(|v| v.to_string()),
+            "fallback_model" => self.fallback_model.clone(),
+            "default_profile" => self.default_profile.clone(),
+            "protected_branches" => self.protected_branches.as_ref().map(|v| v.join(", ")),
+            "scopes" => self.scopes.as_ref().map(|v| v.join(", ")),
+            "ticket_pattern" => self.ticket_pattern.clone(),
+            "ticket_footer" => self.ticket_footer.clone(),
+            "date_format" => self.date_format.clone(),
+            "milestone" => self.milestone.clone(),
+            "cost_confirmation_threshold" => {
+                self.cost_confirmation_threshold.map(|v| v.to_string())
+            }
+            "confirm_send" => self.confirm_send.map(|v| v.to_string()),
+            "exclude_patterns" => self.exclude_patterns.as_ref().map(|v| v.join(", ")),
+            "system_prompt_file" => self.system_prompt_file.clone(),
+            "user_prompt_file" => self.user_prompt_file.clone(),
+            "temperature" => self.temperature.map(|v| v.to_string()),
+            "top_p" => self.top_p.map(|v| v.to_string()),
+            "max_tokens" => self.max_tokens.map(|v| v.to_string()),
+            "review_prompt" => self.review_prompt.clone(),
+            "trailers" => self.trailers.as_ref().map(|v| v.join(", ")),
+            "signoff" => self.signoff.map(|v| v.to_string()),
+            "gpg_sign" => self.gpg_sign.map(|v| v.to_string()),
+            "gpg_key_id" => self.gpg_key_id.clone(),
+            "no_verify" => self.no_verify.map(|v| v.to_string()),
+            "generated_patterns" => self.generated_patterns.as_ref().map(|v| v.join(", ")),
+            "secret_scan" => self.secret_scan.map(|v| v.to_string()),
+            "secret_patterns" => self.secret_patterns.as_ref().map(|v| v.join(", ")),
+            "history_context" => self.history_context.map(|v| v.to_string()),
+            "symbol_summary" => self.symbol_summary.map(|v| v.to_string()),
+            "embedding_model" => self.embedding_model.clone(),
+            "ticket_tracker" => self.ticket_tracker.map(|v| v.to_string()),
+            "ticket_api_base_url" => self.ticket_api_base_url.clone(),
+            "ticket_api_token" => self.ticket_api_token.clone(),
+            "ticket_api_user" => self.ticket_api_user.clone(),
+            "commit_types" => self.commit_types.as_ref().map(|v| v.join(", ")),
+            "max_header_length" => self.max_header_length.map(|v| v.to_string()),
+            "diff_priority" => self.diff_priority.as_ref().map(|weights| {
+                weights
+                    .iter()
+                    .map(|(role, weight)| format!("{role}={weight}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }),
+            "model_prices" => self.model_prices.as_ref().map(|prices| {
+                prices
+                    .iter()
+                    .map(|(model, price)| {
+                        format!(
+                            "{model}={}/{}",
+                            price.input_per_million, price.output_per_million
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }),
+            "max_rate_limit_wait_secs" => self.max_rate_limit_wait_secs.map(|v| v.to_string()),
+            "structured_output" => self.structured_output.map(|v| v.to_string()),
+            "spell_check" => self.spell_check.map(|v| v.to_string()),
             _ => None,
         }
     }
 
-    pub fn get_api_token(&self) -> Result<&String> {
-        self.api_token.as_ref().context(
-            "API token not found. Please set it using 'aic config set api_token YOUR_TOKEN'",
+    // The environment takes precedence so CI jobs can keep secrets out of config files,
+    // except over the token of a selected profile, whose base URL is likely another host
+    pub fn get_api_token(&self) -> Result<String> {
+        if let Some(token) = &self.profile_api_token {
+            return Ok(token.clone());
+        }
+        if let Some((_, token)) = Self::api_token_from_env() {
+            return Ok(token);
+        }
+
+        self.api_token.clone().context(
+            "API token not found. Please set it using 'aic config set api_token YOUR_TOKEN' \
+             or the AIC_API_TOKEN environment variable",
         )
     }
 
+    /// The first non-empty API token environment variable, with its name
+    pub fn api_token_from_env() -> Option<(&'static str, String)> {
+        API_TOKEN_ENV_VARS.iter().find_map(|&name| {
+            env::var(name)
+                .ok()
+                .filter(|token| !token.trim().is_empty())
+                .map(|token| (name, token.trim().to_string()))
+        })
+    }
+
     pub fn get_api_base_url(&self) -> &str {
         self.api_base_url
             .as_deref()
@@ -238,8 +1423,566 @@ impl Config {
             .unwrap_or(DEFAULT_SYSTEM_PROMPT)
     }
 
-    pub fn get_user_prompt(&self) -> &str {
-        self.user_prompt.as_deref().unwrap_or(DEFAULT_USER_PROMPT)
+    pub fn get_user_prompt(&self) -> &str {
+        self.user_prompt.as_deref().unwrap_or(DEFAULT_USER_PROMPT)
+    }
+
+    pub fn get_review_prompt(&self) -> &str {
+        self.review_prompt
+            .as_deref()
+            .unwrap_or(DEFAULT_REVIEW_PROMPT)
+    }
+
+    pub fn get_max_input_tokens(&self) -> Option<usize> {
+        self.max_input_tokens
+    }
+
+    pub fn get_retry_policy(&self) -> RetryPolicy {
+        let default = RetryPolicy::default();
+        RetryPolicy {
+            max_retries: self.max_retries.unwrap_or(default.max_retries),
+            base_delay: self
+                .retry_base_delay_ms
+                .map_or(default.base_delay, Duration::from_millis),
+            max_rate_limit_wait: self
+                .max_rate_limit_wait_secs
+                .map_or(default.max_rate_limit_wait, Duration::from_secs),
+        }
+    }
+
+    pub fn get_request_timeout(&self) -> Duration {
+        self.request_timeout_secs
+            .map_or(DEFAULT_REQUEST_TIMEOUT, Duration::from_secs)
+    }
+
+    pub fn get_max_generation_time(&self) -> Option<Duration> {
+        self.max_generation_seconds.map(Duration::from_secs)
+    }
+
+    pub fn get_fallback_model(&self) -> Option<&str> {
+        self.fallback_model.as_deref()
+    }
+
+    pub fn get_requests_per_minute(&self) -> Option<u32> {
+        self.requests_per_minute
+    }
+
+    pub fn get_cost_confirmation_threshold(&self) -> Option<f64> {
+        self.cost_confirmation_threshold
+    }
+
+    /// Whether to show the rendered prompt and ask before sending it
+    pub fn get_confirm_send(&self) -> bool {
+        self.confirm_send.unwrap_or(false)
+    }
+
+    /// Issue tracker the ticket of the branch is fetched from, see [`crate::tracker`]
+    pub fn get_ticket_tracker(&self) -> Option<TrackerKind> {
+        self.ticket_tracker
+    }
+
+    pub fn get_ticket_api_base_url(&self) -> Option<&str> {
+        self.ticket_api_base_url.as_deref()
+    }
+
+    /// Token for the issue tracker, the environment takes precedence like for `api_token`
+    pub fn get_ticket_api_token(&self) -> Option<String> {
+        env::var(TICKET_API_TOKEN_ENV_VAR)
+            .ok()
+            .or_else(|| self.ticket_api_token.clone())
+            .map(|token| token.trim().to_string())
+            .filter(|token| !token.is_empty())
+    }
+
+    /// Account the token belongs to, for Jira Cloud's basic authentication
+    pub fn get_ticket_api_user(&self) -> Option<&str> {
+        self.ticket_api_user.as_deref()
+    }
+
+    /// Recent commit subjects shown to the model as examples of the project's style, 0 for none
+    pub fn get_history_context(&self) -> usize {
+        self.history_context.unwrap_or(0)
+    }
+
+    pub fn get_symbol_summary(&self) -> bool {
+        self.symbol_summary.unwrap_or(false)
+    }
+
+    pub fn get_embedding_model(&self) -> &str {
+        self.embedding_model
+            .as_deref()
+            .unwrap_or(DEFAULT_EMBEDDING_MODEL)
+    }
+
+    /// Files summarized in one line instead of sent in full, besides binaries
+    pub fn get_generated_patterns(&self) -> Vec<String> {
+        self.generated_patterns.clone().unwrap_or_else(|| {
+            generated::DEFAULT_GENERATED_PATTERNS
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect()
+        })
+    }
+
+    pub fn get_secret_scan(&self) -> SecretScan {
+        self.secret_scan.unwrap_or(SecretScan::Confirm)
+    }
+
+    /// Scanner for credentials in the diff, with the configured patterns
+    pub fn get_secret_scanner(&self) -> Result<secrets::Scanner> {
+        secrets::Scanner::new(self.secret_patterns.as_deref().unwrap_or_default())
+    }
+
+    /// What to strip from the diff before it is sent, see `[redact]`
+    pub fn get_redaction(&self) -> redact::Rules {
+        self.redact.clone().unwrap_or_default()
+    }
+
+    pub fn get_snapshot_patterns(&self) -> Vec<String> {
+        self.snapshot_patterns.clone().unwrap_or_else(|| {
+            DEFAULT_SNAPSHOT_PATTERNS
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect()
+        })
+    }
+
+    /// Trailers appended to every generated message, as `Key: value` lines
+    pub fn get_trailers(&self) -> Vec<String> {
+        self.trailers.clone().unwrap_or_default()
+    }
+
+    /// Extra arguments for `git commit`: `--signoff`, `--gpg-sign[=<keyid>]` and `--no-verify`
+    ///
+    /// Setting `gpg_key_id` signs with that key even when `gpg_sign` is unset.
+    pub fn get_commit_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.signoff.unwrap_or(false) {
+            args.push("--signoff".to_string());
+        }
+        match self.gpg_key_id.as_deref().filter(|key| !key.is_empty()) {
+            Some(key) if self.gpg_sign != Some(false) => args.push(format!("--gpg-sign={key}")),
+            None if self.gpg_sign.unwrap_or(false) => args.push("--gpg-sign".to_string()),
+            _ => {}
+        }
+        if self.no_verify.unwrap_or(false) {
+            args.push("--no-verify".to_string());
+        }
+        args
+    }
+
+    /// Show the prompt and ask before sending it for this run (`--show-prompt`)
+    pub fn with_show_prompt(mut self, show_prompt: bool) -> Self {
+        if show_prompt {
+            self.confirm_send = Some(true);
+        }
+        self
+    }
+
+    /// Turn on the commit flags given on the command line, `gpg_sign` is `Some("")` for the default key
+    pub fn with_commit_flag_overrides(
+        mut self,
+        signoff: bool,
+        gpg_sign: Option<&str>,
+        no_verify: bool,
+    ) -> Self {
+        if signoff {
+            self.signoff = Some(true);
+        }
+        if let Some(key) = gpg_sign {
+            self.gpg_sign = Some(true);
+            if !key.is_empty() {
+                self.gpg_key_id = Some(key.to_string());
+            }
+        }
+        if no_verify {
+            self.no_verify = Some(true);
+        }
+        self
+    }
+
+    pub fn get_exclude_patterns(&self) -> Vec<String> {
+        self.exclude_patterns.clone().unwrap_or_default()
+    }
+
+    pub fn get_commit_validation(&self) -> CommitValidation {
+        self.commit_validation.unwrap_or(CommitValidation::Off)
+    }
+
+    pub fn get_max_repair_attempts(&self) -> usize {
+        self.max_repair_attempts
+            .unwrap_or(DEFAULT_MAX_REPAIR_ATTEMPTS)
+    }
+
+    pub fn get_scopes(&self) -> Vec<String> {
+        self.scopes.clone().unwrap_or_default()
+    }
+
+    /// Pattern for the ticket ID in branch names, `None` when set to an empty string
+    ///
+    /// Defaults to GitHub issue numbers when the tracker is GitHub.
+    pub fn get_ticket_pattern(&self) -> Result<Option<Regex>> {
+        let default = match self.ticket_tracker {
+            Some(TrackerKind::Github) => GITHUB_TICKET_PATTERN,
+            _ => DEFAULT_TICKET_PATTERN,
+        };
+        let pattern = self.ticket_pattern.as_deref().unwrap_or(default);
+        if pattern.is_empty() {
+            return Ok(None);
+        }
+
+        Regex::new(pattern)
+            .map(Some)
+            .context(format!("Invalid ticket_pattern: {pattern}"))
+    }
+
+    pub fn get_ticket_footer(&self) -> &str {
+        let default = match self.ticket_tracker {
+            Some(TrackerKind::Github) => GITHUB_TICKET_FOOTER,
+            _ => DEFAULT_TICKET_FOOTER,
+        };
+        self.ticket_footer.as_deref().unwrap_or(default)
+    }
+
+    pub fn get_date_format(&self) -> &str {
+        self.date_format.as_deref().unwrap_or(DEFAULT_DATE_FORMAT)
+    }
+
+    pub fn get_milestone(&self) -> Option<&str> {
+        self.milestone.as_deref()
+    }
+
+    /// Conventional commit types the prompt asks for and the validator accepts
+    pub fn get_commit_types(&self) -> Vec<String> {
+        match &self.commit_types {
+            Some(types) => types.clone(),
+            None => lint::DEFAULT_COMMIT_TYPES
+                .iter()
+                .map(|commit_type| commit_type.to_string())
+                .collect(),
+        }
+    }
+
+    pub fn get_max_header_length(&self) -> usize {
+        self.max_header_length
+            .unwrap_or(lint::DEFAULT_MAX_HEADER_LENGTH)
+    }
+
+    /// Price of `model` from `model_prices`, the longest matching prefix
+    /// winning, or its list price
+    pub fn get_model_pricing(&self, model: &str) -> Option<ModelPricing> {
+        self.model_prices
+            .iter()
+            .flatten()
+            .filter(|(prefix, _)| model.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, &pricing)| pricing)
+            .or_else(|| llm::known_pricing(model))
+    }
+
+    /// Weights deciding which files are kept when the diff is truncated
+    pub fn get_diff_priority(&self) -> DiffPriority {
+        DiffPriority::new(self.diff_priority.clone().unwrap_or_default())
+    }
+
+    pub fn get_structured_output(&self) -> bool {
+        self.structured_output.unwrap_or(false)
+    }
+
+    /// Language of the spell check of generated messages, `None` when it is off
+    pub fn get_spell_check(&self) -> Option<Language> {
+        self.spell_check
+    }
+
+    /// Body templates by commit type, see `[templates]`
+    pub fn get_templates(&self) -> BTreeMap<String, String> {
+        self.templates.clone().unwrap_or_default()
+    }
+
+    pub fn get_lint_rules(&self) -> LintRules {
+        LintRules {
+            types: self.get_commit_types(),
+            scopes: self.get_scopes(),
+            max_header_length: self.get_max_header_length(),
+            templates: self.get_templates(),
+        }
+    }
+
+    pub fn get_protected_branches(&self) -> Vec<String> {
+        self.protected_branches.clone().unwrap_or_default()
+    }
+
+    /// Whether `--push` must not push to the branch without confirmation
+    pub fn is_protected_branch(&self, branch: &str) -> bool {
+        self.get_protected_branches()
+            .iter()
+            .any(|pattern| glob::matches_exact(pattern, branch))
+    }
+
+    pub fn get_sampling(&self) -> Sampling {
+        Sampling {
+            temperature: self.temperature,
+            top_p: self.top_p,
+            max_tokens: self.max_tokens,
+        }
+    }
+
+    pub fn get_large_diff_strategy(&self) -> LargeDiffStrategy {
+        self.large_diff_strategy
+            .unwrap_or(LargeDiffStrategy::Summarize)
+    }
+
+    /// Size in bytes above which a diff is only sent after confirmation, `0` for no limit
+    pub fn get_max_diff_bytes(&self) -> usize {
+        self.max_diff_bytes.unwrap_or(DEFAULT_MAX_DIFF_BYTES)
+    }
+}
+
+/// Name of the environment variable overriding a config key, e.g. `AIC_MODEL`
+pub fn env_var_name(key: &str) -> String {
+    format!("AIC_{}", key.to_uppercase())
+}
+
+/// The non-empty `AIC_<KEY>` environment variables, as variable, key and value
+pub fn env_overrides() -> Vec<(String, &'static str, String)> {
+    KEYS.iter()
+        .filter(|&&key| !matches!(key, "profiles" | "redact" | "templates"))
+        .filter_map(|&key| {
+            let var = env_var_name(key);
+            let value = env::var(&var)
+                .ok()
+                .filter(|value| !value.trim().is_empty())?;
+            Some((var, key, value))
+        })
+        .collect()
+}
+
+/// Commented `.aic.toml` written by `aic init`
+///
+/// The shareable settings of `global` are filled in, the API token and prompt
+/// files are never copied. Everything else is left as a commented example.
+pub fn project_template(global: Option<&Config>) -> String {
+    let string = |value: &String| toml::Value::from(value.as_str()).to_string();
+    let model = global.and_then(|config| config.model.as_ref()).map(string);
+    let api_base_url = global
+        .and_then(|config| config.api_base_url.as_ref())
+        .map(string);
+    let system_prompt = global
+        .and_then(|config| config.system_prompt.as_ref())
+        .filter(|prompt| prompt.as_str() != DEFAULT_SYSTEM_PROMPT)
+        .map(string);
+    let user_prompt = global
+        .and_then(|config| config.user_prompt.as_ref())
+        .filter(|prompt| prompt.as_str() != DEFAULT_USER_PROMPT)
+        .map(string);
+    let exclude_patterns = global
+        .and_then(|config| config.exclude_patterns.clone())
+        .map(|patterns| toml::Value::from(patterns).to_string());
+
+    let setting = |key: &str, value: Option<String>, example: &str| match value {
+        Some(value) => format!("{key} = {value}\n"),
+        None => format!("# {key} = {example}\n"),
+    };
+
+    let mut template = String::from(
+        "# aic settings for this repository, they take precedence over the global config.\n\
+         # Keep the API token out of this file: set it in the global config or in the\n\
+         # AIC_API_TOKEN environment variable. Check the file with `aic config validate`.\n\n\
+         # Model and OpenAI-compatible endpoint\n",
+    );
+    template.push_str(&setting("model", model, "\"gpt-4o\""));
+    template.push_str(&setting(
+        "api_base_url",
+        api_base_url,
+        "\"https://api.openai.com/v1\"",
+    ));
+
+    template.push_str(
+        "\n# Prompts, `{}` or `{{diff}}` in user_prompt is replaced by the diff. Longer\n\
+         # prompts can live in files relative to this one, or in .aic/prompts/system.md\n\
+         # and .aic/prompts/user.md\n",
+    );
+    template.push_str(&setting(
+        "system_prompt",
+        system_prompt,
+        "\"You write concise conventional commit messages.\"",
+    ));
+    template.push_str(&setting(
+        "user_prompt",
+        user_prompt,
+        "\"Describe this change:\\n{{diff}}\"",
+    ));
+    template.push_str(&setting(
+        "review_prompt",
+        None,
+        "\"Review these changes for bugs, missing tests and style issues.\"",
+    ));
+    template.push_str(&setting(
+        "system_prompt_file",
+        None,
+        "\".aic/prompts/system.md\"",
+    ));
+    template.push_str(&setting(
+        "user_prompt_file",
+        None,
+        "\".aic/prompts/user.md\"",
+    ));
+
+    template.push_str("\n# Files left out of the diff sent to the model\n");
+    template.push_str(&setting(
+        "exclude_patterns",
+        exclude_patterns,
+        "[\"*.lock\", \"dist/\"]",
+    ));
+
+    template.push_str(
+        "\n# Language of the messages, set through the system prompt, e.g.\n\
+         # system_prompt = \"... Write the commit message in German.\"\n",
+    );
+    template
+}
+
+// Split a `profiles.<name>.<key>` key into the profile name and key
+fn parse_profile_key(key: &str) -> Option<(&str, &str)> {
+    key.strip_prefix("profiles.")?
+        .rsplit_once('.')
+        .filter(|(name, _)| !name.is_empty())
+}
+
+// Parse a comma-separated list value, an empty string is an empty list
+pub(crate) fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// Read a prompt from a file, naming the setting it came from when that fails
+fn read_prompt_file(source: &str, path: &Path) -> Result<String> {
+    if !path.is_file() {
+        anyhow::bail!(
+            "Prompt file not found: {} (set by {})",
+            path.display(),
+            source
+        );
+    }
+    let prompt = fs::read_to_string(path)
+        .with_context(|| format!("Could not read prompt file {} ({})", path.display(), source))?;
+    Ok(prompt.trim_end().to_string())
+}
+
+// Patterns of a gitignore-style file, without blank lines and comments
+fn parse_ignore_patterns(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+// Parse a configuration value that must be true or false
+pub(crate) fn parse_bool(key: &str, value: &str) -> Result<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" => Ok(false),
+        _ => Err(anyhow::anyhow!(
+            "Invalid value for {}: expected true or false, got '{}'",
+            key,
+            value
+        )),
+    }
+}
+
+// Parse a configuration value that must be a non-negative integer
+fn parse_count(key: &str, value: &str) -> Result<usize> {
+    value.trim().parse::<usize>().map_err(|_| {
+        anyhow::anyhow!(
+            "Invalid value for {}: expected a non-negative integer, got '{}'",
+            key,
+            value
+        )
+    })
+}
+
+// Parse a configuration value that must be a positive integer
+fn parse_amount(key: &str, value: &str) -> Result<f64> {
+    match value.trim().parse::<f64>() {
+        Ok(parsed) if parsed.is_finite() && parsed >= 0.0 => Ok(parsed),
+        _ => Err(anyhow::anyhow!(
+            "Invalid value for {}: expected an amount in USD, got '{}'",
+            key,
+            value
+        )),
+    }
+}
+
+// Parse prices written as `model=input/output, ...` in USD per million tokens
+fn parse_prices(key: &str, value: &str) -> Result<BTreeMap<String, ModelPricing>> {
+    parse_list(value)
+        .iter()
+        .map(|item| {
+            let (model, prices) = item
+                .split_once('=')
+                .and_then(|(model, prices)| Some((model.trim(), prices.split_once('/')?)))
+                .filter(|(model, _)| !model.is_empty())
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Invalid value for {}: expected 'model=input/output', got '{}'",
+                        key,
+                        item
+                    )
+                })?;
+            let pricing =
+                ModelPricing::new(parse_amount(key, prices.0)?, parse_amount(key, prices.1)?);
+            Ok((model.to_string(), pricing))
+        })
+        .collect()
+}
+
+// Parse weights written as `kind=weight, ...`, e.g. `docs=6, tests=1`
+fn parse_weights(key: &str, value: &str) -> Result<BTreeMap<FileRole, u32>> {
+    parse_list(value)
+        .iter()
+        .map(|item| {
+            let (role, weight) = item.split_once('=').ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Invalid value for {}: expected 'kind=weight', got '{}'",
+                    key,
+                    item
+                )
+            })?;
+            let role = role
+                .parse::<FileRole>()
+                .with_context(|| format!("Invalid value for {key}"))?;
+            Ok((role, parse_count(key, weight)? as u32))
+        })
+        .collect()
+}
+
+fn parse_in_range(key: &str, value: &str, min: f64, max: f64) -> Result<f64> {
+    match value.trim().parse::<f64>() {
+        Ok(parsed) if (min..=max).contains(&parsed) => Ok(parsed),
+        _ => Err(anyhow::anyhow!(
+            "Invalid value for {}: expected a number from {} to {}, got '{}'",
+            key,
+            min,
+            max,
+            value
+        )),
+    }
+}
+
+fn parse_positive(key: &str, value: &str) -> Result<usize> {
+    match value.trim().parse::<usize>() {
+        Ok(parsed) if parsed > 0 => Ok(parsed),
+        _ => Err(anyhow::anyhow!(
+            "Invalid value for {}: expected a positive integer, got '{}'",
+            key,
+            value
+        )),
     }
 }
 
@@ -271,8 +2014,8 @@ mod tests {
         let config_dir = temp_dir.path().join(".config").join("aic");
         fs::create_dir_all(&config_dir).expect("Failed to create config directory");
 
-        // Set the HOME environment variable to the temporary directory
-        env::set_var("HOME", temp_dir.path());
+        // Keep the global config in the temporary directory
+        use_test_config_base(&temp_dir.path().join(".config"));
 
         // Create config directly
         let mut config = Config::default();
@@ -296,6 +2039,141 @@ mod tests {
             .set("invalid_key", Some("value".to_string()))
             .is_err());
         assert!(config.get("invalid_key").is_none());
+
+        // Test numeric values
+        config
+            .set("max_input_tokens", Some("8000".to_string()))
+            .unwrap();
+        assert_eq!(config.get("max_input_tokens").unwrap(), "8000");
+        assert_eq!(config.get_max_input_tokens(), Some(8000));
+        assert!(config
+            .set("max_input_tokens", Some("lots".to_string()))
+            .is_err());
+        assert!(config
+            .set("max_input_tokens", Some("0".to_string()))
+            .is_err());
+
+        config
+            .set("cost_confirmation_threshold", Some("0.10".to_string()))
+            .unwrap();
+        assert_eq!(config.get("cost_confirmation_threshold").unwrap(), "0.1");
+        assert_eq!(config.get_cost_confirmation_threshold(), Some(0.1));
+        assert!(config
+            .set("cost_confirmation_threshold", Some("-1".to_string()))
+            .is_err());
+
+        assert!(!config.get_confirm_send());
+        config.set("confirm_send", Some("yes".to_string())).unwrap();
+        assert_eq!(config.get("confirm_send").unwrap(), "true");
+        config.set("confirm_send", None).unwrap();
+        assert!(Config::default().with_show_prompt(true).get_confirm_send());
+
+        // Test enum values
+        config
+            .set("large_diff_strategy", Some("Truncate".to_string()))
+            .unwrap();
+        assert_eq!(config.get("large_diff_strategy").unwrap(), "truncate");
+        assert!(config
+            .set("large_diff_strategy", Some("ignore".to_string()))
+            .is_err());
+        config
+            .set("commit_validation", Some("block".to_string()))
+            .unwrap();
+        assert_eq!(config.get("commit_validation").unwrap(), "block");
+
+        // Test profile values
+        config
+            .set("profiles.work.model", Some("internal-model".to_string()))
+            .unwrap();
+        assert_eq!(config.get("profiles.work.model").unwrap(), "internal-model");
+        assert!(config.get("profiles.work.api_token").is_none());
+        assert!(config
+            .set("profiles.work.temperature", Some("1".to_string()))
+            .is_err());
+        config.set("profiles.work.model", None).unwrap();
+        assert!(config.profiles.is_none());
+
+        // Test list values
+        config
+            .set("snapshot_patterns", Some("*.snap, ,*.golden".to_string()))
+            .unwrap();
+        assert_eq!(config.get("snapshot_patterns").unwrap(), "*.snap, *.golden");
+        config
+            .set("snapshot_patterns", Some(String::new()))
+            .unwrap();
+        assert!(config.get_snapshot_patterns().is_empty());
+        assert!(config
+            .get_generated_patterns()
+            .contains(&"Cargo.lock".to_string()));
+        config
+            .set("generated_patterns", Some("src/gen/".to_string()))
+            .unwrap();
+        assert_eq!(config.get_generated_patterns(), vec!["src/gen/"]);
+
+        config
+            .set_value("redact.paths", Some("secrets/**".to_string()))
+            .unwrap();
+        config
+            .set_value("redact.ips", Some("true".to_string()))
+            .unwrap();
+        assert_eq!(config.get("redact.paths").unwrap(), "secrets/**");
+        assert_eq!(config.get_redaction().describe(), "paths (secrets/**), ips");
+        assert!(config
+            .set_value("redact.names", Some("true".to_string()))
+            .is_err());
+        config.set_value("redact.paths", None).unwrap();
+        config.set_value("redact.ips", None).unwrap();
+        assert!(config.redact.is_none());
+
+        assert_eq!(config.get_secret_scan(), SecretScan::Confirm);
+        config
+            .set("secret_scan", Some("Redact".to_string()))
+            .unwrap();
+        assert_eq!(config.get("secret_scan").unwrap(), "redact");
+        assert!(config
+            .set("secret_scan", Some("ignore".to_string()))
+            .is_err());
+        config
+            .set("secret_patterns", Some(r"corp-[0-9]+".to_string()))
+            .unwrap();
+        assert!(!config
+            .get_secret_scanner()
+            .unwrap()
+            .scan("+key = corp-42\n")
+            .is_empty());
+        assert!(config
+            .set("secret_patterns", Some("(unclosed".to_string()))
+            .is_err());
+
+        // Patterns from --exclude add to the configured ones
+        assert!(config.get_exclude_patterns().is_empty());
+        config
+            .set(
+                "exclude_patterns",
+                Some("package-lock.json, *.min.js".to_string()),
+            )
+            .unwrap();
+        let config = config.with_exclude_patterns(&["target/**".to_string()]);
+        assert_eq!(
+            config.get_exclude_patterns(),
+            vec!["package-lock.json", "*.min.js", "target/**"]
+        );
+        let mut config = config.with_exclude_patterns(&[]);
+        assert_eq!(config.get_exclude_patterns().len(), 3);
+
+        // Zero retries is allowed, a zero delay is not
+        config.set("max_retries", Some("0".to_string())).unwrap();
+        assert_eq!(config.get("max_retries").unwrap(), "0");
+        assert!(config
+            .set("retry_base_delay_ms", Some("0".to_string()))
+            .is_err());
+
+        // Ticket patterns must compile, an empty one turns the footer off
+        assert!(config
+            .set("ticket_pattern", Some("[A-Z+".to_string()))
+            .is_err());
+        config.set("ticket_pattern", Some(String::new())).unwrap();
+        assert!(config.get_ticket_pattern().unwrap().is_none());
     }
 
     #[test]
@@ -341,6 +2219,60 @@ mod tests {
             model: Some("test-model".to_string()),
             system_prompt: Some("test system prompt".to_string()),
             user_prompt: Some("test user prompt".to_string()),
+            max_input_tokens: Some(4096),
+            large_diff_strategy: Some(LargeDiffStrategy::Truncate),
+            max_diff_bytes: Some(4096),
+            max_retries: Some(0),
+            retry_base_delay_ms: Some(250),
+            request_timeout_secs: Some(30),
+            requests_per_minute: Some(20),
+            snapshot_patterns: Some(vec!["*.golden".to_string()]),
+            commit_validation: Some(CommitValidation::Block),
+            max_repair_attempts: Some(0),
+            max_generation_seconds: Some(15),
+            fallback_model: Some("gpt-4o-mini".to_string()),
+            default_profile: None,
+            protected_branches: Some(vec!["main".to_string(), "release/*".to_string()]),
+            scopes: Some(vec!["core".to_string(), "cli".to_string()]),
+            ticket_pattern: Some(r"issue-(\d+)".to_string()),
+            ticket_footer: Some("Closes #{ticket}".to_string()),
+            date_format: Some("%e %B %Y".to_string()),
+            milestone: Some("Q3 launch".to_string()),
+            cost_confirmation_threshold: Some(0.05),
+            confirm_send: Some(true),
+            exclude_patterns: None,
+            system_prompt_file: None,
+            user_prompt_file: None,
+            temperature: None,
+            top_p: None,
+            max_tokens: None,
+            review_prompt: None,
+            trailers: None,
+            signoff: Some(true),
+            gpg_sign: None,
+            gpg_key_id: None,
+            no_verify: None,
+            generated_patterns: None,
+            secret_scan: Some(SecretScan::Redact),
+            secret_patterns: None,
+            history_context: None,
+            symbol_summary: Some(true),
+            embedding_model: Some("local".to_string()),
+            ticket_tracker: None,
+            ticket_api_base_url: None,
+            ticket_api_token: None,
+            ticket_api_user: None,
+            commit_types: Some(vec!["wip".to_string(), "feat".to_string()]),
+            max_header_length: Some(100),
+            max_rate_limit_wait_secs: Some(300),
+            structured_output: Some(true),
+            spell_check: Some(Language::En),
+            redact: None,
+            templates: None,
+            model_prices: None,
+            diff_priority: None,
+            profiles: None,
+            profile_api_token: None,
         };
 
         assert_eq!(config.get_api_token().unwrap(), "test-token");
@@ -348,6 +2280,50 @@ mod tests {
         assert_eq!(config.get_model(), "test-model");
         assert_eq!(config.get_system_prompt(), "test system prompt");
         assert_eq!(config.get_user_prompt(), "test user prompt");
+        assert_eq!(config.get_max_input_tokens(), Some(4096));
+        assert_eq!(
+            config.get_large_diff_strategy(),
+            LargeDiffStrategy::Truncate
+        );
+        assert_eq!(config.get_max_diff_bytes(), 4096);
+        assert_eq!(config.get_spell_check(), Some(Language::En));
+        assert!(config.get_symbol_summary());
+        assert_eq!(config.get_embedding_model(), "local");
+        assert_eq!(
+            config.get_retry_policy(),
+            RetryPolicy {
+                max_retries: 0,
+                base_delay: Duration::from_millis(250),
+                max_rate_limit_wait: Duration::from_secs(300),
+            }
+        );
+        assert_eq!(config.get_request_timeout(), Duration::from_secs(30));
+        assert_eq!(config.get_requests_per_minute(), Some(20));
+        assert_eq!(config.get_snapshot_patterns(), vec!["*.golden"]);
+        assert_eq!(config.get_secret_scan(), SecretScan::Redact);
+        assert_eq!(config.get_commit_validation(), CommitValidation::Block);
+        assert_eq!(config.get_max_repair_attempts(), 0);
+        assert_eq!(config.get_scopes(), vec!["core", "cli"]);
+        assert_eq!(
+            config.get_ticket_pattern().unwrap().unwrap().as_str(),
+            r"issue-(\d+)"
+        );
+        assert_eq!(config.get_ticket_footer(), "Closes #{ticket}");
+        assert_eq!(config.get_date_format(), "%e %B %Y");
+        assert_eq!(config.get_milestone(), Some("Q3 launch"));
+        assert_eq!(config.get_lint_rules().scopes, vec!["core", "cli"]);
+        assert_eq!(config.get_lint_rules().types, vec!["wip", "feat"]);
+        assert!(config.is_protected_branch("main"));
+        assert!(config.is_protected_branch("release/1.2"));
+        assert!(!config.is_protected_branch("feature/main"));
+        assert!(!config.is_protected_branch("release/1.2/hotfix"));
+        assert_eq!(
+            config.get_max_generation_time(),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(config.get_fallback_model(), Some("gpt-4o-mini"));
+        assert_eq!(config.get_cost_confirmation_threshold(), Some(0.05));
+        assert!(config.get_confirm_send());
 
         // Test defaults when values are None
         let empty_config = Config {
@@ -356,13 +2332,350 @@ mod tests {
             model: None,
             system_prompt: None,
             user_prompt: None,
+            ..Default::default()
         };
 
+        for name in API_TOKEN_ENV_VARS {
+            env::remove_var(name);
+        }
         assert!(empty_config.get_api_token().is_err());
         assert_eq!(empty_config.get_api_base_url(), "https://api.openai.com/v1");
         assert_eq!(empty_config.get_model(), "gpt-3.5-turbo");
         assert_eq!(empty_config.get_system_prompt(), DEFAULT_SYSTEM_PROMPT);
         assert_eq!(empty_config.get_user_prompt(), DEFAULT_USER_PROMPT);
+        assert_eq!(empty_config.get_max_input_tokens(), None);
+        assert_eq!(
+            empty_config.get_large_diff_strategy(),
+            LargeDiffStrategy::Summarize
+        );
+        assert_eq!(empty_config.get_max_diff_bytes(), DEFAULT_MAX_DIFF_BYTES);
+        assert_eq!(empty_config.get_spell_check(), None);
+        assert!(!empty_config.get_symbol_summary());
+        assert_eq!(empty_config.get_embedding_model(), DEFAULT_EMBEDDING_MODEL);
+        assert_eq!(empty_config.get_retry_policy(), RetryPolicy::default());
+        assert_eq!(empty_config.get_request_timeout(), DEFAULT_REQUEST_TIMEOUT);
+        assert_eq!(empty_config.get_requests_per_minute(), None);
+        assert_eq!(
+            empty_config.get_snapshot_patterns(),
+            DEFAULT_SNAPSHOT_PATTERNS
+        );
+        assert_eq!(empty_config.get_commit_validation(), CommitValidation::Off);
+        assert!(!empty_config.is_protected_branch("main"));
+        assert!(empty_config.get_scopes().is_empty());
+        assert_eq!(empty_config.get_commit_types(), lint::DEFAULT_COMMIT_TYPES);
+        assert_eq!(
+            empty_config.get_ticket_pattern().unwrap().unwrap().as_str(),
+            DEFAULT_TICKET_PATTERN
+        );
+        assert_eq!(empty_config.get_ticket_footer(), DEFAULT_TICKET_FOOTER);
+        let github_config = Config {
+            ticket_tracker: Some(TrackerKind::Github),
+            ..Config::default()
+        };
+        assert_eq!(
+            github_config
+                .get_ticket_pattern()
+                .unwrap()
+                .unwrap()
+                .as_str(),
+            GITHUB_TICKET_PATTERN
+        );
+        assert_eq!(github_config.get_ticket_footer(), GITHUB_TICKET_FOOTER);
+        assert_eq!(empty_config.get_date_format(), DEFAULT_DATE_FORMAT);
+        assert_eq!(empty_config.get_milestone(), None);
+        assert_eq!(
+            empty_config.get_max_repair_attempts(),
+            DEFAULT_MAX_REPAIR_ATTEMPTS
+        );
+        assert_eq!(empty_config.get_max_generation_time(), None);
+        assert_eq!(empty_config.get_fallback_model(), None);
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        env::set_current_dir(temp_dir.path()).expect("Failed to change directory");
+        fs::write(temp_dir.path().join("user.md"), "From a file: {}\n").unwrap();
+
+        let config: Config = toml::from_str(
+            "model = \"file-model\"\nmax_retries = 3\nsystem_prompt_file = \"/nowhere.md\"\n",
+        )
+        .unwrap();
+        env::set_var("AIC_MODEL", "env-model");
+        env::set_var("AIC_MAX_RETRIES", "1");
+        env::set_var("AIC_SCOPES", "api, cli");
+        env::set_var("AIC_MILESTONE", "  ");
+        env::set_var("AIC_SYSTEM_PROMPT", "From the environment");
+        env::set_var("AIC_USER_PROMPT_FILE", "user.md");
+
+        let result = config.with_env_overrides();
+        for key in [
+            "AIC_MODEL",
+            "AIC_MAX_RETRIES",
+            "AIC_SCOPES",
+            "AIC_MILESTONE",
+            "AIC_SYSTEM_PROMPT",
+            "AIC_USER_PROMPT_FILE",
+        ] {
+            env::remove_var(key);
+        }
+
+        let config = result.unwrap();
+        assert_eq!(config.get_model(), "env-model");
+        assert_eq!(config.max_retries, Some(1));
+        assert_eq!(config.get_scopes(), vec!["api", "cli"]);
+        // Blank variables are ignored
+        assert_eq!(config.get_milestone(), None);
+        // An inline prompt from the environment replaces the config's prompt file
+        assert_eq!(config.get_system_prompt(), "From the environment");
+        assert!(config.system_prompt_file.is_none());
+        assert_eq!(config.get_user_prompt(), "From a file: {}");
+
+        // Invalid values name the variable
+        env::set_var("AIC_MAX_RETRIES", "many");
+        let error = Config::default().with_env_overrides().unwrap_err();
+        env::remove_var("AIC_MAX_RETRIES");
+        assert!(format!("{error:#}").contains("AIC_MAX_RETRIES"));
+    }
+
+    #[test]
+    fn test_api_token_from_env() {
+        let config = Config {
+            api_token: Some("config-token".to_string()),
+            ..Default::default()
+        };
+
+        for name in API_TOKEN_ENV_VARS {
+            env::remove_var(name);
+        }
+        assert_eq!(config.get_api_token().unwrap(), "config-token");
+
+        // The environment wins over the config file, AIC_API_TOKEN over OPENAI_API_KEY
+        env::set_var("OPENAI_API_KEY", "openai-token");
+        assert_eq!(config.get_api_token().unwrap(), "openai-token");
+        env::set_var("AIC_API_TOKEN", "aic-token");
+        assert_eq!(config.get_api_token().unwrap(), "aic-token");
+        assert_eq!(
+            Config::api_token_from_env(),
+            Some(("AIC_API_TOKEN", "aic-token".to_string()))
+        );
+
+        // Empty variables are ignored
+        env::set_var("AIC_API_TOKEN", "");
+        assert_eq!(config.get_api_token().unwrap(), "openai-token");
+
+        // A selected profile's token isn't replaced by a key meant for another host
+        let toml = r#"
+            [profiles.work]
+            api_token = "work-token"
+            api_base_url = "https://gateway.internal/v1"
+
+            [profiles.personal]
+            model = "gpt-4o"
+        "#;
+        let work = toml::from_str::<Config>(toml)
+            .unwrap()
+            .with_profile(Some("work"))
+            .unwrap();
+        assert_eq!(work.get_api_token().unwrap(), "work-token");
+        assert_eq!(work.profile_api_token, Some("work-token".to_string()));
+        let personal = toml::from_str::<Config>(toml)
+            .unwrap()
+            .with_profile(Some("personal"))
+            .unwrap();
+        assert_eq!(personal.get_api_token().unwrap(), "openai-token");
+
+        for name in API_TOKEN_ENV_VARS {
+            env::remove_var(name);
+        }
+    }
+
+    #[test]
+    fn test_profiles() {
+        let toml = r#"
+            api_token = "top-token"
+            model = "gpt-4o"
+            default_profile = "work"
+
+            [profiles.work]
+            api_base_url = "https://gateway.internal/v1"
+            model = "internal-model"
+
+            [profiles.personal]
+            api_token = "personal-token"
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.profile_names(), vec!["personal", "work"]);
+        assert_eq!(config.get("profiles.work.model").unwrap(), "internal-model");
+
+        // default_profile applies when no profile is selected
+        let work = toml::from_str::<Config>(toml)
+            .unwrap()
+            .with_profile(None)
+            .unwrap();
+        assert_eq!(work.get_api_base_url(), "https://gateway.internal/v1");
+        assert_eq!(work.get_model(), "internal-model");
+        assert_eq!(work.api_token, Some("top-token".to_string()));
+
+        // An explicit profile wins, unset profile values fall back to the top level
+        let personal = toml::from_str::<Config>(toml)
+            .unwrap()
+            .with_profile(Some("personal"))
+            .unwrap();
+        assert_eq!(personal.api_token, Some("personal-token".to_string()));
+        assert_eq!(personal.get_model(), "gpt-4o");
+
+        let err = toml::from_str::<Config>(toml)
+            .unwrap()
+            .with_profile(Some("missing"))
+            .unwrap_err();
+        assert!(err.to_string().contains("personal, work"));
+
+        // Profiles survive a save and load round trip
+        let serialized = toml::to_string_pretty(&config).unwrap();
+        let reloaded: Config = toml::from_str(&serialized).unwrap();
+        assert_eq!(reloaded.profiles, config.profiles);
+    }
+
+    #[test]
+    fn test_model_prices() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [model_prices]
+            "gpt-4o" = { input = 2.0, output = 8.0 }
+            "my-model" = { input = 0.5, output = 1.5 }
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.get_model_pricing("my-model-large"),
+            Some(ModelPricing::new(0.5, 1.5))
+        );
+        // Configured prices replace list prices, the longest prefix wins
+        assert_eq!(
+            config.get_model_pricing("gpt-4o-2024-08-06"),
+            Some(ModelPricing::new(2.0, 8.0))
+        );
+        assert_eq!(
+            config.get_model_pricing("gpt-4.1-mini"),
+            Some(ModelPricing::new(0.40, 1.60))
+        );
+        assert_eq!(config.get_model_pricing("llama3"), None);
+
+        config
+            .set_value(
+                "model_prices",
+                Some("llama3=0/0, gpt-4o=2.5/10".to_string()),
+            )
+            .unwrap();
+        assert_eq!(
+            config.get("model_prices").unwrap(),
+            "gpt-4o=2.5/10, llama3=0/0"
+        );
+        assert!(config
+            .set_value("model_prices", Some("llama3=1".to_string()))
+            .is_err());
+        assert!(config
+            .set_value("model_prices", Some("llama3=a/1".to_string()))
+            .is_err());
+
+        let serialized = toml::to_string_pretty(&config).unwrap();
+        let reloaded: Config = toml::from_str(&serialized).unwrap();
+        assert_eq!(reloaded.model_prices, config.model_prices);
+    }
+
+    #[test]
+    fn test_diff_priority() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [diff_priority]
+            docs = 6
+            generated = 0
+        "#,
+        )
+        .unwrap();
+        let priority = config.get_diff_priority();
+        assert_eq!(priority.weight(FileRole::Docs), 6);
+        assert_eq!(priority.weight(FileRole::Generated), 0);
+        assert_eq!(priority.weight(FileRole::Source), 5);
+
+        config
+            .set_value("diff_priority", Some("tests=7, source=1".to_string()))
+            .unwrap();
+        assert_eq!(config.get("diff_priority").unwrap(), "source=1, tests=7");
+        assert!(config
+            .set_value("diff_priority", Some("vendor=1".to_string()))
+            .is_err());
+        assert!(config
+            .set_value("diff_priority", Some("docs".to_string()))
+            .is_err());
+        assert!(toml::from_str::<Config>("[diff_priority]\nvendor = 1\n").is_err());
+    }
+
+    #[test]
+    fn test_templates() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [templates]
+            fix = "Root cause:\nFix:"
+        "#,
+        )
+        .unwrap();
+        assert_eq!(config.get("templates.fix").unwrap(), "Root cause:\nFix:");
+        assert_eq!(config.get("templates.feat"), None);
+
+        config
+            .set_value("templates.feat", Some("Why:".to_string()))
+            .unwrap();
+        assert_eq!(
+            config.get_lint_rules().templates.keys().collect::<Vec<_>>(),
+            vec!["feat", "fix"]
+        );
+        assert!(config
+            .set_value("templates.", Some("Why:".to_string()))
+            .is_err());
+
+        // A project template replaces the global one of its type only
+        let project: Config = toml::from_str("[templates]\nfeat = \"Motivation:\"").unwrap();
+        let global: Config = toml::from_str(&toml::to_string(&config).unwrap()).unwrap();
+        let merged = Config::merge(global, project);
+        assert_eq!(merged.get("templates.feat").unwrap(), "Motivation:");
+        assert_eq!(merged.get("templates.fix").unwrap(), "Root cause:\nFix:");
+
+        config.set_value("templates.feat", None).unwrap();
+        config.set_value("templates.fix", None).unwrap();
+        assert!(config.templates.is_none());
+    }
+
+    #[test]
+    fn test_migrate_config_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let legacy_dir = temp_dir.path().join(".config").join("aic");
+        let config_dir = temp_dir.path().join("xdg").join("aic");
+
+        // Nothing to move without a legacy config
+        assert!(!migrate_config_dir(&legacy_dir, &config_dir).unwrap());
+        assert!(!config_dir.exists());
+
+        fs::create_dir_all(&legacy_dir).unwrap();
+        fs::write(legacy_dir.join("config.toml"), "model = \"legacy\"\n").unwrap();
+        fs::write(legacy_dir.join("system_prompt.md"), "legacy prompt").unwrap();
+        assert!(migrate_config_dir(&legacy_dir, &config_dir).unwrap());
+        assert_eq!(
+            fs::read_to_string(config_dir.join("config.toml")).unwrap(),
+            "model = \"legacy\"\n"
+        );
+        assert!(config_dir.join("system_prompt.md").exists());
+        assert!(!legacy_dir.exists());
+
+        // An existing config is never overwritten
+        fs::create_dir_all(&legacy_dir).unwrap();
+        fs::write(legacy_dir.join("config.toml"), "model = \"older\"\n").unwrap();
+        assert!(!migrate_config_dir(&legacy_dir, &config_dir).unwrap());
+        assert!(legacy_dir.join("config.toml").exists());
+
+        // Same directory
+        assert!(!migrate_config_dir(&legacy_dir, &legacy_dir).unwrap());
     }
 
     #[test]
@@ -380,8 +2693,8 @@ mod tests {
         let project_dir = temp_dir.path().join("project");
         fs::create_dir_all(&project_dir).expect("Failed to create project directory");
 
-        // Set HOME to our test home dir
-        env::set_var("HOME", &home_dir);
+        // Keep the global config in our test home dir
+        use_test_config_base(&home_dir.join(".config"));
 
         // Create global config file with the expected URL
         let global_config = Config {
@@ -390,6 +2703,7 @@ mod tests {
             model: Some("global-model".to_string()),
             system_prompt: Some("global system prompt".to_string()),
             user_prompt: Some("global user prompt".to_string()),
+            ..Default::default()
         };
 
         let config_path = config_dir.join("config.toml");
@@ -405,6 +2719,7 @@ mod tests {
             model: Some("project-model".to_string()),     // Override model
             system_prompt: Some("project system prompt".to_string()), // Override system prompt
             user_prompt: None,                            // Use global user prompt
+            ..Default::default()
         };
 
         let project_config_path = project_dir.join(".aic.toml");
@@ -460,4 +2775,250 @@ mod tests {
         );
         assert_eq!(merged.user_prompt, Some("global user prompt".to_string()));
     }
+
+    #[test]
+    fn test_ignore_file() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let repo_dir = temp_dir.path().join("repo");
+        let sub_dir = repo_dir.join("src");
+        fs::create_dir_all(repo_dir.join(".git")).unwrap();
+        fs::create_dir_all(&sub_dir).unwrap();
+
+        env::set_current_dir(&sub_dir).expect("Failed to change directory");
+        assert!(Config::find_ignore_file().is_none());
+        assert!(Config::load_ignore_patterns().unwrap().is_empty());
+
+        // Found at the repository root from a subdirectory
+        fs::write(
+            repo_dir.join(".aicignore"),
+            "# Generated files\n*.lock\n\n  dist/  \n!dist/README.md\n*.[ch]\n\\#file\n",
+        )
+        .unwrap();
+        assert!(Config::find_ignore_file().is_some());
+        assert_eq!(
+            Config::load_ignore_patterns().unwrap(),
+            vec!["*.lock", "dist/", "!dist/README.md", "*.[ch]", "\\#file"]
+        );
+    }
+
+    #[test]
+    fn test_trailer_overrides() {
+        let mut config = Config::default();
+        config
+            .set_value(
+                "trailers",
+                Some("Signed-off-by: Jane <jane@example.com>, AI-assisted-by: aic".to_string()),
+            )
+            .unwrap();
+        assert_eq!(config.get_trailers().len(), 2);
+
+        // A flag replaces the trailer with the same key, an empty value removes it
+        let config = config
+            .with_trailer_overrides(&[
+                "signed-off-by=Joe <joe@example.com>".to_string(),
+                "AI-assisted-by=".to_string(),
+                "Reviewed-by=Ann".to_string(),
+            ])
+            .unwrap();
+        assert_eq!(
+            config.get_trailers(),
+            vec!["signed-off-by: Joe <joe@example.com>", "Reviewed-by: Ann"]
+        );
+
+        assert!(Config::default()
+            .with_trailer_overrides(&["Reviewed by=Ann".to_string()])
+            .is_err());
+        assert!(Config::default()
+            .with_trailer_overrides(&["Reviewed-by".to_string()])
+            .is_err());
+    }
+
+    #[test]
+    fn test_commit_args() {
+        let mut config = Config::default();
+        assert!(config.get_commit_args().is_empty());
+
+        config
+            .set_value("signoff", Some("true".to_string()))
+            .unwrap();
+        config
+            .set_value("no_verify", Some("yes".to_string()))
+            .unwrap();
+        assert_eq!(config.get_commit_args(), vec!["--signoff", "--no-verify"]);
+        assert!(config
+            .set_value("gpg_sign", Some("maybe".to_string()))
+            .is_err());
+
+        // A key id signs with that key unless signing is turned off
+        config
+            .set_value("gpg_key_id", Some("ABC123".to_string()))
+            .unwrap();
+        assert!(config
+            .get_commit_args()
+            .contains(&"--gpg-sign=ABC123".to_string()));
+        config
+            .set_value("gpg_sign", Some("false".to_string()))
+            .unwrap();
+        assert!(!config
+            .get_commit_args()
+            .iter()
+            .any(|arg| arg.starts_with("--gpg-sign")));
+
+        let config = Config::default().with_commit_flag_overrides(false, Some(""), false);
+        assert_eq!(config.get_commit_args(), vec!["--gpg-sign"]);
+        let config = config.with_commit_flag_overrides(true, Some("DEF456"), false);
+        assert_eq!(
+            config.get_commit_args(),
+            vec!["--signoff", "--gpg-sign=DEF456"]
+        );
+    }
+
+    #[test]
+    fn test_sampling() {
+        let mut config = Config::default();
+        assert_eq!(config.get_sampling(), Sampling::default());
+
+        config
+            .set_value("temperature", Some("0.2".to_string()))
+            .unwrap();
+        config
+            .set_value("max_tokens", Some("300".to_string()))
+            .unwrap();
+        assert!(config
+            .set_value("temperature", Some("2.5".to_string()))
+            .is_err());
+        assert!(config.set_value("top_p", Some("-0.1".to_string())).is_err());
+        assert!(config
+            .set_value("max_tokens", Some("0".to_string()))
+            .is_err());
+        assert_eq!(config.get("temperature").unwrap(), "0.2");
+
+        // Flags override the config and are checked like it
+        let config = config
+            .with_sampling_overrides(None, Some(0.9), Some(100))
+            .unwrap();
+        assert_eq!(
+            config.get_sampling(),
+            Sampling {
+                temperature: Some(0.2),
+                top_p: Some(0.9),
+                max_tokens: Some(100),
+            }
+        );
+        assert!(config
+            .with_sampling_overrides(Some(3.0), None, None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_project_template() {
+        // Without a global config every setting is a commented example
+        let template: toml::Table = project_template(None).parse().unwrap();
+        assert!(template.is_empty());
+
+        let global: Config = toml::from_str(
+            "api_token = \"secret\"\n\
+             model = \"gpt-4o\"\n\
+             user_prompt = \"Summarize \\\"this\\\":\\n{}\"\n\
+             exclude_patterns = [\"*.lock\"]\n",
+        )
+        .unwrap();
+        let mut global = Config::merge(Config::default(), global);
+        global.system_prompt_file = Some("/home/me/system.md".to_string());
+        let template = project_template(Some(&global));
+        assert!(!template.contains("secret"));
+
+        let copied: Config = toml::from_str(&template).unwrap();
+        assert_eq!(copied.model.as_deref(), Some("gpt-4o"));
+        assert_eq!(
+            copied.api_base_url.as_deref(),
+            Some("https://api.openai.com/v1")
+        );
+        assert_eq!(
+            copied.user_prompt.as_deref(),
+            Some("Summarize \"this\":\n{}")
+        );
+        assert_eq!(copied.exclude_patterns, Some(vec!["*.lock".to_string()]));
+        // Default prompts and prompt files stay out
+        assert!(copied.system_prompt.is_none());
+        assert!(copied.system_prompt_file.is_none());
+        assert!(copied.api_token.is_none());
+    }
+
+    #[test]
+    fn test_project_config_path() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let repo_dir = temp_dir.path().join("repo");
+        let sub_dir = repo_dir.join("src");
+        fs::create_dir_all(&sub_dir).unwrap();
+
+        env::set_current_dir(&sub_dir).expect("Failed to change directory");
+        assert!(Config::project_config_path().is_err());
+
+        // A new file goes to the repository root, an existing one is kept
+        fs::create_dir_all(repo_dir.join(".git")).unwrap();
+        assert!(Config::project_config_path()
+            .unwrap()
+            .ends_with("repo/.aic.toml"));
+        fs::write(sub_dir.join(PROJECT_CONFIG_FILENAME), "").unwrap();
+        assert!(Config::project_config_path()
+            .unwrap()
+            .ends_with("src/.aic.toml"));
+    }
+
+    #[test]
+    fn test_commitlint_config() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let repo_dir = temp_dir.path();
+        fs::create_dir_all(repo_dir.join(".git")).unwrap();
+        env::set_current_dir(repo_dir).expect("Failed to change directory");
+        assert!(Config::load_commitlint_config().unwrap().is_none());
+
+        fs::write(
+            repo_dir.join(".commitlintrc.yml"),
+            "rules:\n  type-enum: [2, always, [feat, fix, deps]]\n  header-max-length: [2, always, 50]\n",
+        )
+        .unwrap();
+        let config = Config::load_commitlint_config().unwrap().unwrap();
+        assert_eq!(config.get_commit_types(), vec!["feat", "fix", "deps"]);
+        assert_eq!(config.get_max_header_length(), 50);
+        assert!(config.scopes.is_none());
+    }
+
+    #[test]
+    fn test_prompt_files() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let dir = temp_dir.path();
+        fs::write(dir.join("system.txt"), "Write terse commits.\n").unwrap();
+
+        // Files take precedence over the inline prompts and are resolved against the directory
+        let config: Config =
+            toml::from_str("system_prompt = \"inline\"\nsystem_prompt_file = \"system.txt\"\n")
+                .unwrap();
+        let config = config.with_prompt_files(dir).unwrap();
+        assert_eq!(config.get_system_prompt(), "Write terse commits.");
+        assert_eq!(
+            config.system_prompt_file,
+            Some(dir.join("system.txt").display().to_string())
+        );
+
+        // Missing files name the path and the setting
+        let config: Config = toml::from_str("user_prompt_file = \"prompts/user.md\"\n").unwrap();
+        let error = config.with_prompt_files(dir).unwrap_err().to_string();
+        assert!(error.contains("prompts/user.md") && error.contains("user_prompt_file"));
+
+        // .aic/prompts only fills the prompts the project config leaves unset
+        let prompts_dir = dir.join(".aic").join("prompts");
+        fs::create_dir_all(&prompts_dir).unwrap();
+        fs::write(prompts_dir.join("system.md"), "Project system prompt").unwrap();
+        fs::write(
+            prompts_dir.join("user.md"),
+            "Project user prompt:\n{{diff}}\n",
+        )
+        .unwrap();
+        let config: Config = toml::from_str("user_prompt = \"own {}\"\n").unwrap();
+        let config = config.with_project_prompts(&prompts_dir).unwrap();
+        assert_eq!(config.get_system_prompt(), "Project system prompt");
+        assert_eq!(config.get_user_prompt(), "own {}");
+    }
 }
@@ -0,0 +1,690 @@
+use crate::config::Config;
+use crate::git;
+use crate::lint;
+use crate::symbols;
+use crate::template;
+use crate::ticket;
+use crate::tracker::{self, Ticket};
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Date format used when `date_format` is not set
+pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Commit subjects available as `{{recent_commits}}` when `history_context` is not set
+const RECENT_COMMITS: usize = 5;
+
+/// Introduces the recent commit subjects appended to the system prompt with `history_context`
+const HISTORY_CONTEXT_PREFIX: &str =
+    "Recent commit messages in this repository. Match their style, scopes and language:";
+
+/// Introduces the ticket fetched from the issue tracker, appended to the system prompt
+const TICKET_CONTEXT_PREFIX: &str =
+    "The changes are for this ticket. Use it to explain why they were made:";
+
+/// The commit types listed by the default system prompt, replaced with `commit_types`
+const DEFAULT_TYPE_LIST: &str =
+    "feat, fix, docs, style, refactor, perf, test, build, ci, chore, or revert";
+
+/// Introduces the configured commit types in system prompts that don't list them
+const COMMIT_TYPES_PREFIX: &str = "Use only these commit types:";
+
+/// Introduces the configured body templates, appended to the system prompt
+const TEMPLATES_PREFIX: &str =
+    "Commits of these types need a body that follows their template, with every section filled in:";
+
+/// Introduces the declarations the diff changes, appended to the system prompt with `symbol_summary`
+const SYMBOLS_PREFIX: &str =
+    "Functions, types and methods the changes add, remove or modify, by file:";
+
+/// The header length rule of the default system prompt, replaced with `max_header_length`
+const DEFAULT_LENGTH_RULE: &str = "Keep the first line under 72 characters";
+
+/// Variables describing the project, which are appended to the system prompt
+/// when a prompt uses none of them
+const CONTEXT_VARIABLES: &[&str] = &[
+    "date",
+    "version",
+    "milestone",
+    "branch",
+    "ticket",
+    "ticket_title",
+    "ticket_description",
+    "recent_commits",
+    "files",
+    "symbols",
+];
+
+const MONTH_NAMES: &[&str] = &[
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/// Template variables available in the system and user prompts
+///
+/// `{date}`, `{version}` and `{milestone}` are replaced wherever they appear,
+/// as are `{{date}}`, `{{version}}`, `{{milestone}}`, `{{branch}}`, `{{ticket}}`,
+/// `{{recent_commits}}` and `{{files}}`. `{{diff}}` marks where the diff goes,
+/// like `{}`. Prompts that use none of the variables get the known values
+/// appended to the system prompt instead, so release commits come out right
+/// with the default prompts.
+///
+/// `{{ticket_title}}` and `{{ticket_description}}` come from the issue tracker,
+/// see [`PromptContext::with_ticket_details`]. A fetched ticket is appended to
+/// the system prompt unless a prompt places it itself.
+///
+/// With `history_examples`, the recent commit subjects are also appended to
+/// the system prompt as examples, unless a prompt places them itself.
+///
+/// `{{commit_types}}` lists the allowed commit types. Configured
+/// `commit_types` replace the list of the default system prompt, and are
+/// appended to other prompts that don't place them. A configured
+/// `max_header_length` likewise replaces or adds the header length rule.
+///
+/// `{{templates}}` lists the configured body templates by commit type, which
+/// are appended to prompts that don't place them.
+///
+/// With `symbol_summary`, `{{symbols}}` lists the functions, types and methods
+/// the diff adds, removes or modifies, and is appended to prompts that don't
+/// place it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptContext {
+    pub date: String,
+    pub version: Option<String>,
+    pub milestone: Option<String>,
+    pub branch: Option<String>,
+    pub ticket: Option<String>,
+    pub ticket_details: Option<Ticket>,
+    pub recent_commits: Vec<String>,
+    pub files: Vec<String>,
+    pub history_examples: bool,
+
+    /// Configured commit types, `None` for the Conventional Commits ones
+    pub commit_types: Option<Vec<String>>,
+
+    /// Configured maximum header length, `None` for the default
+    pub max_header_length: Option<usize>,
+
+    /// Configured body templates by commit type
+    pub templates: BTreeMap<String, String>,
+
+    /// Whether [`PromptContext::with_files`] also lists the changed symbols
+    pub symbol_summary: bool,
+
+    /// Changed declarations, e.g. `src/config.rs: modified fn load`
+    pub symbols: Vec<String>,
+}
+
+impl PromptContext {
+    /// Collect today's date, the project version, the configured milestone,
+    /// the current branch with its ticket and the latest commit subjects
+    pub fn gather(config: &Config) -> Self {
+        let days = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs() / 86_400);
+        let branch = git::current_branch().ok().flatten();
+        let ticket = config
+            .get_ticket_pattern()
+            .ok()
+            .flatten()
+            .zip(branch.as_deref())
+            .and_then(|(pattern, branch)| ticket::extract_ticket(&pattern, branch));
+
+        let history_context = config.get_history_context();
+        let recent_count = match history_context {
+            0 => RECENT_COMMITS,
+            count => count,
+        };
+
+        Self {
+            date: format_date(days as i64, config.get_date_format()),
+            version: git::repo_root()
+                .ok()
+                .and_then(|root| project_version(&root)),
+            milestone: config.get_milestone().map(str::to_string),
+            branch,
+            ticket,
+            ticket_details: None,
+            recent_commits: git::recent_subjects(recent_count).unwrap_or_default(),
+            files: Vec::new(),
+            history_examples: history_context > 0,
+            commit_types: config.commit_types.clone(),
+            max_header_length: config.max_header_length,
+            templates: config.get_templates(),
+            symbol_summary: config.get_symbol_summary(),
+            symbols: Vec::new(),
+        }
+    }
+
+    /// Fetch the title and description of the branch's ticket from the
+    /// configured issue tracker
+    ///
+    /// Failures are reported on stderr and leave the details out, so a tracker
+    /// that can't be reached doesn't keep anyone from committing.
+    pub async fn with_ticket_details(mut self, config: &Config) -> Self {
+        let Some(id) = self.ticket.clone() else {
+            return self;
+        };
+        let fetched = match tracker::from_config(config) {
+            Ok(Some(source)) => tracker::fetch_ticket(source.as_ref(), &id).await,
+            Ok(None) => return self,
+            Err(err) => Err(err),
+        };
+        match fetched {
+            Ok(ticket) => self.ticket_details = Some(ticket),
+            Err(err) => eprintln!(
+                "{}",
+                format!("⚠️  Could not fetch ticket {id}, generating without it: {err:#}").yellow()
+            ),
+        }
+        self
+    }
+
+    /// Add the files changed by the diff the prompts are for, and the
+    /// symbols it changes with `symbol_summary`
+    ///
+    /// Pass the whole diff: the symbols then cover what a truncated diff
+    /// leaves out.
+    pub fn with_files(mut self, diff: &str) -> Self {
+        self.files = git::split_diff_by_file(diff)
+            .into_iter()
+            .map(|file| file.path)
+            .collect();
+        if self.symbol_summary {
+            self.symbols = symbols::changed_symbols(diff, git::blob_text)
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+        }
+        self
+    }
+
+    /// Replace the template variables, unknown values become `unknown`
+    pub fn render(&self, template: &str) -> String {
+        let or_unknown = |value: Option<&str>| value.unwrap_or("unknown").to_string();
+        let list = |items: &[String]| match items {
+            [] => "unknown".to_string(),
+            _ => items.join("\n"),
+        };
+
+        template::render(template, |name| match name {
+            "diff" => Some("{}".to_string()),
+            "date" => Some(self.date.clone()),
+            "version" => Some(or_unknown(self.version.as_deref())),
+            "milestone" => Some(or_unknown(self.milestone.as_deref())),
+            "branch" => Some(or_unknown(self.branch.as_deref())),
+            "ticket" => Some(or_unknown(self.ticket.as_deref())),
+            "ticket_title" => Some(or_unknown(
+                self.ticket_details
+                    .as_ref()
+                    .map(|ticket| ticket.title.as_str()),
+            )),
+            "ticket_description" => Some(or_unknown(
+                self.ticket_details
+                    .as_ref()
+                    .and_then(|ticket| ticket.description.as_deref()),
+            )),
+            "recent_commits" => Some(list(&self.recent_commits)),
+            "files" => Some(list(&self.files)),
+            "symbols" => Some(list(&self.symbols)),
+            "commit_types" => Some(match &self.commit_types {
+                Some(types) => types.join(", "),
+                None => lint::DEFAULT_COMMIT_TYPES.join(", "),
+            }),
+            "templates" => Some(match self.templates.is_empty() {
+                true => "unknown".to_string(),
+                false => self.template_list(),
+            }),
+            _ => None,
+        })
+        .replace("{date}", &self.date)
+        .replace("{version}", self.version.as_deref().unwrap_or("unknown"))
+        .replace(
+            "{milestone}",
+            self.milestone.as_deref().unwrap_or("unknown"),
+        )
+    }
+
+    /// Render both prompts, appending the context to the system prompt when
+    /// neither of them uses a variable
+    pub fn apply(&self, system_prompt: &str, user_prompt: &str) -> (String, String) {
+        let prompts = [system_prompt, user_prompt];
+        let uses_variables = prompts.iter().any(|prompt| {
+            ["{date}", "{version}", "{milestone}"]
+                .iter()
+                .any(|variable| prompt.contains(variable))
+                || template::variables(prompt)
+                    .iter()
+                    .any(|name| CONTEXT_VARIABLES.contains(&name.as_str()))
+        });
+        let places = |names: &[&str]| {
+            prompts.iter().any(|prompt| {
+                template::variables(prompt)
+                    .iter()
+                    .any(|name| names.contains(&name.as_str()))
+            })
+        };
+        let places_history = places(&["recent_commits"]);
+        let places_ticket = places(&["ticket_title", "ticket_description"]);
+        let places_types = places(&["commit_types"]);
+        let places_templates = places(&["templates"]);
+        let places_symbols = places(&["symbols"]);
+
+        let mut system_prompt = self.render(system_prompt);
+        if let Some(types) = self.commit_types.as_ref().filter(|_| !places_types) {
+            system_prompt = match system_prompt.contains(DEFAULT_TYPE_LIST) {
+                true => system_prompt.replace(DEFAULT_TYPE_LIST, &or_list(types)),
+                false => format!(
+                    "{system_prompt}\n\n{COMMIT_TYPES_PREFIX} {}",
+                    types.join(", ")
+                ),
+            };
+        }
+        if let Some(length) = self.max_header_length {
+            let rule = format!("Keep the first line under {length} characters");
+            system_prompt = match system_prompt.contains(DEFAULT_LENGTH_RULE) {
+                true => system_prompt.replace(DEFAULT_LENGTH_RULE, &rule),
+                false => format!("{system_prompt}\n\n{rule}."),
+            };
+        }
+        if !self.templates.is_empty() && !places_templates {
+            system_prompt = format!(
+                "{system_prompt}\n\n{TEMPLATES_PREFIX}\n\n{}",
+                self.template_list()
+            );
+        }
+        if !self.symbols.is_empty() && !places_symbols {
+            let symbols: Vec<String> = self
+                .symbols
+                .iter()
+                .map(|symbol| format!("- {symbol}"))
+                .collect();
+            system_prompt = format!(
+                "{system_prompt}\n\n{SYMBOLS_PREFIX}\n{}",
+                symbols.join("\n")
+            );
+        }
+        if let Some(ticket) = self.ticket_details.as_ref().filter(|_| !places_ticket) {
+            system_prompt = format!(
+                "{system_prompt}\n\n{TICKET_CONTEXT_PREFIX}\n{}: {}",
+                ticket.id, ticket.title
+            );
+            if let Some(description) = &ticket.description {
+                system_prompt = format!("{system_prompt}\n\n{description}");
+            }
+        }
+        if self.history_examples && !places_history && !self.recent_commits.is_empty() {
+            let examples: Vec<String> = self
+                .recent_commits
+                .iter()
+                .map(|subject| format!("- {subject}"))
+                .collect();
+            system_prompt = format!(
+                "{system_prompt}\n\n{HISTORY_CONTEXT_PREFIX}\n{}",
+                examples.join("\n")
+            );
+        }
+        if uses_variables {
+            return (system_prompt, self.render(user_prompt));
+        }
+
+        let mut context = format!("Context: today is {}", self.date);
+        if let Some(version) = &self.version {
+            context.push_str(&format!(", the current project version is {version}"));
+        }
+        if let Some(milestone) = &self.milestone {
+            context.push_str(&format!(", the active milestone is {milestone}"));
+        }
+        context.push_str(". Use these for release-related commits.");
+
+        (
+            format!("{system_prompt}\n\n{context}"),
+            self.render(user_prompt),
+        )
+    }
+
+    // Each template under its commit type, separated by blank lines
+    fn template_list(&self) -> String {
+        self.templates
+            .iter()
+            .map(|(commit_type, template)| format!("{commit_type}:\n{}", template.trim()))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+// `a, b, or c`, as the default system prompt lists types
+fn or_list(items: &[String]) -> String {
+    match items {
+        [] => String::new(),
+        [item] => item.clone(),
+        [rest @ .., last] => format!("{}, or {last}", rest.join(", ")),
+    }
+}
+
+/// Version of the project at the repository root, from `Cargo.toml`,
+/// `package.json` or `pyproject.toml`
+pub fn project_version(repo_root: &Path) -> Option<String> {
+    let read_toml = |name: &str| -> Option<toml::Value> {
+        toml::from_str(&fs::read_to_string(repo_root.join(name)).ok()?).ok()
+    };
+    let string_at = |value: &toml::Value, path: &[&str]| -> Option<String> {
+        path.iter()
+            .try_fold(value, |value, key| value.get(key))?
+            .as_str()
+            .map(str::to_string)
+    };
+
+    if let Some(cargo) = read_toml("Cargo.toml") {
+        if let Some(version) = string_at(&cargo, &["package", "version"])
+            .or_else(|| string_at(&cargo, &["workspace", "package", "version"]))
+        {
+            return Some(version);
+        }
+    }
+
+    if let Some(package) = fs::read_to_string(repo_root.join("package.json"))
+        .ok()
+        .and_then(|contents| serde_json::from_str::<serde_json::Value>(&contents).ok())
+    {
+        if let Some(version) = package["version"].as_str() {
+            return Some(version.to_string());
+        }
+    }
+
+    read_toml("pyproject.toml").and_then(|pyproject| {
+        string_at(&pyproject, &["project", "version"])
+            .or_else(|| string_at(&pyproject, &["tool", "poetry", "version"]))
+    })
+}
+
+/// Format a date given in days since 1970-01-01 (UTC)
+///
+/// Supports `%Y`, `%m`, `%d`, `%e` (day without padding), `%B` (month name)
+/// and `%b` (abbreviated month name).
+pub fn format_date(days: i64, format: &str) -> String {
+    let (year, month, day) = civil_from_days(days);
+    let month_name = MONTH_NAMES[month as usize - 1];
+
+    format
+        .replace("%Y", &year.to_string())
+        .replace("%m", &format!("{month:02}"))
+        .replace("%d", &format!("{day:02}"))
+        .replace("%e", &day.to_string())
+        .replace("%B", month_name)
+        .replace("%b", &month_name[..3])
+}
+
+// Convert days since the Unix epoch to a (year, month, day) civil date,
+// following Howard Hinnant's `civil_from_days`
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn context() -> PromptContext {
+        PromptContext {
+            date: "2025-03-01".to_string(),
+            version: Some("1.4.0".to_string()),
+            milestone: None,
+            branch: Some("feature/JIRA-123-login".to_string()),
+            ticket: Some("JIRA-123".to_string()),
+            ticket_details: None,
+            recent_commits: vec!["feat: add login form".to_string()],
+            files: Vec::new(),
+            history_examples: false,
+            commit_types: None,
+            max_header_length: None,
+            templates: BTreeMap::new(),
+            symbol_summary: false,
+            symbols: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date(0, DEFAULT_DATE_FORMAT), "1970-01-01");
+        // 2024 is a leap year
+        assert_eq!(format_date(19_782, DEFAULT_DATE_FORMAT), "2024-02-29");
+        assert_eq!(format_date(20_148, "%e %B %Y"), "1 March 2025");
+        assert_eq!(format_date(20_148, "%d %b %Y"), "01 Mar 2025");
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            context().render("Release {version} on {date} for {milestone}"),
+            "Release 1.4.0 on 2025-03-01 for unknown"
+        );
+
+        let diff = "diff --git a/src/login.rs b/src/login.rs\n+fn login() {}\n\
+            diff --git a/src/main.rs b/src/main.rs\n+mod login;\n";
+        assert_eq!(
+            context().with_files(diff).render(
+                "{{branch}} ({{ ticket }}) on {{date}}\n{{recent_commits}}\n{{files}}\n{{diff}}"
+            ),
+            "feature/JIRA-123-login (JIRA-123) on 2025-03-01\n\
+             feat: add login form\nsrc/login.rs\nsrc/main.rs\n{}"
+        );
+        assert_eq!(context().render("{{files}} {{other}}"), "unknown {{other}}");
+    }
+
+    #[test]
+    fn test_apply() {
+        // Prompts using variables are rendered as they are
+        let (system, user) = context().apply("Today is {date}.", "{}");
+        assert_eq!(system, "Today is 2025-03-01.");
+        assert_eq!(user, "{}");
+
+        // Otherwise the known context is appended to the system prompt
+        let (system, user) = context().apply("Write commits.", "{}");
+        assert_eq!(
+            system,
+            "Write commits.\n\nContext: today is 2025-03-01, the current project version \
+             is 1.4.0. Use these for release-related commits."
+        );
+        assert_eq!(user, "{}");
+
+        // The diff placeholder alone doesn't count as using the context
+        let (system, user) = context().apply("Write commits for {{branch}}.", "{{diff}}");
+        assert_eq!(system, "Write commits for feature/JIRA-123-login.");
+        assert_eq!(user, "{}");
+        let (system, user) = context().apply("Write commits.", "```diff\n{{ diff }}\n```");
+        assert!(system.starts_with("Write commits.\n\nContext: today is"));
+        assert_eq!(user, "```diff\n{}\n```");
+    }
+
+    #[test]
+    fn test_apply_ticket_details() {
+        let ticket_context = PromptContext {
+            ticket_details: Some(Ticket {
+                id: "JIRA-123".to_string(),
+                title: "Let users sign in".to_string(),
+                description: Some("Support OAuth.".to_string()),
+            }),
+            ..context()
+        };
+
+        let (system, _) = ticket_context.apply("Write commits for {{branch}}.", "{}");
+        assert_eq!(
+            system,
+            format!(
+                "Write commits for feature/JIRA-123-login.\n\n{TICKET_CONTEXT_PREFIX}\n\
+                 JIRA-123: Let users sign in\n\nSupport OAuth."
+            )
+        );
+
+        // Prompts that place the ticket themselves don't get it twice
+        let (system, _) = ticket_context.apply("Ticket: {{ticket_title}}", "{}");
+        assert_eq!(system, "Ticket: Let users sign in");
+        assert_eq!(
+            context().render("{{ticket_title}}: {{ticket_description}}"),
+            "unknown: unknown"
+        );
+    }
+
+    #[test]
+    fn test_apply_history_examples() {
+        let context = PromptContext {
+            recent_commits: vec![
+                "feat(auth): add login form".to_string(),
+                "fix(auth): trim user names".to_string(),
+            ],
+            history_examples: true,
+            ..context()
+        };
+
+        let (system, _) = context.apply("Write commits for {{branch}}.", "{}");
+        assert_eq!(
+            system,
+            format!(
+                "Write commits for feature/JIRA-123-login.\n\n{HISTORY_CONTEXT_PREFIX}\n\
+                 - feat(auth): add login form\n- fix(auth): trim user names"
+            )
+        );
+        let (system, _) = context.apply("Write commits.", "{}");
+        assert!(system.contains(HISTORY_CONTEXT_PREFIX));
+        assert!(system.ends_with("Use these for release-related commits."));
+
+        // Prompts that place the subjects themselves don't get them twice
+        let (system, _) = context.apply("Like these:\n{{recent_commits}}", "{}");
+        assert!(!system.contains(HISTORY_CONTEXT_PREFIX));
+    }
+
+    #[test]
+    fn test_apply_commit_types() {
+        let typed_context = PromptContext {
+            commit_types: Some(vec![
+                "feat".to_string(),
+                "wip".to_string(),
+                "infra".to_string(),
+            ]),
+            ..context()
+        };
+
+        // The list of the default prompt is replaced
+        let default_prompt = Config::default().get_system_prompt().to_string();
+        assert!(default_prompt.contains(DEFAULT_TYPE_LIST));
+        let (system, _) = typed_context.apply(&default_prompt, "{{diff}}");
+        assert!(system.contains("Start with a type: feat, wip, or infra\n"));
+        let (system, _) = PromptContext {
+            max_header_length: Some(50),
+            ..context()
+        }
+        .apply(&default_prompt, "{{diff}}");
+        assert!(system.contains("Keep the first line under 50 characters\n"));
+
+        // Other prompts get the types appended unless they place them
+        let (system, _) = PromptContext {
+            max_header_length: Some(50),
+            ..typed_context.clone()
+        }
+        .apply("Write commits for {{branch}}.", "{}");
+        assert_eq!(
+            system,
+            format!(
+                "Write commits for feature/JIRA-123-login.\n\n{COMMIT_TYPES_PREFIX} feat, wip, infra\n\n\
+                 Keep the first line under 50 characters."
+            )
+        );
+        let (system, _) = typed_context.apply("Types: {{commit_types}}", "{}");
+        assert!(system.starts_with("Types: feat, wip, infra\n\nContext:"));
+        assert_eq!(
+            context().render("{{commit_types}}"),
+            lint::DEFAULT_COMMIT_TYPES.join(", ")
+        );
+    }
+
+    #[test]
+    fn test_apply_templates() {
+        let templated_context = PromptContext {
+            templates: BTreeMap::from([
+                ("fix".to_string(), "Root cause:\nFix:\n".to_string()),
+                ("feat".to_string(), "Why:".to_string()),
+            ]),
+            ..context()
+        };
+
+        let (system, _) = templated_context.apply("Write a commit.", "{}");
+        assert!(system.starts_with(&format!(
+            "Write a commit.\n\n{TEMPLATES_PREFIX}\n\nfeat:\nWhy:\n\nfix:\nRoot cause:\nFix:\n\n"
+        )));
+
+        // Prompts that place the templates don't get them twice
+        let (system, _) = templated_context.apply("Templates:\n{{templates}}", "{}");
+        assert!(system.starts_with("Templates:\nfeat:\nWhy:\n\nfix:"));
+        assert!(!system.contains(TEMPLATES_PREFIX));
+        assert_eq!(context().render("{{templates}}"), "unknown");
+        assert!(!context()
+            .apply("Write a commit.", "{}")
+            .0
+            .contains(TEMPLATES_PREFIX));
+    }
+
+    #[test]
+    fn test_apply_symbols() {
+        let diff = "diff --git a/src/lib.rs b/src/lib.rs\n@@ -1 +1 @@\n+pub fn parse() {}\n";
+        assert!(context().with_files(diff).symbols.is_empty());
+
+        let summarized = PromptContext {
+            symbol_summary: true,
+            ..context()
+        }
+        .with_files(diff);
+        assert_eq!(summarized.symbols, vec!["src/lib.rs: added fn parse"]);
+        let (system, _) = summarized.apply("Write a commit.", "{}");
+        assert!(system.starts_with(&format!(
+            "Write a commit.\n\n{SYMBOLS_PREFIX}\n- src/lib.rs: added fn parse\n\n"
+        )));
+
+        // Prompts that place the symbols don't get them twice
+        let (system, _) = summarized.apply("Changed:\n{{symbols}}", "{}");
+        assert_eq!(system, "Changed:\nsrc/lib.rs: added fn parse");
+    }
+
+    #[test]
+    fn test_project_version() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        assert_eq!(project_version(root), None);
+
+        fs::write(root.join("package.json"), r#"{"version": "2.0.1"}"#).unwrap();
+        assert_eq!(project_version(root), Some("2.0.1".to_string()));
+
+        // Cargo.toml takes precedence, including workspace versions
+        fs::write(
+            root.join("Cargo.toml"),
+            "[workspace.package]\nversion = \"0.3.0\"\n",
+        )
+        .unwrap();
+        assert_eq!(project_version(root), Some("0.3.0".to_string()));
+    }
+}
@@ -0,0 +1,313 @@
+use crate::config::Config;
+use crate::generated;
+use crate::git;
+use crate::lint::{self, LintRules};
+use crate::llm::LlmClient;
+use crate::scheduler::Scheduler;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+/// A prompt variant to evaluate, read from a TOML file
+///
+/// Unset fields fall back to the active configuration, so a file only needs
+/// the parts that differ.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PromptVariant {
+    /// Name shown in the report, the file stem
+    #[serde(skip)]
+    pub name: String,
+
+    pub system_prompt: Option<String>,
+    pub user_prompt: Option<String>,
+    pub model: Option<String>,
+}
+
+impl PromptVariant {
+    /// Load a variant from a TOML file, named after the file
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents =
+            fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+        let mut variant: Self =
+            toml::from_str(&contents).context(format!("Failed to parse {}", path.display()))?;
+        variant.name = path.file_stem().map_or_else(
+            || path.display().to_string(),
+            |stem| stem.to_string_lossy().into_owned(),
+        );
+
+        Ok(variant)
+    }
+
+    pub fn system_prompt<'a>(&'a self, config: &'a Config) -> &'a str {
+        self.system_prompt
+            .as_deref()
+            .unwrap_or(config.get_system_prompt())
+    }
+
+    pub fn user_prompt<'a>(&'a self, config: &'a Config) -> &'a str {
+        self.user_prompt
+            .as_deref()
+            .unwrap_or(config.get_user_prompt())
+    }
+
+    pub fn model<'a>(&'a self, config: &'a Config) -> &'a str {
+        self.model.as_deref().unwrap_or(config.get_model())
+    }
+}
+
+/// A historical commit replayed through every variant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sample {
+    pub rev: String,
+    pub message: String,
+    pub diff: String,
+}
+
+impl Sample {
+    /// Load the non-merge commits of a revision range, skipping commits without a diff
+    ///
+    /// Diffs are condensed like those of new commits, so the prompts match.
+    pub fn load_range(
+        range: &str,
+        snapshot_patterns: &[String],
+        generated_patterns: &[String],
+    ) -> Result<Vec<Self>> {
+        let mut samples = Vec::new();
+        for rev in git::commits_in_range(range)? {
+            let diff = git::commit_diff(&rev)?;
+            if diff.trim().is_empty() {
+                continue;
+            }
+
+            samples.push(Self {
+                message: git::commit_message(&rev)?,
+                diff: generated::condense_generated(
+                    &git::condense_snapshots(&diff, snapshot_patterns),
+                    generated_patterns,
+                    git::blob_size,
+                ),
+                rev,
+            });
+        }
+
+        Ok(samples)
+    }
+}
+
+/// Results of one variant over all samples
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VariantScore {
+    pub name: String,
+    pub model: String,
+
+    /// Samples a message was generated for
+    pub generated: usize,
+
+    /// Samples whose request failed
+    pub failed: usize,
+
+    /// Generated messages without any lint violation
+    pub compliant: usize,
+
+    /// Sum of the similarities to the original headers
+    pub similarity_sum: f64,
+}
+
+impl VariantScore {
+    /// Share of generated messages that follow the commit rules
+    pub fn compliance(&self) -> f64 {
+        ratio(self.compliant as f64, self.generated)
+    }
+
+    /// Mean similarity of the generated headers to the original ones
+    pub fn similarity(&self) -> f64 {
+        ratio(self.similarity_sum, self.generated)
+    }
+}
+
+fn ratio(value: f64, count: usize) -> f64 {
+    if count == 0 {
+        0.0
+    } else {
+        value / count as f64
+    }
+}
+
+/// Generate a message for every sample with one variant and score the results
+pub async fn score_variant(
+    name: &str,
+    client: &LlmClient,
+    system_prompt: &str,
+    user_prompt: &str,
+    samples: &[Sample],
+    rules: &LintRules,
+    requests_per_minute: Option<u32>,
+) -> VariantScore {
+    let items = samples
+        .iter()
+        .map(|sample| (sample.rev.chars().take(7).collect(), sample))
+        .collect();
+    let report = Scheduler::new(requests_per_minute)
+        .run(&format!("   {name}"), items, async |sample: &&Sample| {
+            let diff = client.fit_diff(&sample.diff, system_prompt, user_prompt);
+            let mut messages = client
+                .generate_commit_messages(&diff, system_prompt, user_prompt, 1)
+                .await?;
+            Ok(messages.remove(0))
+        })
+        .await;
+
+    let mut score = VariantScore {
+        name: name.to_string(),
+        model: client.model().to_string(),
+        ..Default::default()
+    };
+    for (sample, outcome) in samples.iter().zip(&report.outcomes) {
+        let Ok(message) = &outcome.result else {
+            score.failed += 1;
+            continue;
+        };
+
+        score.generated += 1;
+        if lint::lint_message(message, rules).is_empty() {
+            score.compliant += 1;
+        }
+        score.similarity_sum += header_similarity(message, &sample.message);
+    }
+
+    score
+}
+
+/// Word overlap of two commit headers, from 0 (nothing shared) to 1 (same words)
+///
+/// The Dice coefficient of the lowercased words, so `feat(auth): add login`
+/// and `feat: add OAuth login` are close while unrelated headers score 0.
+pub fn header_similarity(generated: &str, original: &str) -> f64 {
+    let words = |message: &str| -> BTreeSet<String> {
+        message
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect()
+    };
+    let (generated, original) = (words(generated), words(original));
+    if generated.is_empty() && original.is_empty() {
+        return 1.0;
+    }
+
+    let shared = generated.intersection(&original).count();
+    2.0 * shared as f64 / (generated.len() + original.len()) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+    use wiremock::{
+        matchers::{body_string_contains, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    #[test]
+    fn test_load_variant() {
+        let temp_dir = TempDir::new().unwrap();
+        let variant_path = temp_dir.path().join("terse.toml");
+        fs::write(&variant_path, "system_prompt = \"Be terse.\"\n").unwrap();
+
+        let variant = PromptVariant::load(&variant_path).unwrap();
+        assert_eq!(variant.name, "terse");
+
+        // Unset fields come from the configuration
+        let config = Config::default();
+        assert_eq!(variant.system_prompt(&config), "Be terse.");
+        assert_eq!(variant.user_prompt(&config), config.get_user_prompt());
+        assert_eq!(variant.model(&config), config.get_model());
+
+        fs::write(&variant_path, "sytem_prompt = \"typo\"\n").unwrap();
+        assert!(PromptVariant::load(&variant_path).is_err());
+    }
+
+    #[test]
+    fn test_header_similarity() {
+        assert_eq!(
+            header_similarity("fix: handle errors", "fix: handle errors"),
+            1.0
+        );
+        assert_eq!(
+            header_similarity("feat: add login", "docs: update readme"),
+            0.0
+        );
+        // Only the header counts
+        assert_eq!(
+            header_similarity("fix: handle errors\n\nDetails", "Fix: Handle errors"),
+            1.0
+        );
+
+        let similarity = header_similarity("feat(auth): add login", "feat: add OAuth login");
+        assert!((similarity - 0.75).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_score_variant() {
+        let mock_server = MockServer::start().await;
+        let reply = |content: &str| {
+            ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{ "message": { "role": "assistant", "content": content } }]
+            }))
+        };
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_string_contains("parser.rs"))
+            .respond_with(reply("fix(parser): handle empty input"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_string_contains("readme.md"))
+            .respond_with(reply("Updated the readme."))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_string_contains("broken.rs"))
+            .respond_with(ResponseTemplate::new(400))
+            .mount(&mock_server)
+            .await;
+
+        let sample = |rev: &str, message: &str, file: &str| Sample {
+            rev: rev.to_string(),
+            message: message.to_string(),
+            diff: format!("diff --git a/{file} b/{file}\n+change\n"),
+        };
+        let samples = vec![
+            sample("aaaaaaaa", "fix(parser): handle empty input", "parser.rs"),
+            sample("bbbbbbbb", "docs: update readme", "readme.md"),
+            sample("cccccccc", "fix: broken", "broken.rs"),
+        ];
+
+        let client = LlmClient::new("token", &mock_server.uri(), "gpt-4o-mini");
+        let score = score_variant(
+            "baseline",
+            &client,
+            "system",
+            "{}",
+            &samples,
+            &LintRules::default(),
+            None,
+        )
+        .await;
+
+        assert_eq!(score.model, "gpt-4o-mini");
+        assert_eq!((score.generated, score.failed, score.compliant), (2, 1, 1));
+        assert_eq!(score.compliance(), 0.5);
+        // 1 for the exact match, 1/3 for sharing only "readme"
+        assert!((score.similarity() - 2.0 / 3.0).abs() < 1e-9);
+    }
+}
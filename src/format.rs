@@ -0,0 +1,241 @@
+use crate::lint;
+use serde::Deserialize;
+
+/// Column body lines are wrapped at, as `git log` and most tools expect
+pub const BODY_WIDTH: usize = 72;
+
+/// Clean up a generated commit message without another request
+///
+/// Strips the code fences and quotes models wrap messages in, trims trailing
+/// whitespace, collapses runs of blank lines, puts a blank line between the
+/// subject and the body and wraps body lines longer than [`BODY_WIDTH`].
+/// Trailers, indented lines and words longer than a line are left as they are.
+pub fn format_message(message: &str) -> String {
+    let message = message.replace("\r\n", "\n");
+    let message = strip_quotes(strip_fences(message.trim()));
+
+    let mut lines = message.lines().map(str::trim_end);
+    let subject = lines.by_ref().find(|line| !line.trim().is_empty());
+    let Some(subject) = subject else {
+        return String::new();
+    };
+
+    let mut formatted = subject.trim().to_string();
+    let mut blank = true;
+    for line in lines {
+        if line.is_empty() {
+            blank = true;
+            continue;
+        }
+        formatted.push('\n');
+        if blank {
+            formatted.push('\n');
+            blank = false;
+        }
+        formatted.push_str(&wrap_line(line, BODY_WIDTH));
+    }
+    formatted
+}
+
+/// The parts of a commit message a model returns as JSON with `structured_output`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CommitFields {
+    #[serde(rename = "type")]
+    pub commit_type: String,
+    #[serde(default)]
+    pub scope: Option<String>,
+    pub subject: String,
+    #[serde(default)]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub breaking: bool,
+}
+
+impl CommitFields {
+    /// Read the fields from a reply, `None` when it isn't the JSON object asked for
+    pub fn parse(reply: &str) -> Option<Self> {
+        let fields: Self = serde_json::from_str(strip_fences(reply.trim())).ok()?;
+        let complete = !fields.commit_type.trim().is_empty() && !fields.subject.trim().is_empty();
+        complete.then_some(fields)
+    }
+
+    /// Assemble `type(scope)!: subject` and the body into a formatted message
+    pub fn to_message(&self) -> String {
+        let mut header = self.commit_type.trim().to_string();
+        if let Some(scope) = self.scope.as_deref().map(str::trim) {
+            if !scope.is_empty() {
+                header = format!("{header}({scope})");
+            }
+        }
+        if self.breaking {
+            header.push('!');
+        }
+
+        // Models sometimes repeat the type and scope in the subject
+        let subject = self.subject.trim();
+        let subject = lint::parse_header(subject).map_or(subject, |parsed| parsed.subject);
+        let mut message = format!("{header}: {}", subject.trim_end_matches('.'));
+        if let Some(body) = self.body.as_deref().map(str::trim) {
+            if !body.is_empty() {
+                message = format!("{message}\n\n{body}");
+            }
+        }
+        format_message(&message)
+    }
+}
+
+// Drop a ```fence around the whole message, with or without a language
+fn strip_fences(message: &str) -> &str {
+    let Some(rest) = message.strip_prefix("```") else {
+        return message;
+    };
+    let Some((info, body)) = rest.split_once('\n') else {
+        return message;
+    };
+    if info.contains(char::is_whitespace) {
+        return message;
+    }
+    body.trim_end()
+        .strip_suffix("```")
+        .map(str::trim)
+        .unwrap_or(message)
+}
+
+// Drop one pair of quotes or backticks around the whole message
+fn strip_quotes(message: &str) -> &str {
+    for quote in ['"', '\'', '`'] {
+        if let Some(inner) = message
+            .strip_prefix(quote)
+            .and_then(|rest| rest.strip_suffix(quote))
+        {
+            if !inner.contains(quote) {
+                return inner.trim();
+            }
+        }
+    }
+    message
+}
+
+// Wrap a body line at `width`, indenting continuations of list items under their text
+fn wrap_line(line: &str, width: usize) -> String {
+    if line.chars().count() <= width || line.starts_with([' ', '\t']) || lint::is_footer_line(line)
+    {
+        return line.to_string();
+    }
+
+    let indent = " ".repeat(list_marker_width(line));
+    let mut wrapped = String::new();
+    let mut current = String::new();
+    for word in line.split_whitespace() {
+        let prefix = if wrapped.is_empty() { "" } else { &indent };
+        if !current.is_empty()
+            && prefix.len() + current.chars().count() + 1 + word.chars().count() > width
+        {
+            wrapped.push_str(prefix);
+            wrapped.push_str(&current);
+            wrapped.push('\n');
+            current.clear();
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !wrapped.is_empty() {
+        wrapped.push_str(&indent);
+    }
+    wrapped.push_str(&current);
+    wrapped
+}
+
+// Width of a `- `, `* ` or `1. ` list marker at the start of a line
+fn list_marker_width(line: &str) -> usize {
+    if line.starts_with("- ") || line.starts_with("* ") {
+        return 2;
+    }
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    if digits > 0 && line[digits..].starts_with(". ") {
+        return digits + 2;
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_message() {
+        assert_eq!(
+            format_message("```text\nfeat: add login  \nAdd a login form.\n```"),
+            "feat: add login\n\nAdd a login form."
+        );
+        assert_eq!(format_message("\"fix: typo\""), "fix: typo");
+        assert_eq!(format_message("`fix: typo`"), "fix: typo");
+        assert_eq!(
+            format_message("fix: handle \"quoted\" names"),
+            "fix: handle \"quoted\" names"
+        );
+        assert_eq!(
+            format_message("\r\n\nfix: typo\r\n\r\n\r\n\r\nBody\r\n\nRefs: #1\n"),
+            "fix: typo\n\nBody\n\nRefs: #1"
+        );
+        assert_eq!(format_message("  \n```\n```"), "");
+    }
+
+    #[test]
+    fn test_commit_fields() {
+        let fields = CommitFields::parse(
+            r#"{"type": "feat", "scope": "api", "subject": "Add rate limits.",
+                "body": "Limit clients to 100 requests per minute.", "breaking": true}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            fields.to_message(),
+            "feat(api)!: Add rate limits\n\nLimit clients to 100 requests per minute."
+        );
+
+        // Optional fields may be null or missing, fences are stripped
+        let fields = CommitFields::parse(
+            "```json\n{\"type\": \"fix\", \"scope\": null, \"subject\": \"fix: handle empty diffs\"}\n```",
+        )
+        .unwrap();
+        assert_eq!(fields.to_message(), "fix: handle empty diffs");
+
+        assert_eq!(CommitFields::parse("fix: not json"), None);
+        assert_eq!(
+            CommitFields::parse(r#"{"type": "fix", "subject": " "}"#),
+            None
+        );
+    }
+
+    #[test]
+    fn test_wrap_body() {
+        let body = "Wrap the body of generated messages at 72 columns, so that git log \
+                    and other tools show it without long lines.";
+        let formatted = format_message(&format!("feat: wrap bodies\n\n{body}"));
+        assert!(formatted.lines().all(|line| line.len() <= BODY_WIDTH));
+        assert_eq!(
+            formatted.split_whitespace().collect::<Vec<_>>(),
+            format!("feat: wrap bodies {body}")
+                .split_whitespace()
+                .collect::<Vec<_>>()
+        );
+
+        // List items keep their continuation lines under the text
+        let item = "- Read the settings from the environment before the config files so \
+                    that CI jobs can override them";
+        let wrapped = wrap_line(item, BODY_WIDTH);
+        assert!(wrapped.lines().skip(1).all(|line| line.starts_with("  ")));
+        let wrapped = wrap_line(&item.replacen("- ", "12. ", 1), BODY_WIDTH);
+        assert!(wrapped.lines().skip(1).all(|line| line.starts_with("    ")));
+
+        // Trailers, indented code and long URLs are not broken up
+        let trailer = format!("Co-authored-by: {}", "Jane Doe ".repeat(10));
+        assert_eq!(wrap_line(&trailer, BODY_WIDTH), trailer);
+        let code = format!("    {}", "let x = 1; ".repeat(10));
+        assert_eq!(wrap_line(&code, BODY_WIDTH), code);
+        let url = format!("https://example.com/{}", "a".repeat(80));
+        assert_eq!(wrap_line(&url, BODY_WIDTH), url);
+    }
+}
@@ -0,0 +1,271 @@
+use crate::git::{self, FileDiff};
+use crate::glob;
+use std::path::Path;
+
+/// Lock files, recognized by name wherever they are
+pub const LOCKFILES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "bun.lockb",
+    "poetry.lock",
+    "Pipfile.lock",
+    "uv.lock",
+    "Gemfile.lock",
+    "composer.lock",
+    "go.sum",
+    "flake.lock",
+];
+
+/// Files summarized by default besides binaries, see `generated_patterns`
+pub const DEFAULT_GENERATED_PATTERNS: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "poetry.lock",
+    "Pipfile.lock",
+    "uv.lock",
+    "Gemfile.lock",
+    "composer.lock",
+    "go.sum",
+    "flake.lock",
+    "*.min.js",
+    "*.min.css",
+    "*.map",
+];
+
+/// Added lines longer than this mark a file as minified, whatever its name
+const MINIFIED_LINE_LENGTH: usize = 1000;
+
+/// Kind of file whose content is summarized instead of sent to the model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    Binary,
+    Lockfile,
+    Minified,
+    Generated,
+}
+
+/// Classify a file of the diff, `None` for files the model should read
+///
+/// Binaries and files with very long lines are always recognized, lock files
+/// and other generated files only when they match `patterns`.
+pub fn classify<S: AsRef<str>>(file: &FileDiff, patterns: &[S]) -> Option<FileKind> {
+    if file.diff.lines().any(|line| {
+        line == "GIT binary patch"
+            || (line.starts_with("Binary files ") && line.ends_with(" differ"))
+    }) {
+        return Some(FileKind::Binary);
+    }
+
+    let name = file.path.rsplit('/').next().unwrap_or_default();
+    let long_lines = file
+        .diff
+        .lines()
+        .any(|line| line.starts_with('+') && line.len() > MINIFIED_LINE_LENGTH);
+    if glob::matches_any(patterns, &file.path) {
+        return Some(if LOCKFILES.contains(&name) {
+            FileKind::Lockfile
+        } else if name.contains(".min.") || long_lines {
+            FileKind::Minified
+        } else {
+            FileKind::Generated
+        });
+    }
+    long_lines.then_some(FileKind::Minified)
+}
+
+/// Replace the content of binary, minified, lock and generated files with a
+/// one-line summary each, e.g. `[modified binary image assets/logo.png, +2KB]`
+///
+/// `blob_size` looks up the size of a blob from the `index` line of a binary
+/// file, [`git::blob_size`] for the current repository.
+pub fn condense_generated<S: AsRef<str>>(
+    diff: &str,
+    patterns: &[S],
+    blob_size: impl Fn(&str) -> Option<u64>,
+) -> String {
+    let files = git::split_diff_by_file(diff);
+    if files.iter().all(|file| classify(file, patterns).is_none()) {
+        return diff.to_string();
+    }
+
+    files
+        .iter()
+        .map(|file| match classify(file, patterns) {
+            Some(kind) => format!("[{}]\n", summarize(file, kind, &blob_size)),
+            None => file.diff.clone(),
+        })
+        .collect()
+}
+
+// `modified lockfile Cargo.lock, +12 -4 lines`
+fn summarize(file: &FileDiff, kind: FileKind, blob_size: impl Fn(&str) -> Option<u64>) -> String {
+    let description = match kind {
+        FileKind::Binary => binary_description(&file.path),
+        FileKind::Lockfile => "lockfile",
+        FileKind::Minified => "minified asset",
+        FileKind::Generated => "generated file",
+    };
+    if kind != FileKind::Binary {
+        return summarize_text(file, description);
+    }
+
+    let summary = format!("{} {description} {}", change_status(&file.diff), file.path);
+    match size_change(&file.diff, blob_size) {
+        Some(change) => format!("{summary}, {change}"),
+        None => summary,
+    }
+}
+
+/// One line describing the change of a text file without its content, e.g.
+/// `modified generated file src/schema.rs, +12 -4 lines`
+pub fn summarize_text(file: &FileDiff, description: &str) -> String {
+    let summary = format!("{} {description} {}", change_status(&file.diff), file.path);
+    let stat = git::FileStat::of(file);
+    let change = match (stat.insertions, stat.deletions) {
+        (0, 0) => return summary,
+        (added, 0) => format!("+{added} lines"),
+        (0, removed) => format!("-{removed} lines"),
+        (added, removed) => format!("+{added} -{removed} lines"),
+    };
+    format!("{summary}, {change}")
+}
+
+fn change_status(diff: &str) -> &'static str {
+    let header: Vec<&str> = diff
+        .lines()
+        .take_while(|line| !line.starts_with("@@"))
+        .collect();
+    if header.iter().any(|line| line.starts_with("new file mode")) {
+        "added"
+    } else if header
+        .iter()
+        .any(|line| line.starts_with("deleted file mode"))
+    {
+        "deleted"
+    } else if header.iter().any(|line| line.starts_with("rename to ")) {
+        "renamed"
+    } else {
+        "modified"
+    }
+}
+
+fn binary_description(path: &str) -> &'static str {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "png" | "jpg" | "jpeg" | "gif" | "webp" | "ico" | "bmp" | "tiff" | "avif" => "binary image",
+        "woff" | "woff2" | "ttf" | "otf" | "eot" => "binary font",
+        "zip" | "gz" | "tgz" | "tar" | "xz" | "7z" | "jar" => "binary archive",
+        "pdf" => "binary document",
+        _ => "binary file",
+    }
+}
+
+// `+2KB` from the sizes of the blobs on the `index <old>..<new>` line
+fn size_change(diff: &str, blob_size: impl Fn(&str) -> Option<u64>) -> Option<String> {
+    let ids = diff
+        .lines()
+        .find_map(|line| line.strip_prefix("index "))?
+        .split_whitespace()
+        .next()?;
+    let (old, new) = ids.split_once("..")?;
+    let size = |id: &str| match id.trim_start_matches('0') {
+        "" => Some(0),
+        _ => blob_size(id),
+    };
+
+    let delta = size(new)? as i64 - size(old)? as i64;
+    let sign = if delta < 0 { '-' } else { '+' };
+    let bytes = delta.unsigned_abs();
+    Some(if bytes >= 1024 * 1024 {
+        format!("{sign}{}MB", bytes.div_ceil(1024 * 1024))
+    } else if bytes >= 1024 {
+        format!("{sign}{}KB", bytes.div_ceil(1024))
+    } else {
+        format!("{sign}{bytes}B")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, diff: &str) -> FileDiff {
+        FileDiff {
+            path: path.to_string(),
+            diff: format!("diff --git a/{path} b/{path}\n{diff}"),
+        }
+    }
+
+    #[test]
+    fn test_classify() {
+        let patterns = DEFAULT_GENERATED_PATTERNS;
+        let binary = file(
+            "assets/logo.png",
+            "index 1234567..89abcde 100644\nBinary files a/assets/logo.png and b/assets/logo.png differ\n",
+        );
+        assert_eq!(classify(&binary, patterns), Some(FileKind::Binary));
+        let lock = file("web/yarn.lock", "@@ -1 +1 @@\n-a\n+b\n");
+        assert_eq!(classify(&lock, patterns), Some(FileKind::Lockfile));
+        let minified = file("dist/app.min.js", "@@ -1 +1 @@\n-a\n+b\n");
+        assert_eq!(classify(&minified, patterns), Some(FileKind::Minified));
+        let bundle = file(
+            "dist/app.js",
+            &format!("@@ -0,0 +1 @@\n+{}\n", "x".repeat(2000)),
+        );
+        assert_eq!(classify(&bundle, patterns), Some(FileKind::Minified));
+        let source = file("src/main.rs", "@@ -1 +1 @@\n-a\n+b\n");
+        assert_eq!(classify(&source, patterns), None);
+
+        // Patterns replace the defaults, binaries are always recognized
+        assert_eq!(classify(&lock, &[] as &[&str]), None);
+        assert_eq!(classify(&binary, &[] as &[&str]), Some(FileKind::Binary));
+        let generated = file("src/gen/api.rs", "@@ -1 +1 @@\n-a\n+b\n");
+        assert_eq!(
+            classify(&generated, &["src/gen/"]),
+            Some(FileKind::Generated)
+        );
+    }
+
+    #[test]
+    fn test_condense_generated() {
+        let diff = "diff --git a/src/lib.rs b/src/lib.rs\n\
+                    --- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1 +1 @@\n-a\n+b\n\
+                    diff --git a/assets/logo.png b/assets/logo.png\n\
+                    index 1234567..89abcde 100644\n\
+                    Binary files a/assets/logo.png and b/assets/logo.png differ\n\
+                    diff --git a/Cargo.lock b/Cargo.lock\n\
+                    --- a/Cargo.lock\n+++ b/Cargo.lock\n@@ -1,2 +1,3 @@\n-x\n+y\n+z\n\
+                    diff --git a/font.woff2 b/font.woff2\n\
+                    new file mode 100644\nindex 0000000..1111111\nGIT binary patch\nliteral 10\n";
+        let sizes = |id: &str| match id {
+            "1234567" => Some(1000),
+            "89abcde" => Some(3048),
+            "1111111" => Some(10),
+            _ => None,
+        };
+        assert_eq!(
+            condense_generated(diff, DEFAULT_GENERATED_PATTERNS, sizes),
+            "diff --git a/src/lib.rs b/src/lib.rs\n\
+             --- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1 +1 @@\n-a\n+b\n\
+             [modified binary image assets/logo.png, +2KB]\n\
+             [modified lockfile Cargo.lock, +2 -1 lines]\n\
+             [added binary font font.woff2, +10B]\n"
+        );
+
+        let plain = "diff --git a/a.rs b/a.rs\n@@ -1 +1 @@\n-a\n+b\n";
+        assert_eq!(
+            condense_generated(plain, DEFAULT_GENERATED_PATTERNS, |_| None),
+            plain
+        );
+    }
+}
@@ -1,105 +1,2855 @@
+//! Git operations on the repository of the current directory
+//!
+//! Everything goes through libgit2, so it works without a git binary on PATH
+//! and fails with a [`GitError`] instead of git's stderr. The binary is only
+//! run for what libgit2 doesn't do:
+//!
+//! - [`commit`], [`commit_paths`] and [`GitRepo::commit`] when a commit hook
+//!   is installed, `commit.gpgSign` is set, a merge or rebase is unfinished,
+//!   or `commit_args` has a flag besides `--signoff`, `--no-verify` and
+//!   `--allow-empty`
+//! - [`push_changes`] when a `pre-push` hook is installed, or libgit2 can't
+//!   reach or authenticate with the remote
+
+use crate::glob;
+use crate::lint;
 use anyhow::{Context, Result};
 use colored::Colorize;
+use git2::{
+    BranchType, Cred, CredentialType, Delta, DiffFindOptions, DiffOptions, ErrorClass, ErrorCode,
+    IndexAddOption, ObjectType, Oid, Pathspec, PathspecFlags, PushOptions, RemoteCallbacks,
+    Repository, Signature, Tree,
+};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
 use std::process::Command;
 
-/// Get the diff for staged changes in the git repository
-pub fn get_diff() -> Result<String> {
-    // Check git installation and is in a repo by `git status`
-    let git_status_output = Command::new("git").arg("status").output()?;
+/// Why a git operation failed, for callers that react to more than the message
+#[derive(Debug)]
+pub enum GitError {
+    /// No repository contains the current directory
+    NotARepository,
+
+    /// Neither `user.name` and `user.email` nor the `GIT_AUTHOR_*` variables are set
+    MissingIdentity,
+
+    /// The staged changes don't differ from HEAD
+    NothingToCommit,
+
+    /// A push without a remote given, tracked or guessable
+    NoRemote,
+
+    /// HEAD is detached and no branch to push to was given
+    DetachedHead,
+
+    /// The remote refused every credential offered
+    Authentication {
+        remote: String,
+    },
+
+    /// The remote refused to update `reference`, e.g. because it has newer commits
+    PushRejected {
+        reference: String,
+        reason: String,
+    },
+
+    /// The git binary, run for hooks or signing, failed or couldn't be started
+    CommandFailed {
+        command: String,
+        code: Option<i32>,
+    },
+
+    Libgit2(git2::Error),
+}
+
+impl fmt::Display for GitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotARepository => write!(f, "Not inside a git repository"),
+            Self::MissingIdentity => write!(
+                f,
+                "No author identity, set it with 'git config --global user.name \"...\"' \
+                 and 'git config --global user.email \"...\"'"
+            ),
+            Self::NothingToCommit => write!(f, "Nothing to commit, stage some changes first"),
+            Self::NoRemote => write!(f, "No remote to push to, add one or pass --remote"),
+            Self::DetachedHead => write!(f, "HEAD is detached, name the branch to push to"),
+            Self::Authentication { remote } => {
+                write!(f, "Authentication with {remote} failed")
+            }
+            Self::PushRejected { reference, reason } => {
+                write!(f, "The remote rejected {reference}: {reason}")
+            }
+            Self::CommandFailed {
+                command,
+                code: Some(code),
+            } => write!(f, "'{command}' failed with exit code {code}"),
+            Self::CommandFailed {
+                command,
+                code: None,
+            } => write!(f, "'{command}' could not be run"),
+            Self::Libgit2(err) => write!(f, "{}", err.message()),
+        }
+    }
+}
+
+impl std::error::Error for GitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Libgit2(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<git2::Error> for GitError {
+    fn from(err: git2::Error) -> Self {
+        match err.code() {
+            ErrorCode::NotFound if err.class() == ErrorClass::Repository => Self::NotARepository,
+            _ => Self::Libgit2(err),
+        }
+    }
+}
+
+/// The repository of the current directory, honoring `GIT_DIR` and friends
+pub fn open_repository() -> Result<Repository, GitError> {
+    Repository::open_from_env().map_err(GitError::from)
+}
+
+/// Root of the working tree, without the trailing slash libgit2 keeps
+fn workdir(repo: &Repository) -> Result<PathBuf, GitError> {
+    match repo.workdir() {
+        Some(workdir) => Ok(workdir.components().collect()),
+        None => Err(GitError::NotARepository),
+    }
+}
+
+/// How a file changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeStatus {
+    Added,
+    Deleted,
+    Modified,
+    Renamed,
+    Copied,
+
+    /// E.g. a file replaced by a symlink
+    TypeChanged,
+}
+
+/// A `@@ -old_start,old_lines +new_start,new_lines @@` section of a file's diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+
+    /// The `@@` line, with the enclosing function git found after it
+    pub header: String,
+}
+
+/// The change of a single file, with its part of the unified diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    /// Path after the change, relative to the repository root
+    pub path: String,
+
+    /// Path before a rename or copy
+    pub old_path: Option<String>,
+    pub status: ChangeStatus,
+    pub binary: bool,
+    pub hunks: Vec<Hunk>,
+
+    /// `diff --git` header and hunks, as `git diff` prints them
+    pub patch: String,
+}
+
+/// Files matching git pathspec patterns, relative to the repository root
+struct Exclusions(Option<Pathspec>);
+
+impl Exclusions {
+    fn new(exclude: &[String]) -> Result<Self, GitError> {
+        match exclude {
+            [] => Ok(Self(None)),
+            _ => Ok(Self(Some(Pathspec::new(exclude)?))),
+        }
+    }
+
+    fn excludes(&self, path: &str) -> bool {
+        self.0
+            .as_ref()
+            .is_some_and(|pathspec| pathspec.matches_path(Path::new(path), PathspecFlags::DEFAULT))
+    }
+}
+
+/// Changes of a diff, one per file, without those matching `exclude` on either side
+fn file_changes(diff: &git2::Diff, exclude: &[String]) -> Result<Vec<FileChange>, GitError> {
+    let exclusions = Exclusions::new(exclude)?;
+    let path_of = |file: git2::DiffFile| {
+        file.path()
+            .map(|path| path.to_string_lossy().replace('\\', "/"))
+    };
+
+    let mut changes = Vec::new();
+    for index in 0..diff.deltas().len() {
+        let Some(mut patch) = git2::Patch::from_diff(diff, index)? else {
+            continue;
+        };
+        let delta = patch.delta();
+        let status = match delta.status() {
+            Delta::Added | Delta::Untracked => ChangeStatus::Added,
+            Delta::Deleted => ChangeStatus::Deleted,
+            Delta::Modified => ChangeStatus::Modified,
+            Delta::Renamed => ChangeStatus::Renamed,
+            Delta::Copied => ChangeStatus::Copied,
+            Delta::Typechange => ChangeStatus::TypeChanged,
+            _ => continue,
+        };
+        let old = path_of(delta.old_file());
+        let Some(path) = path_of(delta.new_file()).or_else(|| old.clone()) else {
+            continue;
+        };
+        if exclusions.excludes(&path) || old.as_deref().is_some_and(|old| exclusions.excludes(old))
+        {
+            continue;
+        }
+
+        let binary = delta.flags().is_binary();
+        let mut hunks = Vec::new();
+        for index in 0..patch.num_hunks() {
+            let (hunk, _) = patch.hunk(index)?;
+            hunks.push(Hunk {
+                old_start: hunk.old_start(),
+                old_lines: hunk.old_lines(),
+                new_start: hunk.new_start(),
+                new_lines: hunk.new_lines(),
+                header: String::from_utf8_lossy(hunk.header())
+                    .trim_end()
+                    .to_string(),
+            });
+        }
+        let patch = String::from_utf8_lossy(&patch.to_buf()?).into_owned();
+        changes.push(FileChange {
+            old_path: matches!(status, ChangeStatus::Renamed | ChangeStatus::Copied)
+                .then_some(old)
+                .flatten(),
+            path,
+            status,
+            binary,
+            hunks,
+            patch,
+        });
+    }
+    Ok(changes)
+}
+
+/// Diff options matching `git diff`'s defaults
+fn diff_options() -> DiffOptions {
+    let mut options = DiffOptions::new();
+    options.include_typechange(true);
+    options
+}
+
+/// Detect renames the way `git diff` does by default
+fn find_renames(diff: &mut git2::Diff, untracked: bool) -> Result<(), GitError> {
+    let mut options = DiffFindOptions::new();
+    options.renames(true).for_untracked(untracked);
+    diff.find_similar(Some(&mut options))?;
+    Ok(())
+}
+
+/// Tree a diff starts from, `None` for the empty tree or an unborn HEAD
+fn base_tree<'r>(repo: &'r Repository, base: &str) -> Result<Option<Tree<'r>>, GitError> {
+    if base == EMPTY_TREE {
+        return Ok(None);
+    }
+    match repo.revparse_single(base) {
+        Ok(object) => Ok(Some(object.peel_to_tree()?)),
+        Err(err) if base == "HEAD" && is_unborn(&err) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn is_unborn(err: &git2::Error) -> bool {
+    matches!(err.code(), ErrorCode::UnbornBranch | ErrorCode::NotFound)
+}
+
+/// Changes of the index against `base`
+fn staged_changes_in(
+    repo: &Repository,
+    base: &str,
+    exclude: &[String],
+) -> Result<Vec<FileChange>, GitError> {
+    let tree = base_tree(repo, base)?;
+    let mut diff = repo.diff_tree_to_index(tree.as_ref(), None, Some(&mut diff_options()))?;
+    find_renames(&mut diff, false)?;
+    file_changes(&diff, exclude)
+}
+
+/// Staged changes file by file, with their hunks and renames, without the files matching `exclude`
+pub fn staged_changes(exclude: &[String]) -> Result<Vec<FileChange>, GitError> {
+    staged_changes_in(&open_repository()?, "HEAD", exclude)
+}
+
+/// The unified diff of some changes, as `git diff` prints it
+pub fn patch_of(changes: &[FileChange]) -> String {
+    changes.iter().map(|change| change.patch.as_str()).collect()
+}
+
+/// Get the diff for staged changes in the git repository, without the files matching `exclude`
+pub fn get_diff(exclude: &[String]) -> Result<String> {
+    let changes = match staged_changes(exclude) {
+        Ok(changes) => changes,
+        Err(GitError::NotARepository) => {
+            eprintln!("{}", "⚠️  Make sure you're in a git repository.".yellow());
+            return Ok("".to_string());
+        }
+        Err(err) => return Err(err).context("Failed to diff the staged changes"),
+    };
+
+    let diff = patch_of(&changes);
+    tracing::debug!(
+        bytes = diff.len(),
+        files = changes.len(),
+        excluded = exclude.len(),
+        "read the staged diff"
+    );
+    Ok(diff)
+}
+
+/// Git object id of some content, e.g. to recognize a diff again later
+pub fn hash_object(content: &str) -> Result<String> {
+    let id = Oid::hash_object(ObjectType::Blob, content.as_bytes())
+        .context("Failed to hash the diff")?;
+    Ok(id.to_string())
+}
+
+/// Object name of the empty tree, the base of a root commit
+const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+/// Changes of the working tree against `base`, untracked files that aren't
+/// ignored included as new files
+fn worktree_changes(
+    repo: &Repository,
+    base: &str,
+    exclude: &[String],
+    renames: bool,
+) -> Result<Vec<FileChange>, GitError> {
+    let tree = base_tree(repo, base)?;
+    let mut options = diff_options();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .show_untracked_content(true);
+    let mut diff = repo.diff_tree_to_workdir_with_index(tree.as_ref(), Some(&mut options))?;
+    if renames {
+        find_renames(&mut diff, true)?;
+    }
+    file_changes(&diff, exclude)
+}
+
+/// Diff of every change in the working tree against `base`, from whatever
+/// directory of the repository, without touching the index
+///
+/// Tracked changes are diffed against `base` and untracked files that aren't
+/// ignored are shown as new files. Files matching `exclude` are left out.
+pub fn worktree_diff(base: &str, exclude: &[String]) -> Result<String> {
+    let changes = worktree_changes(&open_repository()?, base, exclude, true)
+        .context("Failed to diff the working tree")?;
+    Ok(patch_of(&changes))
+}
+
+/// Files `worktree_diff` covers, relative to the current directory
+///
+/// Renames are listed as a deletion and an addition, so staging the files
+/// stages both sides.
+pub fn worktree_files(base: &str, exclude: &[String]) -> Result<Vec<String>> {
+    let repo = open_repository()?;
+    let changes = worktree_changes(&repo, base, exclude, false)
+        .context("Failed to list the changed files")?;
+
+    // Paths in the diff are relative to the top of the repository
+    let cwd = env::current_dir()?.canonicalize()?;
+    let to_top = match cwd.strip_prefix(workdir(&repo)?.canonicalize()?) {
+        Ok(inside) => "../".repeat(inside.components().count()),
+        Err(_) => String::new(),
+    };
+    Ok(changes
+        .into_iter()
+        .map(|change| format!("{to_top}{}", change.path))
+        .collect())
+}
+
+/// Path of `file`, absolute or relative to the current directory, from the
+/// repository root with `/` separators, empty for the root itself
+fn index_path(repo: &Repository, file: &str) -> Result<String> {
+    let cwd = env::current_dir()?.canonicalize()?;
+    let mut path = PathBuf::new();
+    for component in cwd.join(file).components() {
+        match component {
+            Component::ParentDir => {
+                path.pop();
+            }
+            Component::CurDir => {}
+            component => path.push(component),
+        }
+    }
+
+    let relative = path
+        .strip_prefix(workdir(repo)?.canonicalize()?)
+        .map_err(|_| anyhow::anyhow!("{file} is outside the repository"))?;
+    Ok(relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/"))
+}
+
+/// Stage exactly `files`, including deletions
+///
+/// Paths are relative to the current directory, a directory stages
+/// everything below it.
+pub fn stage_files(files: &[String]) -> Result<()> {
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    let repo = open_repository()?;
+    let paths = files
+        .iter()
+        .map(|file| index_path(&repo, file))
+        .collect::<Result<Vec<String>>>()?;
+    // An empty pathspec matches the whole repository
+    let paths = match paths.iter().any(String::is_empty) {
+        true => Vec::new(),
+        false => paths,
+    };
+
+    let mut index = repo.index().map_err(GitError::from)?;
+    index
+        .add_all(&paths, IndexAddOption::DEFAULT, None)
+        .and_then(|()| index.update_all(&paths, None))
+        .and_then(|()| index.write())
+        .map_err(GitError::from)
+        .context("Failed to stage the changes")
+}
+
+/// Stage every change below the current directory, like `git add .`
+pub fn stage_all() -> Result<()> {
+    stage_files(&[".".to_string()])
+}
+
+/// Revision an amended HEAD is compared with: its parent, or the empty tree for a root commit
+pub fn amend_base() -> Result<String> {
+    let repo = open_repository()?;
+    let head = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .context("There is no commit to amend")?;
+
+    Ok(match head.parent_id(0) {
+        Ok(parent) => parent.to_string(),
+        Err(_) => EMPTY_TREE.to_string(),
+    })
+}
+
+/// Diff of the staged changes against `base`, what HEAD becomes when amended,
+/// without the files matching `exclude`
+pub fn staged_diff_from(base: &str, exclude: &[String]) -> Result<String> {
+    let changes = staged_changes_in(&open_repository()?, base, exclude)
+        .with_context(|| format!("Failed to diff the staged changes against {base}"))?;
+    Ok(patch_of(&changes))
+}
+
+/// Name of the checked out branch, also before its first commit
+fn branch_name(repo: &Repository) -> Result<Option<String>, GitError> {
+    let head = repo.find_reference("HEAD")?;
+    Ok(head
+        .symbolic_target()
+        .and_then(|target| target.strip_prefix("refs/heads/"))
+        .map(str::to_string))
+}
+
+/// Get the name of the checked out branch, `None` for a detached HEAD
+pub fn current_branch() -> Result<Option<String>> {
+    Ok(branch_name(&open_repository()?)?)
+}
+
+/// A setting of the checked out branch, e.g. `remote` for `branch.<name>.remote`
+fn branch_config(repo: &Repository, key: &str) -> Result<Option<String>, GitError> {
+    let Some(branch) = branch_name(repo)? else {
+        return Ok(None);
+    };
+    let value = repo
+        .config()?
+        .get_string(&format!("branch.{branch}.{key}"))
+        .ok();
+    Ok(value.filter(|value| !value.is_empty()))
+}
+
+/// Remote branch the checked out branch tracks, e.g. `release/1.2` for `origin/release/1.2`
+fn upstream_branch(repo: &Repository) -> Result<Option<String>, GitError> {
+    let merge = branch_config(repo, "merge")?;
+    Ok(merge.map(|merge| {
+        merge
+            .strip_prefix("refs/heads/")
+            .unwrap_or(&merge)
+            .to_string()
+    }))
+}
+
+/// Branch a plain `git push` updates: the upstream branch if one is set,
+/// otherwise the checked out branch
+pub fn push_target_branch() -> Result<Option<String>> {
+    let repo = open_repository()?;
+    match upstream_branch(&repo)? {
+        Some(branch) => Ok(Some(branch)),
+        None => Ok(branch_name(&repo)?),
+    }
+}
+
+/// Root directory of the working tree
+pub fn repo_root() -> Result<PathBuf> {
+    Ok(workdir(&open_repository()?)?)
+}
+
+/// Directory git runs hooks from, honoring `core.hooksPath`
+pub fn hooks_dir() -> Result<PathBuf> {
+    Ok(hooks_dir_of(&open_repository()?)?)
+}
+
+fn hooks_dir_of(repo: &Repository) -> Result<PathBuf, GitError> {
+    match repo.config()?.get_path("core.hooksPath") {
+        Ok(path) if path.is_absolute() => Ok(path),
+        Ok(path) => Ok(workdir(repo)?.join(path)),
+        Err(_) => Ok(repo.commondir().join("hooks")),
+    }
+}
+
+/// Directory for aic's per-repository state (`.git/aic`), created on demand
+pub fn aic_dir() -> Result<PathBuf> {
+    let aic_dir = open_repository()?.path().join("aic");
+    if !aic_dir.exists() {
+        fs::create_dir_all(&aic_dir).context("Failed to create .git/aic directory")?;
+    }
+
+    Ok(aic_dir)
+}
+
+/// List the paths of all staged files, relative to the repository root
+pub fn staged_files() -> Result<Vec<String>> {
+    let changes = staged_changes(&[]).context("Failed to list staged files")?;
+    Ok(changes.into_iter().map(|change| change.path).collect())
+}
+
+/// Remove the given paths, relative to the repository root, from the index,
+/// keeping the working tree changes
+pub fn unstage_files(files: &[String]) -> Result<()> {
+    let repo = open_repository()?;
+    // Before the first commit there is nothing to reset to, so unstaging removes
+    let head = match repo.head() {
+        Ok(head) => Some(head.peel(ObjectType::Commit).map_err(GitError::from)?),
+        Err(err) if is_unborn(&err) => None,
+        Err(err) => return Err(GitError::from(err).into()),
+    };
+    repo.reset_default(head.as_ref(), files)
+        .map_err(GitError::from)
+        .context("Failed to unstage files")
+}
+
+/// Whether a local branch with this name exists
+pub fn branch_exists(name: &str) -> Result<bool> {
+    let repo = open_repository()?;
+    let exists = repo.find_branch(name, BranchType::Local).is_ok();
+    Ok(exists)
+}
+
+/// Create a branch at HEAD and switch to it, keeping the working tree and index
+pub fn create_branch(name: &str) -> Result<()> {
+    let repo = open_repository()?;
+    let create = || -> Result<(), git2::Error> {
+        // Before the first commit the new branch is born with it
+        match repo.head().and_then(|head| head.peel_to_commit()) {
+            Ok(head) => {
+                repo.branch(name, &head, false)?;
+            }
+            Err(err) if is_unborn(&err) => {
+                if repo.find_branch(name, BranchType::Local).is_ok() {
+                    return Err(git2::Error::from_str(
+                        "a branch of that name already exists",
+                    ));
+                }
+            }
+            Err(err) => return Err(err),
+        }
+        repo.set_head(&format!("refs/heads/{name}"))
+    };
+
+    create()
+        .map_err(GitError::from)
+        .with_context(|| format!("Failed to create branch {name}"))
+}
+
+/// Write the index as a tree object and return its id, to restore staged
+/// content later with [`restore_staged`]
+pub fn write_index_tree() -> Result<String> {
+    let repo = open_repository()?;
+    let id = repo
+        .index()
+        .and_then(|mut index| index.write_tree())
+        .map_err(GitError::from)
+        .context("Failed to save the staged changes")?;
+    Ok(id.to_string())
+}
+
+/// Stage the given paths, relative to the repository root, as they are in
+/// `tree`, leaving the working tree alone
+///
+/// Unlike `git add`, this brings back exactly what was staged, including
+/// partially staged files.
+pub fn restore_staged(tree: &str, files: &[String]) -> Result<()> {
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    let repo = open_repository()?;
+    let restore = || -> Result<(), git2::Error> {
+        let tree = repo.find_tree(Oid::from_str(tree)?)?;
+        let mut index = repo.index()?;
+        for file in files {
+            let path = Path::new(file);
+            match tree.get_path(path) {
+                Ok(entry) => index.add(&git2::IndexEntry {
+                    ctime: git2::IndexTime::new(0, 0),
+                    mtime: git2::IndexTime::new(0, 0),
+                    dev: 0,
+                    ino: 0,
+                    mode: entry.filemode() as u32,
+                    uid: 0,
+                    gid: 0,
+                    file_size: 0,
+                    id: entry.id(),
+                    flags: 0,
+                    flags_extended: 0,
+                    path: file.as_bytes().to_vec(),
+                })?,
+                Err(_) => index.remove_path(path)?,
+            }
+        }
+        index.write()
+    };
+    restore()
+        .map_err(GitError::from)
+        .context("Failed to restage the changes")
+}
+
+/// The part of a unified diff that belongs to a single file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileDiff {
+    pub path: String,
+    pub diff: String,
+}
+
+/// Lines added and removed in one file of a diff, as `git diff --stat` counts them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStat {
+    pub path: String,
+    pub insertions: usize,
+    pub deletions: usize,
+    pub binary: bool,
+}
+
+impl FileStat {
+    pub fn of(file: &FileDiff) -> Self {
+        let binary = file.diff.lines().any(|line| {
+            line == "GIT binary patch"
+                || (line.starts_with("Binary files ") && line.ends_with(" differ"))
+        });
+        // The `+++`/`---` file headers come before the first hunk
+        let body = file.diff.lines().skip_while(|line| !line.starts_with("@@"));
+        let (insertions, deletions) = body.fold((0, 0), |(added, removed), line| {
+            if line.starts_with('+') {
+                (added + 1, removed)
+            } else if line.starts_with('-') {
+                (added, removed + 1)
+            } else {
+                (added, removed)
+            }
+        });
+
+        Self {
+            path: file.path.clone(),
+            insertions,
+            deletions,
+            binary,
+        }
+    }
+}
+
+/// The `git diff --stat` numbers of a unified diff, one entry per file
+pub fn diff_stat(diff: &str) -> Vec<FileStat> {
+    split_diff_by_file(diff).iter().map(FileStat::of).collect()
+}
+
+/// Split a unified diff into one chunk per file
+///
+/// Text before the first `diff --git` header is dropped.
+pub fn split_diff_by_file(diff: &str) -> Vec<FileDiff> {
+    let mut files: Vec<FileDiff> = Vec::new();
+
+    for line in diff.split_inclusive('\n') {
+        if let Some(header) = line.strip_prefix("diff --git ") {
+            // The path after `b/` is the post-change path, also for renames
+            let path = header
+                .trim_end()
+                .rsplit_once(" b/")
+                .map_or(header.trim_end(), |(_, path)| path)
+                .to_string();
+            files.push(FileDiff {
+                path,
+                diff: String::new(),
+            });
+        }
+
+        if let Some(file) = files.last_mut() {
+            file.diff.push_str(line);
+        }
+    }
+
+    files
+}
+
+/// Replace the content of snapshot files matching `patterns` with a one-line
+/// summary, so regenerated test snapshots don't drown out the real change
+pub fn condense_snapshots<S: AsRef<str>>(diff: &str, patterns: &[S]) -> String {
+    let (snapshots, others): (Vec<FileDiff>, Vec<FileDiff>) = split_diff_by_file(diff)
+        .into_iter()
+        .partition(|file| glob::matches_any(patterns, &file.path));
+
+    if snapshots.is_empty() {
+        return diff.to_string();
+    }
+
+    let mut condensed: String = others.iter().map(|file| file.diff.as_str()).collect();
+    let names: Vec<&str> = snapshots.iter().map(|file| file.path.as_str()).collect();
+    condensed.push_str(&format!(
+        "[{} snapshot file(s) updated: {}]\n",
+        snapshots.len(),
+        names.join(", ")
+    ));
+    condensed
+}
+
+/// Leave out the files ignored by the gitignore-style `patterns`, noting their
+/// names so the model still knows they changed
+pub fn omit_ignored<S: AsRef<str>>(diff: &str, patterns: &[S]) -> String {
+    let (ignored, kept): (Vec<FileDiff>, Vec<FileDiff>) = split_diff_by_file(diff)
+        .into_iter()
+        .partition(|file| glob::is_ignored(patterns, &file.path));
+
+    if ignored.is_empty() {
+        return diff.to_string();
+    }
+
+    let mut omitted: String = kept.iter().map(|file| file.diff.as_str()).collect();
+    let names: Vec<&str> = ignored.iter().map(|file| file.path.as_str()).collect();
+    omitted.push_str(&format!(
+        "[{} file(s) changed but left out by .aicignore: {}]\n",
+        ignored.len(),
+        names.join(", ")
+    ));
+    omitted
+}
+
+/// Path of a file in the git directory, e.g. `COMMIT_EDITMSG`, also in worktrees
+pub fn git_path(name: &str) -> Result<PathBuf> {
+    Ok(open_repository()?.path().join(name))
+}
+
+/// URL of the named remote, `None` when there is no such remote
+pub fn remote_url(name: &str) -> Result<Option<String>> {
+    let repo = open_repository()?;
+    let Ok(remote) = repo.find_remote(name) else {
+        return Ok(None);
+    };
+    Ok(remote
+        .url()
+        .filter(|url| !url.is_empty())
+        .map(str::to_string))
+}
+
+/// Size in bytes of a blob, `None` when it can't be read
+pub fn blob_size(id: &str) -> Option<u64> {
+    let repo = open_repository().ok()?;
+    let blob = repo.revparse_single(id).ok()?.peel_to_blob().ok()?;
+    Some(blob.size() as u64)
+}
+
+/// Text of the blob `id` of `path`, from the `index <old>..<new>` line of a
+/// diff, `None` when it can't be read
+///
+/// A diff against the working tree names blobs that were never stored, so the
+/// file itself is read when its hash is the one named.
+pub fn blob_text(id: &str, path: &str) -> Option<String> {
+    let repo = open_repository().ok()?;
+    if let Ok(blob) = repo
+        .revparse_single(id)
+        .and_then(|object| object.peel_to_blob())
+    {
+        return String::from_utf8(blob.content().to_vec()).ok();
+    }
+
+    let file = workdir(&repo).ok()?.join(path);
+    let hash = Oid::hash_file(ObjectType::Blob, &file).ok()?.to_string();
+    let unhashed = id.trim_start_matches('0').is_empty();
+    match unhashed || hash.starts_with(id) {
+        true => fs::read_to_string(file).ok(),
+        false => None,
+    }
+}
+
+/// Pager git uses, from GIT_PAGER, core.pager or PAGER, falling back to `less`
+pub fn pager() -> String {
+    let configured = match open_repository() {
+        Ok(repo) => repo.config(),
+        Err(_) => git2::Config::open_default(),
+    }
+    .and_then(|config| config.get_string("core.pager"))
+    .ok();
+    env::var("GIT_PAGER")
+        .ok()
+        .or(configured)
+        .or_else(|| env::var("PAGER").ok())
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| "less".to_string())
+}
+
+/// Remote tracking branches that already contain the given revision
+pub fn remote_branches_containing(rev: &str) -> Result<Vec<String>> {
+    let repo = open_repository()?;
+    let target = resolve_in(&repo, rev)?;
+
+    let mut branches = Vec::new();
+    for branch in repo
+        .branches(Some(BranchType::Remote))
+        .map_err(GitError::from)?
+    {
+        let (branch, _) = branch.map_err(GitError::from)?;
+        let reference = branch.get();
+        // `origin/HEAD` only points at another remote branch
+        if reference.kind() == Some(git2::ReferenceType::Symbolic) {
+            continue;
+        }
+        let (Some(name), Some(tip)) = (reference.shorthand(), reference.target()) else {
+            continue;
+        };
+        let contains = tip == target
+            || repo
+                .graph_descendant_of(tip, target)
+                .map_err(GitError::from)?;
+        if contains && !name.ends_with("/HEAD") {
+            branches.push(name.to_string());
+        }
+    }
+    branches.sort();
+    Ok(branches)
+}
+
+/// Commit a revision points at
+fn resolve_in(repo: &Repository, rev: &str) -> Result<Oid> {
+    repo.revparse_single(rev)
+        .and_then(|object| object.peel_to_commit())
+        .map(|commit| commit.id())
+        .map_err(|_| anyhow::anyhow!("Unknown commit '{}'", rev))
+}
+
+/// Non-merge commits reachable from HEAD, newest first, at most `count`
+///
+/// A repository without commits has no history yet.
+fn branch_history(repo: &Repository, count: Option<usize>) -> Result<Vec<git2::Commit<'_>>> {
+    let mut walk = repo.revwalk().map_err(GitError::from)?;
+    if walk.push_head().is_err() {
+        return Ok(Vec::new());
+    }
+    walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)
+        .map_err(GitError::from)?;
+
+    let mut commits = Vec::new();
+    for id in walk {
+        if count.is_some_and(|count| commits.len() >= count) {
+            break;
+        }
+        let commit = repo
+            .find_commit(id.map_err(GitError::from)?)
+            .map_err(GitError::from)?;
+        if commit.parent_count() <= 1 {
+            commits.push(commit);
+        }
+    }
+    Ok(commits)
+}
+
+/// Message of a commit as `git log --format=%B` shows it
+fn message_of(commit: &git2::Commit) -> String {
+    String::from_utf8_lossy(commit.message_bytes())
+        .trim()
+        .to_string()
+}
+
+/// `YYYY-MM-DD` of a commit time, in the time zone it was made in
+fn date_of(time: git2::Time) -> String {
+    let seconds = time.seconds() + i64::from(time.offset_minutes()) * 60;
+    // Days since 1970-01-01 to a civil date, after Howard Hinnant's algorithm
+    let days = seconds.div_euclid(86_400) + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Non-merge commits in a revision range such as `main..HEAD`, oldest first
+pub fn commits_in_range(range: &str) -> Result<Vec<String>> {
+    let repo = open_repository()?;
+    let walk = || -> Result<Vec<String>, git2::Error> {
+        let mut walk = repo.revwalk()?;
+        match range.contains("..") {
+            true => walk.push_range(range)?,
+            false => walk.push(repo.revparse_single(range)?.peel_to_commit()?.id())?,
+        }
+        walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME | git2::Sort::REVERSE)?;
+
+        let mut commits = Vec::new();
+        for id in walk {
+            let commit = repo.find_commit(id?)?;
+            if commit.parent_count() <= 1 {
+                commits.push(commit.id().to_string());
+            }
+        }
+        Ok(commits)
+    };
+
+    walk().map_err(|err| anyhow::anyhow!("Invalid revision range '{}': {}", range, err.message()))
+}
+
+/// Subjects of the latest commits on the current branch, newest first,
+/// without merge commits
+pub fn recent_subjects(count: usize) -> Result<Vec<String>> {
+    let repo = open_repository()?;
+    let commits = branch_history(&repo, Some(count))?;
+    Ok(commits
+        .iter()
+        .map(|commit| commit.summary().unwrap_or_default().to_string())
+        .collect())
+}
+
+/// Full messages of the latest non-merge commits on the current branch, newest first
+pub fn recent_commits(count: usize) -> Result<Vec<String>> {
+    let repo = open_repository()?;
+    let commits = branch_history(&repo, Some(count))?;
+    Ok(commits
+        .iter()
+        .map(message_of)
+        .filter(|message| !message.is_empty())
+        .collect())
+}
+
+/// A commit of the current branch as `aic search` indexes it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    pub id: String,
+
+    /// Author date, e.g. `2024-05-01`
+    pub date: String,
+    pub message: String,
+}
+
+/// Every non-merge commit on the current branch, newest first
+pub fn log_entries() -> Result<Vec<LogEntry>> {
+    let repo = open_repository()?;
+    let commits = branch_history(&repo, None)?;
+    Ok(commits
+        .iter()
+        .map(|commit| LogEntry {
+            id: commit.id().to_string(),
+            date: date_of(commit.author().when()),
+            message: message_of(commit),
+        })
+        .collect())
+}
+
+/// Full message of a commit
+pub fn commit_message(rev: &str) -> Result<String> {
+    let repo = open_repository()?;
+    let commit = repo
+        .revparse_single(rev)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(GitError::from)
+        .with_context(|| format!("Failed to read the message of {rev}"))?;
+    Ok(message_of(&commit))
+}
+
+/// Full object name of the commit a revision points at
+pub fn resolve_commit(rev: &str) -> Result<String> {
+    Ok(resolve_in(&open_repository()?, rev)?.to_string())
+}
+
+/// Whether `ancestor` is reachable from `rev`, or is `rev` itself
+pub fn is_ancestor(ancestor: &str, rev: &str) -> Result<bool> {
+    let repo = open_repository()?;
+    let (Ok(ancestor), Ok(rev)) = (resolve_in(&repo, ancestor), resolve_in(&repo, rev)) else {
+        return Ok(false);
+    };
+    Ok(ancestor == rev
+        || repo
+            .graph_descendant_of(rev, ancestor)
+            .map_err(GitError::from)?)
+}
+
+/// Replace the messages of commits on the current branch, keeping their trees and authors
+///
+/// `oldest` and every commit after it up to HEAD are recreated, taking the
+/// message from `messages` (keyed by full commit id) or keeping the original
+/// one. The trees don't change, so the index and working tree stay as they
+/// are. History after `oldest` must be linear. Returns the new HEAD.
+pub fn reword_commits(oldest: &str, messages: &HashMap<String, String>) -> Result<String> {
+    let repo = open_repository()?;
+    let head = resolve_in(&repo, "HEAD")?;
+    let oldest = resolve_in(&repo, oldest)?;
+    if oldest != head
+        && !repo
+            .graph_descendant_of(head, oldest)
+            .map_err(GitError::from)?
+    {
+        anyhow::bail!("{} is not on the current branch", oldest);
+    }
+
+    let mut walk = repo.revwalk().map_err(GitError::from)?;
+    walk.push(head)
+        .and_then(|()| walk.hide(oldest))
+        .and_then(|()| walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE))
+        .map_err(GitError::from)?;
+    let later = walk
+        .collect::<Result<Vec<Oid>, _>>()
+        .map_err(GitError::from)?;
+    let committer = identity(&repo, "COMMITTER")?;
+
+    let mut parent: Option<Oid> = None;
+    for (index, id) in std::iter::once(oldest).chain(later).enumerate() {
+        let commit = repo.find_commit(id).map_err(GitError::from)?;
+        if commit.parent_count() > 1 {
+            anyhow::bail!(
+                "Cannot reword across the merge commit {}, the history must be linear",
+                id
+            );
+        }
+        // The first commit keeps its original parent
+        if index == 0 {
+            parent = commit.parent_ids().next();
+        }
+
+        let message = match messages.get(&id.to_string()) {
+            Some(message) => message.clone(),
+            None => message_of(&commit),
+        };
+        let parents = parent
+            .map(|parent| repo.find_commit(parent))
+            .transpose()
+            .map_err(GitError::from)?;
+        let tree = commit.tree().map_err(GitError::from)?;
+        let recreated = repo
+            .commit(
+                None,
+                &commit.author(),
+                &committer,
+                &format!("{}\n", message.trim_end()),
+                &tree,
+                &parents.iter().collect::<Vec<_>>(),
+            )
+            .map_err(GitError::from)
+            .with_context(|| format!("Failed to recreate commit {id}"))?;
+        parent = Some(recreated);
+    }
+
+    let new_head = parent.context("No commits to reword")?;
+    repo.head()
+        .and_then(|mut reference| reference.set_target(new_head, "aic reword"))
+        .map_err(GitError::from)
+        .context("Failed to move HEAD to the reworded commits")?;
+
+    Ok(new_head.to_string())
+}
+
+/// Most recent tag reachable from `rev`, `None` if there is none
+pub fn latest_tag(rev: &str) -> Result<Option<String>> {
+    let repo = open_repository()?;
+    let object = repo.revparse_single(rev).map_err(GitError::from)?;
+    let mut options = git2::DescribeOptions::new();
+    options.describe_tags();
+    // Fails when no tag is reachable
+    let Ok(description) = object.describe(&options) else {
+        return Ok(None);
+    };
+
+    let mut format = git2::DescribeFormatOptions::new();
+    format.abbreviated_size(0);
+    let tag = description.format(Some(&format)).map_err(GitError::from)?;
+    Ok(Some(tag).filter(|tag| !tag.is_empty()))
+}
+
+/// Whether a tag of that name exists
+pub fn tag_exists(name: &str) -> Result<bool> {
+    let repo = open_repository()?;
+    let exists = repo.find_reference(&format!("refs/tags/{name}")).is_ok();
+    Ok(exists)
+}
+
+/// `git commit` flags a commit made with libgit2 honors, any other needs git itself
+const LIBGIT2_COMMIT_FLAGS: &[&str] = &["--signoff", "-s", "--no-verify", "-n", "--allow-empty"];
+
+/// Hooks git runs around a commit, with whether `--no-verify` skips them
+const COMMIT_HOOKS: &[(&str, bool)] = &[
+    ("pre-commit", true),
+    ("prepare-commit-msg", false),
+    ("commit-msg", true),
+    ("post-commit", false),
+];
+
+/// Whether git would run the hook `name`, which needs it to be executable
+fn has_hook(repo: &Repository, name: &str) -> bool {
+    let Ok(path) = hooks_dir_of(repo).map(|dir| dir.join(name)) else {
+        return false;
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata()
+            .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+/// Why a commit has to be made by git itself, `None` when libgit2 can make it
+///
+/// libgit2 runs no hooks, doesn't sign, doesn't conclude a merge and knows
+/// only some of the flags.
+fn needs_git_binary(repo: &Repository, args: &[String]) -> Option<String> {
+    if let Some(arg) = args
+        .iter()
+        .find(|arg| !LIBGIT2_COMMIT_FLAGS.contains(&arg.as_str()))
+    {
+        return Some(format!("the {arg} flag"));
+    }
+
+    let no_verify = args.iter().any(|arg| arg == "--no-verify" || arg == "-n");
+    if let Some((hook, _)) = COMMIT_HOOKS
+        .iter()
+        .find(|(hook, skippable)| !(no_verify && *skippable) && has_hook(repo, hook))
+    {
+        return Some(format!("the {hook} hook"));
+    }
+
+    let signs = repo
+        .config()
+        .and_then(|config| config.get_bool("commit.gpgSign"))
+        .unwrap_or(false);
+    if signs {
+        return Some("commit.gpgSign".to_string());
+    }
+
+    (repo.state() != git2::RepositoryState::Clean).then(|| "an unfinished merge".to_string())
+}
+
+/// Run git in `root` with the terminal attached, e.g. for hooks that prompt
+fn run_git<S: AsRef<std::ffi::OsStr>>(root: &Path, args: &[S]) -> Result<(), GitError> {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(root).args(args);
+    tracing::debug!(command = ?command, "running git");
+
+    let name = args
+        .first()
+        .map(|arg| format!("git {}", arg.as_ref().to_string_lossy()))
+        .unwrap_or_else(|| "git".to_string());
+    match command.status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(GitError::CommandFailed {
+            command: name,
+            code: status.code(),
+        }),
+        Err(_) => Err(GitError::CommandFailed {
+            command: name,
+            code: None,
+        }),
+    }
+}
+
+/// Author or committer, `role` being `AUTHOR` or `COMMITTER`, from the
+/// `GIT_<role>_*` variables or `user.name` and `user.email`
+fn identity(repo: &Repository, role: &str) -> Result<Signature<'static>, GitError> {
+    let config = repo.config()?;
+    let value = |var: String, key: &str| {
+        env::var(var)
+            .ok()
+            .or_else(|| config.get_string(key).ok())
+            .filter(|value| !value.trim().is_empty())
+    };
+
+    match (
+        value(format!("GIT_{role}_NAME"), "user.name"),
+        value(format!("GIT_{role}_EMAIL"), "user.email"),
+    ) {
+        (Some(name), Some(email)) => Ok(Signature::now(&name, &email)?),
+        _ => Err(GitError::MissingIdentity),
+    }
+}
+
+/// Commit `tree` on HEAD, or replace HEAD with it when `amend`
+fn write_commit(
+    repo: &Repository,
+    tree: &Tree,
+    message: &str,
+    amend: bool,
+    args: &[String],
+) -> Result<Oid, GitError> {
+    let author = identity(repo, "AUTHOR")?;
+    let committer = identity(repo, "COMMITTER")?;
+    let mut message = git2::message_prettify(message, None)?;
+    if args.iter().any(|arg| arg == "--signoff" || arg == "-s") {
+        let trailer = format!(
+            "Signed-off-by: {} <{}>",
+            committer.name().unwrap_or_default(),
+            committer.email().unwrap_or_default()
+        );
+        if message.lines().last() != Some(trailer.as_str()) {
+            message = format!("{}\n", lint::append_footer(&message, &trailer));
+        }
+    }
+
+    let head = match repo.head() {
+        Ok(head) => Some(head.peel_to_commit()?),
+        Err(err) if is_unborn(&err) => None,
+        Err(err) => return Err(err.into()),
+    };
+    if amend {
+        // Amending keeps the author, like git does
+        let head = head.ok_or_else(|| git2::Error::from_str("There is no commit to amend"))?;
+        return Ok(head.amend(
+            Some("HEAD"),
+            None,
+            Some(&committer),
+            None,
+            Some(&message),
+            Some(tree),
+        )?);
+    }
+
+    let parent_tree = head.as_ref().map(|head| head.tree_id());
+    let unchanged = match parent_tree {
+        Some(parent_tree) => parent_tree == tree.id(),
+        None => tree.is_empty(),
+    };
+    if unchanged && !args.iter().any(|arg| arg == "--allow-empty") {
+        return Err(GitError::NothingToCommit);
+    }
+    let parents: Vec<&git2::Commit> = head.iter().collect();
+    Ok(repo.commit(Some("HEAD"), &author, &committer, &message, tree, &parents)?)
+}
+
+fn commit_in(
+    repo: &Repository,
+    message: &str,
+    amend: bool,
+    args: &[String],
+) -> Result<(), GitError> {
+    if let Some(reason) = needs_git_binary(repo, args) {
+        tracing::debug!(reason, "committing with the git binary");
+        let mut git_args = vec!["commit".to_string()];
+        if amend {
+            git_args.push("--amend".to_string());
+        }
+        git_args.extend(args.iter().cloned());
+        git_args.extend(["-m".to_string(), message.to_string()]);
+        return run_git(&workdir(repo)?, &git_args);
+    }
+
+    let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+    let id = write_commit(repo, &tree, message, amend, args)?;
+    tracing::debug!(%id, amend, "committed with libgit2");
+    Ok(())
+}
+
+/// Commit the staged changes, replacing HEAD with `amend`
+///
+/// `args` are `git commit` flags like `--signoff`. The commit is made by git
+/// itself when hooks, signing or flags libgit2 doesn't know call for it.
+pub fn commit(message: &str, amend: bool, args: &[String]) -> Result<(), GitError> {
+    commit_in(&open_repository()?, message, amend, args)
+}
+
+/// Commit exactly `paths`, leaving anything else that is staged out of the commit
+pub fn commit_paths(paths: &[String], message: &str) -> Result<()> {
+    stage_files(paths)?;
+    let repo = open_repository()?;
+    let failed = || format!("Failed to commit {}", paths.join(", "));
+    if let Some(reason) = needs_git_binary(&repo, &[]) {
+        tracing::debug!(reason, "committing with the git binary");
+        let args: Vec<&str> = ["commit", "-m", message, "--"]
+            .into_iter()
+            .chain(paths.iter().map(String::as_str))
+            .collect();
+        return run_git(&workdir(&repo)?, &args).with_context(failed);
+    }
+
+    // The tree of HEAD with only these paths as they are staged
+    let paths = paths
+        .iter()
+        .map(|path| index_path(&repo, path))
+        .collect::<Result<Vec<String>>>()?;
+    let commit = || -> Result<Oid, GitError> {
+        let staged = repo.index()?;
+        let mut only = git2::Index::new()?;
+        if let Ok(head) = repo.head().and_then(|head| head.peel_to_tree()) {
+            only.read_tree(&head)?;
+        }
+        for path in &paths {
+            match staged.get_path(Path::new(path), 0) {
+                Some(entry) => only.add(&entry)?,
+                None => only.remove_path(Path::new(path))?,
+            }
+        }
+        let tree = repo.find_tree(only.write_tree_to(&repo)?)?;
+        write_commit(&repo, &tree, message, false, &[])
+    };
+    commit().with_context(failed)?;
+
+    Ok(())
+}
+
+/// Create an annotated tag on HEAD, keeping the message as written
+pub fn create_annotated_tag(name: &str, message: &str) -> Result<()> {
+    let repo = open_repository()?;
+    let create = || -> Result<(), GitError> {
+        let head = repo.head()?.peel(ObjectType::Commit)?;
+        let tagger = identity(&repo, "COMMITTER")?;
+        let message = git2::message_prettify(message, None)?;
+        repo.tag(name, &head, &tagger, &message, false)?;
+        Ok(())
+    };
+
+    create().with_context(|| format!("Failed to create tag {name}"))
+}
+
+/// Committer date of a commit as `YYYY-MM-DD`
+pub fn commit_date(rev: &str) -> Result<String> {
+    let repo = open_repository()?;
+    let commit = repo
+        .revparse_single(rev)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(GitError::from)
+        .with_context(|| format!("Failed to read the date of {rev}"))?;
+    let date = date_of(commit.committer().when());
+    Ok(date)
+}
+
+/// Diff a commit introduced, also for the root commit
+pub fn commit_diff(rev: &str) -> Result<String> {
+    let repo = open_repository()?;
+    let read = || -> Result<String, GitError> {
+        let commit = repo.revparse_single(rev)?.peel_to_commit()?;
+        let parent = match commit.parent_count() {
+            0 => None,
+            _ => Some(commit.parent(0)?.tree()?),
+        };
+        let mut diff = repo.diff_tree_to_tree(
+            parent.as_ref(),
+            Some(&commit.tree()?),
+            Some(&mut diff_options()),
+        )?;
+        find_renames(&mut diff, false)?;
+        Ok(patch_of(&file_changes(&diff, &[])?))
+    };
+
+    read().with_context(|| format!("Failed to read the diff of {rev}"))
+}
+
+/// Diff of a revision range, e.g. `main...HEAD` for the changes since the branch point,
+/// or of the working tree against a single revision, without the files matching `exclude`
+pub fn diff_range(range: &str, exclude: &[String]) -> Result<String> {
+    let repo = open_repository()?;
+    // An empty side of a range means HEAD, as in `main..`
+    let commit = |rev: &str| -> Result<git2::Commit<'_>, GitError> {
+        let rev = if rev.is_empty() { "HEAD" } else { rev };
+        Ok(repo.revparse_single(rev)?.peel_to_commit()?)
+    };
+    let tree = |rev: &str| -> Result<Tree<'_>, GitError> { Ok(commit(rev)?.tree()?) };
+    let read = || -> Result<String, GitError> {
+        let mut diff = if let Some((from, to)) = range.split_once("...") {
+            let to = commit(to)?;
+            let base = repo.merge_base(commit(from)?.id(), to.id())?;
+            repo.diff_tree_to_tree(
+                Some(&repo.find_commit(base)?.tree()?),
+                Some(&to.tree()?),
+                Some(&mut diff_options()),
+            )?
+        } else if let Some((from, to)) = range.split_once("..") {
+            repo.diff_tree_to_tree(
+                Some(&tree(from)?),
+                Some(&tree(to)?),
+                Some(&mut diff_options()),
+            )?
+        } else {
+            repo.diff_tree_to_workdir_with_index(Some(&tree(range)?), Some(&mut diff_options()))?
+        };
+        find_renames(&mut diff, false)?;
+        Ok(patch_of(&file_changes(&diff, exclude)?))
+    };
+
+    let diff = read().with_context(|| format!("Failed to diff {range}"))?;
+    tracing::debug!(range, bytes = diff.len(), "read the diff of a range");
+    Ok(diff)
+}
+
+/// Default branch of `origin`, e.g. `origin/main`, if the remote HEAD is known
+pub fn default_remote_branch() -> Result<Option<String>> {
+    let repo = open_repository()?;
+    let Ok(head) = repo.find_reference("refs/remotes/origin/HEAD") else {
+        return Ok(None);
+    };
+    Ok(head
+        .symbolic_target()
+        .and_then(|target| target.strip_prefix("refs/remotes/"))
+        .filter(|branch| !branch.is_empty())
+        .map(str::to_string))
+}
+
+/// Where a push goes, unset parts follow the upstream and git's defaults
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PushTarget {
+    pub remote: Option<String>,
+
+    /// Remote branch to push HEAD to, the branch of the same name when unset
+    pub branch: Option<String>,
+}
+
+impl PushTarget {
+    /// Arguments of `git push` for the target, `set_upstream` tracks the pushed branch
+    ///
+    /// A branch is only pushed to a named remote, so `branch` is ignored without `remote`.
+    pub fn push_args(&self, set_upstream: bool) -> Vec<String> {
+        let mut args = vec!["push".to_string()];
+        if set_upstream {
+            args.push("-u".to_string());
+        }
+        if let Some(remote) = &self.remote {
+            args.push(remote.clone());
+            args.push(match &self.branch {
+                Some(branch) => format!("HEAD:{branch}"),
+                None => "HEAD".to_string(),
+            });
+        }
+        args
+    }
+}
+
+/// Remote the checked out branch tracks, `None` without an upstream
+pub fn upstream_remote() -> Result<Option<String>> {
+    Ok(branch_config(&open_repository()?, "remote")?)
+}
+
+/// Remote a branch without upstream is pushed to: `origin`, or the only remote
+pub fn default_remote() -> Result<Option<String>> {
+    Ok(default_remote_of(&open_repository()?)?)
+}
+
+fn default_remote_of(repo: &Repository) -> Result<Option<String>, GitError> {
+    let remotes = repo.remotes()?;
+    let remotes: Vec<&str> = remotes.iter().flatten().collect();
+    Ok(match remotes.as_slice() {
+        _ if remotes.contains(&"origin") => Some("origin".to_string()),
+        [remote] => Some(remote.to_string()),
+        _ => None,
+    })
+}
+
+/// Remote and branch a push of HEAD to `target` goes to, as `git push` picks them
+fn push_destination(repo: &Repository, target: &PushTarget) -> Result<(String, String), GitError> {
+    let remote = match &target.remote {
+        Some(remote) => remote.clone(),
+        None => branch_config(repo, "remote")?
+            .or(default_remote_of(repo)?)
+            .ok_or(GitError::NoRemote)?,
+    };
+
+    let branch = match (&target.remote, &target.branch) {
+        (Some(_), Some(branch)) => Some(branch.clone()),
+        (Some(_), None) => branch_name(repo)?,
+        (None, _) => match upstream_branch(repo)? {
+            Some(branch) => Some(branch),
+            None => branch_name(repo)?,
+        },
+    };
+    Ok((remote, branch.ok_or(GitError::DetachedHead)?))
+}
+
+/// Push HEAD to `branch` of `remote` with libgit2, trying the SSH agent, then
+/// git's credential helpers
+fn push_head(repo: &Repository, remote: &str, branch: &str) -> Result<(), GitError> {
+    let config = repo.config()?;
+    let mut tried = CredentialType::empty();
+    let out_of_credentials = Cell::new(false);
+    let rejected = RefCell::new(None);
+
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(|url, username, allowed| {
+        let untried = |kind| allowed.contains(kind) && !tried.contains(kind);
+        if untried(CredentialType::SSH_KEY) {
+            tried |= CredentialType::SSH_KEY;
+            return Cred::ssh_key_from_agent(username.unwrap_or("git"));
+        }
+        if untried(CredentialType::USER_PASS_PLAINTEXT) {
+            tried |= CredentialType::USER_PASS_PLAINTEXT;
+            return Cred::credential_helper(&config, url, username);
+        }
+        if untried(CredentialType::DEFAULT) {
+            tried |= CredentialType::DEFAULT;
+            return Cred::default();
+        }
+        out_of_credentials.set(true);
+        Err(git2::Error::from_str("no more credentials to try"))
+    });
+    callbacks.push_update_reference(|reference, status| {
+        if let Some(reason) = status {
+            *rejected.borrow_mut() = Some((reference.to_string(), reason.to_string()));
+        }
+        Ok(())
+    });
+
+    let mut options = PushOptions::new();
+    options.remote_callbacks(callbacks);
+    let reference = format!("refs/heads/{branch}");
+    let result = repo
+        .find_remote(remote)?
+        .push(&[format!("HEAD:{reference}")], Some(&mut options));
+    drop(options);
+
+    match result {
+        Err(err) if out_of_credentials.get() || err.code() == ErrorCode::Auth => {
+            tracing::debug!(%err, "authentication failed");
+            Err(GitError::Authentication {
+                remote: remote.to_string(),
+            })
+        }
+        Err(err) if err.code() == ErrorCode::NotFastForward => Err(GitError::PushRejected {
+            reference,
+            reason: "the remote has commits HEAD doesn't, pull them first".to_string(),
+        }),
+        Err(err) => Err(err.into()),
+        Ok(()) => match rejected.into_inner() {
+            Some((reference, reason)) => Err(GitError::PushRejected { reference, reason }),
+            None => Ok(()),
+        },
+    }
+}
+
+/// Push committed changes to `target`, setting it as upstream with `set_upstream`
+///
+/// git itself pushes when there is a pre-push hook, or when libgit2 can't
+/// reach the remote, e.g. because of SSH settings or credentials it doesn't know.
+pub fn push_changes(target: &PushTarget, set_upstream: bool) -> Result<(), GitError> {
+    let repo = open_repository()?;
+    let (remote, branch) = push_destination(&repo, target)?;
+
+    let pushed = if has_hook(&repo, "pre-push") {
+        false
+    } else {
+        println!("{} Pushing HEAD to {remote}/{branch}...", "▶".green());
+        match push_head(&repo, &remote, &branch) {
+            Ok(()) => true,
+            Err(err @ GitError::Authentication { .. }) => {
+                tracing::debug!(%err, "pushing with the git binary instead");
+                false
+            }
+            Err(GitError::Libgit2(err))
+                if matches!(
+                    err.class(),
+                    ErrorClass::Net | ErrorClass::Ssh | ErrorClass::Http | ErrorClass::Ssl
+                ) =>
+            {
+                tracing::debug!(%err, "pushing with the git binary instead");
+                false
+            }
+            Err(err) => return Err(err),
+        }
+    };
+
+    if !pushed {
+        // Spelled out so git pushes where libgit2 would have, even without an upstream
+        let resolved = PushTarget {
+            remote: Some(remote.clone()),
+            branch: Some(branch.clone()),
+        };
+        let args = resolved.push_args(set_upstream);
+        println!("{} Running 'git {}'...", "▶".green(), args.join(" "));
+        run_git(&workdir(&repo)?, &args)?;
+    } else if let (true, Some(local)) = (set_upstream, branch_name(&repo)?) {
+        let mut config = repo.config()?;
+        config.set_str(&format!("branch.{local}.remote"), &remote)?;
+        config.set_str(
+            &format!("branch.{local}.merge"),
+            &format!("refs/heads/{branch}"),
+        )?;
+    }
+
+    println!("{} Changes pushed successfully.", "✔".green());
+    Ok(())
+}
+
+/// A repository state that makes a commit fail or go somewhere unexpected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreflightIssue {
+    DetachedHead,
+    RebaseInProgress,
+    MergeInProgress,
+    CherryPickInProgress,
+
+    /// Staged files with leftover conflict markers
+    ConflictMarkers(Vec<String>),
+
+    /// Identity settings git has no value for, e.g. `user.email`
+    MissingIdentity(Vec<&'static str>),
+}
+
+impl PreflightIssue {
+    /// Whether committing has to wait until the issue is fixed
+    pub fn is_blocking(&self) -> bool {
+        matches!(self, Self::ConflictMarkers(_) | Self::MissingIdentity(_))
+    }
+}
+
+impl fmt::Display for PreflightIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DetachedHead => write!(
+                f,
+                "HEAD is detached, the commit won't be on any branch. \
+                 Create one first with 'git switch -c <branch>'"
+            ),
+            Self::RebaseInProgress => write!(
+                f,
+                "A rebase is in progress. After committing, continue it with 'git rebase --continue'"
+            ),
+            Self::MergeInProgress => write!(
+                f,
+                "A merge is in progress, the commit concludes it. \
+                 Run 'git merge --abort' to start over instead"
+            ),
+            Self::CherryPickInProgress => write!(
+                f,
+                "A cherry-pick is in progress. After committing, continue it with \
+                 'git cherry-pick --continue'"
+            ),
+            Self::ConflictMarkers(locations) => write!(
+                f,
+                "Staged files still have conflict markers ({}). Resolve them and stage the files again",
+                locations.join(", ")
+            ),
+            Self::MissingIdentity(keys) => write!(
+                f,
+                "{} not set, so git can't record who made the commit. Set {} with {}",
+                keys.join(" and "),
+                if keys.len() == 1 { "it" } else { "them" },
+                keys.iter()
+                    .map(|key| format!("'git config --global {key} \"...\"'"))
+                    .collect::<Vec<_>>()
+                    .join(" and ")
+            ),
+        }
+    }
+}
+
+/// Check the repository for states that make committing fail or surprise:
+/// a detached HEAD, an unfinished rebase, merge or cherry-pick, conflict
+/// markers in the staged changes and a missing author identity
+pub fn preflight() -> Result<Vec<PreflightIssue>> {
+    let repo = open_repository()?;
+    let mut issues = Vec::new();
+
+    if branch_name(&repo)?.is_none() {
+        issues.push(PreflightIssue::DetachedHead);
+    }
+
+    let in_progress = |name: &str| repo.path().join(name).exists();
+    if in_progress("rebase-merge") || in_progress("rebase-apply") {
+        issues.push(PreflightIssue::RebaseInProgress);
+    } else if in_progress("MERGE_HEAD") {
+        issues.push(PreflightIssue::MergeInProgress);
+    } else if in_progress("CHERRY_PICK_HEAD") {
+        issues.push(PreflightIssue::CherryPickInProgress);
+    }
+
+    let markers: Vec<String> = staged_changes_in(&repo, "HEAD", &[])?
+        .into_iter()
+        .filter(|change| {
+            change
+                .patch
+                .lines()
+                .filter_map(|line| line.strip_prefix('+'))
+                .any(is_conflict_marker)
+        })
+        .map(|change| change.path)
+        .collect();
+    if !markers.is_empty() {
+        issues.push(PreflightIssue::ConflictMarkers(markers));
+    }
+
+    let config = repo.config()?;
+    let is_set = |var: &str, key: &str| {
+        env::var(var).is_ok_and(|value| !value.trim().is_empty())
+            || config
+                .get_string(key)
+                .is_ok_and(|value| !value.trim().is_empty())
+    };
+    let missing: Vec<&'static str> = [
+        ("GIT_AUTHOR_NAME", "user.name"),
+        ("GIT_AUTHOR_EMAIL", "user.email"),
+    ]
+    .into_iter()
+    .filter(|(var, key)| !is_set(var, key))
+    .map(|(_, key)| key)
+    .collect();
+    if !missing.is_empty() {
+        issues.push(PreflightIssue::MissingIdentity(missing));
+    }
+
+    Ok(issues)
+}
+
+/// Whether an added line is a marker git leaves in a conflicted file, as
+/// `git diff --check` spots them
+fn is_conflict_marker(line: &str) -> bool {
+    ['<', '|', '=', '>'].into_iter().any(|marker| {
+        line.strip_prefix(&marker.to_string().repeat(7))
+            .is_some_and(|rest| match marker {
+                '=' => rest.trim().is_empty(),
+                _ => rest.is_empty() || rest.starts_with(char::is_whitespace),
+            })
+    })
+}
+
+/// A repository at a fixed path, for tools that embed aic
+///
+/// The functions of this module work on the repository of the current
+/// directory, as the CLI does. A `GitRepo` works in its own working tree
+/// instead, so an editor or TUI can serve several repositories at once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitRepo {
+    root: PathBuf,
+}
+
+impl GitRepo {
+    /// Open the repository whose working tree contains `path`
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let repo = Repository::discover(path)
+            .map_err(GitError::from)
+            .with_context(|| format!("Not inside a git repository: {}", path.display()))?;
+
+        Ok(Self {
+            root: workdir(&repo)?,
+        })
+    }
+
+    /// Root directory of the working tree
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Staged changes file by file, without the files matching `exclude`
+    pub fn staged_changes(&self, exclude: &[String]) -> Result<Vec<FileChange>, GitError> {
+        staged_changes_in(&self.repository()?, "HEAD", exclude)
+    }
+
+    /// Diff of the staged changes, without the files matching `exclude`
+    pub fn staged_diff(&self, exclude: &[String]) -> Result<String> {
+        Ok(patch_of(&self.staged_changes(exclude)?))
+    }
+
+    /// Paths of the staged files, relative to the root
+    pub fn staged_files(&self) -> Result<Vec<String>> {
+        let changes = self.staged_changes(&[])?;
+        Ok(changes.into_iter().map(|change| change.path).collect())
+    }
+
+    /// Name of the checked out branch, `None` for a detached HEAD
+    pub fn current_branch(&self) -> Result<Option<String>> {
+        Ok(branch_name(&self.repository()?)?)
+    }
+
+    /// Subjects of the latest commits on the current branch, newest first,
+    /// without merge commits
+    pub fn recent_subjects(&self, count: usize) -> Result<Vec<String>> {
+        let repo = self.repository()?;
+        let commits = branch_history(&repo, Some(count))?;
+        Ok(commits
+            .iter()
+            .map(|commit| commit.summary().unwrap_or_default().to_string())
+            .collect())
+    }
+
+    /// Commit the staged changes, `args` are extra `git commit` flags like `--signoff`
+    pub fn commit(&self, message: &str, args: &[String]) -> Result<(), GitError> {
+        commit_in(&self.repository()?, message, false, args)
+    }
+
+    fn repository(&self) -> Result<Repository, GitError> {
+        Ok(Repository::open(&self.root)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::File;
+    use std::io::Write;
+    use tempfile::Builder;
+
+    #[test]
+    fn test_get_diff_with_staged_changes() -> Result<()> {
+        // Create a temporary git repository
+        let tmp_dir = Builder::new()
+            .prefix("test_get_diff_with_staged_changes")
+            .tempdir()
+            .unwrap();
+        let repo_path = tmp_dir.path();
+
+        // Initialize git repository
+        Command::new("git")
+            .args(["init"])
+            .current_dir(repo_path)
+            .output()?;
+
+        // Configure git user for the test
+        Command::new("git")
+            .args(["config", "user.name", "Test User"])
+            .current_dir(repo_path)
+            .output()?;
+        Command::new("git")
+            .args(["config", "user.email", "test@example.com"])
+            .current_dir(repo_path)
+            .output()?;
+
+        // Create and stage a test file
+        let test_file = repo_path.join("test.txt");
+        let test_content = "Hello, World!";
+        File::create(&test_file)?.write_all(test_content.as_bytes())?;
+
+        Command::new("git")
+            .args(["add", "test.txt"])
+            .current_dir(repo_path)
+            .output()?;
+
+        // Change to the test directory
+        env::set_current_dir(repo_path)?;
+
+        // Get the diff
+        let diff = get_diff(&[])?;
+
+        // Verify the diff contains our changes
+        let normalized_diff = diff.replace("\r\n", "\n");
+        assert!(normalized_diff.contains("test.txt"));
+        assert!(normalized_diff.contains(test_content));
+
+        Ok(())
+    }
 
-    if !git_status_output.status.success() {
-        println!(
-            "{}",
-            "⚠️  Make sure git is installed and you're in a git repository.".yellow()
+    #[test]
+    fn test_hash_object() -> Result<()> {
+        // Works outside a repository too
+        let tmp_dir = Builder::new().prefix("test_hash_object").tempdir()?;
+        env::set_current_dir(tmp_dir.path())?;
+        assert_eq!(
+            hash_object("hello\n")?,
+            "ce013625030ba8dba906f756967f9e9ca394464a"
         );
-        return Ok("".to_string());
+        Ok(())
     }
 
-    // Get the diff of staged changes
-    let output = Command::new("git")
-        .args(["diff", "--staged"])
-        .output()
-        .context("Failed to execute git diff command.")?;
+    #[test]
+    fn test_split_diff_by_file() {
+        let diff = "diff --git a/src/main.rs b/src/main.rs\n\
+            index 1234567..89abcde 100644\n\
+            --- a/src/main.rs\n\
+            +++ b/src/main.rs\n\
+            @@ -1 +1 @@\n\
+            -fn main() {}\n\
+            +fn main() { run() }\n\
+            diff --git a/old.txt b/docs/new.txt\n\
+            similarity index 100%\n\
+            rename from old.txt\n\
+            rename to docs/new.txt\n";
 
-    // Parse diff content
-    let diff = String::from_utf8_lossy(&output.stdout).into_owned();
-    Ok(diff)
-}
+        let files = split_diff_by_file(diff);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, "src/main.rs");
+        assert!(files[0].diff.starts_with("diff --git a/src/main.rs"));
+        assert!(files[0].diff.ends_with("+fn main() { run() }\n"));
+        assert_eq!(files[1].path, "docs/new.txt");
+        assert!(files[1].diff.contains("rename to docs/new.txt"));
 
-/// Push committed changes to the remote repository
-pub fn push_changes() -> Result<()> {
-    println!("{} Running 'git push'...", "▶".green());
-    let output = Command::new("git")
-        .arg("push")
-        .output()
-        .context("Failed to execute git push command.")?;
+        // Concatenating the chunks restores the diff
+        let joined: String = files.iter().map(|file| file.diff.as_str()).collect();
+        assert_eq!(joined, diff);
 
-    if !output.status.success() {
-        let error_message = String::from_utf8_lossy(&output.stderr).into_owned();
-        eprintln!(
-            "{}",
-            format!("⚠️  Failed to push changes: {error_message}").red()
+        assert!(split_diff_by_file("").is_empty());
+    }
+
+    #[test]
+    fn test_diff_stat() {
+        let diff = "diff --git a/src/main.rs b/src/main.rs\n\
+            index 1234567..89abcde 100644\n\
+            --- a/src/main.rs\n\
+            +++ b/src/main.rs\n\
+            @@ -1,2 +1,3 @@\n\
+            -fn main() {}\n\
+            +fn main() { run() }\n\
+            +\n\
+            +--- not a header\n\
+             // context\n\
+            diff --git a/logo.png b/logo.png\n\
+            index 1111111..2222222 100644\n\
+            Binary files a/logo.png and b/logo.png differ\n";
+
+        let stats = diff_stat(diff);
+        assert_eq!(
+            stats[0],
+            FileStat {
+                path: "src/main.rs".to_string(),
+                insertions: 3,
+                deletions: 1,
+                binary: false,
+            }
         );
-        anyhow::bail!("Git push failed");
+        assert!(stats[1].binary);
+        assert_eq!(stats[1].insertions + stats[1].deletions, 0);
+        assert!(diff_stat("").is_empty());
     }
 
-    println!("{} Changes pushed successfully.", "✔".green());
-    Ok(())
-}
+    #[test]
+    fn test_condense_snapshots() {
+        let diff = "diff --git a/src/lib.rs b/src/lib.rs\n\
+            +pub fn render() {}\n\
+            diff --git a/tests/snapshots/render__page.snap b/tests/snapshots/render__page.snap\n\
+            +<html>lots of regenerated markup</html>\n\
+            diff --git a/web/__snapshots__/App.test.js.snap b/web/__snapshots__/App.test.js.snap\n\
+            +exports[`App renders`] = `<div />`;\n";
+        let patterns = ["*.snap", "__snapshots__"];
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::env;
-    use std::fs::File;
-    use std::io::Write;
-    use tempfile::Builder;
+        let condensed = condense_snapshots(diff, &patterns);
+        assert!(condensed.contains("+pub fn render() {}"));
+        assert!(!condensed.contains("regenerated markup"));
+        assert!(!condensed.contains("exports["));
+        assert!(condensed.contains(
+            "[2 snapshot file(s) updated: tests/snapshots/render__page.snap, web/__snapshots__/App.test.js.snap]"
+        ));
+
+        // Nothing to condense without matches
+        assert_eq!(condense_snapshots(diff, &["*.png"]), diff);
+    }
 
     #[test]
-    fn test_get_diff_with_staged_changes() -> Result<()> {
-        // Create a temporary git repository
+    fn test_omit_ignored() {
+        let diff = "diff --git a/src/lib.rs b/src/lib.rs\n\
+            +pub fn render() {}\n\
+            diff --git a/Cargo.lock b/Cargo.lock\n\
+            +checksum = \"abc\"\n";
+
+        let omitted = omit_ignored(diff, &["*.lock"]);
+        assert!(omitted.contains("+pub fn render() {}"));
+        assert!(!omitted.contains("checksum"));
+        assert!(omitted.contains("[1 file(s) changed but left out by .aicignore: Cargo.lock]"));
+
+        // Re-included files are kept
+        assert_eq!(omit_ignored(diff, &["*.lock", "!Cargo.lock"]), diff);
+        assert_eq!(omit_ignored::<&str>(diff, &[]), diff);
+    }
+
+    #[test]
+    fn test_omit_ignored_classes_and_escapes() {
+        let file = |path: &str| format!("diff --git a/{path} b/{path}\n+changed\n");
+        let diff = [
+            "src/main.c",
+            "include/util.h",
+            "src/lib.rs",
+            "#file",
+            "docs/#file",
+        ]
+        .map(file)
+        .concat();
+
+        // Character classes and escaped comment characters, as in .gitignore
+        let omitted = omit_ignored(&diff, &["*.[ch]", "\\#file"]);
+        assert!(omitted.contains("diff --git a/src/lib.rs"));
+        assert!(omitted.contains(
+            "[4 file(s) changed but left out by .aicignore: \
+             src/main.c, include/util.h, #file, docs/#file]"
+        ));
+
+        // A file re-included inside an ignored directory
+        let diff = ["vendor/lib.c", "vendor/README.md", "vendor/nested/keep.md"]
+            .map(file)
+            .concat();
+        let omitted = omit_ignored(&diff, &["vendor/", "!vendor/README.md", "!keep.md"]);
+        assert!(omitted.contains("diff --git a/vendor/README.md"));
+        assert!(omitted.contains("diff --git a/vendor/nested/keep.md"));
+        assert!(omitted.contains("[1 file(s) changed but left out by .aicignore: vendor/lib.c]"));
+    }
+
+    #[test]
+    fn test_staged_files_and_unstage() -> Result<()> {
         let tmp_dir = Builder::new()
-            .prefix("test_get_diff_with_staged_changes")
+            .prefix("test_staged_files_and_unstage")
             .tempdir()
             .unwrap();
         let repo_path = tmp_dir.path();
 
-        // Initialize git repository
         Command::new("git")
             .args(["init"])
             .current_dir(repo_path)
             .output()?;
 
-        // Configure git user for the test
+        fs::create_dir_all(repo_path.join("src"))?;
+        fs::write(repo_path.join("src").join("main.rs"), "fn main() {}")?;
+        fs::write(repo_path.join("README.md"), "# Test")?;
+
         Command::new("git")
-            .args(["config", "user.name", "Test User"])
+            .args(["add", "."])
+            .current_dir(repo_path)
+            .output()?;
+
+        env::set_current_dir(repo_path)?;
+
+        let files = staged_files()?;
+        assert_eq!(files, vec!["README.md", "src/main.rs"]);
+
+        // Unstaged files stay in the working tree
+        let tree = write_index_tree()?;
+        unstage_files(&["README.md".to_string()])?;
+        assert_eq!(staged_files()?, vec!["src/main.rs"]);
+        assert!(repo_path.join("README.md").exists());
+
+        // Restaging brings back what was staged, not the working tree
+        fs::write(repo_path.join("README.md"), "# Changed")?;
+        restore_staged(&tree, &["README.md".to_string()])?;
+        assert_eq!(files, staged_files()?);
+        let staged = Command::new("git").args(["show", ":README.md"]).output()?;
+        assert_eq!(String::from_utf8_lossy(&staged.stdout), "# Test");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_git_repo() -> Result<()> {
+        let tmp_dir = Builder::new().prefix("test_git_repo").tempdir()?;
+        let repo_path = tmp_dir.path();
+        Command::new("git")
+            .args(["init", "-q", "-b", "main"])
+            .current_dir(repo_path)
+            .output()?;
+        Command::new("git")
+            .args(["config", "user.name", "Test"])
             .current_dir(repo_path)
             .output()?;
         Command::new("git")
             .args(["config", "user.email", "test@example.com"])
             .current_dir(repo_path)
             .output()?;
+        fs::create_dir(repo_path.join("src"))?;
+        fs::write(repo_path.join("src/lib.rs"), "pub fn a() {}\n")?;
+        fs::write(repo_path.join("Cargo.lock"), "# lock\n")?;
+        Command::new("git")
+            .args(["add", "."])
+            .current_dir(repo_path)
+            .output()?;
 
-        // Create and stage a test file
-        let test_file = repo_path.join("test.txt");
-        let test_content = "Hello, World!";
-        File::create(&test_file)?.write_all(test_content.as_bytes())?;
+        // Opened from a subdirectory, without changing the working directory
+        let repo = GitRepo::open(repo_path.join("src"))?;
+        assert_eq!(repo.root().canonicalize()?, repo_path.canonicalize()?);
+        assert_eq!(repo.current_branch()?.as_deref(), Some("main"));
+        assert_eq!(repo.staged_files()?, vec!["Cargo.lock", "src/lib.rs"]);
+        let diff = repo.staged_diff(&["*.lock".to_string()])?;
+        assert!(diff.contains("src/lib.rs") && !diff.contains("Cargo.lock"));
+
+        repo.commit("feat: add a", &["--no-verify".to_string()])?;
+        assert_eq!(repo.recent_subjects(5)?, vec!["feat: add a"]);
+        assert!(repo.staged_files()?.is_empty());
+        assert!(repo.commit("empty", &[]).is_err());
+
+        assert!(GitRepo::open(env::temp_dir().join("aic-not-a-repo")).is_err());
+        Ok(())
+    }
 
+    #[test]
+    fn test_create_branch() -> Result<()> {
+        let tmp_dir = Builder::new().prefix("test_create_branch").tempdir()?;
+        let repo_path = tmp_dir.path();
+        env::set_current_dir(repo_path)?;
+        Command::new("git").args(["init", "-q"]).output()?;
         Command::new("git")
-            .args(["add", "test.txt"])
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+            .args(["commit", "-q", "--allow-empty", "-m", "init"])
+            .output()?;
+
+        assert!(!branch_exists("feat/add-login")?);
+        create_branch("feat/add-login")?;
+        assert!(branch_exists("feat/add-login")?);
+        assert_eq!(current_branch()?.as_deref(), Some("feat/add-login"));
+        assert!(create_branch("feat/add-login").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_current_branch_and_aic_dir() -> Result<()> {
+        let tmp_dir = Builder::new()
+            .prefix("test_current_branch_and_aic_dir")
+            .tempdir()
+            .unwrap();
+        let repo_path = tmp_dir.path();
+
+        Command::new("git")
+            .args(["init", "-b", "feature/login"])
             .current_dir(repo_path)
             .output()?;
 
-        // Change to the test directory
         env::set_current_dir(repo_path)?;
 
-        // Get the diff
-        let diff = get_diff()?;
+        // Works before the first commit
+        assert_eq!(current_branch()?, Some("feature/login".to_string()));
 
-        // Verify the diff contains our changes
-        let normalized_diff = diff.replace("\r\n", "\n");
-        assert!(normalized_diff.contains("test.txt"));
-        assert!(normalized_diff.contains(test_content));
+        let aic_dir = aic_dir()?;
+        assert!(aic_dir.ends_with(".git/aic"));
+        assert_eq!(repo_root()?.canonicalize()?, repo_path.canonicalize()?);
+        assert!(aic_dir.is_dir());
+        assert!(hooks_dir()?.ends_with(".git/hooks"));
+
+        // A shared hooks directory takes precedence
+        Command::new("git")
+            .args(["config", "core.hooksPath", ".githooks"])
+            .output()?;
+        assert!(hooks_dir()?.ends_with(".githooks"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remote_branches_containing() -> Result<()> {
+        let tmp_dir = Builder::new()
+            .prefix("test_remote_branches_containing")
+            .tempdir()
+            .unwrap();
+        let repo_path = tmp_dir.path();
+
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .args([
+                    "-c",
+                    "user.name=Test User",
+                    "-c",
+                    "user.email=test@example.com",
+                ])
+                .args(args)
+                .current_dir(repo_path)
+                .output()
+        };
+        git(&["init"])?;
+        git(&["commit", "--allow-empty", "-m", "feat: first"])?;
+
+        env::set_current_dir(repo_path)?;
+        assert!(remote_branches_containing("HEAD")?.is_empty());
+        assert_eq!(default_remote_branch()?, None);
+
+        // Pretend the commit was pushed
+        git(&["update-ref", "refs/remotes/origin/main", "HEAD"])?;
+        git(&[
+            "symbolic-ref",
+            "refs/remotes/origin/HEAD",
+            "refs/remotes/origin/main",
+        ])?;
+        assert_eq!(remote_branches_containing("HEAD")?, vec!["origin/main"]);
+        assert_eq!(default_remote_branch()?, Some("origin/main".to_string()));
+
+        // A newer local commit is not on the remote yet
+        git(&["commit", "--allow-empty", "-m", "fix: second"])?;
+        assert!(remote_branches_containing("HEAD")?.is_empty());
+
+        // Without an upstream a push goes to the branch of the same name
+        let branch = current_branch()?.unwrap();
+        assert_eq!(push_target_branch()?, Some(branch.clone()));
+        git(&["checkout", "-b", "feature/login"])?;
+        assert_eq!(push_target_branch()?, Some("feature/login".to_string()));
+        git(&["remote", "add", "origin", "https://example.com/repo.git"])?;
+        assert_eq!(upstream_remote()?, None);
+        assert_eq!(default_remote()?, Some("origin".to_string()));
+        git(&["branch", "--set-upstream-to", "origin/main"])?;
+        assert_eq!(push_target_branch()?, Some("main".to_string()));
+        assert_eq!(upstream_remote()?, Some("origin".to_string()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_preflight() -> Result<()> {
+        let tmp_dir = Builder::new().prefix("test_preflight").tempdir().unwrap();
+        let repo_path = tmp_dir.path();
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .args(args)
+                .current_dir(repo_path)
+                .output()
+        };
+        git(&["init"])?;
+        git(&["config", "user.name", "Test User"])?;
+        git(&["config", "user.email", "test@example.com"])?;
+        git(&["commit", "--allow-empty", "-m", "feat: first"])?;
+
+        env::set_current_dir(repo_path)?;
+        assert!(preflight()?.is_empty());
+
+        fs::write(
+            repo_path.join("notes.txt"),
+            "<<<<<<< HEAD\nours\n=======\ntheirs\n>>>>>>> feature\n",
+        )?;
+        git(&["add", "notes.txt"])?;
+        git(&["checkout", "--detach"])?;
+        fs::write(repo_path.join(".git/MERGE_HEAD"), "")?;
+        git(&["config", "user.email", ""])?;
+
+        let issues = preflight()?;
+        assert_eq!(
+            issues,
+            vec![
+                PreflightIssue::DetachedHead,
+                PreflightIssue::MergeInProgress,
+                PreflightIssue::ConflictMarkers(vec!["notes.txt".to_string()]),
+                PreflightIssue::MissingIdentity(vec!["user.email"]),
+            ]
+        );
+        let blocking: Vec<bool> = issues.iter().map(PreflightIssue::is_blocking).collect();
+        assert_eq!(blocking, vec![false, false, true, true]);
+        assert!(issues[3]
+            .to_string()
+            .contains("'git config --global user.email \"...\"'"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_push_args() {
+        assert_eq!(PushTarget::default().push_args(false), vec!["push"]);
+        let target = PushTarget {
+            remote: Some("origin".to_string()),
+            branch: None,
+        };
+        assert_eq!(target.push_args(true), vec!["push", "-u", "origin", "HEAD"]);
+        let target = PushTarget {
+            remote: Some("fork".to_string()),
+            branch: Some("review/login".to_string()),
+        };
+        assert_eq!(
+            target.push_args(false),
+            vec!["push", "fork", "HEAD:review/login"]
+        );
+    }
+
+    #[test]
+    fn test_commit_history() -> Result<()> {
+        let tmp_dir = Builder::new()
+            .prefix("test_commit_history")
+            .tempdir()
+            .unwrap();
+        let repo_path = tmp_dir.path();
+
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .args([
+                    "-c",
+                    "user.name=Test User",
+                    "-c",
+                    "user.email=test@example.com",
+                ])
+                .args(args)
+                .current_dir(repo_path)
+                .output()
+        };
+        git(&["init"])?;
+        fs::write(repo_path.join("a.txt"), "one\n")?;
+        git(&["add", "."])?;
+        git(&["commit", "-m", "feat: add a\n\nWith a body"])?;
+        fs::write(repo_path.join("a.txt"), "two\n")?;
+        git(&["commit", "-am", "fix: change a"])?;
+
+        env::set_current_dir(repo_path)?;
+
+        let commits = commits_in_range("HEAD")?;
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits_in_range("HEAD~1..HEAD")?, vec![commits[1].clone()]);
+        assert!(commits_in_range("nope..HEAD").is_err());
+
+        // Oldest first, and the root commit has a diff too
+        assert_eq!(commit_message(&commits[0])?, "feat: add a\n\nWith a body");
+        assert!(commit_diff(&commits[0])?.contains("+one"));
+        let diff = commit_diff(&commits[1])?;
+        assert!(diff.contains("-one") && diff.contains("+two"));
+        assert_eq!(diff_range("HEAD~1...HEAD", &[])?, diff);
+        assert!(diff_range("nope...HEAD", &[]).is_err());
+
+        // Newest first, with the whole message
+        let entries = log_entries()?;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].id, commits[1]);
+        assert_eq!(entries[1].message, "feat: add a\n\nWith a body");
+        assert_eq!(entries[1].date.len(), "2024-05-01".len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_worktree_diff() -> Result<()> {
+        let tmp_dir = Builder::new()
+            .prefix("test_worktree_diff")
+            .tempdir()
+            .unwrap();
+        let repo_path = tmp_dir.path();
+
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .args([
+                    "-c",
+                    "user.name=Test User",
+                    "-c",
+                    "user.email=test@example.com",
+                ])
+                .args(args)
+                .current_dir(repo_path)
+                .output()
+        };
+        git(&["init"])?;
+        fs::write(repo_path.join("tracked.txt"), "one\n")?;
+        fs::write(repo_path.join(".gitignore"), "ignored.txt\n")?;
+        git(&["add", "."])?;
+        git(&["commit", "-m", "feat: add files"])?;
+
+        fs::write(repo_path.join("tracked.txt"), "two\n")?;
+        fs::write(repo_path.join("new.txt"), "fresh\n")?;
+        fs::write(repo_path.join("ignored.txt"), "secret\n")?;
+
+        env::set_current_dir(repo_path)?;
+        let diff = worktree_diff("HEAD", &[])?;
+        assert!(diff.contains("-one") && diff.contains("+two"));
+        assert!(diff.contains("b/new.txt") && diff.contains("+fresh"));
+        assert!(!diff.contains("secret"));
+
+        // The old side is stored, the new one only in the working tree
+        let file = &split_diff_by_file(&diff)[1];
+        let (old, new) = file
+            .diff
+            .lines()
+            .find_map(|line| line.strip_prefix("index "))
+            .and_then(|index| index.split_whitespace().next()?.split_once(".."))
+            .unwrap();
+        assert_eq!(blob_text(old, &file.path).as_deref(), Some("one\n"));
+        assert_eq!(blob_text(new, &file.path).as_deref(), Some("two\n"));
+        assert_eq!(blob_text("1234567", &file.path), None);
+
+        // Nothing was staged
+        assert!(get_diff(&[])?.is_empty());
+
+        // Renames are staged on both sides, ignored files are left out
+        git(&["mv", "tracked.txt", "moved.txt"])?;
+        git(&["reset", "-q"])?;
+        let mut files = worktree_files("HEAD", &[])?;
+        files.sort();
+        assert_eq!(files, vec!["moved.txt", "new.txt", "tracked.txt"]);
+
+        // From a subdirectory the whole repository is covered, relative to it
+        fs::create_dir(repo_path.join("docs"))?;
+        env::set_current_dir(repo_path.join("docs"))?;
+        assert!(worktree_diff("HEAD", &[])?.contains("b/new.txt"));
+        let mut nested = worktree_files("HEAD", &[])?;
+        nested.sort();
+        assert_eq!(nested, vec!["../moved.txt", "../new.txt", "../tracked.txt"]);
+        env::set_current_dir(repo_path)?;
+
+        // Excluded files are neither described nor staged
+        let exclude = ["*.txt".to_string()];
+        assert!(worktree_diff("HEAD", &exclude)?.is_empty());
+        assert!(worktree_files("HEAD", &exclude)?.is_empty());
+
+        stage_files(&files)?;
+        let staged = get_diff(&[])?;
+        assert!(staged.contains("deleted file mode") && staged.contains("b/moved.txt"));
+        let partial = get_diff(&["new.txt".to_string()])?;
+        assert!(partial.contains("b/moved.txt") && !partial.contains("b/new.txt"));
+        assert!(staged.contains("b/new.txt") && !staged.contains("secret"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tags() -> Result<()> {
+        let tmp_dir = Builder::new().prefix("test_tags").tempdir().unwrap();
+        let repo_path = tmp_dir.path();
+
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .args([
+                    "-c",
+                    "user.name=Test User",
+                    "-c",
+                    "user.email=test@example.com",
+                ])
+                .args(args)
+                .current_dir(repo_path)
+                .output()
+        };
+        git(&["init"])?;
+        git(&["commit", "--allow-empty", "-m", "feat: first"])?;
+
+        env::set_current_dir(repo_path)?;
+        assert_eq!(latest_tag("HEAD")?, None);
+        assert!(!tag_exists("v1.0.0")?);
+
+        git(&["tag", "v1.0.0"])?;
+        git(&["commit", "--allow-empty", "-m", "fix: second"])?;
+        assert_eq!(latest_tag("HEAD")?, Some("v1.0.0".to_string()));
+        assert!(tag_exists("v1.0.0")?);
+
+        // The tagger identity comes from the repository config
+        git(&["config", "user.name", "Test User"])?;
+        git(&["config", "user.email", "test@example.com"])?;
+        create_annotated_tag("v1.1.0", "Fix the second thing\n\n- fix: second\n")?;
+        assert_eq!(latest_tag("HEAD")?, Some("v1.1.0".to_string()));
+        let output = git(&["tag", "-l", "--format=%(contents)", "v1.1.0"])?;
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout).trim(),
+            "Fix the second thing\n\n- fix: second"
+        );
+        assert!(create_annotated_tag("v1.1.0", "again").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_amend_diff() -> Result<()> {
+        let tmp_dir = Builder::new().prefix("test_amend_diff").tempdir().unwrap();
+        let repo_path = tmp_dir.path();
+
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .args([
+                    "-c",
+                    "user.name=Test User",
+                    "-c",
+                    "user.email=test@example.com",
+                ])
+                .args(args)
+                .current_dir(repo_path)
+                .output()
+        };
+        git(&["init"])?;
+        env::set_current_dir(repo_path)?;
+        assert!(amend_base().is_err());
+
+        // A root commit is compared with the empty tree
+        fs::write(repo_path.join("a.txt"), "one\n")?;
+        git(&["add", "."])?;
+        git(&["commit", "-m", "feat: add a"])?;
+        assert_eq!(amend_base()?, EMPTY_TREE);
+        assert!(staged_diff_from(&amend_base()?, &[])?.contains("+one"));
+
+        // Otherwise with the parent, including newly staged changes
+        fs::write(repo_path.join("a.txt"), "two\n")?;
+        git(&["commit", "-am", "fix: change a"])?;
+        fs::write(repo_path.join("b.txt"), "new\n")?;
+        git(&["add", "b.txt"])?;
+        let diff = staged_diff_from(&amend_base()?, &[])?;
+        assert!(diff.contains("-one") && diff.contains("+two") && diff.contains("+new"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reword_commits() -> Result<()> {
+        let tmp_dir = Builder::new()
+            .prefix("test_reword_commits")
+            .tempdir()
+            .unwrap();
+        let repo_path = tmp_dir.path();
+
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .args(args)
+                .current_dir(repo_path)
+                .output()
+        };
+        git(&["init"])?;
+        git(&["config", "user.name", "Test User"])?;
+        git(&["config", "user.email", "test@example.com"])?;
+        for (content, message) in [("one", "first"), ("two", "wip"), ("three", "third")] {
+            fs::write(repo_path.join("a.txt"), content)?;
+            git(&["add", "."])?;
+            git(&["-c", "user.name=Original Author", "commit", "-m", message])?;
+        }
+        fs::write(repo_path.join("a.txt"), "uncommitted")?;
+
+        env::set_current_dir(repo_path)?;
+        let commits = commits_in_range("HEAD")?;
+        let tree = |rev: &str| {
+            let output = git(&["rev-parse", &format!("{rev}^{{tree}}")]).unwrap();
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        let old_tree = tree("HEAD");
+
+        let messages = HashMap::from([(commits[1].clone(), "fix: handle two".to_string())]);
+        let new_head = reword_commits(&commits[1], &messages)?;
+        assert_eq!(resolve_commit("HEAD")?, new_head);
+
+        // Only the message changed, the trees, authors and working tree are kept
+        let reworded = commits_in_range("HEAD")?;
+        assert_eq!(reworded[0], commits[0]);
+        assert_ne!(reworded[1], commits[1]);
+        let messages: Vec<String> = reworded
+            .iter()
+            .map(|rev| commit_message(rev).unwrap())
+            .collect();
+        assert_eq!(messages, vec!["first", "fix: handle two", "third"]);
+        assert_eq!(tree("HEAD"), old_tree);
+        let output = git(&["log", "-1", "--format=%an", "HEAD~1"])?;
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout).trim(),
+            "Original Author"
+        );
+        assert_eq!(fs::read_to_string(repo_path.join("a.txt"))?, "uncommitted");
+
+        // Commits off the current branch are refused
+        assert!(reword_commits(&commits[2], &HashMap::new()).is_err());
+        assert!(is_ancestor(&reworded[1], "HEAD")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_staged_changes() -> Result<()> {
+        let tmp_dir = Builder::new().prefix("test_staged_changes").tempdir()?;
+        let repo_path = tmp_dir.path();
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .current_dir(repo_path)
+                .output()
+        };
+        git(&["init", "-q"])?;
+        let lines: String = (1..=20).map(|n| format!("line {n}\n")).collect();
+        fs::write(repo_path.join("old.txt"), &lines)?;
+        git(&["add", "."])?;
+        git(&["commit", "-q", "-m", "feat: add old"])?;
+
+        git(&["mv", "old.txt", "new.txt"])?;
+        fs::write(
+            repo_path.join("new.txt"),
+            lines.replace("line 2\n", "line two\n"),
+        )?;
+        fs::write(repo_path.join("logo.png"), [0u8, 159, 146, 150])?;
+        fs::write(repo_path.join("Cargo.lock"), "# lock\n")?;
+        git(&["add", "-A"])?;
+
+        env::set_current_dir(repo_path)?;
+        let changes = staged_changes(&["*.lock".to_string()])?;
+        assert_eq!(changes.len(), 2);
+
+        let logo = &changes[0];
+        assert_eq!(logo.path, "logo.png");
+        assert_eq!(logo.status, ChangeStatus::Added);
+        assert!(logo.binary && logo.hunks.is_empty());
+
+        let renamed = &changes[1];
+        assert_eq!(renamed.status, ChangeStatus::Renamed);
+        assert_eq!(renamed.old_path.as_deref(), Some("old.txt"));
+        assert_eq!(
+            renamed.hunks,
+            vec![Hunk {
+                old_start: 1,
+                old_lines: 5,
+                new_start: 1,
+                new_lines: 5,
+                header: "@@ -1,5 +1,5 @@".to_string(),
+            }]
+        );
+        assert!(renamed
+            .patch
+            .contains("rename from old.txt\nrename to new.txt\n"));
+        assert!(renamed.patch.contains("-line 2\n+line two\n"));
+        assert_eq!(patch_of(&changes), get_diff(&["*.lock".to_string()])?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_commit() -> Result<()> {
+        let tmp_dir = Builder::new().prefix("test_commit").tempdir()?;
+        let repo_path = tmp_dir.path();
+        let git = |args: &[&str]| -> Result<String> {
+            let output = Command::new("git")
+                .args(args)
+                .current_dir(repo_path)
+                .output()?;
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        };
+        git(&["init", "-q"])?;
+        git(&["config", "user.name", "Test User"])?;
+        git(&["config", "user.email", "test@example.com"])?;
+        env::set_current_dir(repo_path)?;
+        assert!(matches!(
+            commit("feat: nothing", false, &[]),
+            Err(GitError::NothingToCommit)
+        ));
+
+        fs::write(repo_path.join("a.txt"), "one\n")?;
+        stage_all()?;
+        commit("feat: add a", false, &["--signoff".to_string()])?;
+        assert_eq!(
+            git(&["log", "-1", "--format=%B"])?.trim(),
+            "feat: add a\n\nSigned-off-by: Test User <test@example.com>"
+        );
+        assert!(staged_files()?.is_empty());
+
+        // Amending replaces HEAD instead of adding a commit
+        fs::write(repo_path.join("b.txt"), "two\n")?;
+        stage_files(&["b.txt".to_string()])?;
+        commit("feat: add a and b", true, &[])?;
+        assert_eq!(git(&["log", "--format=%s"])?, "feat: add a and b\n");
+        assert_eq!(git(&["ls-tree", "--name-only", "HEAD"])?, "a.txt\nb.txt\n");
+
+        // Only the given paths are committed, the rest stays staged
+        fs::write(repo_path.join("a.txt"), "changed\n")?;
+        fs::write(repo_path.join("c.txt"), "three\n")?;
+        stage_files(&["a.txt".to_string()])?;
+        commit_paths(&["c.txt".to_string()], "feat: add c")?;
+        assert_eq!(git(&["show", "--format=", "--name-only"])?, "c.txt\n");
+        assert_eq!(staged_files()?, vec!["a.txt"]);
+
+        // Hooks need git itself, which runs them
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let hook = repo_path.join(".git/hooks/commit-msg");
+            fs::write(&hook, "#!/bin/sh\necho 'Reviewed-by: Hook' >> \"$1\"\n")?;
+            fs::set_permissions(&hook, fs::Permissions::from_mode(0o755))?;
+            commit("fix: change a", false, &[])?;
+            assert!(git(&["log", "-1", "--format=%B"])?.contains("Reviewed-by: Hook"));
+
+            fs::write(repo_path.join("a.txt"), "again\n")?;
+            stage_all()?;
+            commit("fix: skip hooks", false, &["--no-verify".to_string()])?;
+            assert_eq!(
+                git(&["log", "-1", "--format=%B"])?.trim(),
+                "fix: skip hooks"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_push_changes_with_hook() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp_dir = Builder::new()
+            .prefix("test_push_changes_with_hook")
+            .tempdir()?;
+        let remote_path = tmp_dir.path().join("remote.git");
+        let repo_path = tmp_dir.path().join("repo");
+        let git = |dir: &Path, args: &[&str]| -> Result<String> {
+            let output = Command::new("git")
+                .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .current_dir(dir)
+                .output()?;
+            Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        };
+        fs::create_dir_all(&repo_path)?;
+        git(tmp_dir.path(), &["init", "-q", "--bare", "remote.git"])?;
+        git(&repo_path, &["init", "-q", "-b", "feature"])?;
+        git(
+            &repo_path,
+            &["commit", "-q", "--allow-empty", "-m", "feat: first"],
+        )?;
+        git(
+            &repo_path,
+            &["remote", "add", "origin", &remote_path.to_string_lossy()],
+        )?;
+
+        // A pre-push hook hands the push to git, which must not need an upstream
+        let hook = repo_path.join(".git/hooks/pre-push");
+        fs::write(&hook, "#!/bin/sh\nexit 0\n")?;
+        fs::set_permissions(&hook, fs::Permissions::from_mode(0o755))?;
+
+        env::set_current_dir(&repo_path)?;
+        push_changes(&PushTarget::default(), true)?;
+        assert_eq!(
+            git(&remote_path, &["rev-parse", "feature"])?,
+            git(&repo_path, &["rev-parse", "HEAD"])?
+        );
+        assert_eq!(upstream_remote()?.as_deref(), Some("origin"));
+        assert_eq!(push_target_branch()?.as_deref(), Some("feature"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_push_changes() -> Result<()> {
+        let tmp_dir = Builder::new().prefix("test_push_changes").tempdir()?;
+        let remote_path = tmp_dir.path().join("remote.git");
+        let repo_path = tmp_dir.path().join("repo");
+        let other_path = tmp_dir.path().join("other");
+        let git = |dir: &Path, args: &[&str]| -> Result<String> {
+            let output = Command::new("git")
+                .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .current_dir(dir)
+                .output()?;
+            Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        };
+        fs::create_dir_all(&repo_path)?;
+        git(tmp_dir.path(), &["init", "-q", "--bare", "remote.git"])?;
+        git(&repo_path, &["init", "-q", "-b", "main"])?;
+        git(
+            &repo_path,
+            &["commit", "-q", "--allow-empty", "-m", "feat: first"],
+        )?;
+
+        env::set_current_dir(&repo_path)?;
+        assert!(matches!(
+            push_changes(&PushTarget::default(), false),
+            Err(GitError::NoRemote)
+        ));
+
+        git(
+            &repo_path,
+            &["remote", "add", "origin", &remote_path.to_string_lossy()],
+        )?;
+        push_changes(&PushTarget::default(), true)?;
+        assert_eq!(
+            git(&remote_path, &["rev-parse", "main"])?,
+            git(&repo_path, &["rev-parse", "HEAD"])?
+        );
+        assert_eq!(upstream_remote()?.as_deref(), Some("origin"));
+        assert_eq!(push_target_branch()?.as_deref(), Some("main"));
+
+        // A branch of another name only on the remote
+        let target = PushTarget {
+            remote: Some("origin".to_string()),
+            branch: Some("review/first".to_string()),
+        };
+        push_changes(&target, false)?;
+        assert!(!git(&remote_path, &["rev-parse", "review/first"])?.is_empty());
+
+        // Commits someone else pushed first make the push fail
+        git(
+            tmp_dir.path(),
+            &["clone", "-q", "-b", "main", "remote.git", "other"],
+        )?;
+        git(
+            &other_path,
+            &["commit", "-q", "--allow-empty", "-m", "fix: theirs"],
+        )?;
+        git(&other_path, &["push", "-q"])?;
+        git(
+            &repo_path,
+            &["commit", "-q", "--allow-empty", "-m", "fix: ours"],
+        )?;
+        let result = push_changes(&PushTarget::default(), false);
+        assert!(
+            matches!(result, Err(GitError::PushRejected { .. })),
+            "{result:?}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_without_git_binary() -> Result<()> {
+        let tmp_dir = Builder::new().prefix("test_without_git_binary").tempdir()?;
+        let remote_path = tmp_dir.path().join("remote.git");
+        let repo_path = tmp_dir.path().join("repo");
+        fs::create_dir_all(&repo_path)?;
+        let git =
+            |dir: &Path, args: &[&str]| Command::new("git").args(args).current_dir(dir).output();
+        git(tmp_dir.path(), &["init", "-q", "--bare", "remote.git"])?;
+        git(&repo_path, &["init", "-q", "-b", "main"])?;
+        git(&repo_path, &["config", "user.name", "Test User"])?;
+        git(&repo_path, &["config", "user.email", "test@example.com"])?;
+        git(
+            &repo_path,
+            &["remote", "add", "origin", &remote_path.to_string_lossy()],
+        )?;
+        env::set_current_dir(&repo_path)?;
+
+        // Every command run from here on would fail to start
+        let path = env::var_os("PATH").unwrap_or_default();
+        env::set_var("PATH", "");
+        let flow = || -> Result<Vec<LogEntry>> {
+            fs::write(repo_path.join("a.txt"), "one\n")?;
+            stage_all()?;
+            assert!(get_diff(&[])?.contains("+one"));
+            commit("feat: add a", false, &["--signoff".to_string()])?;
+
+            create_branch("feature")?;
+            fs::write(repo_path.join("a.txt"), "two\n")?;
+            stage_files(&["a.txt".to_string()])?;
+            commit("fix: change a", false, &[])?;
+            create_annotated_tag("v1.0.0", "Release 1.0.0")?;
+            push_changes(&PushTarget::default(), true)?;
+
+            assert_eq!(recent_subjects(5)?, vec!["fix: change a", "feat: add a"]);
+            assert!(diff_range("main...HEAD", &[])?.contains("+two"));
+            assert_eq!(latest_tag("HEAD")?.as_deref(), Some("v1.0.0"));
+            assert_eq!(upstream_remote()?.as_deref(), Some("origin"));
+            log_entries()
+        };
+        let result = flow();
+        env::set_var("PATH", path);
+
+        let entries = result?;
+        assert_eq!(entries.len(), 2);
+        assert!(entries[1]
+            .message
+            .ends_with("Signed-off-by: Test User <test@example.com>"));
+        let pushed = git(&remote_path, &["rev-parse", "feature"])?;
+        assert_eq!(
+            String::from_utf8_lossy(&pushed.stdout).trim(),
+            entries[0].id
+        );
 
         Ok(())
     }
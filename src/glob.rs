@@ -0,0 +1,139 @@
+use globset::GlobBuilder;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+/// Match a repository-relative path against a gitignore-style glob pattern
+///
+/// The syntax is that of `.gitignore`, including `**`, `[...]` classes, `\`
+/// escapes, a trailing `/` for directories and a leading `/` to anchor the
+/// pattern to the repository root. A pattern matches everything inside the
+/// directories it matches.
+pub fn matches(pattern: &str, path: &str) -> bool {
+    matches_any(&[pattern], path)
+}
+
+/// Whether a path matches any of the patterns
+pub fn matches_any<S: AsRef<str>>(patterns: &[S], path: &str) -> bool {
+    // Without an order between the patterns a leading `!` is just a character
+    let patterns: Vec<String> = patterns
+        .iter()
+        .map(|pattern| match pattern.as_ref().trim().strip_prefix('!') {
+            Some(rest) => format!("\\!{rest}"),
+            None => pattern.as_ref().to_string(),
+        })
+        .collect();
+    gitignore(patterns.iter().map(String::as_str))
+        .matched_path_or_any_parents(path, false)
+        .is_ignore()
+}
+
+/// Whether a path is ignored by gitignore-style rules, where the last matching
+/// pattern wins and a leading `!` re-includes a path an earlier pattern ignored
+pub fn is_ignored<S: AsRef<str>>(patterns: &[S], path: &str) -> bool {
+    gitignore(patterns.iter().map(AsRef::as_ref))
+        .matched_path_or_any_parents(path, false)
+        .is_ignore()
+}
+
+/// Match a whole name such as a branch against a glob, without the path rules of `matches`
+///
+/// `*` and `?` stop at `/`, `**` crosses it.
+pub fn matches_exact(pattern: &str, name: &str) -> bool {
+    GlobBuilder::new(pattern.trim())
+        .literal_separator(true)
+        .backslash_escape(true)
+        .build()
+        .is_ok_and(|glob| glob.compile_matcher().is_match(name))
+}
+
+// Rules of a `.gitignore` holding the patterns, invalid ones are skipped
+fn gitignore<'a>(patterns: impl Iterator<Item = &'a str>) -> Gitignore {
+    let mut builder = GitignoreBuilder::new("");
+    for pattern in patterns {
+        if let Err(err) = builder.add_line(None, pattern.trim()) {
+            tracing::warn!(%err, "skipping invalid glob pattern");
+        }
+    }
+    builder.build().unwrap_or_else(|_| Gitignore::empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_basename_patterns() {
+        assert!(matches("*.snap", "tests/__snapshots__/cli.snap"));
+        assert!(matches("*.snap", "cli.snap"));
+        assert!(!matches("*.snap", "cli.snap.new"));
+        assert!(matches("package-lock.json", "web/package-lock.json"));
+        assert!(matches("file?.rs", "src/file1.rs"));
+        assert!(!matches("file?.rs", "src/file10.rs"));
+    }
+
+    #[test]
+    fn test_directory_patterns() {
+        assert!(matches("__snapshots__", "tests/__snapshots__/cli.snap"));
+        assert!(matches("target/", "target/debug/aic"));
+        assert!(!matches("target/", "target"));
+        assert!(matches("/target", "target/debug/aic"));
+        assert!(!matches("/target", "crates/target/debug/aic"));
+    }
+
+    #[test]
+    fn test_path_patterns() {
+        assert!(matches("**/__snapshots__/**", "a/b/__snapshots__/c/d.snap"));
+        assert!(matches("**/__snapshots__/**", "__snapshots__/d.snap"));
+        assert!(matches("target/**", "target/debug/aic"));
+        assert!(matches("src/*.rs", "src/main.rs"));
+        assert!(!matches("src/*.rs", "src/bin/main.rs"));
+        assert!(matches("src/**/*.rs", "src/main.rs"));
+        assert!(matches("src/**/*.rs", "src/bin/main.rs"));
+        assert!(matches("docs", "docs/guide.md"));
+    }
+
+    #[test]
+    fn test_matches_any() {
+        let patterns = vec!["*.min.js".to_string(), "dist/".to_string()];
+        assert!(matches_any(&patterns, "static/app.min.js"));
+        assert!(matches_any(&patterns, "dist/app.js"));
+        assert!(!matches_any(&patterns, "src/app.js"));
+        assert!(!matches_any::<String>(&[], "src/app.js"));
+        assert!(!matches("", "src/app.js"));
+    }
+
+    #[test]
+    fn test_is_ignored() {
+        let patterns = vec!["*.lock", "fixtures/", "!fixtures/keep.json"];
+        assert!(is_ignored(&patterns, "Cargo.lock"));
+        assert!(is_ignored(&patterns, "tests/fixtures/big.json"));
+        assert!(!is_ignored(&patterns, "fixtures/keep.json"));
+        assert!(!is_ignored(&patterns, "src/main.rs"));
+
+        // The last matching pattern wins
+        assert!(is_ignored(&["!Cargo.lock", "*.lock"], "Cargo.lock"));
+        assert!(!is_ignored::<&str>(&[], "Cargo.lock"));
+    }
+
+    #[test]
+    fn test_classes_and_escapes() {
+        assert!(matches("*.[ch]", "src/main.c"));
+        assert!(matches("*.[ch]", "include/main.h"));
+        assert!(!matches("*.[ch]", "src/main.rs"));
+        assert!(matches("file[!0-9].rs", "file_.rs"));
+        assert!(!matches("file[!0-9].rs", "file1.rs"));
+        assert!(matches("\\#file", "#file"));
+        assert!(matches("\\*.rs", "*.rs"));
+        assert!(!matches("\\*.rs", "main.rs"));
+    }
+
+    #[test]
+    fn test_matches_exact() {
+        assert!(matches_exact("main", "main"));
+        assert!(!matches_exact("main", "fix/main"));
+        assert!(matches_exact("release/*", "release/1.2"));
+        assert!(!matches_exact("release/*", "release/1.2/hotfix"));
+        assert!(matches_exact("release/**", "release/1.2/hotfix"));
+        assert!(matches_exact("release/v[0-9]*", "release/v2"));
+        assert!(!matches_exact("release/v[0-9]*", "release/next"));
+    }
+}
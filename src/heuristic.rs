@@ -0,0 +1,293 @@
+use crate::git;
+use crate::lint::LintRules;
+use crate::scope;
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// Declarations whose names describe what a hunk touches
+static SYMBOL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(?:fn|def|function|func|class|struct|enum|trait|interface|impl)\s+([A-Za-z_][A-Za-z0-9_]*)")
+        .expect("valid symbol pattern")
+});
+
+/// File names that belong to the build system
+const BUILD_FILES: &[&str] = &[
+    "Cargo.toml",
+    "Cargo.lock",
+    "build.rs",
+    "package.json",
+    "package-lock.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "pyproject.toml",
+    "go.mod",
+    "go.sum",
+    "Makefile",
+    "Dockerfile",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Added,
+    Deleted,
+    Modified,
+}
+
+// What a diff does to one file
+#[derive(Debug)]
+struct FileChange {
+    path: String,
+    status: Status,
+    insertions: usize,
+    deletions: usize,
+    symbols: Vec<String>,
+}
+
+impl FileChange {
+    fn parse(file: &git::FileDiff) -> Self {
+        let mut change = Self {
+            path: file.path.clone(),
+            status: Status::Modified,
+            insertions: 0,
+            deletions: 0,
+            symbols: Vec::new(),
+        };
+
+        for line in file.diff.lines() {
+            if line.starts_with("new file mode") {
+                change.status = Status::Added;
+            } else if line.starts_with("deleted file mode") {
+                change.status = Status::Deleted;
+            } else if line.starts_with("+++") || line.starts_with("---") {
+                continue;
+            } else if let Some(context) = line.strip_prefix("@@") {
+                // The function a hunk is in follows the second `@@`
+                let context = context.split_once("@@").map_or("", |(_, rest)| rest);
+                change.add_symbols(context);
+            } else if let Some(added) = line.strip_prefix('+') {
+                change.insertions += 1;
+                change.add_symbols(added);
+            } else if let Some(removed) = line.strip_prefix('-') {
+                change.deletions += 1;
+                change.add_symbols(removed);
+            }
+        }
+
+        change
+    }
+
+    fn add_symbols(&mut self, line: &str) {
+        for captures in SYMBOL.captures_iter(line) {
+            let symbol = captures[1].to_string();
+            if !self.symbols.contains(&symbol) {
+                self.symbols.push(symbol);
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+}
+
+/// Write a conventional commit message from the diff alone, without a model
+///
+/// The type comes from the kind of files touched, the scope from their
+/// directories and the subject from file and function names. The body is
+/// the diffstat. Meant as a fallback when the API can't be reached, so the
+/// result is plain but always follows `rules`.
+pub fn generate_message(
+    diff: &str,
+    is_package_dir: impl Fn(&str) -> bool,
+    rules: &LintRules,
+) -> String {
+    let changes: Vec<FileChange> = git::split_diff_by_file(diff)
+        .iter()
+        .map(FileChange::parse)
+        .collect();
+    if changes.is_empty() {
+        return "chore: update files".to_string();
+    }
+
+    let commit_type = commit_type(&changes);
+    let commit_type = if rules.types.iter().any(|allowed| allowed == commit_type) {
+        commit_type
+    } else {
+        "chore"
+    };
+
+    // Only a scope the whole change agrees on, and not `docs(docs)` or `ci(.github)`
+    let paths: Vec<String> = changes.iter().map(|change| change.path.clone()).collect();
+    let scopes = scope::infer_scopes(&paths, is_package_dir, &rules.scopes);
+    let prefix = match scopes.as_slice() {
+        [scope] if scope != commit_type && !scope.starts_with('.') => {
+            format!("{commit_type}({scope})")
+        }
+        _ => commit_type.to_string(),
+    };
+
+    let header = [subject(&changes, true), subject(&changes, false)]
+        .into_iter()
+        .map(|subject| format!("{prefix}: {subject}"))
+        .find(|header| header.chars().count() <= rules.max_header_length)
+        .unwrap_or_else(|| format!("{commit_type}: update {} files", changes.len()));
+
+    let insertions: usize = changes.iter().map(|change| change.insertions).sum();
+    let deletions: usize = changes.iter().map(|change| change.deletions).sum();
+    format!(
+        "{header}\n\n{} file(s) changed, {insertions} insertion(s)(+), {deletions} deletion(s)(-)",
+        changes.len()
+    )
+}
+
+// Type shared by every file, or `feat` for new source files and `chore` otherwise
+fn commit_type(changes: &[FileChange]) -> &'static str {
+    let kinds: Vec<Option<&'static str>> =
+        changes.iter().map(|change| kind_of(&change.path)).collect();
+    if let Some(Some(kind)) = kinds.first() {
+        if kinds.iter().all(|other| other == &Some(*kind)) {
+            return kind;
+        }
+    }
+
+    let adds_source = changes
+        .iter()
+        .zip(&kinds)
+        .any(|(change, kind)| change.status == Status::Added && kind.is_none());
+    if adds_source {
+        "feat"
+    } else {
+        "chore"
+    }
+}
+
+// Type implied by a path on its own, `None` for source files
+fn kind_of(path: &str) -> Option<&'static str> {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let extension = name.rsplit_once('.').map_or("", |(_, extension)| extension);
+
+    if path.starts_with(".github/") || path.starts_with(".circleci/") || name == ".gitlab-ci.yml" {
+        Some("ci")
+    } else if BUILD_FILES.contains(&name) {
+        Some("build")
+    } else if path.starts_with("tests/")
+        || path.contains("/tests/")
+        || name.starts_with("test_")
+        || ["_test.", ".test.", ".spec."]
+            .iter()
+            .any(|marker| name.contains(marker))
+    {
+        Some("test")
+    } else if ["md", "rst", "adoc"].contains(&extension)
+        || path.starts_with("docs/")
+        || path.starts_with("doc/")
+    {
+        Some("docs")
+    } else {
+        None
+    }
+}
+
+// Subject naming the changed files, and their functions when `detailed`
+fn subject(changes: &[FileChange], detailed: bool) -> String {
+    let verb = if changes.iter().all(|change| change.status == Status::Added) {
+        "add"
+    } else if changes
+        .iter()
+        .all(|change| change.status == Status::Deleted)
+    {
+        "remove"
+    } else {
+        "update"
+    };
+
+    match changes {
+        [change] if detailed && verb == "update" => match change.symbols.as_slice() {
+            [symbol] => format!("update {symbol} in {}", change.name()),
+            [first, second] => format!("update {first} and {second} in {}", change.name()),
+            _ => format!("update {}", change.name()),
+        },
+        [change] => format!("{verb} {}", change.name()),
+        [first, second] => format!("{verb} {} and {}", first.name(), second.name()),
+        [first, second, rest @ ..] if detailed => format!(
+            "{verb} {}, {} and {} more file(s)",
+            first.name(),
+            second.name(),
+            rest.len()
+        ),
+        _ => format!("{verb} {} files", changes.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_diff(path: &str, status: &str, body: &str) -> String {
+        format!("diff --git a/{path} b/{path}\n{status}--- a/{path}\n+++ b/{path}\n{body}")
+    }
+
+    #[test]
+    fn test_single_file_with_symbols() {
+        let diff = file_diff(
+            "src/lint.rs",
+            "",
+            "@@ -10,3 +10,4 @@ pub fn parse_header(header: &str) {\n-    old\n+    new\n+    more\n",
+        );
+        assert_eq!(
+            generate_message(&diff, |_| false, &LintRules::default()),
+            "chore(lint): update parse_header in lint.rs\n\n\
+             1 file(s) changed, 2 insertion(s)(+), 1 deletion(s)(-)"
+        );
+    }
+
+    #[test]
+    fn test_new_source_file_is_a_feature() {
+        let diff = file_diff("src/heuristic.rs", "new file mode 100644\n", "+fn a() {}\n")
+            + &file_diff("README.md", "", "+docs\n");
+        let message = generate_message(&diff, |_| false, &LintRules::default());
+        assert!(message.starts_with("feat(heuristic): update heuristic.rs and README.md\n"));
+    }
+
+    #[test]
+    fn test_type_from_paths() {
+        let docs = file_diff("docs/guide.md", "", "+text\n") + &file_diff("README.md", "", "+x\n");
+        assert!(generate_message(&docs, |_| false, &LintRules::default())
+            .starts_with("docs: update guide.md and README.md"));
+
+        let ci = file_diff(
+            ".github/workflows/ci.yml",
+            "deleted file mode 100644\n",
+            "-x\n",
+        );
+        assert!(generate_message(&ci, |_| false, &LintRules::default())
+            .starts_with("ci: remove ci.yml"));
+
+        // Types outside the allowlist fall back to chore
+        let rules = LintRules {
+            types: vec!["feat".to_string(), "chore".to_string()],
+            ..LintRules::default()
+        };
+        assert!(generate_message(&docs, |_| false, &rules).starts_with("chore(docs): "));
+    }
+
+    #[test]
+    fn test_long_subjects_are_shortened() {
+        let diff: String = (0..4)
+            .map(|i| {
+                file_diff(
+                    &format!("a_rather_long_module_name_number_{i}.rs"),
+                    "",
+                    "+x\n",
+                )
+            })
+            .collect();
+        let rules = LintRules::default();
+        let header = generate_message(&diff, |_| false, &rules)
+            .lines()
+            .next()
+            .unwrap()
+            .to_string();
+        assert_eq!(header, "chore: update 4 files");
+    }
+}
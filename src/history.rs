@@ -0,0 +1,213 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const HISTORY_FILENAME: &str = "history.jsonl";
+
+/// Generations kept when the history is rewritten
+const MAX_ENTRIES: usize = 1000;
+
+/// A generated commit message, one line of `history.jsonl`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Short random id used by `aic history reuse`
+    pub id: String,
+
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+
+    /// Root of the repository the message was generated in, empty outside one
+    pub repo: String,
+
+    /// Git object id of the diff the message describes
+    pub diff_hash: String,
+
+    /// Model that wrote the message, `heuristic` when it was guessed offline
+    pub model: String,
+
+    pub message: String,
+
+    #[serde(default)]
+    pub committed: bool,
+}
+
+impl Entry {
+    pub fn new(repo: &str, diff_hash: &str, model: &str, message: &str) -> Self {
+        let id = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+
+        Self {
+            id,
+            timestamp,
+            repo: repo.to_string(),
+            diff_hash: diff_hash.to_string(),
+            model: model.to_string(),
+            message: message.to_string(),
+            committed: false,
+        }
+    }
+
+    /// First line of the message
+    pub fn subject(&self) -> &str {
+        self.message.lines().next().unwrap_or_default()
+    }
+}
+
+/// Directory for aic's data, `~/.local/share/aic` on Linux and macOS
+pub fn data_dir() -> Result<PathBuf> {
+    let home_dir = dirs::home_dir().context("Could not find home directory")?;
+    let data_dir = if cfg!(target_os = "windows") {
+        home_dir
+            .join("AppData")
+            .join("Local")
+            .join("aic")
+            .join("data")
+    } else {
+        home_dir.join(".local").join("share").join("aic")
+    };
+    if !data_dir.exists() {
+        fs::create_dir_all(&data_dir).context("Failed to create data directory")?;
+    }
+
+    Ok(data_dir)
+}
+
+pub fn history_path() -> Result<PathBuf> {
+    Ok(data_dir()?.join(HISTORY_FILENAME))
+}
+
+/// All entries, oldest first, skipping lines that can't be parsed
+pub fn load_from(path: &Path) -> Result<Vec<Entry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let contents = fs::read_to_string(path).context("Could not read the history")?;
+    Ok(contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Append an entry to the history
+pub fn append_to(path: &Path, entry: &Entry) -> Result<()> {
+    let line = serde_json::to_string(entry).context("Failed to serialize history entry")?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .context("Could not open the history")?;
+    writeln!(file, "{line}").context("Failed to write to the history")
+}
+
+/// Mark an entry as committed, keeping the newest `MAX_ENTRIES` entries
+pub fn mark_committed_in(path: &Path, id: &str) -> Result<()> {
+    let mut entries = load_from(path)?;
+    for entry in entries.iter_mut().filter(|entry| entry.id == id) {
+        entry.committed = true;
+    }
+
+    let skip = entries.len().saturating_sub(MAX_ENTRIES);
+    let mut contents = String::new();
+    for entry in &entries[skip..] {
+        contents.push_str(&serde_json::to_string(entry)?);
+        contents.push('\n');
+    }
+    fs::write(path, contents).context("Failed to write the history")
+}
+
+/// Entries matching `query` in their message, newest first
+///
+/// With `repo`, only entries generated in that repository are returned.
+pub fn search(entries: &[Entry], query: Option<&str>, repo: Option<&str>) -> Vec<Entry> {
+    let query = query.map(str::to_lowercase);
+    entries
+        .iter()
+        .rev()
+        .filter(|entry| repo.is_none_or(|repo| entry.repo == repo))
+        .filter(|entry| {
+            query
+                .as_ref()
+                .is_none_or(|query| entry.message.to_lowercase().contains(query))
+        })
+        .cloned()
+        .collect()
+}
+
+/// The entry whose id starts with `id`, which must be unambiguous
+pub fn find<'a>(entries: &'a [Entry], id: &str) -> Result<&'a Entry> {
+    let matches: Vec<&Entry> = entries
+        .iter()
+        .filter(|entry| !id.is_empty() && entry.id.starts_with(id))
+        .collect();
+    match matches.as_slice() {
+        [entry] => Ok(entry),
+        [] => anyhow::bail!("No generated message with id '{}' in the history", id),
+        _ => anyhow::bail!(
+            "The id '{}' matches several messages, use more characters",
+            id
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entry(id: &str, repo: &str, message: &str) -> Entry {
+        Entry {
+            id: id.to_string(),
+            timestamp: 1_700_000_000,
+            repo: repo.to_string(),
+            diff_hash: "abc".to_string(),
+            model: "gpt-4o".to_string(),
+            message: message.to_string(),
+            committed: false,
+        }
+    }
+
+    #[test]
+    fn test_append_and_mark_committed() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join(HISTORY_FILENAME);
+        assert!(load_from(&path).unwrap().is_empty());
+
+        append_to(&path, &entry("aaaa1111", "/repo", "feat: add login")).unwrap();
+        append_to(&path, &entry("bbbb2222", "/repo", "fix: typo")).unwrap();
+        // Broken lines don't hide the others
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        writeln!(file, "not json").unwrap();
+
+        mark_committed_in(&path, "bbbb2222").unwrap();
+        let entries = load_from(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(!entries[0].committed);
+        assert!(entries[1].committed);
+    }
+
+    #[test]
+    fn test_search_and_find() {
+        let entries = vec![
+            entry("aaaa1111", "/repo", "feat: add login\n\nWith a body"),
+            entry("aaab2222", "/other", "fix: login redirect"),
+            entry("cccc3333", "/repo", "docs: update README"),
+        ];
+
+        let found = search(&entries, Some("LOGIN"), None);
+        let ids: Vec<&str> = found.iter().map(|entry| entry.id.as_str()).collect();
+        assert_eq!(ids, vec!["aaab2222", "aaaa1111"]);
+        assert_eq!(search(&entries, None, Some("/repo")).len(), 2);
+        assert_eq!(found[1].subject(), "feat: add login");
+
+        assert_eq!(find(&entries, "cc").unwrap().id, "cccc3333");
+        assert!(find(&entries, "aaa").is_err());
+        assert!(find(&entries, "zz").is_err());
+        assert!(find(&entries, "").is_err());
+    }
+}
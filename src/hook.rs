@@ -0,0 +1,122 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the hook git runs before opening the commit message editor
+pub const HOOK_NAME: &str = "prepare-commit-msg";
+
+/// Marks hooks written by aic, so they can be replaced or removed safely
+const HOOK_MARKER: &str = "# Installed by aic";
+
+/// The hook never fails the commit, a missing or failing aic leaves the message alone
+const HOOK_SCRIPT: &str = "#!/bin/sh\n\
+    # Installed by aic: generate the commit message from the staged diff\n\
+    command -v aic >/dev/null 2>&1 || exit 0\n\
+    aic hook run \"$@\" || true\n";
+
+/// Whether the hook at `path` was written by aic
+pub fn is_aic_hook(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|contents| contents.contains(HOOK_MARKER))
+}
+
+/// Write the hook into `hooks_dir`, replacing another hook only with `force`
+pub fn install(hooks_dir: &Path, force: bool) -> Result<PathBuf> {
+    let path = hooks_dir.join(HOOK_NAME);
+    if path.exists() && !is_aic_hook(&path) && !force {
+        anyhow::bail!(
+            "{} already exists and was not installed by aic. Pass --force to replace it",
+            path.display()
+        );
+    }
+
+    fs::create_dir_all(hooks_dir).context("Failed to create the hooks directory")?;
+    fs::write(&path, HOOK_SCRIPT).context(format!("Failed to write {}", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))
+            .context(format!("Failed to make {} executable", path.display()))?;
+    }
+
+    Ok(path)
+}
+
+/// Remove the hook from `hooks_dir` if aic installed it, returns whether it did
+pub fn uninstall(hooks_dir: &Path) -> Result<bool> {
+    let path = hooks_dir.join(HOOK_NAME);
+    if !is_aic_hook(&path) {
+        return Ok(false);
+    }
+
+    fs::remove_file(&path).context(format!("Failed to remove {}", path.display()))?;
+    Ok(true)
+}
+
+/// Whether to generate a message for a commit, given the hook's `source` argument
+///
+/// Messages given with `-m`/`-F`, merges, squashes and amends (`commit`) already
+/// have a message the user chose. Plain `git commit` has no source, and a
+/// configured `commit.template` is `template`.
+pub fn should_generate(source: Option<&str>) -> bool {
+    matches!(source, None | Some("") | Some("template"))
+}
+
+/// Put the generated message above what git prepared, keeping its comments
+/// and any template text for the editor
+pub fn prepend_message(existing: &str, message: &str) -> String {
+    format!("{}\n\n{existing}", message.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_install_and_uninstall() {
+        let temp_dir = TempDir::new().unwrap();
+        let hooks_dir = temp_dir.path().join("hooks");
+
+        let path = install(&hooks_dir, false).unwrap();
+        assert!(is_aic_hook(&path));
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .contains("aic hook run \"$@\""));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(
+                fs::metadata(&path).unwrap().permissions().mode() & 0o111,
+                0o111
+            );
+        }
+
+        // Reinstalling replaces our own hook, someone else's needs --force
+        install(&hooks_dir, false).unwrap();
+        fs::write(&path, "#!/bin/sh\necho custom\n").unwrap();
+        assert!(install(&hooks_dir, false).is_err());
+        assert!(!uninstall(&hooks_dir).unwrap());
+        assert!(path.exists());
+
+        install(&hooks_dir, true).unwrap();
+        assert!(uninstall(&hooks_dir).unwrap());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_should_generate() {
+        assert!(should_generate(None));
+        assert!(should_generate(Some("template")));
+        for source in ["message", "merge", "squash", "commit"] {
+            assert!(!should_generate(Some(source)));
+        }
+    }
+
+    #[test]
+    fn test_prepend_message() {
+        assert_eq!(
+            prepend_message("# Please enter the commit message\n", "feat: add hook\n"),
+            "feat: add hook\n\n# Please enter the commit message\n"
+        );
+    }
+}
@@ -0,0 +1,265 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Another commit message tool whose configuration can be imported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportSource {
+    /// opencommit, `~/.opencommit`
+    OpenCommit,
+
+    /// aicommits, `~/.aicommits`
+    AiCommits,
+
+    /// cz-git's AI options, `.czrc` in the project, home or `~/.config` directory
+    Cz,
+}
+
+impl FromStr for ImportSource {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "opencommit" | "oco" => Ok(Self::OpenCommit),
+            "aicommits" => Ok(Self::AiCommits),
+            "cz" | "cz-git" | "czg" => Ok(Self::Cz),
+            _ => Err(anyhow::anyhow!(
+                "Unknown tool '{}': expected 'opencommit', 'aicommits' or 'cz'",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ImportSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OpenCommit => write!(f, "opencommit"),
+            Self::AiCommits => write!(f, "aicommits"),
+            Self::Cz => write!(f, "cz"),
+        }
+    }
+}
+
+/// Settings translated from another tool, ready to apply with `Config::set`
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ImportPlan {
+    /// aic key and value pairs
+    pub settings: Vec<(&'static str, String)>,
+
+    /// Keys of the other tool that have no aic equivalent
+    pub skipped: Vec<String>,
+}
+
+impl ImportSource {
+    /// Candidate config files, most specific first
+    fn config_paths(&self) -> Result<Vec<PathBuf>> {
+        let home_dir = dirs::home_dir().context("Could not find home directory")?;
+        Ok(match self {
+            Self::OpenCommit => vec![home_dir.join(".opencommit")],
+            Self::AiCommits => vec![home_dir.join(".aicommits")],
+            Self::Cz => {
+                let current_dir = env::current_dir().context("Failed to get current directory")?;
+                vec![
+                    current_dir.join(".czrc"),
+                    home_dir.join(".czrc"),
+                    home_dir.join(".config").join(".czrc"),
+                ]
+            }
+        })
+    }
+
+    /// Environment variables the tool reads its API token from
+    fn token_env_vars(&self) -> &'static [&'static str] {
+        match self {
+            Self::OpenCommit => &["OCO_API_KEY", "OCO_OPENAI_API_KEY"],
+            Self::AiCommits => &["OPENAI_KEY"],
+            Self::Cz => &[],
+        }
+    }
+
+    /// Map a key of the tool's config to the aic key and value
+    fn translate_key(&self, key: &str, value: &str) -> Option<(&'static str, String)> {
+        let value = value.to_string();
+        match (self, key) {
+            (Self::OpenCommit, "OCO_API_KEY" | "OCO_OPENAI_API_KEY") => Some(("api_token", value)),
+            (Self::OpenCommit, "OCO_API_URL" | "OCO_OPENAI_BASE_PATH") => {
+                Some(("api_base_url", value))
+            }
+            (Self::OpenCommit, "OCO_MODEL") => Some(("model", value)),
+            (Self::OpenCommit, "OCO_TOKENS_MAX_INPUT") => Some(("max_input_tokens", value)),
+            (Self::AiCommits, "OPENAI_KEY") => Some(("api_token", value)),
+            (Self::AiCommits, "model") => Some(("model", value)),
+            // aicommits takes milliseconds, rounded up so short timeouts don't become zero
+            (Self::AiCommits, "timeout") => value
+                .parse::<u64>()
+                .ok()
+                .map(|ms| ("request_timeout_secs", ms.div_ceil(1000).max(1).to_string())),
+            (Self::Cz, "openAIToken") => Some(("api_token", value)),
+            (Self::Cz, "apiEndpoint") => Some(("api_base_url", value)),
+            (Self::Cz, "aiModel") => Some(("model", value)),
+            _ => None,
+        }
+    }
+
+    /// Translate the contents of the tool's config file
+    pub fn translate(&self, contents: &str) -> Result<ImportPlan> {
+        let entries = match self {
+            Self::OpenCommit | Self::AiCommits => parse_ini(contents),
+            Self::Cz => parse_czrc(contents)?,
+        };
+
+        let mut plan = ImportPlan::default();
+        for (key, value) in entries {
+            if value.is_empty() || value == "undefined" {
+                continue;
+            }
+            match self.translate_key(&key, &value) {
+                Some((aic_key, value)) => {
+                    // Several keys can map to one setting, the first one wins
+                    if !plan
+                        .settings
+                        .iter()
+                        .any(|(existing, _)| *existing == aic_key)
+                    {
+                        plan.settings.push((aic_key, value));
+                    }
+                }
+                None => plan.skipped.push(key),
+            }
+        }
+
+        Ok(plan)
+    }
+
+    /// Read the tool's config and translate it, falling back to its token
+    /// environment variables when the file holds no token
+    pub fn load(&self) -> Result<(Option<PathBuf>, ImportPlan)> {
+        let path = self.config_paths()?.into_iter().find(|path| path.exists());
+
+        let mut plan = match &path {
+            Some(path) => {
+                let contents = fs::read_to_string(path)
+                    .context(format!("Failed to read {}", path.display()))?;
+                self.translate(&contents)?
+            }
+            None => ImportPlan::default(),
+        };
+
+        if !plan.settings.iter().any(|(key, _)| *key == "api_token") {
+            let token = self
+                .token_env_vars()
+                .iter()
+                .find_map(|name| env::var(name).ok().filter(|token| !token.is_empty()));
+            if let Some(token) = token {
+                plan.settings.insert(0, ("api_token", token));
+            }
+        }
+
+        Ok((path, plan))
+    }
+}
+
+// Parse `KEY=value` lines as written by opencommit and aicommits
+fn parse_ini(contents: &str) -> BTreeMap<String, String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with(['#', ';', '[']))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| {
+            let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+            (key.trim().to_string(), value.to_string())
+        })
+        .collect()
+}
+
+// Parse the top-level string options of a JSON `.czrc`
+fn parse_czrc(contents: &str) -> Result<BTreeMap<String, String>> {
+    let json: serde_json::Value =
+        serde_json::from_str(contents).context("Failed to parse .czrc")?;
+    let object = json.as_object().context(".czrc is not a JSON object")?;
+
+    Ok(object
+        .iter()
+        .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_source() {
+        assert_eq!(
+            "OpenCommit".parse::<ImportSource>().unwrap(),
+            ImportSource::OpenCommit
+        );
+        assert_eq!("cz-git".parse::<ImportSource>().unwrap(), ImportSource::Cz);
+        assert!("gitmoji".parse::<ImportSource>().is_err());
+    }
+
+    #[test]
+    fn test_translate_opencommit() {
+        let contents = "OCO_API_KEY=sk-123\n\
+                        OCO_OPENAI_API_KEY=sk-old\n\
+                        OCO_MODEL=gpt-4o-mini\n\
+                        OCO_API_URL=undefined\n\
+                        OCO_TOKENS_MAX_INPUT=40960\n\
+                        OCO_LANGUAGE=en\n";
+        let plan = ImportSource::OpenCommit.translate(contents).unwrap();
+
+        assert_eq!(
+            plan.settings,
+            vec![
+                ("api_token", "sk-123".to_string()),
+                ("model", "gpt-4o-mini".to_string()),
+                ("max_input_tokens", "40960".to_string()),
+            ]
+        );
+        assert_eq!(plan.skipped, vec!["OCO_LANGUAGE"]);
+    }
+
+    #[test]
+    fn test_translate_aicommits() {
+        let contents = "OPENAI_KEY=\"sk-abc\"\nmodel=gpt-4\ntimeout=10500\nlocale=en\n";
+        let plan = ImportSource::AiCommits.translate(contents).unwrap();
+
+        assert_eq!(
+            plan.settings,
+            vec![
+                ("api_token", "sk-abc".to_string()),
+                ("model", "gpt-4".to_string()),
+                ("request_timeout_secs", "11".to_string()),
+            ]
+        );
+        assert_eq!(plan.skipped, vec!["locale"]);
+    }
+
+    #[test]
+    fn test_translate_cz() {
+        let contents = r#"{
+            "openAIToken": "sk-cz",
+            "apiEndpoint": "https://gateway.example.com/v1",
+            "aiModel": "gpt-4o",
+            "useEmoji": true,
+            "types": [{ "value": "feat" }]
+        }"#;
+        let plan = ImportSource::Cz.translate(contents).unwrap();
+
+        assert_eq!(
+            plan.settings,
+            vec![
+                ("model", "gpt-4o".to_string()),
+                ("api_base_url", "https://gateway.example.com/v1".to_string()),
+                ("api_token", "sk-cz".to_string()),
+            ]
+        );
+        assert!(ImportSource::Cz.translate("not json").is_err());
+    }
+}
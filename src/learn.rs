@@ -0,0 +1,136 @@
+use anyhow::{Context, Result};
+
+/// Commits `aic learn` needs before a style guide is worth deriving
+pub const MIN_COMMITS: usize = 5;
+
+/// Starts the style guide section `aic learn` appends to the system prompt
+pub const STYLE_GUIDE_HEADING: &str = "Project commit style (learned from the history):";
+
+/// The system prompt with `guide` as its style guide section
+///
+/// A section from an earlier run is replaced, so learning again doesn't
+/// stack guides.
+pub fn augment_prompt(system_prompt: &str, guide: &str) -> String {
+    let base = system_prompt
+        .split(STYLE_GUIDE_HEADING)
+        .next()
+        .unwrap_or_default()
+        .trim_end();
+    format!("{base}\n\n{STYLE_GUIDE_HEADING}\n{}", guide.trim())
+}
+
+/// Set the top-level string `key` in the TOML `contents`, keeping comments and
+/// everything else as written
+///
+/// An existing value is replaced where it is, a new one goes before the first
+/// table. The value is written as a multi-line literal string when possible.
+pub fn set_toml_string(contents: &str, key: &str, value: &str) -> Result<String> {
+    let entry = match value.contains("'''") {
+        false => format!("{key} = '''\n{value}'''\n"),
+        true => format!("{key} = {}\n", toml::Value::from(value)),
+    };
+
+    let lines: Vec<&str> = contents.split_inclusive('\n').collect();
+    let mut existing = None;
+    let mut first_table = None;
+    let mut index = 0;
+    while index < lines.len() {
+        let line = lines[index].trim();
+        if line.starts_with('[') {
+            first_table = Some(index);
+            break;
+        }
+
+        // Skip to the end of multi-line strings, whose lines may look like anything
+        let start = index;
+        if let Some((name, rest)) = line.split_once('=') {
+            let rest = rest.trim_start();
+            if let Some(delimiter) = ["\"\"\"", "'''"]
+                .into_iter()
+                .find(|delimiter| rest.starts_with(delimiter))
+            {
+                if !rest[3..].contains(delimiter) {
+                    index += 1;
+                    while index < lines.len() && !lines[index].contains(delimiter) {
+                        index += 1;
+                    }
+                }
+            }
+            if name.trim() == key {
+                existing = Some(start..index + 1);
+            }
+        }
+        index += 1;
+    }
+
+    let updated = match (existing, first_table) {
+        (Some(range), _) => {
+            let end = range.end.min(lines.len());
+            let tail = lines[end..].concat();
+            format!("{}{entry}{tail}", lines[..range.start].concat())
+        }
+        (None, Some(table)) => format!(
+            "{}{entry}\n{}",
+            lines[..table].concat(),
+            lines[table..].concat()
+        ),
+        (None, None) if contents.is_empty() || contents.ends_with('\n') => {
+            format!("{contents}{entry}")
+        }
+        (None, None) => format!("{contents}\n{entry}"),
+    };
+
+    // Only write what reads back as the intended value
+    let table: toml::Table = toml::from_str(&updated).context("Failed to update the TOML file")?;
+    if table.get(key).and_then(|value| value.as_str()) != Some(value) {
+        anyhow::bail!("Failed to update {} in the TOML file", key);
+    }
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_augment_prompt() {
+        let prompt = augment_prompt("Write commits.\n", "- Use scopes\n");
+        assert_eq!(
+            prompt,
+            format!("Write commits.\n\n{STYLE_GUIDE_HEADING}\n- Use scopes")
+        );
+        assert_eq!(
+            augment_prompt(&prompt, "- No emoji"),
+            format!("Write commits.\n\n{STYLE_GUIDE_HEADING}\n- No emoji")
+        );
+    }
+
+    #[test]
+    fn test_set_toml_string() {
+        // New keys go before the first table, comments stay
+        let contents = "# aic settings\nmodel = \"gpt-4o\"\n\n[profiles.work]\nmodel = \"x\"\n";
+        let updated = set_toml_string(contents, "system_prompt", "Be brief.\n- Scopes").unwrap();
+        assert_eq!(
+            updated,
+            "# aic settings\nmodel = \"gpt-4o\"\n\nsystem_prompt = '''\nBe brief.\n- Scopes'''\n\
+             \n[profiles.work]\nmodel = \"x\"\n"
+        );
+
+        // Existing values are replaced in place, multi-line ones included
+        let contents = "system_prompt = \"\"\"\nOld\n[not a table]\n\"\"\"\nmodel = \"m\"\n";
+        assert_eq!(
+            set_toml_string(contents, "system_prompt", "New").unwrap(),
+            "system_prompt = '''\nNew'''\nmodel = \"m\"\n"
+        );
+        assert_eq!(
+            set_toml_string("system_prompt = 'Old'", "system_prompt", "a ''' b").unwrap(),
+            "system_prompt = \"a ''' b\"\n"
+        );
+        assert_eq!(
+            set_toml_string("", "system_prompt", "New").unwrap(),
+            "system_prompt = '''\nNew'''\n"
+        );
+
+        assert!(set_toml_string("model = ", "system_prompt", "New").is_err());
+    }
+}
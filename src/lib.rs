@@ -0,0 +1,92 @@
+//! Commit message generation behind the `aic` command line tool
+//!
+//! The library loads the same configuration files as the CLI, reads changes
+//! from a git repository and asks an OpenAI-compatible API for a message, so
+//! TUIs, editor plugins and other tools can generate messages the way `aic`
+//! does:
+//!
+//! ```no_run
+//! use aic_core::{format, Config, GitRepo, LlmClient};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let config = Config::load()?;
+//! let repo = GitRepo::open(".")?;
+//! let diff = repo.staged_diff(&config.get_exclude_patterns())?;
+//!
+//! let client = LlmClient::new(
+//!     &config.get_api_token()?,
+//!     config.get_api_base_url(),
+//!     config.get_model(),
+//! );
+//! let messages = client
+//!     .generate_commit_messages(&diff, config.get_system_prompt(), config.get_user_prompt(), 1)
+//!     .await?;
+//! repo.commit(&messages[0], &config.get_commit_args())?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Errors are [`anyhow::Error`]s carrying the context of what failed.
+//! Interrupted requests fail with [`commands::Cancelled`].
+
+pub mod branch;
+pub mod breaking;
+pub mod bump;
+pub mod cache;
+pub mod capabilities;
+pub mod changelog;
+pub mod commitlint;
+pub mod config;
+pub mod context;
+pub mod format;
+pub mod generated;
+pub mod git;
+pub mod glob;
+pub mod heuristic;
+pub mod history;
+pub mod learn;
+pub mod lint;
+pub mod llm;
+pub mod logging;
+pub mod memory;
+pub mod priority;
+pub mod redact;
+pub mod scope;
+pub mod search;
+pub mod secrets;
+pub mod spell;
+pub mod split;
+pub mod stats;
+pub mod symbols;
+pub mod template;
+pub mod ticket;
+pub mod tracker;
+pub mod usage;
+
+// The command line interface, public for the `aic` binary rather than for embedding
+#[doc(hidden)]
+pub mod cli;
+#[doc(hidden)]
+pub mod commands;
+#[doc(hidden)]
+pub mod doctor;
+#[doc(hidden)]
+pub mod experiment;
+#[doc(hidden)]
+pub mod hook;
+#[doc(hidden)]
+pub mod import;
+#[doc(hidden)]
+pub mod lock;
+#[doc(hidden)]
+pub mod scheduler;
+#[doc(hidden)]
+pub mod serve;
+#[doc(hidden)]
+pub mod ui;
+#[doc(hidden)]
+pub mod validate;
+
+pub use config::Config;
+pub use git::GitRepo;
+pub use llm::LlmClient;
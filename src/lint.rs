@@ -0,0 +1,592 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Commit types accepted by default, as listed in the Conventional Commits spec
+pub const DEFAULT_COMMIT_TYPES: &[&str] = &[
+    "feat", "fix", "docs", "style", "refactor", "perf", "test", "build", "ci", "chore", "revert",
+];
+
+/// Maximum header length accepted by default
+pub const DEFAULT_MAX_HEADER_LENGTH: usize = 72;
+
+/// Verbs recognized in third-person form (`adds`, `fixes`) by the imperative mood check
+const COMMON_VERBS: &[&str] = &[
+    "add",
+    "adjust",
+    "allow",
+    "avoid",
+    "bump",
+    "change",
+    "clean",
+    "create",
+    "delete",
+    "disable",
+    "document",
+    "drop",
+    "enable",
+    "ensure",
+    "extract",
+    "fix",
+    "handle",
+    "implement",
+    "improve",
+    "introduce",
+    "make",
+    "merge",
+    "move",
+    "prevent",
+    "refactor",
+    "remove",
+    "rename",
+    "replace",
+    "revert",
+    "set",
+    "simplify",
+    "support",
+    "update",
+    "upgrade",
+    "use",
+];
+
+/// Words ending in `-ed` or `-ing` that are already imperative
+const IMPERATIVE_EXCEPTIONS: &[&str] = &[
+    "bleed", "bring", "embed", "exceed", "feed", "need", "ping", "proceed", "seed", "shed",
+    "shred", "speed", "spring", "string", "succeed", "swing",
+];
+
+/// Rules a commit message is checked against
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintRules {
+    pub types: Vec<String>,
+
+    /// Allowed scopes, any scope when empty
+    pub scopes: Vec<String>,
+
+    pub max_header_length: usize,
+
+    /// Body templates by commit type, whose sections the body must contain
+    pub templates: BTreeMap<String, String>,
+}
+
+impl Default for LintRules {
+    fn default() -> Self {
+        Self {
+            types: DEFAULT_COMMIT_TYPES
+                .iter()
+                .map(|commit_type| commit_type.to_string())
+                .collect(),
+            scopes: Vec::new(),
+            max_header_length: DEFAULT_MAX_HEADER_LENGTH,
+            templates: BTreeMap::new(),
+        }
+    }
+}
+
+/// A single rule violation, named after the equivalent commitlint rule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Violation {
+    pub rule: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} [{}]", self.message, self.rule)
+    }
+}
+
+/// The parts of a conventional commit header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header<'a> {
+    pub commit_type: &'a str,
+    pub scope: Option<&'a str>,
+    pub breaking: bool,
+    pub subject: &'a str,
+}
+
+/// Parse `type(scope)!: subject`, returning `None` if the header doesn't follow the format
+pub fn parse_header(header: &str) -> Option<Header<'_>> {
+    let (prefix, subject) = header.split_once(": ")?;
+    let (prefix, breaking) = match prefix.strip_suffix('!') {
+        Some(prefix) => (prefix, true),
+        None => (prefix, false),
+    };
+    let (commit_type, scope) = match prefix.split_once('(') {
+        Some((commit_type, rest)) => (commit_type, Some(rest.strip_suffix(')')?)),
+        None => (prefix, None),
+    };
+
+    let valid_type = !commit_type.is_empty()
+        && commit_type
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-');
+    let valid_scope = scope.is_none_or(|scope| {
+        !scope.is_empty() && !scope.contains(['(', ')']) && !scope.trim().is_empty()
+    });
+    if !valid_type || !valid_scope {
+        return None;
+    }
+
+    Some(Header {
+        commit_type,
+        scope,
+        breaking,
+        subject: subject.trim(),
+    })
+}
+
+/// Check a commit message against the rules
+pub fn lint_message(message: &str, rules: &LintRules) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let mut lines = message.trim().lines();
+    let header = lines.next().unwrap_or_default().trim_end();
+
+    if header.is_empty() {
+        violations.push(Violation {
+            rule: "header-empty",
+            message: "The commit message is empty".to_string(),
+        });
+        return violations;
+    }
+
+    match parse_header(header) {
+        Some(parsed) => {
+            if !rules
+                .types
+                .iter()
+                .any(|allowed| allowed == parsed.commit_type)
+            {
+                violations.push(Violation {
+                    rule: "type-enum",
+                    message: format!(
+                        "Type '{}' is not one of: {}",
+                        parsed.commit_type,
+                        rules.types.join(", ")
+                    ),
+                });
+            }
+            if let Some(scope) = parsed.scope {
+                if !rules.scopes.is_empty() && !rules.scopes.iter().any(|allowed| allowed == scope)
+                {
+                    violations.push(Violation {
+                        rule: "scope-enum",
+                        message: format!(
+                            "Scope '{}' is not one of: {}",
+                            scope,
+                            rules.scopes.join(", ")
+                        ),
+                    });
+                }
+            }
+            if parsed.subject.is_empty() {
+                violations.push(Violation {
+                    rule: "subject-empty",
+                    message: "The subject is empty".to_string(),
+                });
+            }
+            if !is_imperative(parsed.subject) {
+                violations.push(Violation {
+                    rule: "subject-imperative",
+                    message:
+                        "The subject should use the imperative mood ('add', not 'added' or 'adds')"
+                            .to_string(),
+                });
+            }
+            if parsed.subject.ends_with('.') {
+                violations.push(Violation {
+                    rule: "subject-full-stop",
+                    message: "The subject must not end with a period".to_string(),
+                });
+            }
+            if let Some(template) = rules.templates.get(parsed.commit_type) {
+                let body: Vec<String> = message
+                    .trim()
+                    .lines()
+                    .skip(1)
+                    .map(|line| line.trim_start().to_lowercase())
+                    .collect();
+                for section in template_sections(template) {
+                    let label = section.to_lowercase();
+                    if !body.iter().any(|line| line.starts_with(&label)) {
+                        violations.push(Violation {
+                            rule: "body-template",
+                            message: format!(
+                                "The body of a '{}' commit must have a '{}' section",
+                                parsed.commit_type, section
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+        None => violations.push(Violation {
+            rule: "header-format",
+            message: "The header must look like 'type(scope): subject'".to_string(),
+        }),
+    }
+
+    let header_length = header.chars().count();
+    if header_length > rules.max_header_length {
+        violations.push(Violation {
+            rule: "header-max-length",
+            message: format!(
+                "The header is {} characters long, the maximum is {}",
+                header_length, rules.max_header_length
+            ),
+        });
+    }
+
+    if lines.next().is_some_and(|line| !line.trim().is_empty()) {
+        violations.push(Violation {
+            rule: "body-leading-blank",
+            message: "The body must be separated from the header by a blank line".to_string(),
+        });
+    }
+
+    violations
+}
+
+/// Section labels of a body template, the `Label:` starting each of its lines
+///
+/// `"Root cause: <what went wrong>\nFix: <how>"` has the sections
+/// `Root cause:` and `Fix:`, lines without a label are free text.
+pub fn template_sections(template: &str) -> Vec<&str> {
+    template
+        .lines()
+        .map(str::trim)
+        .filter_map(|line| {
+            let end = line.find(':')?;
+            let label = line[..end].trim();
+            let is_label = !label.is_empty()
+                && line[end + 1..]
+                    .chars()
+                    .next()
+                    .is_none_or(char::is_whitespace)
+                && label
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'));
+            is_label.then(|| &line[..=end])
+        })
+        .collect()
+}
+
+/// Whether a line is a git trailer like `Refs: ABC-1`, `Closes #12` or `BREAKING CHANGE: ...`
+pub fn is_footer_line(line: &str) -> bool {
+    if line.starts_with("BREAKING CHANGE: ") {
+        return true;
+    }
+    let Some(separator) = line.find(": ").or_else(|| line.find(" #")) else {
+        return false;
+    };
+    let token = &line[..separator];
+    !token.is_empty() && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// Split a `Key: value` trailer into its key and value
+pub fn parse_trailer(trailer: &str) -> Option<(&str, &str)> {
+    let trailer = trailer.trim();
+    if !is_footer_line(trailer) {
+        return None;
+    }
+    let (key, value) = trailer.split_once(": ")?;
+    let value = value.trim();
+    (!value.is_empty()).then_some((key, value))
+}
+
+/// Append trailers to a message, skipping those it already has
+pub fn append_trailers(message: &str, trailers: &[String]) -> String {
+    let mut message = message.trim_end().to_string();
+    for trailer in trailers {
+        let trailer = trailer.trim();
+        if trailer.is_empty() || message.lines().any(|line| line.trim() == trailer) {
+            continue;
+        }
+        message = append_footer(&message, trailer);
+    }
+    message
+}
+
+/// Append a footer to a message, joining an existing footer block at the end
+pub fn append_footer(message: &str, footer: &str) -> String {
+    let message = message.trim_end();
+    let last_paragraph = message.rsplit("\n\n").next().unwrap_or_default();
+    let has_footers = message.contains("\n\n") && last_paragraph.lines().all(is_footer_line);
+
+    if has_footers {
+        format!("{message}\n{footer}")
+    } else {
+        format!("{message}\n\n{footer}")
+    }
+}
+
+/// Heuristic check that a subject starts with an imperative verb
+///
+/// Flags past tense (`added`), gerunds (`adding`) and the third person of
+/// common verbs (`adds`, `fixes`, `simplifies`).
+pub fn is_imperative(subject: &str) -> bool {
+    let Some(first_word) = subject.split_whitespace().next() else {
+        return true;
+    };
+    let word = first_word.to_lowercase();
+    if IMPERATIVE_EXCEPTIONS.contains(&word.as_str()) {
+        return true;
+    }
+    if word.len() > 4 && (word.ends_with("ed") || word.ends_with("ing")) {
+        return false;
+    }
+
+    let third_person_base = [
+        word.strip_suffix("ies").map(|stem| format!("{stem}y")),
+        word.strip_suffix("es").map(str::to_string),
+        word.strip_suffix('s').map(str::to_string),
+    ];
+    !third_person_base
+        .into_iter()
+        .flatten()
+        .any(|base| COMMON_VERBS.contains(&base.as_str()))
+}
+
+/// What to do when the final message breaks the commit rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommitValidation {
+    /// Don't check the message
+    Off,
+
+    /// Show the violations and commit anyway
+    Warn,
+
+    /// Refuse to commit until the message is fixed or the user overrides
+    Block,
+}
+
+impl FromStr for CommitValidation {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "warn" => Ok(Self::Warn),
+            "block" => Ok(Self::Block),
+            _ => Err(anyhow::anyhow!(
+                "Invalid value for commit_validation: expected 'off', 'warn' or 'block', got '{}'",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for CommitValidation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Off => write!(f, "off"),
+            Self::Warn => write!(f, "warn"),
+            Self::Block => write!(f, "block"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules() -> LintRules {
+        LintRules::default()
+    }
+
+    #[test]
+    fn test_parse_header() {
+        assert_eq!(
+            parse_header("feat(auth)!: add OAuth login"),
+            Some(Header {
+                commit_type: "feat",
+                scope: Some("auth"),
+                breaking: true,
+                subject: "add OAuth login",
+            })
+        );
+        assert_eq!(
+            parse_header("fix: handle empty diff").map(|h| (h.scope, h.breaking)),
+            Some((None, false))
+        );
+        assert_eq!(parse_header("Add OAuth login"), None);
+        assert_eq!(parse_header("feat(auth: add login"), None);
+        assert_eq!(parse_header("feat(): add login"), None);
+        assert_eq!(parse_header("feat add: login"), None);
+    }
+
+    #[test]
+    fn test_valid_messages() {
+        assert!(lint_message("fix(parser): correct string interpolation", &rules()).is_empty());
+        assert!(lint_message(
+            "feat(auth): implement OAuth2\n\n1. Add providers\n2. Store tokens\n",
+            &rules()
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_violations() {
+        let rule_names = |message: &str| -> Vec<&'static str> {
+            lint_message(message, &rules())
+                .into_iter()
+                .map(|violation| violation.rule)
+                .collect()
+        };
+
+        assert_eq!(rule_names(""), vec!["header-empty"]);
+        assert_eq!(rule_names("Updated stuff"), vec!["header-format"]);
+        assert_eq!(rule_names("feature: add login"), vec!["type-enum"]);
+        assert_eq!(rule_names("fix: handle errors."), vec!["subject-full-stop"]);
+        assert_eq!(
+            rule_names("fix: handled errors"),
+            vec!["subject-imperative"]
+        );
+        assert_eq!(
+            rule_names("fix: handle errors\nmore details"),
+            vec!["body-leading-blank"]
+        );
+        assert_eq!(
+            rule_names(&format!("fix: {}", "a".repeat(80))),
+            vec!["header-max-length"]
+        );
+    }
+
+    #[test]
+    fn test_is_imperative() {
+        assert!(is_imperative("add OAuth login"));
+        assert!(is_imperative("embed fonts in the PDF export"));
+        assert!(is_imperative("ping the server on startup"));
+        assert!(is_imperative("process queued jobs"));
+        assert!(is_imperative("update docs"));
+        assert!(is_imperative(""));
+
+        assert!(!is_imperative("added OAuth login"));
+        assert!(!is_imperative("Adding OAuth login"));
+        assert!(!is_imperative("fixes the parser"));
+        assert!(!is_imperative("simplifies retries"));
+        assert!(!is_imperative("removes dead code"));
+    }
+
+    #[test]
+    fn test_custom_types() {
+        let rules = LintRules {
+            types: vec!["wip".to_string()],
+            ..LintRules::default()
+        };
+        assert!(lint_message("wip: half done", &rules).is_empty());
+        assert_eq!(lint_message("feat: add login", &rules).len(), 1);
+    }
+
+    #[test]
+    fn test_scope_allowlist() {
+        let rules = LintRules {
+            scopes: vec!["core".to_string(), "cli".to_string()],
+            ..LintRules::default()
+        };
+        assert!(lint_message("fix(core): handle errors", &rules).is_empty());
+        // The scope stays optional
+        assert!(lint_message("fix: handle errors", &rules).is_empty());
+        assert_eq!(
+            lint_message("fix(parser): handle errors", &rules)[0].rule,
+            "scope-enum"
+        );
+    }
+
+    #[test]
+    fn test_template_sections() {
+        assert_eq!(
+            template_sections("Root cause: <what went wrong>\nFix:\n\n- details"),
+            vec!["Root cause:", "Fix:"]
+        );
+        assert!(template_sections("Explain the change").is_empty());
+        assert!(template_sections("See https://example.com").is_empty());
+    }
+
+    #[test]
+    fn test_body_template() {
+        let rules = LintRules {
+            templates: BTreeMap::from([(
+                "fix".to_string(),
+                "Root cause: <what went wrong>\nFix: <how>".to_string(),
+            )]),
+            ..LintRules::default()
+        };
+        assert!(lint_message(
+            "fix: handle empty diff\n\nRoot cause: the diff was unwrapped\nFix: check it first",
+            &rules
+        )
+        .is_empty());
+        // Types without a template may have any body
+        assert!(lint_message("feat: add login", &rules).is_empty());
+
+        let violations = lint_message("fix: handle empty diff\n\nFix: check it", &rules);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].rule, "body-template");
+        assert!(violations[0].message.contains("'Root cause:'"));
+    }
+
+    #[test]
+    fn test_parse_trailer() {
+        assert_eq!(
+            parse_trailer("Co-authored-by: Jane <jane@example.com>"),
+            Some(("Co-authored-by", "Jane <jane@example.com>"))
+        );
+        assert_eq!(
+            parse_trailer(" AI-assisted-by: aic "),
+            Some(("AI-assisted-by", "aic"))
+        );
+        assert_eq!(parse_trailer("Signed off by: Jane"), None);
+        assert_eq!(parse_trailer("Signed-off-by:"), None);
+    }
+
+    #[test]
+    fn test_append_trailers() {
+        let trailers = vec![
+            "Signed-off-by: Jane <jane@example.com>".to_string(),
+            "AI-assisted-by: aic".to_string(),
+        ];
+        assert_eq!(
+            append_trailers("feat: add login\n", &trailers),
+            "feat: add login\n\nSigned-off-by: Jane <jane@example.com>\nAI-assisted-by: aic"
+        );
+        // Trailers the message already has are not repeated
+        assert_eq!(
+            append_trailers("fix: typo\n\nRefs: ABC-1\nAI-assisted-by: aic", &trailers),
+            "fix: typo\n\nRefs: ABC-1\nAI-assisted-by: aic\nSigned-off-by: Jane <jane@example.com>"
+        );
+        assert_eq!(append_trailers("fix: typo", &[]), "fix: typo");
+    }
+
+    #[test]
+    fn test_append_footer() {
+        assert_eq!(
+            append_footer("feat: add login\n", "Refs: ABC-1"),
+            "feat: add login\n\nRefs: ABC-1"
+        );
+        assert_eq!(
+            append_footer("feat: add login\n\nUse OAuth: the new way", "Refs: ABC-1"),
+            "feat: add login\n\nUse OAuth: the new way\n\nRefs: ABC-1"
+        );
+        // Footers stay together in the last paragraph
+        assert_eq!(
+            append_footer(
+                "feat: drop v1\n\nBody\n\nBREAKING CHANGE: v1 is gone\nCloses #12",
+                "Refs: ABC-1"
+            ),
+            "feat: drop v1\n\nBody\n\nBREAKING CHANGE: v1 is gone\nCloses #12\nRefs: ABC-1"
+        );
+    }
+
+    #[test]
+    fn test_commit_validation_parse() {
+        assert_eq!(
+            "Block".parse::<CommitValidation>().unwrap(),
+            CommitValidation::Block
+        );
+        assert_eq!(CommitValidation::Warn.to_string(), "warn");
+        assert!("strict".parse::<CommitValidation>().is_err());
+    }
+}
@@ -1,169 +1,3085 @@
+use crate::capabilities::ModelCapabilities;
+use crate::format;
+use crate::git::FileDiff;
+use crate::priority::{self, DiffPriority};
 use anyhow::{Context, Result};
-use reqwest::Client;
+use colored::Colorize;
+use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer};
+use tiktoken_rs::{cl100k_base_singleton, o200k_base_singleton, CoreBPE, Rank};
+use tracing::Instrument;
 
-#[derive(Serialize, Deserialize)]
+/// Context window assumed for models missing from `KNOWN_CONTEXT_WINDOWS`
+const DEFAULT_CONTEXT_WINDOW: usize = 16_000;
+
+/// Input context windows of common models, matched by model name prefix
+/// (more specific prefixes first)
+const KNOWN_CONTEXT_WINDOWS: &[(&str, usize)] = &[
+    ("gpt-4o", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4.1", 1_000_000),
+    ("gpt-4-32k", 32_000),
+    ("gpt-4", 8_000),
+    ("gpt-3.5-turbo", 16_000),
+    ("o1", 128_000),
+    ("o3", 200_000),
+    ("o4", 200_000),
+    ("deepseek", 64_000),
+    ("claude", 200_000),
+];
+
+/// Typical completion length of a commit message, for cost estimates
+const EXPECTED_OUTPUT_TOKENS: usize = 200;
+
+/// List prices of common models in USD per million input and output tokens,
+/// matched by model name prefix (more specific prefixes first)
+const KNOWN_PRICING: &[(&str, ModelPricing)] = &[
+    ("gpt-4o-mini", ModelPricing::new(0.15, 0.60)),
+    ("gpt-4o", ModelPricing::new(2.50, 10.00)),
+    ("gpt-4.1-nano", ModelPricing::new(0.10, 0.40)),
+    ("gpt-4.1-mini", ModelPricing::new(0.40, 1.60)),
+    ("gpt-4.1", ModelPricing::new(2.00, 8.00)),
+    ("gpt-4-turbo", ModelPricing::new(10.00, 30.00)),
+    ("gpt-4", ModelPricing::new(30.00, 60.00)),
+    ("gpt-3.5-turbo", ModelPricing::new(0.50, 1.50)),
+    ("o1", ModelPricing::new(15.00, 60.00)),
+    ("o3-mini", ModelPricing::new(1.10, 4.40)),
+    ("o3", ModelPricing::new(2.00, 8.00)),
+    ("o4-mini", ModelPricing::new(1.10, 4.40)),
+    ("deepseek-chat", ModelPricing::new(0.27, 1.10)),
+    ("deepseek-reasoner", ModelPricing::new(0.55, 2.19)),
+];
+
+/// Price of a model in USD per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPricing {
+    #[serde(rename = "input")]
+    pub input_per_million: f64,
+    #[serde(rename = "output")]
+    pub output_per_million: f64,
+}
+
+/// List price of `model`, `None` for models missing from `KNOWN_PRICING`
+pub fn known_pricing(model: &str) -> Option<ModelPricing> {
+    KNOWN_PRICING
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map(|&(_, pricing)| pricing)
+}
+
+impl ModelPricing {
+    pub const fn new(input_per_million: f64, output_per_million: f64) -> Self {
+        Self {
+            input_per_million,
+            output_per_million,
+        }
+    }
+
+    /// Estimated cost of a request sending `prompt_tokens` and asking for `candidates` messages
+    pub fn estimate_cost(&self, prompt_tokens: usize, candidates: u8) -> f64 {
+        let output_tokens = EXPECTED_OUTPUT_TOKENS * usize::from(candidates.max(1));
+        (prompt_tokens as f64 * self.input_per_million
+            + output_tokens as f64 * self.output_per_million)
+            / 1_000_000.0
+    }
+
+    /// Cost of the tokens the API reported as used
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        (usage.prompt_tokens as f64 * self.input_per_million
+            + usage.completion_tokens as f64 * self.output_per_million)
+            / 1_000_000.0
+    }
+}
+
+/// System prompt for the first stage of large diff summarization
+const SUMMARY_SYSTEM_PROMPT: &str = "You summarize parts of a git diff so that a commit message \
+    can be written from the summaries later. For every file in the diff, state in one or two \
+    short sentences what changed and, when it is apparent, why. Be concise and factual and \
+    mention renamed or removed items by name. Do not write a commit message.";
+
+/// User prompt for the first stage of large diff summarization
+const SUMMARY_USER_PROMPT: &str = "Summarize the following diff:\n\n```diff\n{}\n```";
+
+/// System prompt for explaining a change to a reader
+const EXPLAIN_SYSTEM_PROMPT: &str = "You explain git diffs to developers. Describe in plain \
+    language what the change does and, when it is apparent, why, in a short paragraph or a few \
+    bullet points. Mention the most important files and symbols by name. Do not write a commit \
+    message.";
+
+/// User prompt for explaining a change
+const EXPLAIN_USER_PROMPT: &str = "Explain the following diff:\n\n```diff\n{}\n```";
+
+/// System prompt for explaining an existing commit
+const EXPLAIN_COMMIT_SYSTEM_PROMPT: &str = "You explain existing git commits to developers doing \
+    code archaeology or reviews. In plain English, describe what the commit changes and, from \
+    its message and the code, why. Then say why it might matter: behavior users or callers \
+    notice, risks, and follow-up work it implies. Mention the most important files and symbols \
+    by name. Keep it to a few short paragraphs or bullet points.";
+
+/// User prompt for explaining a commit, `{message}` is replaced by its message
+const EXPLAIN_COMMIT_USER_PROMPT: &str =
+    "Commit message:\n\n```text\n{message}\n```\n\nDiff of the commit:\n\n```diff\n{}\n```";
+
+/// System prompt for translating a commit message, `{language}` is replaced
+const TRANSLATE_SYSTEM_PROMPT: &str = "You translate git commit messages into {language}. Keep \
+    the structure of the message: the Conventional Commits type, scope and `!` stay in English, \
+    as do trailer keys such as `Refs:` or `Signed-off-by:` and their values. Keep blank lines \
+    and bullet points, and leave code, identifiers, file paths and ticket IDs untouched. A \
+    message already in {language} is returned as it is. Reply with the translated message only.";
+
+/// User prompt for reviewing staged changes, `{}` is replaced by the diff
+const REVIEW_USER_PROMPT: &str = "Review the following staged changes:\n\n```diff\n{}\n```";
+
+/// System prompt for grading an existing commit message
+const GRADE_SYSTEM_PROMPT: &str = "You review git commit messages. Grade how well the message \
+    describes the diff on a scale from 1 to 10, judging accuracy, clarity and the Conventional \
+    Commits format. Start with `Score: N/10`, then list at most three concrete improvements and, \
+    if the score is below 8, end with an improved message in a ```text block. Be brief.";
+
+/// User prompt for grading a commit message, `{message}` is replaced by the message
+const GRADE_USER_PROMPT: &str =
+    "Commit message:\n\n```text\n{message}\n```\n\nDiff of the commit:\n\n```diff\n{}\n```";
+
+/// System prompt for writing a pull request description
+const PULL_REQUEST_SYSTEM_PROMPT: &str = "You write pull request descriptions. On the first line, \
+    write a concise title in the imperative mood, without a trailing period or markdown. Then \
+    leave a blank line and write a markdown body: a short summary of what the branch changes and \
+    why, a `## Changes` list of the notable changes, and anything reviewers should pay attention \
+    to. Do not invent tests or issues that are not mentioned.";
+
+/// User prompt for a pull request, `{commits}` is replaced by the commit list
+const PULL_REQUEST_USER_PROMPT: &str = "Commits on the branch:\n\n{commits}\n\n\
+    Diff against the base branch:\n\n```diff\n{}\n```";
+
+/// System prompt for turning grouped commits into release notes
+const RELEASE_NOTES_SYSTEM_PROMPT: &str = "You write release notes for a changelog in the Keep a \
+    Changelog format. Rewrite the grouped commit subjects into clear, user-facing entries. Keep the \
+    `### Added`, `### Changed`, `### Removed`, `### Fixed` and `### Security` headings that are \
+    present, merge entries describing the same change, and keep breaking changes marked. Reply \
+    with the markdown sections only, without a release heading or any other text.";
+
+/// User prompt for release notes, `{}` is replaced by the grouped commits
+const RELEASE_NOTES_USER_PROMPT: &str = "Commits since the last release:\n\n{}";
+
+/// System prompt for an annotated release tag message
+const TAG_SYSTEM_PROMPT: &str = "You write annotated git tag messages for releases. On the first \
+    line, summarize the release in one sentence without the tag name. Then leave a blank line and \
+    list the notable changes as plain-text `- ` bullets, new features first, then fixes, then \
+    other changes, merging related commits. Mark breaking changes. Do not use markdown headings.";
+
+/// User prompt for a tag message, `{tag}` and `{since}` are replaced
+const TAG_USER_PROMPT: &str = "Tag: {tag}\n\nCommits {since}:\n\n{}";
+
+/// System prompt for grouping staged changes into separate commits
+const SPLIT_SYSTEM_PROMPT: &str = "You split staged git changes into a series of small, logically \
+    separate commits. Group the files so that each group is one coherent change that could be \
+    committed on its own, and order the groups the way they should be committed, e.g. refactorings \
+    and dependencies before the features that use them. Put every file in exactly one group. Reply \
+    with a JSON array only, without markdown: [{\"name\": \"short description\", \"files\": [\"path\"]}]";
+
+/// User prompt for grouping changes, `{files}` is replaced by the staged files
+const SPLIT_USER_PROMPT: &str = "Staged files:\n\n{files}\n\nDiff:\n\n```diff\n{}\n```";
+
+/// System prompt for naming a branch after a diff
+const BRANCH_SYSTEM_PROMPT: &str = "You name git branches. Summarize the diff as a Conventional \
+    Commits header `type: summary`, where the summary has at most six words in the imperative \
+    mood, without a scope, ticket numbers or a trailing period. Reply with the header only.";
+
+/// User prompt for naming a branch, `{}` is replaced by the diff
+const BRANCH_USER_PROMPT: &str = "Name a branch for the following diff:\n\n```diff\n{}\n```";
+
+/// System prompt for deriving a style guide from a repository's commit messages
+const LEARN_SYSTEM_PROMPT: &str = "You study the commit history of a repository. Write a short \
+    style guide that lets someone write new commit messages indistinguishable from these: the \
+    commit types used, scope conventions with the common scopes, tense and mood, capitalization, \
+    subject length, emoji usage, ticket references, and when and how bodies are written. Reply \
+    with at most ten `- ` bullet points, no headings and no examples of your own.";
+
+/// User prompt for deriving a style guide, `{}` is replaced by the commit messages
+const LEARN_USER_PROMPT: &str = "Commit messages, newest first, separated by lines of `---`:\n\n{}";
+
+/// Introduces the commits of a branch that is squashed into one commit
+const SQUASH_PROMPT_PREFIX: &str = "The changes are a whole branch squashed into a single commit. \
+    Describe what the branch achieves as a whole rather than commit by commit. Its commits were:";
+
+/// Introduces the user's hint when a message is regenerated
+const REGENERATE_HINT_PREFIX: &str =
+    "The user asked for a different commit message. Take this into account:";
+
+/// Follow-up turn asking for a revised commit message, `{}` is replaced by the
+/// user's instructions
+const REFINE_USER_PROMPT: &str = "Revise the commit message following these instructions: {}\n\n\
+    Reply with the complete revised commit message only.";
+
+/// Appended to the system prompt when messages are requested as JSON fields
+const STRUCTURED_OUTPUT_PROMPT: &str = "Reply with a JSON object with these fields: \"type\" \
+    (the commit type), \"scope\" (the scope, or null), \"subject\" (the summary in the \
+    imperative mood, without type and scope), \"body\" (the explanation, or null) and \
+    \"breaking\" (true if the change breaks backward compatibility).";
+
+/// `max_tokens` of the probe whether the model accepts that parameter
+const PROBE_MAX_TOKENS: u32 = 16;
+
+/// Marker appended to a diff that was cut to fit the token budget
+const TRUNCATION_MARKER: &str = "[... diff truncated to fit the model context window ...]";
+
+#[derive(Clone, Serialize, Deserialize)]
 struct Message {
     role: String,
     content: String,
 }
 
-#[derive(Serialize)]
-struct OpenAIRequest {
-    model: String,
-    messages: Vec<Message>,
-}
+#[derive(Serialize)]
+struct OpenAIRequest {
+    model: String,
+    messages: Vec<Message>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    n: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<bool>,
+    #[serde(flatten)]
+    sampling: Sampling,
+    /// `max_tokens` for reasoning models, which reject that name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_completion_tokens: Option<u32>,
+}
+
+/// Sampling parameters sent with generation requests, left to the API's
+/// defaults when unset
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Sampling {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    message: Message,
+}
+
+#[derive(Deserialize)]
+struct OpenAIResponse {
+    choices: Vec<Choice>,
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+#[derive(Deserialize)]
+struct ModelEntry {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct ModelList {
+    data: Vec<ModelEntry>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingList {
+    data: Vec<EmbeddingEntry>,
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingEntry {
+    #[serde(default)]
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+/// Tokens billed for requests, as reported by the API
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    #[serde(default)]
+    pub prompt_tokens: usize,
+    #[serde(default)]
+    pub completion_tokens: usize,
+    #[serde(default)]
+    pub total_tokens: usize,
+}
+
+impl Usage {
+    pub fn add(&mut self, other: Usage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+    }
+}
+
+#[derive(Deserialize)]
+struct Delta {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct StreamChoice {
+    delta: Delta,
+}
+
+/// One `data:` event of a streamed chat completion
+#[derive(Deserialize)]
+struct StreamChunk {
+    choices: Vec<StreamChoice>,
+}
+
+/// Request timeout used when none is configured
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Upper bound for a single backoff between retries
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Longest wait the API may ask for before a retry, unless configured
+const DEFAULT_MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+// When the request waiting for a retry is sent again, for progress displays
+static RETRYING_AT: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// How often and how long to wait before retrying transient API failures
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt
+    pub max_retries: usize,
+
+    /// Delay before the first retry, doubled for each further retry
+    pub base_delay: Duration,
+
+    /// Longest wait the API may ask for with `Retry-After` or rate limit
+    /// headers; the request fails instead of waiting longer
+    pub max_rate_limit_wait: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(500),
+            max_rate_limit_wait: DEFAULT_MAX_RATE_LIMIT_WAIT,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Exponential backoff with up to 50% random jitter for the given retry (0-based)
+    pub fn backoff(&self, retry: usize) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry as u32))
+            .min(MAX_RETRY_DELAY);
+        let jitter_range = exponential.as_millis() as u64 / 2;
+        let jitter = match jitter_range {
+            0 => 0,
+            range => random_u64() % (range + 1),
+        };
+
+        (exponential + Duration::from_millis(jitter)).min(MAX_RETRY_DELAY)
+    }
+}
+
+// Build the HTTP client, falling back to one without a timeout if the TLS backend fails
+fn build_http_client(timeout: Duration) -> Client {
+    Client::builder()
+        .timeout(timeout)
+        .build()
+        .unwrap_or_default()
+}
+
+// Random number from the std hasher's per-instance random keys, good enough for jitter
+fn random_u64() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+/// Text of one `data:` line of a streamed completion, if it carries any
+fn parse_stream_line(line: &str) -> Option<String> {
+    let data = line.trim().strip_prefix("data:")?.trim();
+    if data == "[DONE]" {
+        return None;
+    }
+
+    let chunk: StreamChunk = serde_json::from_str(data).ok()?;
+    chunk
+        .choices
+        .into_iter()
+        .next()?
+        .delta
+        .content
+        .filter(|content| !content.is_empty())
+}
+
+/// Whether a response status is worth retrying
+fn is_transient(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// How long the API asks to wait before retrying: `Retry-After` in seconds,
+/// `retry-after-ms`, or the reset time of an exhausted `x-ratelimit-*` limit
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name)?.to_str().ok().map(str::trim);
+    if let Some(delay) = header("retry-after-ms")
+        .and_then(|ms| ms.parse::<f64>().ok())
+        .and_then(|ms| Duration::try_from_secs_f64(ms / 1000.0).ok())
+    {
+        return Some(delay);
+    }
+    if let Some(delay) = header("retry-after")
+        .and_then(|seconds| seconds.parse::<f64>().ok())
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+    {
+        return Some(delay);
+    }
+
+    // OpenAI style limits, only the ones that ran out are waited for
+    ["requests", "tokens"]
+        .iter()
+        .filter(|limit| {
+            header(&format!("x-ratelimit-remaining-{limit}")).is_none_or(|left| left == "0")
+        })
+        .filter_map(|limit| parse_reset(header(&format!("x-ratelimit-reset-{limit}"))?))
+        .max()
+}
+
+/// Parse a rate limit reset time like `6m0s`, `1.5s`, `20ms` or plain seconds
+fn parse_reset(value: &str) -> Option<Duration> {
+    if let Ok(seconds) = value.parse::<f64>() {
+        return Duration::try_from_secs_f64(seconds).ok();
+    }
+
+    let mut seconds = 0.0;
+    let mut rest = value;
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let number_end = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .filter(|&end| end > 0)?;
+        let number: f64 = rest[..number_end].parse().ok()?;
+        rest = &rest[number_end..];
+        let unit_end = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        seconds += number
+            * match &rest[..unit_end] {
+                "h" => 3600.0,
+                "m" => 60.0,
+                "s" => 1.0,
+                "ms" => 0.001,
+                _ => return None,
+            };
+        rest = &rest[unit_end..];
+    }
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+/// `response_format` asking for the fields of a commit message by JSON schema
+fn commit_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "json_schema",
+        "json_schema": {
+            "name": "commit_message",
+            "strict": true,
+            "schema": {
+                "type": "object",
+                "properties": {
+                    "type": { "type": "string" },
+                    "scope": { "type": ["string", "null"] },
+                    "subject": { "type": "string" },
+                    "body": { "type": ["string", "null"] },
+                    "breaking": { "type": "boolean" }
+                },
+                "required": ["type", "scope", "subject", "body", "breaking"],
+                "additionalProperties": false
+            }
+        }
+    })
+}
+
+/// Change what a request was rejected for, as named in the API's error, and
+/// describe the change; `None` when there is nothing left to change
+///
+/// Reasoning models reject `max_tokens`, sampling parameters and the `system`
+/// role with a 400 naming the parameter. A rejected `response_format` is
+/// dropped last, since its instructions are in the prompt as well.
+fn adapt_to_error(request: &mut OpenAIRequest, error: &str) -> Option<&'static str> {
+    let param = serde_json::from_str::<serde_json::Value>(error)
+        .ok()
+        .and_then(|body| body["error"]["param"].as_str().map(str::to_string))
+        .unwrap_or_default();
+    let names = |name: &str| param == name || error.contains(&format!("'{name}'"));
+
+    if request.sampling.max_tokens.is_some() && names("max_tokens") {
+        request.max_completion_tokens = request.sampling.max_tokens.take();
+        return Some("max_tokens sent as max_completion_tokens");
+    }
+    if (request.sampling.temperature.is_some() && names("temperature"))
+        || (request.sampling.top_p.is_some() && names("top_p"))
+    {
+        request.sampling.temperature = None;
+        request.sampling.top_p = None;
+        return Some("sampling parameters dropped");
+    }
+    let has_system = request
+        .messages
+        .iter()
+        .any(|message| message.role == "system");
+    if has_system && (param.starts_with("messages") || error.contains("'system'")) {
+        request.messages = merge_system_messages(std::mem::take(&mut request.messages));
+        return Some("system prompt merged into the user message");
+    }
+    if request.response_format.take().is_some() {
+        return Some("response_format dropped");
+    }
+    None
+}
+
+/// Put the content of `system` messages in front of the first user message
+fn merge_system_messages(messages: Vec<Message>) -> Vec<Message> {
+    let (system, mut messages): (Vec<Message>, Vec<Message>) = messages
+        .into_iter()
+        .partition(|message| message.role == "system");
+    let system_prompt = system
+        .into_iter()
+        .map(|message| message.content)
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    if let Some(user) = messages.iter_mut().find(|message| message.role == "user") {
+        user.content = format!("{system_prompt}\n\n{}", user.content);
+    }
+    messages
+}
+
+/// Time left until a rate limited or failed request is retried, `None` when
+/// no request is waiting for a retry
+pub fn retry_wait() -> Option<Duration> {
+    let until = (*RETRYING_AT.lock().unwrap_or_else(|err| err.into_inner()))?;
+    Some(until.saturating_duration_since(Instant::now())).filter(|left| !left.is_zero())
+}
+
+/// Chat messages of a commit message generation, kept across follow-up turns
+pub struct Conversation {
+    messages: Vec<Message>,
+}
+
+/// Client for an OpenAI-compatible chat completions API
+pub struct LlmClient {
+    http: Client,
+    api_token: String,
+    api_base_url: String,
+    model: String,
+    capabilities: ModelCapabilities,
+    max_input_tokens: Option<usize>,
+    retry_policy: RetryPolicy,
+    sampling: Sampling,
+    structured_output: bool,
+    diff_priority: DiffPriority,
+
+    /// Usage summed over the responses that reported it
+    usage: Mutex<Option<Usage>>,
+}
+
+impl LlmClient {
+    pub fn new(api_token: &str, api_base_url: &str, model: &str) -> Self {
+        Self {
+            http: build_http_client(DEFAULT_REQUEST_TIMEOUT),
+            api_token: api_token.to_string(),
+            api_base_url: api_base_url.to_string(),
+            model: model.to_string(),
+            capabilities: ModelCapabilities::for_model(model),
+            max_input_tokens: None,
+            retry_policy: RetryPolicy::default(),
+            sampling: Sampling::default(),
+            structured_output: false,
+            diff_priority: DiffPriority::default(),
+            usage: Mutex::new(None),
+        }
+    }
+
+    /// Adapt requests to what the model is known to support
+    pub fn with_capabilities(mut self, capabilities: ModelCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Override the input token budget, otherwise the model's known context window is used
+    pub fn with_max_input_tokens(mut self, max_input_tokens: Option<usize>) -> Self {
+        self.max_input_tokens = max_input_tokens;
+        self
+    }
+
+    /// Give up on a single request attempt after `timeout`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.http = build_http_client(timeout);
+        self
+    }
+
+    /// Retry transient failures (429, 5xx, timeouts) according to the policy
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Send these sampling parameters with every generation request
+    pub fn with_sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    /// Ask for commit messages as JSON fields and assemble them, rather than
+    /// taking the model's text as it is
+    pub fn with_structured_output(mut self, structured_output: bool) -> Self {
+        self.structured_output = structured_output;
+        self
+    }
+
+    /// Keep files by these weights when the diff has to be truncated
+    pub fn with_diff_priority(mut self, diff_priority: DiffPriority) -> Self {
+        self.diff_priority = diff_priority;
+        self
+    }
+
+    /// Tokens used by the requests so far, `None` when the API reported no usage
+    pub fn usage(&self) -> Option<Usage> {
+        *self.usage.lock().expect("usage lock poisoned")
+    }
+
+    /// Tokens used since the last call, resetting the count
+    pub fn take_usage(&self) -> Option<Usage> {
+        self.usage.lock().expect("usage lock poisoned").take()
+    }
+
+    /// Number of prompt tokens the model accepts
+    pub fn input_token_budget(&self) -> usize {
+        self.max_input_tokens.unwrap_or_else(|| {
+            KNOWN_CONTEXT_WINDOWS
+                .iter()
+                .find(|(prefix, _)| self.model.starts_with(prefix))
+                .map_or(DEFAULT_CONTEXT_WINDOW, |&(_, window)| window)
+        })
+    }
+
+    /// List price of the model, `None` for models missing from `KNOWN_PRICING`
+    pub fn pricing(&self) -> Option<ModelPricing> {
+        known_pricing(&self.model)
+    }
+
+    /// Truncate the diff so the rendered prompts fit the input token budget
+    ///
+    /// The hunks of the most valuable files are kept, see `diff_priority`, and
+    /// the files left out are listed. Text that isn't a diff, or whose first
+    /// hunk alone is too large, is cut at a line boundary instead.
+    pub fn fit_diff<'a>(
+        &self,
+        diff: &'a str,
+        system_prompt: &str,
+        user_prompt: &str,
+    ) -> Cow<'a, str> {
+        let model = self.model.as_str();
+        let overhead = estimate_tokens(system_prompt, model)
+            + estimate_tokens(&user_prompt.replace("{}", ""), model);
+        let budget = self.input_token_budget().saturating_sub(overhead);
+        let diff_tokens = estimate_tokens(diff, model);
+        if diff_tokens <= budget {
+            return Cow::Borrowed(diff);
+        }
+
+        // Hunks are picked by size, start from the diff's own bytes per token
+        // and shrink until the selection fits
+        let reserved = estimate_tokens(TRUNCATION_MARKER, model) + OMISSION_NOTE_TOKENS + 1;
+        let mut max_bytes = budget.saturating_sub(reserved) * diff.len() / diff_tokens;
+        loop {
+            let Some(selection) = priority::select_hunks(diff, max_bytes, &self.diff_priority)
+            else {
+                return truncate_to_tokens(diff, budget, model);
+            };
+            let fitted = format!(
+                "{}{}{TRUNCATION_MARKER}\n",
+                selection.diff,
+                omission_note(&selection.omitted, model)
+            );
+            if max_bytes == 0 || estimate_tokens(&fitted, model) <= budget {
+                return Cow::Owned(fitted);
+            }
+            max_bytes = max_bytes * 9 / 10;
+        }
+    }
+
+    pub fn api_base_url(&self) -> &str {
+        &self.api_base_url
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// The chat completions endpoint URL
+    fn endpoint(&self) -> String {
+        format!(
+            "{}/chat/completions",
+            self.api_base_url.trim_end_matches('/')
+        )
+    }
+
+    /// Ids of the models the API offers, sorted
+    pub async fn list_models(&self) -> Result<Vec<String>> {
+        let endpoint = format!("{}/models", self.api_base_url.trim_end_matches('/'));
+        tracing::info!(%endpoint, "listing models");
+        let response = self
+            .http
+            .get(&endpoint)
+            .header("Authorization", format!("Bearer {}", self.api_token))
+            .send()
+            .await
+            .context(format!("Failed to send request to API at {endpoint}"))?;
+
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            return Err(anyhow::anyhow!("API request failed ({}): {}", status, text));
+        }
+
+        let list: ModelList = serde_json::from_str(&text)
+            .context(format!("Unexpected model list from {endpoint}"))?;
+        let mut models: Vec<String> = list.data.into_iter().map(|model| model.id).collect();
+        models.sort();
+        models.dedup();
+        Ok(models)
+    }
+
+    /// Embed `texts` with the `/embeddings` endpoint, one vector per text in
+    /// the same order
+    pub async fn embed(&self, model: &str, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let endpoint = format!("{}/embeddings", self.api_base_url.trim_end_matches('/'));
+        tracing::info!(%endpoint, model, inputs = texts.len(), "requesting embeddings");
+        let response = self
+            .http
+            .post(&endpoint)
+            .header("Authorization", format!("Bearer {}", self.api_token))
+            .json(&serde_json::json!({ "model": model, "input": texts }))
+            .send()
+            .await
+            .context(format!("Failed to send request to API at {endpoint}"))?;
+
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            return Err(anyhow::anyhow!("API request failed ({}): {}", status, text));
+        }
+
+        let mut list: EmbeddingList = serde_json::from_str(&text)
+            .context(format!("Unexpected embeddings from {endpoint}"))?;
+        if list.data.len() != texts.len() {
+            anyhow::bail!(
+                "Expected {} embeddings from {}, got {}",
+                texts.len(),
+                endpoint,
+                list.data.len()
+            );
+        }
+        if let Some(usage) = list.usage {
+            self.usage
+                .lock()
+                .expect("usage lock poisoned")
+                .get_or_insert_with(Usage::default)
+                .add(usage);
+        }
+        list.data.sort_by_key(|entry| entry.index);
+        Ok(list.data.into_iter().map(|entry| entry.embedding).collect())
+    }
+
+    /// Generate `count` candidate commit messages based on the git diff
+    ///
+    /// The `n` parameter is sent so compatible APIs return all candidates in one
+    /// response. Providers that ignore it are called again until enough
+    /// candidates have been collected.
+    pub async fn generate_commit_messages(
+        &self,
+        diff: &str,
+        system_prompt: &str,
+        user_prompt: &str,
+        count: u8,
+    ) -> Result<Vec<String>> {
+        let count = count.max(1);
+        let mut candidates = Vec::with_capacity(count as usize);
+
+        let system_prompt = self.commit_system_prompt(system_prompt);
+        while candidates.len() < count as usize {
+            let remaining = count - candidates.len() as u8;
+            let messages = self.build_messages(&system_prompt, &user_prompt.replace("{}", diff));
+            let choices = self
+                .request_completions_at(messages, remaining, None, self.commit_response_format())
+                .await?;
+
+            candidates.extend(
+                choices
+                    .iter()
+                    .take(remaining as usize)
+                    .map(|choice| self.commit_message_from(choice)),
+            );
+        }
+
+        Ok(candidates)
+    }
+
+    /// Generate another commit message for the same diff, sampled at `temperature`
+    /// for a different take, with an optional hint from the user
+    pub async fn regenerate_commit_message(
+        &self,
+        diff: &str,
+        system_prompt: &str,
+        user_prompt: &str,
+        hint: Option<&str>,
+        temperature: f64,
+    ) -> Result<String> {
+        let system_prompt = match hint {
+            Some(hint) => format!("{system_prompt}\n\n{REGENERATE_HINT_PREFIX} {hint}"),
+            None => system_prompt.to_string(),
+        };
+        let system_prompt = self.commit_system_prompt(&system_prompt);
+        let messages = self.build_messages(&system_prompt, &user_prompt.replace("{}", diff));
+
+        let message = self
+            .request_completions_at(
+                messages,
+                1,
+                Some(temperature),
+                self.commit_response_format(),
+            )
+            .await?
+            .remove(0);
+        Ok(self.commit_message_from(&message))
+    }
+
+    /// Start a conversation from the prompts of a commit message generation
+    pub fn start_conversation(
+        &self,
+        diff: &str,
+        system_prompt: &str,
+        user_prompt: &str,
+    ) -> Conversation {
+        Conversation {
+            messages: self.build_messages(system_prompt, &user_prompt.replace("{}", diff)),
+        }
+    }
+
+    /// Revise `previous`, the message last shown to the user, as instructed
+    ///
+    /// Both turns are added to the conversation once the request succeeds, so
+    /// later instructions can build on earlier ones.
+    pub async fn refine_commit_message(
+        &self,
+        conversation: &mut Conversation,
+        previous: &str,
+        instructions: &str,
+    ) -> Result<String> {
+        let mut messages = conversation.messages.clone();
+        messages.push(Message {
+            role: "assistant".to_string(),
+            content: previous.to_string(),
+        });
+        messages.push(Message {
+            role: "user".to_string(),
+            content: REFINE_USER_PROMPT.replace("{}", instructions),
+        });
+
+        let refined = self
+            .request_completions(messages.clone(), 1)
+            .await?
+            .remove(0);
+        conversation.messages = messages;
+        Ok(format::format_message(&refined))
+    }
+
+    /// Generate one commit message, passing each piece of text to `on_delta` as it arrives
+    ///
+    /// Models without streaming support, and structured output, get a regular
+    /// request whose message is delivered as a single piece. Streamed requests
+    /// are not retried, since part of the output may already have been shown.
+    pub async fn stream_commit_message(
+        &self,
+        diff: &str,
+        system_prompt: &str,
+        user_prompt: &str,
+        mut on_delta: impl FnMut(&str),
+    ) -> Result<String> {
+        if !self.capabilities.streaming || self.structured_output {
+            let message = self
+                .generate_commit_messages(diff, system_prompt, user_prompt, 1)
+                .await?
+                .remove(0);
+            on_delta(&message);
+            return Ok(message);
+        }
+
+        let messages = self.build_messages(system_prompt, &user_prompt.replace("{}", diff));
+        let request = OpenAIRequest {
+            model: self.model.clone(),
+            messages,
+            n: None,
+            response_format: None,
+            stream: Some(true),
+            sampling: self.sampling,
+            max_completion_tokens: None,
+        };
+        let request = self.adapt_request(request);
+        let endpoint = self.endpoint();
+        tracing::info!(%endpoint, model = %self.model, "sending streamed request");
+        let started = Instant::now();
+        let mut response = self
+            .http
+            .post(&endpoint)
+            .header("Authorization", format!("Bearer {}", self.api_token))
+            .header("Content-Type", "application/json")
+            .json(&request)
+            .send()
+            .await
+            .context(format!("Failed to send request to API at {endpoint}"))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(anyhow::anyhow!("API request failed ({}): {}", status, text));
+        }
+
+        // Events may be split across chunks, so only complete lines are parsed
+        let mut pending = Vec::new();
+        let mut message = String::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .context("Failed to read streamed API response")?
+        {
+            pending.extend_from_slice(&chunk);
+            while let Some(end) = pending.iter().position(|&byte| byte == b'\n') {
+                let line: Vec<u8> = pending.drain(..=end).collect();
+                if let Some(delta) = parse_stream_line(&String::from_utf8_lossy(&line)) {
+                    message.push_str(&delta);
+                    on_delta(&delta);
+                }
+            }
+        }
+
+        tracing::info!(
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            chars = message.len(),
+            "stream finished"
+        );
+        Ok(format::format_message(&message))
+    }
+
+    /// Send a single request and return the first choice
+    pub async fn complete(&self, system_prompt: &str, user_content: &str) -> Result<String> {
+        let messages = self.build_messages(system_prompt, user_content);
+        let mut choices = self.request_completions(messages, 1).await?;
+
+        Ok(choices.remove(0))
+    }
+
+    /// Group per-file diffs into chunks that each fit a summarization request
+    ///
+    /// Files larger than a whole chunk are truncated on their own.
+    pub fn summary_chunks(&self, files: &[FileDiff]) -> Vec<String> {
+        let model = self.model.as_str();
+        let overhead = estimate_tokens(SUMMARY_SYSTEM_PROMPT, model)
+            + estimate_tokens(&SUMMARY_USER_PROMPT.replace("{}", ""), model);
+        let budget = self.input_token_budget().saturating_sub(overhead);
+
+        let mut chunks: Vec<String> = Vec::new();
+        let mut current = String::new();
+        for file in files {
+            let file_diff = truncate_to_tokens(&file.diff, budget, model);
+            if !current.is_empty()
+                && estimate_tokens(&current, model) + estimate_tokens(&file_diff, model) > budget
+            {
+                chunks.push(std::mem::take(&mut current));
+            }
+            current.push_str(&file_diff);
+        }
+        if !current.is_empty() {
+            chunks.push(current);
+        }
+
+        chunks
+    }
+
+    /// Explain what a diff does in plain language
+    pub async fn explain_diff(&self, diff: &str) -> Result<String> {
+        let diff = self.fit_diff(diff, EXPLAIN_SYSTEM_PROMPT, EXPLAIN_USER_PROMPT);
+        self.complete(
+            EXPLAIN_SYSTEM_PROMPT,
+            &EXPLAIN_USER_PROMPT.replace("{}", &diff),
+        )
+        .await
+    }
+
+    /// Explain what an existing commit does and why it might matter
+    pub async fn explain_commit(&self, message: &str, diff: &str) -> Result<String> {
+        let user_prompt = EXPLAIN_COMMIT_USER_PROMPT.replace("{message}", message.trim());
+        let diff = self.fit_diff(diff, EXPLAIN_COMMIT_SYSTEM_PROMPT, &user_prompt);
+        self.complete(
+            EXPLAIN_COMMIT_SYSTEM_PROMPT,
+            &user_prompt.replace("{}", &diff),
+        )
+        .await
+    }
+
+    /// Translate a commit message into `language`, e.g. `English` or `ja`
+    pub async fn translate_commit_message(&self, message: &str, language: &str) -> Result<String> {
+        let system_prompt = TRANSLATE_SYSTEM_PROMPT.replace("{language}", language);
+        let translated = self.complete(&system_prompt, message.trim()).await?;
+        Ok(format::format_message(&translated))
+    }
+
+    /// Review a diff for bugs, missing tests and style issues, following `review_prompt`
+    pub async fn review_diff(&self, review_prompt: &str, diff: &str) -> Result<String> {
+        let diff = self.fit_diff(diff, review_prompt, REVIEW_USER_PROMPT);
+        self.complete(review_prompt, &REVIEW_USER_PROMPT.replace("{}", &diff))
+            .await
+    }
+
+    /// Grade a commit message against the diff it describes and suggest improvements
+    pub async fn grade_commit_message(&self, message: &str, diff: &str) -> Result<String> {
+        let user_prompt = GRADE_USER_PROMPT.replace("{message}", message.trim());
+        let diff = self.fit_diff(diff, GRADE_SYSTEM_PROMPT, &user_prompt);
+        self.complete(GRADE_SYSTEM_PROMPT, &user_prompt.replace("{}", &diff))
+            .await
+    }
+
+    /// Write a pull request title and markdown body from a branch's commits and diff
+    pub async fn generate_pull_request(
+        &self,
+        commits: &[String],
+        diff: &str,
+    ) -> Result<(String, String)> {
+        let commit_list: Vec<String> = commits
+            .iter()
+            .map(|message| format!("- {}", message.lines().next().unwrap_or_default()))
+            .collect();
+        let user_prompt = PULL_REQUEST_USER_PROMPT.replace("{commits}", &commit_list.join("\n"));
+        let diff = self.fit_diff(diff, PULL_REQUEST_SYSTEM_PROMPT, &user_prompt);
+
+        let response = self
+            .complete(
+                PULL_REQUEST_SYSTEM_PROMPT,
+                &user_prompt.replace("{}", &diff),
+            )
+            .await?;
+        Ok(split_pull_request(&response))
+    }
+
+    /// Ask how the staged files group into separate commits, replying with a
+    /// JSON array of `{"name", "files"}` objects
+    pub async fn group_changes(&self, files: &[String], diff: &str) -> Result<String> {
+        let user_prompt = SPLIT_USER_PROMPT.replace("{files}", &files.join("\n"));
+        let diff = self.fit_diff(diff, SPLIT_SYSTEM_PROMPT, &user_prompt);
+        self.complete(SPLIT_SYSTEM_PROMPT, &user_prompt.replace("{}", &diff))
+            .await
+    }
+
+    /// Summarize a diff as a short `type: summary` header to name a branch after
+    pub async fn summarize_for_branch(&self, diff: &str) -> Result<String> {
+        let diff = self.fit_diff(diff, BRANCH_SYSTEM_PROMPT, BRANCH_USER_PROMPT);
+        self.complete(
+            BRANCH_SYSTEM_PROMPT,
+            &BRANCH_USER_PROMPT.replace("{}", &diff),
+        )
+        .await
+    }
+
+    /// Derive a style guide from commit messages, as bullet points to add to the system prompt
+    pub async fn derive_style_guide(&self, messages: &[String]) -> Result<String> {
+        let history = messages
+            .iter()
+            .map(|message| message.trim())
+            .collect::<Vec<_>>()
+            .join("\n---\n");
+        let history = self.fit_diff(&history, LEARN_SYSTEM_PROMPT, LEARN_USER_PROMPT);
+        self.complete(
+            LEARN_SYSTEM_PROMPT,
+            &LEARN_USER_PROMPT.replace("{}", &history),
+        )
+        .await
+    }
+
+    /// Write one commit message for a branch squashed into a single commit,
+    /// from its cumulative diff and its commits, following the usual prompts
+    pub async fn generate_squash_message(
+        &self,
+        commits: &[String],
+        diff: &str,
+        system_prompt: &str,
+        user_prompt: &str,
+    ) -> Result<String> {
+        let commit_list: Vec<String> = commits
+            .iter()
+            .map(|message| format!("- {}", message.lines().next().unwrap_or_default()))
+            .collect();
+        let system_prompt = format!(
+            "{system_prompt}\n\n{SQUASH_PROMPT_PREFIX}\n{}",
+            commit_list.join("\n")
+        );
+        let diff = self.fit_diff(diff, &system_prompt, user_prompt);
+
+        let mut messages = self
+            .generate_commit_messages(&diff, &system_prompt, user_prompt, 1)
+            .await?;
+        Ok(messages.remove(0))
+    }
+
+    /// Rewrite commits grouped by changelog section into release notes
+    pub async fn write_release_notes(&self, sections: &str) -> Result<String> {
+        self.complete(
+            RELEASE_NOTES_SYSTEM_PROMPT,
+            &RELEASE_NOTES_USER_PROMPT.replace("{}", sections),
+        )
+        .await
+    }
+
+    /// Write an annotated tag message from the commits since the previous tag
+    pub async fn generate_tag_message(
+        &self,
+        tag: &str,
+        previous_tag: Option<&str>,
+        commits: &[String],
+    ) -> Result<String> {
+        let since = match previous_tag {
+            Some(previous_tag) => format!("since {previous_tag}"),
+            None => "since the start of the project".to_string(),
+        };
+        let commit_list: Vec<String> = commits
+            .iter()
+            .map(|message| format!("- {}", message.lines().next().unwrap_or_default()))
+            .collect();
+        let user_prompt = TAG_USER_PROMPT
+            .replace("{tag}", tag)
+            .replace("{since}", &since)
+            .replace("{}", &commit_list.join("\n"));
+
+        self.complete(TAG_SYSTEM_PROMPT, &user_prompt).await
+    }
+
+    /// First stage of large diff handling: summarize one chunk of the diff
+    pub async fn summarize_chunk(&self, chunk: &str) -> Result<String> {
+        self.complete(
+            SUMMARY_SYSTEM_PROMPT,
+            &SUMMARY_USER_PROMPT.replace("{}", chunk),
+        )
+        .await
+    }
+
+    /// Build the chat messages, folding the system prompt into the user
+    /// message for models that reject the system role
+    fn build_messages(&self, system_prompt: &str, user_content: &str) -> Vec<Message> {
+        if self.capabilities.system_role {
+            vec![
+                Message {
+                    role: "system".to_string(),
+                    content: system_prompt.to_string(),
+                },
+                Message {
+                    role: "user".to_string(),
+                    content: user_content.to_string(),
+                },
+            ]
+        } else {
+            vec![Message {
+                role: "user".to_string(),
+                content: format!("{system_prompt}\n\n{user_content}"),
+            }]
+        }
+    }
+
+    /// Drop or rename the parameters of a request the model doesn't accept
+    fn adapt_request(&self, mut request: OpenAIRequest) -> OpenAIRequest {
+        if !self.capabilities.sampling {
+            request.sampling.temperature = None;
+            request.sampling.top_p = None;
+        }
+        if self.capabilities.max_completion_tokens {
+            request.max_completion_tokens = request.sampling.max_tokens.take();
+        }
+        request
+    }
+
+    /// The system prompt of a commit message generation, asking for JSON
+    /// fields with structured output
+    fn commit_system_prompt<'a>(&self, system_prompt: &'a str) -> Cow<'a, str> {
+        match self.structured_output {
+            true => Cow::Owned(format!("{system_prompt}\n\n{STRUCTURED_OUTPUT_PROMPT}")),
+            false => Cow::Borrowed(system_prompt),
+        }
+    }
+
+    /// The strictest `response_format` for structured output the model accepts,
+    /// models without JSON mode only get the instructions in the prompt
+    fn commit_response_format(&self) -> Option<serde_json::Value> {
+        if !self.structured_output {
+            None
+        } else if self.capabilities.json_schema {
+            Some(commit_schema())
+        } else if self.capabilities.json_mode {
+            Some(serde_json::json!({ "type": "json_object" }))
+        } else {
+            None
+        }
+    }
+
+    /// The commit message of a reply, assembled from its fields with
+    /// structured output
+    fn commit_message_from(&self, reply: &str) -> String {
+        if self.structured_output {
+            if let Some(fields) = format::CommitFields::parse(reply) {
+                return fields.to_message();
+            }
+            tracing::warn!("reply is not the requested JSON object, using it as text");
+        }
+        format::format_message(reply)
+    }
+
+    /// The role and content of each message a commit message generation sends,
+    /// exactly as the model will read them
+    pub fn commit_prompt(
+        &self,
+        diff: &str,
+        system_prompt: &str,
+        user_prompt: &str,
+    ) -> Vec<(String, String)> {
+        self.build_messages(
+            &self.commit_system_prompt(system_prompt),
+            &user_prompt.replace("{}", diff),
+        )
+        .into_iter()
+        .map(|message| (message.role, message.content))
+        .collect()
+    }
+
+    /// The request a commit message generation would send, with the diff left out
+    /// and the token masked, for bug reports
+    pub fn request_skeleton(&self, system_prompt: &str) -> serde_json::Value {
+        let request = OpenAIRequest {
+            model: self.model.clone(),
+            messages: self
+                .build_messages(&self.commit_system_prompt(system_prompt), "<diff omitted>"),
+            n: None,
+            response_format: self.commit_response_format(),
+            stream: None,
+            sampling: self.sampling,
+            max_completion_tokens: None,
+        };
+        let request = self.adapt_request(request);
+
+        serde_json::json!({
+            "method": "POST",
+            "url": self.endpoint(),
+            "headers": {
+                "Authorization": "Bearer <redacted>",
+                "Content-Type": "application/json",
+            },
+            "body": request,
+        })
+    }
+
+    /// Send a single chat completion request and return the content of every choice
+    async fn request_completions(&self, messages: Vec<Message>, n: u8) -> Result<Vec<String>> {
+        self.request_completions_at(messages, n, None, None).await
+    }
+
+    /// Like `request_completions`, sampling at `temperature` instead of the
+    /// configured one and asking for `response_format`
+    async fn request_completions_at(
+        &self,
+        messages: Vec<Message>,
+        n: u8,
+        temperature: Option<f64>,
+        response_format: Option<serde_json::Value>,
+    ) -> Result<Vec<String>> {
+        // Prepare the request to OpenAI API
+        let request = OpenAIRequest {
+            model: self.model.clone(),
+            messages,
+            // Only send `n` when more than one choice is wanted, some providers reject it
+            n: (n > 1).then_some(n),
+            response_format,
+            stream: None,
+            sampling: Sampling {
+                temperature: temperature.or(self.sampling.temperature),
+                ..self.sampling
+            },
+            max_completion_tokens: None,
+        };
+        let mut request = self.adapt_request(request);
+
+        let (mut response_status, mut response_text) = self.send(&request).await?;
+
+        // A model that wasn't probed may reject a parameter, fix the one it names
+        while matches!(
+            response_status,
+            StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY
+        ) {
+            let Some(change) = adapt_to_error(&mut request, &response_text) else {
+                break;
+            };
+            tracing::warn!(status = %response_status, change, "request rejected, sending it again");
+            (response_status, response_text) = self.send(&request).await?;
+        }
+
+        if !response_status.is_success() {
+            return Err(anyhow::anyhow!(
+                "API request failed ({}): {}",
+                response_status,
+                response_text
+            ));
+        }
+
+        let response: OpenAIResponse =
+            serde_json::from_str(&response_text).context("Failed to parse API response")?;
+
+        if let Some(usage) = response.usage {
+            self.usage
+                .lock()
+                .expect("usage lock poisoned")
+                .get_or_insert_with(Usage::default)
+                .add(usage);
+        }
+
+        if response.choices.is_empty() {
+            return Err(anyhow::anyhow!("No response from API"));
+        }
+
+        // Extract the commit messages
+        Ok(response
+            .choices
+            .into_iter()
+            .map(|choice| choice.message.content)
+            .collect())
+    }
+
+    /// Send a request to the chat completions endpoint and return the raw status and body
+    ///
+    /// Rate limits, server errors, timeouts and connection failures are retried
+    /// with exponential backoff, honoring `Retry-After` and rate limit headers
+    /// when the API sends them. A wait longer than `max_rate_limit_wait` fails
+    /// the request instead.
+    async fn send(&self, request: &OpenAIRequest) -> Result<(StatusCode, String)> {
+        let span = tracing::info_span!("request", model = %self.model);
+        self.send_with_retries(request).instrument(span).await
+    }
+
+    async fn send_with_retries(&self, request: &OpenAIRequest) -> Result<(StatusCode, String)> {
+        let endpoint = self.endpoint();
+        let mut retry = 0;
+        tracing::debug!(
+            messages = request.messages.len(),
+            bytes = request
+                .messages
+                .iter()
+                .map(|message| message.content.len())
+                .sum::<usize>(),
+            n = ?request.n,
+            json_mode = request.response_format.is_some(),
+            "built request"
+        );
+
+        loop {
+            tracing::info!(%endpoint, attempt = retry + 1, "sending request");
+            let started = Instant::now();
+            let result = self
+                .http
+                .post(&endpoint)
+                .header("Authorization", format!("Bearer {}", self.api_token))
+                .header("Content-Type", "application/json")
+                .json(request)
+                .send()
+                .await;
+
+            if let Ok(response) = &result {
+                tracing::info!(
+                    status = %response.status(),
+                    elapsed_ms = started.elapsed().as_millis() as u64,
+                    "received response"
+                );
+            }
+            let (reason, delay) = match result {
+                Ok(response) if is_transient(response.status()) => {
+                    let status = response.status();
+                    let delay = retry_after(response.headers());
+                    let text = response.text().await.unwrap_or_default();
+                    if retry >= self.retry_policy.max_retries {
+                        return Ok((status, text));
+                    }
+                    let max_wait = self.retry_policy.max_rate_limit_wait;
+                    if let Some(delay) = delay.filter(|delay| *delay > max_wait) {
+                        anyhow::bail!(
+                            "API request failed ({}): the API asks to wait {}s before retrying, \
+                             longer than max_rate_limit_wait_secs ({}s). {}",
+                            status,
+                            delay.as_secs_f64().ceil(),
+                            max_wait.as_secs(),
+                            text.trim()
+                        );
+                    }
+                    (status.to_string(), delay)
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    let text = response.text().await?;
+                    return Ok((status, text));
+                }
+                Err(err)
+                    if (err.is_timeout() || err.is_connect())
+                        && retry < self.retry_policy.max_retries =>
+                {
+                    (err.to_string(), None)
+                }
+                Err(err) => {
+                    return Err(err).context(format!("Failed to send request to API at {endpoint}"))
+                }
+            };
+
+            let delay = delay.unwrap_or_else(|| self.retry_policy.backoff(retry));
+            retry += 1;
+            tracing::warn!(
+                %reason,
+                delay_ms = delay.as_millis() as u64,
+                retry,
+                "retrying request"
+            );
+            // stderr, so machine-readable output on stdout stays clean
+            eprintln!(
+                "{}",
+                format!(
+                    "⚠️  API request failed ({reason}), retrying in {:.1}s ({retry}/{})...",
+                    delay.as_secs_f64(),
+                    self.retry_policy.max_retries
+                )
+                .yellow()
+            );
+            // Progress displays count down to the retry
+            let until = Instant::now() + delay;
+            *RETRYING_AT.lock().unwrap_or_else(|err| err.into_inner()) = Some(until);
+            tokio::time::sleep(delay).await;
+            let mut retrying_at = RETRYING_AT.lock().unwrap_or_else(|err| err.into_inner());
+            if *retrying_at == Some(until) {
+                *retrying_at = None;
+            }
+        }
+    }
+
+    /// Probe which optional request features the model supports
+    ///
+    /// A plain request is sent first to make sure the connection works at all,
+    /// then each feature is tried on its own. A feature is considered
+    /// unsupported when the API rejects the request shape (400, 404 or 422).
+    pub async fn probe_capabilities(&self) -> Result<ModelCapabilities> {
+        let user_message = || Message {
+            role: "user".to_string(),
+            content: "Reply with the JSON object {\"ok\": true}".to_string(),
+        };
+        let probe_request = |messages: Vec<Message>| OpenAIRequest {
+            model: self.model.clone(),
+            messages,
+            n: None,
+            response_format: None,
+            stream: None,
+            sampling: Sampling::default(),
+            max_completion_tokens: None,
+        };
+
+        // Baseline: without this there is nothing to learn from the other probes
+        let (status, text) = self.send(&probe_request(vec![user_message()])).await?;
+        if !status.is_success() {
+            return Err(anyhow::anyhow!("API request failed ({}): {}", status, text));
+        }
+
+        let system_request = probe_request(vec![
+            Message {
+                role: "system".to_string(),
+                content: "You are a helpful assistant.".to_string(),
+            },
+            user_message(),
+        ]);
+        let system_role = self.probe(&system_request).await?;
+
+        let json_request = OpenAIRequest {
+            response_format: Some(serde_json::json!({ "type": "json_object" })),
+            ..probe_request(vec![user_message()])
+        };
+        let json_mode = self.probe(&json_request).await?;
+
+        let schema_request = OpenAIRequest {
+            response_format: Some(serde_json::json!({
+                "type": "json_schema",
+                "json_schema": {
+                    "name": "probe",
+                    "strict": true,
+                    "schema": {
+                        "type": "object",
+                        "properties": { "ok": { "type": "boolean" } },
+                        "required": ["ok"],
+                        "additionalProperties": false
+                    }
+                }
+            })),
+            ..probe_request(vec![user_message()])
+        };
+        let json_schema = json_mode && self.probe(&schema_request).await?;
+
+        let stream_request = OpenAIRequest {
+            stream: Some(true),
+            ..probe_request(vec![user_message()])
+        };
+        let streaming = self.probe(&stream_request).await?;
+
+        let sampling_request = OpenAIRequest {
+            sampling: Sampling {
+                temperature: Some(0.5),
+                ..Sampling::default()
+            },
+            ..probe_request(vec![user_message()])
+        };
+        let sampling = self.probe(&sampling_request).await?;
+
+        let max_tokens_request = OpenAIRequest {
+            sampling: Sampling {
+                max_tokens: Some(PROBE_MAX_TOKENS),
+                ..Sampling::default()
+            },
+            ..probe_request(vec![user_message()])
+        };
+        let max_completion_tokens = !self.probe(&max_tokens_request).await?;
+
+        Ok(ModelCapabilities {
+            system_role,
+            json_mode,
+            json_schema,
+            streaming,
+            sampling,
+            max_completion_tokens,
+        })
+    }
+
+    /// Send a probe request and report whether the API accepted it
+    async fn probe(&self, request: &OpenAIRequest) -> Result<bool> {
+        let (status, text) = self.send(request).await?;
+
+        match status {
+            s if s.is_success() => Ok(true),
+            StatusCode::BAD_REQUEST | StatusCode::NOT_FOUND | StatusCode::UNPROCESSABLE_ENTITY => {
+                Ok(false)
+            }
+            // Auth, rate limit and server errors say nothing about the feature
+            _ => Err(anyhow::anyhow!("API request failed ({}): {}", status, text)),
+        }
+    }
+}
+
+/// Whether an error means the API could not be reached at all, as opposed to
+/// the API rejecting the request
+pub fn is_unreachable(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause.is::<tokio::time::error::Elapsed>()
+            || cause
+                .downcast_ref::<reqwest::Error>()
+                .is_some_and(|err| err.is_connect() || err.is_timeout())
+    })
+}
+
+/// Offered models whose ids are close to `model`, closest first, to point out typos
+///
+/// Case and separators are ignored, and a few edits are allowed depending on
+/// the length of the id.
+pub fn similar_models<'a>(model: &str, available: &'a [String]) -> Vec<&'a str> {
+    let normalize = |id: &str| -> Vec<char> {
+        id.chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_lowercase())
+            .collect()
+    };
+    let target = normalize(model);
+    let max_distance = (target.len() / 4).max(2);
+
+    let mut similar: Vec<(usize, &str)> = available
+        .iter()
+        .map(|id| (edit_distance(&target, &normalize(id)), id.as_str()))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    similar.sort();
+    similar.into_iter().take(3).map(|(_, id)| id).collect()
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Tokenizer of a model: `o200k_base` for GPT-4o, GPT-4.1 and the o-series,
+/// `cl100k_base` for everything else
+///
+/// Models of other vendors don't publish their tokenizers, `cl100k_base` is
+/// close enough to keep prompts inside their context windows.
+fn tokenizer(model: &str) -> &'static CoreBPE {
+    // Routers prefix the model with its vendor, e.g. `openai/gpt-4o`
+    let name = model.rsplit('/').next().unwrap_or(model);
+    match get_tokenizer(name) {
+        Some(Tokenizer::O200kBase) => o200k_base_singleton(),
+        _ => cl100k_base_singleton(),
+    }
+}
+
+/// Number of tokens in a text with the tokenizer of `model`
+pub fn estimate_tokens(text: &str, model: &str) -> usize {
+    tokenizer(model).encode_ordinary(text).len()
+}
+
+/// Length in bytes of the longest prefix of a text that fits within `max_tokens`
+fn token_prefix_len(text: &str, max_tokens: usize, model: &str) -> usize {
+    let bpe = tokenizer(model);
+    let tokens: Vec<Rank> = bpe
+        .encode_ordinary(text)
+        .into_iter()
+        .take(max_tokens)
+        .collect();
+    let mut len: usize = bpe
+        ._decode_native_and_split(tokens)
+        .map(|bytes| bytes.len())
+        .sum();
+    // A token may end inside a multi-byte character
+    while !text.is_char_boundary(len) {
+        len -= 1;
+    }
+    len
+}
+
+/// Tokens set aside for the note listing the files left out of a truncated diff
+const OMISSION_NOTE_TOKENS: usize = 100;
+
+/// Files named in the note before the rest are only counted
+const OMISSION_NOTE_FILES: usize = 5;
+
+/// Note naming the files whose hunks were left out of a truncated diff, e.g.
+/// `[... left out: Cargo.lock, src/lib.rs (2 of 3 hunks) ...]`
+fn omission_note(omitted: &[(String, usize, usize)], model: &str) -> String {
+    if omitted.is_empty() {
+        return String::new();
+    }
+
+    let mut files: Vec<String> = omitted
+        .iter()
+        .take(OMISSION_NOTE_FILES)
+        .map(|(path, left_out, hunks)| {
+            if left_out < hunks {
+                format!("{path} ({left_out} of {hunks} hunks)")
+            } else {
+                path.clone()
+            }
+        })
+        .collect();
+    if omitted.len() > OMISSION_NOTE_FILES {
+        files.push(format!("{} more", omitted.len() - OMISSION_NOTE_FILES));
+    }
+    let note = format!("[... left out: {} ...]", files.join(", "));
+
+    // Long paths must not push the prompt over the budget
+    let cut = token_prefix_len(&note, OMISSION_NOTE_TOKENS, model);
+    format!("{}\n", &note[..cut])
+}
+
+/// Cut a text at a line boundary so it fits within `max_tokens`
+fn truncate_to_tokens<'a>(text: &'a str, max_tokens: usize, model: &str) -> Cow<'a, str> {
+    if estimate_tokens(text, model) <= max_tokens {
+        return Cow::Borrowed(text);
+    }
+
+    // Leave room for the truncation marker
+    let max_tokens = max_tokens.saturating_sub(estimate_tokens(TRUNCATION_MARKER, model) + 1);
+    let cut = token_prefix_len(text, max_tokens, model);
+    let kept = match text[..cut].rfind('\n') {
+        Some(newline) => &text[..=newline],
+        None => "",
+    };
+
+    Cow::Owned(format!("{kept}{TRUNCATION_MARKER}\n"))
+}
+
+/// Split a generated pull request into its title and body
+///
+/// Models sometimes format the title as a heading or label it, both are removed.
+fn split_pull_request(response: &str) -> (String, String) {
+    let response = response.trim();
+    let (title, body) = response.split_once('\n').unwrap_or((response, ""));
+    let title = title.trim().trim_start_matches('#').trim();
+    let title = title.strip_prefix("Title:").unwrap_or(title).trim();
+
+    (
+        title.trim_matches('*').trim().to_string(),
+        body.trim().to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::{
+        matchers::{body_partial_json, body_string_contains, header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    #[tokio::test]
+    async fn test_generate_commit_message() -> Result<()> {
+        // Start a mock server
+        let mock_server = MockServer::start().await;
+
+        // Create a mock response
+        let mock_response = serde_json::json!({
+            "choices": [{
+                "message": {
+                    "role": "assistant",
+                    "content": "feat: improve greeting message with username support"
+                }
+            }]
+        });
+
+        // Set up the mock expectation
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(header("Authorization", "Bearer test_token"))
+            .and(header("Content-Type", "application/json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(mock_response))
+            .mount(&mock_server)
+            .await;
+
+        // Sample git diff
+        let diff = r#"
+            diff --git a/src/main.rs b/src/main.rs
+            index 1234567..89abcdef 100644
+            --- a/src/main.rs
+            +++ b/src/main.rs
+            @@ -1,3 +1,4 @@
+            +use std::env;
+            fn main() {
+            -    println!("Hello, world!");
+            +    println!("Hello, {}!", env::var("USER").unwrap_or("world".to_string()));
+            }
+            "#;
+
+        let system_prompt = "You are a helpful assistant.";
+        let user_prompt =
+            "Here is the git diff of the staged changes. Generate a commit message that \
+            follows the conventional commit format and best practices. Focus on what changed \
+            and why, not how it changed:\n\n\
+            ```diff\n{}\n```";
+        let model = "gpt-3.5-turbo";
+
+        // Use the mock server URL instead of the real OpenAI API
+        let commit_message = LlmClient::new("test_token", &mock_server.uri(), model)
+            .generate_commit_messages(diff, system_prompt, user_prompt, 1)
+            .await?
+            .remove(0);
+
+        // Verify the response
+        assert_eq!(
+            commit_message,
+            "feat: improve greeting message with username support"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_commit_messages_multiple_candidates() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        // Provider honours `n` and returns every candidate at once, fences and all
+        let mock_response = serde_json::json!({
+            "choices": [
+                { "message": { "role": "assistant", "content": "feat: first" } },
+                { "message": { "role": "assistant", "content": "```\n\"feat: second\"  \n```" } },
+                { "message": { "role": "assistant", "content": "feat: third" } }
+            ]
+        });
+
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(serde_json::json!({ "n": 3 })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(mock_response))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = LlmClient::new("test_token", &mock_server.uri(), "gpt-3.5-turbo");
+        let candidates = client
+            .generate_commit_messages("some diff", "system prompt", "user prompt", 3)
+            .await?;
+
+        assert_eq!(
+            candidates,
+            vec!["feat: first", "feat: second", "feat: third"]
+        );
+        assert_eq!(client.usage(), None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_regenerate_commit_message() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        // Sampled at the given temperature, with the hint added to the system prompt
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(serde_json::json!({
+                "temperature": 1.25,
+                "max_tokens": 200,
+                "messages": [
+                    {
+                        "role": "system",
+                        "content": format!("system prompt\n\n{REGENERATE_HINT_PREFIX} mention the cache")
+                    },
+                    { "role": "user", "content": "user prompt: some diff" }
+                ]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [
+                    { "message": { "role": "assistant", "content": "perf: cache greetings" } }
+                ]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = LlmClient::new("test_token", &mock_server.uri(), "gpt-3.5-turbo")
+            .with_sampling(Sampling {
+                temperature: Some(0.2),
+                top_p: None,
+                max_tokens: Some(200),
+            });
+        let message = client
+            .regenerate_commit_message(
+                "some diff",
+                "system prompt",
+                "user prompt: {}",
+                Some("mention the cache"),
+                1.25,
+            )
+            .await?;
+        assert_eq!(message, "perf: cache greetings");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_models() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/models"))
+            .and(header("Authorization", "Bearer test_token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "object": "list",
+                "data": [
+                    { "id": "gpt-4o", "object": "model", "owned_by": "openai" },
+                    { "id": "gpt-4o-mini", "object": "model" },
+                    { "id": "gpt-3.5-turbo", "object": "model" }
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = LlmClient::new("test_token", &mock_server.uri(), "gpt-4o");
+        assert_eq!(
+            client.list_models().await?,
+            vec!["gpt-3.5-turbo", "gpt-4o", "gpt-4o-mini"]
+        );
+
+        let client = LlmClient::new("wrong_token", &mock_server.uri(), "gpt-4o");
+        assert!(client.list_models().await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_embed() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/embeddings"))
+            .and(body_string_contains("text-embedding-3-small"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "object": "list",
+                "data": [
+                    { "object": "embedding", "index": 1, "embedding": [0.0, 1.0] },
+                    { "object": "embedding", "index": 0, "embedding": [1.0, 0.0] }
+                ],
+                "usage": { "prompt_tokens": 8, "total_tokens": 8 }
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = LlmClient::new("test_token", &mock_server.uri(), "gpt-4o");
+        let texts = vec!["fix: retry".to_string(), "docs: readme".to_string()];
+        assert_eq!(
+            client.embed("text-embedding-3-small", &texts).await?,
+            vec![vec![1.0, 0.0], vec![0.0, 1.0]]
+        );
+        assert_eq!(client.usage().map(|usage| usage.prompt_tokens), Some(8));
+        assert!(client
+            .embed("text-embedding-3-small", &texts[..1])
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_similar_models() {
+        let models: Vec<String> = ["gpt-4o", "gpt-4o-mini", "gpt-3.5-turbo", "claude-3-haiku"]
+            .iter()
+            .map(|id| id.to_string())
+            .collect();
+
+        // Separators and case don't count as edits
+        assert_eq!(similar_models("gpt4o", &models), vec!["gpt-4o"]);
+        assert_eq!(
+            similar_models("GPT-3.5-Turob", &models),
+            vec!["gpt-3.5-turbo"]
+        );
+        assert_eq!(
+            similar_models("claude-3-haku", &models),
+            vec!["claude-3-haiku"]
+        );
+        assert!(similar_models("llama-3-70b", &models).is_empty());
+    }
+
+    #[test]
+    fn test_sampling() {
+        // Unset parameters are left to the API
+        let client = LlmClient::new("test_token", "https://api.example.com", "gpt-4o");
+        let body = &client.request_skeleton("system prompt")["body"];
+        assert!(body.get("temperature").is_none());
+        assert!(body.get("max_tokens").is_none());
+
+        let client = client.with_sampling(Sampling {
+            temperature: Some(0.3),
+            top_p: Some(0.9),
+            max_tokens: Some(400),
+        });
+        let body = &client.request_skeleton("system prompt")["body"];
+        assert_eq!(body["temperature"], 0.3);
+        assert_eq!(body["top_p"], 0.9);
+        assert_eq!(body["max_tokens"], 400);
+    }
+
+    #[test]
+    fn test_commit_prompt() {
+        let client = LlmClient::new("test_token", "https://api.example.com", "gpt-4o");
+        assert_eq!(
+            client.commit_prompt("+fn main() {}", "system prompt", "Diff:\n{}"),
+            vec![
+                ("system".to_string(), "system prompt".to_string()),
+                ("user".to_string(), "Diff:\n+fn main() {}".to_string()),
+            ]
+        );
+
+        // Models without a system role read one user message
+        let client = LlmClient::new("test_token", "https://api.example.com", "o1-mini");
+        assert_eq!(
+            client.commit_prompt("+fn main() {}", "system prompt", "Diff:\n{}"),
+            vec![(
+                "user".to_string(),
+                "system prompt\n\nDiff:\n+fn main() {}".to_string()
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refine_commit_message() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let reply = |content: &str| {
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{ "message": { "role": "assistant", "content": content } }]
+            }))
+        };
+        let system = serde_json::json!({ "role": "system", "content": "system prompt" });
+        let user = serde_json::json!({ "role": "user", "content": "user prompt: some diff" });
+        let turn = |previous: &str, instructions: &str| {
+            [
+                serde_json::json!({ "role": "assistant", "content": previous }),
+                serde_json::json!({
+                    "role": "user",
+                    "content": REFINE_USER_PROMPT.replace("{}", instructions)
+                }),
+            ]
+        };
+        let [first_answer, first_instructions] = turn("feat: add cache", "mention the TTL");
+        let [second_answer, second_instructions] = turn("feat: add cache with TTL", "shorter");
+
+        // The second turn carries the first one, with the message the user saw. Arrays
+        // match by prefix, so the longer conversation is mounted first
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(serde_json::json!({
+                "messages": [
+                    &system, &user, &first_answer, &first_instructions,
+                    &second_answer, &second_instructions
+                ]
+            })))
+            .respond_with(reply("feat: add TTL cache"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(serde_json::json!({
+                "messages": [&system, &user, &first_answer, &first_instructions]
+            })))
+            .respond_with(reply("feat: add cache, expiring after the TTL"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = LlmClient::new("test_token", &mock_server.uri(), "gpt-3.5-turbo");
+        let mut conversation =
+            client.start_conversation("some diff", "system prompt", "user prompt: {}");
+        let refined = client
+            .refine_commit_message(&mut conversation, "feat: add cache", "mention the TTL")
+            .await?;
+        assert_eq!(refined, "feat: add cache, expiring after the TTL");
+
+        // The user edited the message in between, that version is what gets refined
+        let refined = client
+            .refine_commit_message(&mut conversation, "feat: add cache with TTL", "shorter")
+            .await?;
+        assert_eq!(refined, "feat: add TTL cache");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_commit_messages_provider_ignores_n() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        // Provider ignores `n` and always returns a single choice
+        let mock_response = serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": "fix: only one" } }],
+            "usage": { "prompt_tokens": 40, "completion_tokens": 5, "total_tokens": 45 }
+        });
+
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(mock_response))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let client = LlmClient::new("test_token", &mock_server.uri(), "gpt-3.5-turbo");
+        let candidates = client
+            .generate_commit_messages("some diff", "system prompt", "user prompt", 2)
+            .await?;
+
+        assert_eq!(candidates.len(), 2);
+        // Usage adds up over both requests
+        assert_eq!(
+            client.usage(),
+            Some(Usage {
+                prompt_tokens: 80,
+                completion_tokens: 10,
+                total_tokens: 90,
+            })
+        );
+        assert_eq!(
+            client.take_usage().map(|usage| usage.total_tokens),
+            Some(90)
+        );
+        assert_eq!(client.usage(), None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_system_prompt_merged_without_system_role() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        let mock_response = serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": "docs: update readme" } }]
+        });
+
+        // Only a single user message carrying both prompts is accepted
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(serde_json::json!({
+                "messages": [{ "role": "user", "content": "system prompt\n\nuser prompt" }]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(mock_response))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let capabilities = ModelCapabilities {
+            system_role: false,
+            ..Default::default()
+        };
+        let candidates = LlmClient::new("test_token", &mock_server.uri(), "o1-mini")
+            .with_capabilities(capabilities)
+            .generate_commit_messages("some diff", "system prompt", "user prompt", 1)
+            .await?;
+
+        assert_eq!(candidates, vec!["docs: update readme"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_structured_output() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        let fields = serde_json::json!({
+            "type": "feat",
+            "scope": "llm",
+            "subject": "request commit messages as JSON",
+            "body": null,
+            "breaking": false
+        });
+        let mock_response = serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": fields.to_string() } }]
+        });
+
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(serde_json::json!({
+                "response_format": {
+                    "type": "json_schema",
+                    "json_schema": { "name": "commit_message" }
+                }
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(mock_response))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let candidates = LlmClient::new("test_token", &mock_server.uri(), "gpt-4o")
+            .with_structured_output(true)
+            .generate_commit_messages("some diff", "system prompt", "user prompt", 1)
+            .await?;
+        assert_eq!(
+            candidates,
+            vec!["feat(llm): request commit messages as JSON"]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_structured_output_rejected_format() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        // The format is rejected and the reply isn't JSON: the text is used as it is
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({
+                "response_format": { "type": "json_object" }
+            })))
+            .respond_with(ResponseTemplate::new(400).set_body_string("unknown response_format"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{ "message": { "role": "assistant", "content": "fix: plain text" } }]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let capabilities = ModelCapabilities {
+            json_schema: false,
+            ..Default::default()
+        };
+        let candidates = LlmClient::new("test_token", &mock_server.uri(), "deepseek-chat")
+            .with_capabilities(capabilities)
+            .with_structured_output(true)
+            .generate_commit_messages("some diff", "system prompt", "user prompt", 1)
+            .await?;
+        assert_eq!(candidates, vec!["fix: plain text"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reasoning_model_request() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        // Known reasoning models get max_completion_tokens and no temperature up front
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({
+                "max_completion_tokens": 300,
+                "messages": [{ "role": "user", "content": "system prompt\n\nuser prompt" }]
+            })))
+            .and(|request: &wiremock::Request| {
+                let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+                body.get("temperature").is_none() && body.get("max_tokens").is_none()
+            })
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{ "message": { "role": "assistant", "content": "fix: reason" } }]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let sampling = Sampling {
+            temperature: Some(0.2),
+            top_p: None,
+            max_tokens: Some(300),
+        };
+        let candidates = LlmClient::new("test_token", &mock_server.uri(), "o1-mini")
+            .with_sampling(sampling)
+            .generate_commit_messages("some diff", "system prompt", "user prompt", 1)
+            .await?;
+        assert_eq!(candidates, vec!["fix: reason"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rejected_parameters_adapted() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        let unsupported = |param: &str| {
+            ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                "error": {
+                    "message": format!("Unsupported parameter: '{param}'"),
+                    "param": param,
+                    "code": "unsupported_parameter"
+                }
+            }))
+        };
+        let has = |field: &'static str| {
+            move |request: &wiremock::Request| {
+                let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+                body.get(field).is_some()
+            }
+        };
+
+        // A model aic doesn't know rejects one parameter after the other
+        Mock::given(method("POST"))
+            .and(has("max_tokens"))
+            .respond_with(unsupported("max_tokens"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(has("temperature"))
+            .respond_with(unsupported("temperature"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({
+                "messages": [{ "role": "system" }]
+            })))
+            .respond_with(unsupported("messages[0].role"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({
+                "max_completion_tokens": 300,
+                "messages": [{ "role": "user", "content": "system\n\nuser" }]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{ "message": { "role": "assistant", "content": "done" } }]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let sampling = Sampling {
+            temperature: Some(0.2),
+            top_p: None,
+            max_tokens: Some(300),
+        };
+        let message = LlmClient::new("test_token", &mock_server.uri(), "reasoner-large")
+            .with_sampling(sampling)
+            .complete("system", "user")
+            .await?;
+        assert_eq!(message, "done");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_probe_capabilities() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        let ok_response = serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": "{\"ok\": true}" } }]
+        });
+
+        // System role, streaming, temperature and max_tokens are rejected, JSON mode is accepted
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({ "temperature": 0.5 })))
+            .respond_with(ResponseTemplate::new(400).set_body_string("temperature unsupported"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                serde_json::json!({ "max_tokens": PROBE_MAX_TOKENS }),
+            ))
+            .respond_with(ResponseTemplate::new(400).set_body_string("max_tokens unsupported"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({
+                "messages": [{ "role": "system" }]
+            })))
+            .respond_with(ResponseTemplate::new(400).set_body_string("system role unsupported"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({ "stream": true })))
+            .respond_with(ResponseTemplate::new(400).set_body_string("stream unsupported"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(ok_response))
+            .mount(&mock_server)
+            .await;
+
+        let capabilities = LlmClient::new("test_token", &mock_server.uri(), "o1-mini")
+            .probe_capabilities()
+            .await?;
+
+        assert!(!capabilities.system_role);
+        assert!(capabilities.json_mode);
+        assert!(capabilities.json_schema);
+        assert!(!capabilities.streaming);
+        assert!(!capabilities.sampling);
+        assert!(capabilities.max_completion_tokens);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_probe_capabilities_auth_error() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(401).set_body_string("Unauthorized"))
+            .mount(&mock_server)
+            .await;
+
+        let result = LlmClient::new("bad_token", &mock_server.uri(), "gpt-4")
+            .probe_capabilities()
+            .await;
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens("", "gpt-4"), 0);
+        assert_eq!(estimate_tokens("hello world", "gpt-4"), 2);
+        assert_eq!(estimate_tokens("tiktoken is great!", "gpt-4"), 6);
+        assert_eq!(estimate_tokens("提交信息", "gpt-4"), 2);
+
+        // o200k_base packs non-English text tighter than cl100k_base
+        assert_eq!(estimate_tokens("こんにちは世界", "gpt-4"), 4);
+        assert_eq!(estimate_tokens("こんにちは世界", "gpt-4o"), 2);
+        assert_eq!(estimate_tokens("Привет, мир!", "gpt-4o-mini"), 5);
+        assert_eq!(estimate_tokens("Привет, мир!", "o3-mini"), 5);
+
+        // The vendor prefix of a router is ignored, unknown models use cl100k_base
+        assert_eq!(estimate_tokens("こんにちは世界", "openai/gpt-4o"), 2);
+        assert_eq!(estimate_tokens("こんにちは世界", "deepseek-chat"), 4);
+    }
+
+    #[test]
+    fn test_truncate_to_tokens() {
+        let text = "fn main() {}\n".repeat(100);
+        assert!(matches!(
+            truncate_to_tokens(&text, 1000, "gpt-4o"),
+            Cow::Borrowed(_)
+        ));
+
+        let truncated = truncate_to_tokens(&text, 50, "gpt-4o");
+        assert!(truncated.ends_with(&format!("fn main() {{}}\n{TRUNCATION_MARKER}\n")));
+        assert!(estimate_tokens(&truncated, "gpt-4o") <= 50);
+    }
+
+    #[test]
+    fn test_input_token_budget() {
+        let client = LlmClient::new("token", "https://api.openai.com/v1", "gpt-4o-mini");
+        assert_eq!(client.input_token_budget(), 128_000);
+
+        let client = LlmClient::new("token", "https://api.openai.com/v1", "gpt-4");
+        assert_eq!(client.input_token_budget(), 8_000);
+
+        let client = LlmClient::new("token", "https://example.com", "custom-model");
+        assert_eq!(client.input_token_budget(), DEFAULT_CONTEXT_WINDOW);
+
+        let client = client.with_max_input_tokens(Some(500));
+        assert_eq!(client.input_token_budget(), 500);
+    }
+
+    #[test]
+    fn test_fit_diff() {
+        let diff = "+line one\n+line two\n".repeat(200);
+
+        // Small diffs pass through untouched
+        let client = LlmClient::new("token", "https://example.com", "gpt-4o");
+        assert!(matches!(
+            client.fit_diff(&diff, "system", "user {}"),
+            Cow::Borrowed(_)
+        ));
+
+        // Large diffs are cut at a line boundary and marked
+        let client = client.with_max_input_tokens(Some(200));
+        let fitted = client.fit_diff(&diff, "system", "user {}");
+        assert!(fitted.len() < diff.len());
+        assert!(fitted.ends_with(&format!("{TRUNCATION_MARKER}\n")));
+        assert!(fitted
+            .lines()
+            .filter(|line| *line != TRUNCATION_MARKER)
+            .all(|line| line == "+line one" || line == "+line two"));
+        assert!(
+            estimate_tokens(&fitted, "gpt-4o") + estimate_tokens("system user ", "gpt-4o") <= 200
+        );
+    }
+
+    #[test]
+    fn test_fit_diff_keeps_source_hunks() {
+        let file = |path: &str, lines: usize| {
+            format!(
+                "diff --git a/{path} b/{path}\n--- a/{path}\n+++ b/{path}\n@@ -1 +1 @@\n{}",
+                "+changed line\n".repeat(lines)
+            )
+        };
+        let diff = file("Cargo.lock", 400) + &file("README.md", 20) + &file("src/lib.rs", 20);
+
+        let client = LlmClient::new("token", "https://example.com", "gpt-4o")
+            .with_max_input_tokens(Some(400));
+        let fitted = client.fit_diff(&diff, "system", "user {}");
+        assert!(fitted.starts_with("diff --git a/src/lib.rs"));
+        assert!(fitted.contains("diff --git a/README.md"));
+        assert!(!fitted.contains("Cargo.lock\n@@"));
+        assert!(fitted.ends_with(&format!(
+            "[... left out: Cargo.lock ...]\n{TRUNCATION_MARKER}\n"
+        )));
+        assert!(
+            estimate_tokens(&fitted, "gpt-4o") + estimate_tokens("system user ", "gpt-4o") <= 400
+        );
+
+        assert_eq!(
+            omission_note(
+                &[("a.rs".to_string(), 1, 2), ("b.md".to_string(), 1, 1)],
+                "gpt-4o"
+            ),
+            "[... left out: a.rs (1 of 2 hunks), b.md ...]\n"
+        );
+    }
+
+    #[test]
+    fn test_summary_chunks() {
+        let file = |path: &str, lines: usize| FileDiff {
+            path: path.to_string(),
+            diff: format!(
+                "diff --git a/{path} b/{path}\n{}",
+                "+changed line\n".repeat(lines)
+            ),
+        };
+        let files = vec![file("a.rs", 10), file("b.rs", 10), file("c.rs", 2000)];
+
+        // Everything fits into one request
+        let client = LlmClient::new("token", "https://example.com", "gpt-4o");
+        let chunks = client.summary_chunks(&files);
+        assert_eq!(chunks.len(), 1);
+
+        // Small files are packed together, the huge one is truncated on its own
+        let client = client.with_max_input_tokens(Some(400));
+        let chunks = client.summary_chunks(&files);
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[0].contains("a/a.rs") && chunks[0].contains("a/b.rs"));
+        assert!(chunks[1].starts_with("diff --git a/c.rs"));
+        assert!(chunks[1].contains(TRUNCATION_MARKER));
+        assert!(chunks
+            .iter()
+            .all(|chunk| estimate_tokens(chunk, "gpt-4o") <= client.input_token_budget()));
+    }
+
+    #[tokio::test]
+    async fn test_summarize_chunk() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        let mock_response = serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": "src/main.rs: add logging" } }]
+        });
+
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(serde_json::json!({
+                "messages": [{ "role": "system", "content": SUMMARY_SYSTEM_PROMPT }]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(mock_response))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let summary = LlmClient::new("test_token", &mock_server.uri(), "gpt-4o")
+            .summarize_chunk("diff --git a/src/main.rs b/src/main.rs\n+log::info!()")
+            .await?;
+        assert_eq!(summary, "src/main.rs: add logging");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_grade_commit_message() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        let mock_response = serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": "Score: 9/10" } }]
+        });
+
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(serde_json::json!({
+                "messages": [
+                    { "role": "system", "content": GRADE_SYSTEM_PROMPT },
+                    {
+                        "role": "user",
+                        "content": "Commit message:\n\n```text\nfeat: add logging\n```\n\n\
+                                    Diff of the commit:\n\n```diff\n+log::info!()\n```"
+                    }
+                ]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(mock_response))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let grade = LlmClient::new("test_token", &mock_server.uri(), "gpt-4o")
+            .grade_commit_message("feat: add logging\n", "+log::info!()")
+            .await?;
+        assert_eq!(grade, "Score: 9/10");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_group_changes() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        let mock_response = serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": "[]" } }]
+        });
 
-#[derive(Deserialize)]
-struct Choice {
-    message: Message,
-}
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(serde_json::json!({
+                "messages": [
+                    { "role": "system", "content": SPLIT_SYSTEM_PROMPT },
+                    {
+                        "role": "user",
+                        "content": "Staged files:\n\nsrc/a.rs\nREADME.md\n\n\
+                                    Diff:\n\n```diff\n+fn a() {}\n```"
+                    }
+                ]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(mock_response))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
 
-#[derive(Deserialize)]
-struct OpenAIResponse {
-    choices: Vec<Choice>,
-}
+        let files = vec!["src/a.rs".to_string(), "README.md".to_string()];
+        let groups = LlmClient::new("test_token", &mock_server.uri(), "gpt-4o")
+            .group_changes(&files, "+fn a() {}")
+            .await?;
+        assert_eq!(groups, "[]");
 
-/// Generate a commit message based on the git diff
-pub async fn generate_commit_message(
-    diff: &str,
-    system_prompt: &str,
-    user_prompt: &str,
-    api_token: &str,
-    api_base_url: &str,
-    model: &str,
-) -> Result<String> {
-    let client = Client::new();
-
-    // Prepare the request to OpenAI API
-    let request = OpenAIRequest {
-        model: model.to_string(),
-        messages: vec![
-            Message {
-                role: "system".to_string(),
-                content: system_prompt.to_string(),
-            },
-            Message {
-                role: "user".to_string(),
-                content: user_prompt.replace("{}", diff),
-            },
-        ],
-    };
+        Ok(())
+    }
 
-    // Construct the full API endpoint URL
-    let endpoint = format!("{}/chat/completions", api_base_url.trim_end_matches('/'));
+    #[tokio::test]
+    async fn test_explain_commit() -> Result<()> {
+        let mock_server = MockServer::start().await;
 
-    // Send the request to the API
-    let response = client
-        .post(&endpoint)
-        .header("Authorization", format!("Bearer {api_token}"))
-        .header("Content-Type", "application/json")
-        .json(&request)
-        .send()
-        .await
-        .context(format!("Failed to send request to API at {endpoint}"))?;
+        let mock_response = serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": "Adds logging." } }]
+        });
 
-    // Parse the response
-    let response_status = response.status();
-    let response_text = response.text().await?;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(serde_json::json!({
+                "messages": [
+                    { "role": "system", "content": EXPLAIN_COMMIT_SYSTEM_PROMPT },
+                    {
+                        "role": "user",
+                        "content": "Commit message:\n\n```text\nfeat: add logging\n```\n\n\
+                                    Diff of the commit:\n\n```diff\n+log::info!()\n```"
+                    }
+                ]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(mock_response))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
 
-    if !response_status.is_success() {
-        return Err(anyhow::anyhow!(
-            "API request failed ({}): {}",
-            response_status,
-            response_text
-        ));
+        let explanation = LlmClient::new("test_token", &mock_server.uri(), "gpt-4o")
+            .explain_commit("feat: add logging\n", "+log::info!()")
+            .await?;
+        assert_eq!(explanation, "Adds logging.");
+
+        Ok(())
     }
 
-    let response: OpenAIResponse =
-        serde_json::from_str(&response_text).context("Failed to parse API response")?;
+    #[tokio::test]
+    async fn test_review_diff() -> Result<()> {
+        let mock_server = MockServer::start().await;
 
-    // Extract the commit message
-    let commit_message = response
-        .choices
-        .first()
-        .context("No response from API")?
-        .message
-        .content
-        .clone();
+        let mock_response = serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": "No issues found." } }]
+        });
 
-    Ok(commit_message)
-}
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(serde_json::json!({
+                "messages": [
+                    { "role": "system", "content": "Find bugs." },
+                    {
+                        "role": "user",
+                        "content": "Review the following staged changes:\n\n```diff\n+x\n```"
+                    }
+                ]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(mock_response))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use wiremock::{
-        matchers::{header, method, path},
-        Mock, MockServer, ResponseTemplate,
-    };
+        let review = LlmClient::new("test_token", &mock_server.uri(), "gpt-4o")
+            .review_diff("Find bugs.", "+x")
+            .await?;
+        assert_eq!(review, "No issues found.");
+
+        Ok(())
+    }
 
     #[tokio::test]
-    async fn test_generate_commit_message() -> Result<()> {
-        // Start a mock server
+    async fn test_derive_style_guide() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(serde_json::json!({
+                "messages": [
+                    { "role": "system", "content": LEARN_SYSTEM_PROMPT },
+                    {
+                        "role": "user",
+                        "content": "Commit messages, newest first, separated by lines of `---`:\n\n\
+                                    feat(api): add search\n---\nfix(api): trim input\n\nBody"
+                    }
+                ]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [
+                    { "message": { "role": "assistant", "content": "- Always use a scope" } }
+                ]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let guide = LlmClient::new("test_token", &mock_server.uri(), "gpt-4o")
+            .derive_style_guide(&[
+                "feat(api): add search\n".to_string(),
+                "fix(api): trim input\n\nBody".to_string(),
+            ])
+            .await?;
+        assert_eq!(guide, "- Always use a scope");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_squash_message() -> Result<()> {
         let mock_server = MockServer::start().await;
 
-        // Create a mock response
         let mock_response = serde_json::json!({
-            "choices": [{
-                "message": {
-                    "role": "assistant",
-                    "content": "feat: improve greeting message with username support"
-                }
-            }]
+            "choices": [{ "message": { "role": "assistant", "content": "feat: add logging" } }]
         });
 
-        // Set up the mock expectation
         Mock::given(method("POST"))
             .and(path("/chat/completions"))
-            .and(header("Authorization", "Bearer test_token"))
-            .and(header("Content-Type", "application/json"))
+            .and(body_partial_json(serde_json::json!({
+                "messages": [
+                    {
+                        "role": "system",
+                        "content": format!(
+                            "Be brief.\n\n{SQUASH_PROMPT_PREFIX}\n- feat: add logger\n- fix: typo"
+                        )
+                    },
+                    { "role": "user", "content": "Diff:\n+log::info!()" }
+                ]
+            })))
             .respond_with(ResponseTemplate::new(200).set_body_json(mock_response))
+            .expect(1)
             .mount(&mock_server)
             .await;
 
-        // Sample git diff
-        let diff = r#"
-            diff --git a/src/main.rs b/src/main.rs
-            index 1234567..89abcdef 100644
-            --- a/src/main.rs
-            +++ b/src/main.rs
-            @@ -1,3 +1,4 @@
-            +use std::env;
-            fn main() {
-            -    println!("Hello, world!");
-            +    println!("Hello, {}!", env::var("USER").unwrap_or("world".to_string()));
-            }
-            "#;
-
-        let system_prompt = "You are a helpful assistant.";
-        let user_prompt =
-            "Here is the git diff of the staged changes. Generate a commit message that \
-            follows the conventional commit format and best practices. Focus on what changed \
-            and why, not how it changed:\n\n\
-            ```diff\n{}\n```";
-        let model = "gpt-3.5-turbo";
+        let commits = vec![
+            "feat: add logger\n\nWith levels.".to_string(),
+            "fix: typo".to_string(),
+        ];
+        let message = LlmClient::new("test_token", &mock_server.uri(), "gpt-4o")
+            .generate_squash_message(&commits, "+log::info!()", "Be brief.", "Diff:\n{}")
+            .await?;
+        assert_eq!(message, "feat: add logging");
 
-        // Use the mock server URL instead of the real OpenAI API
-        let commit_message = generate_commit_message(
-            diff,
-            system_prompt,
-            user_prompt,
-            "test_token",
-            &mock_server.uri(),
-            model,
-        )
-        .await?;
+        Ok(())
+    }
 
-        // Verify the response
+    #[test]
+    fn test_split_pull_request() {
         assert_eq!(
-            commit_message,
-            "feat: improve greeting message with username support"
+            split_pull_request("Add OAuth login\n\n## Changes\n- Add providers\n"),
+            (
+                "Add OAuth login".to_string(),
+                "## Changes\n- Add providers".to_string()
+            )
         );
+        assert_eq!(
+            split_pull_request("# Title: **Add OAuth login**"),
+            ("Add OAuth login".to_string(), String::new())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_generate_pull_request() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        let mock_response = serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": "Add logging\n\nAdds logs." } }]
+        });
+
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(serde_json::json!({
+                "messages": [
+                    { "role": "system", "content": PULL_REQUEST_SYSTEM_PROMPT },
+                    {
+                        "role": "user",
+                        "content": "Commits on the branch:\n\n- feat: add logging\n- fix: typo\n\n\
+                                    Diff against the base branch:\n\n```diff\n+log::info!()\n```"
+                    }
+                ]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(mock_response))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let commits = vec![
+            "feat: add logging\n\nWith details".to_string(),
+            "fix: typo".to_string(),
+        ];
+        let (title, body) = LlmClient::new("test_token", &mock_server.uri(), "gpt-4o")
+            .generate_pull_request(&commits, "+log::info!()")
+            .await?;
+        assert_eq!(title, "Add logging");
+        assert_eq!(body, "Adds logs.");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_release_notes() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        let mock_response = serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": "### Added\n\n- OAuth login" } }]
+        });
+
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(serde_json::json!({
+                "messages": [
+                    { "role": "system", "content": RELEASE_NOTES_SYSTEM_PROMPT },
+                    {
+                        "role": "user",
+                        "content": "Commits since the last release:\n\n### Added\n\n- auth: add OAuth login"
+                    }
+                ]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(mock_response))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let notes = LlmClient::new("test_token", &mock_server.uri(), "gpt-4o")
+            .write_release_notes("### Added\n\n- auth: add OAuth login")
+            .await?;
+        assert_eq!(notes, "### Added\n\n- OAuth login");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_tag_message() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        let mock_response = serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": "Adds logging\n\n- Logging" } }]
+        });
+
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(serde_json::json!({
+                "messages": [
+                    { "role": "system", "content": TAG_SYSTEM_PROMPT },
+                    {
+                        "role": "user",
+                        "content": "Tag: v1.1.0\n\nCommits since v1.0.0:\n\n- feat: add logging"
+                    }
+                ]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(mock_response))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let commits = vec!["feat: add logging\n\nWith details".to_string()];
+        let message = LlmClient::new("test_token", &mock_server.uri(), "gpt-4o")
+            .generate_tag_message("v1.1.0", Some("v1.0.0"), &commits)
+            .await?;
+        assert_eq!(message, "Adds logging\n\n- Logging");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pricing() {
+        let pricing = LlmClient::new("token", "https://api.example.com", "gpt-4o-mini-2024-07-18")
+            .pricing()
+            .unwrap();
+        assert_eq!(pricing, ModelPricing::new(0.15, 0.60));
+        assert!(LlmClient::new("token", "https://api.example.com", "llama3")
+            .pricing()
+            .is_none());
+
+        // One million prompt tokens plus 200 output tokens per candidate
+        let cost = ModelPricing::new(2.0, 10.0).estimate_cost(1_000_000, 2);
+        assert!((cost - 2.004).abs() < 1e-9);
+
+        let usage = Usage {
+            prompt_tokens: 500_000,
+            completion_tokens: 1000,
+            total_tokens: 501_000,
+        };
+        assert!((ModelPricing::new(2.0, 10.0).cost(&usage) - 1.01).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_retry_backoff() {
+        let policy = RetryPolicy {
+            max_retries: 5,
+            base_delay: Duration::from_millis(100),
+            ..RetryPolicy::default()
+        };
+
+        // Doubles every retry with at most 50% jitter on top
+        for retry in 0..4 {
+            let expected = 100 * 2u64.pow(retry as u32);
+            let delay = policy.backoff(retry).as_millis() as u64;
+            assert!((expected..=expected + expected / 2).contains(&delay));
+        }
+
+        // Never waits longer than the ceiling
+        assert_eq!(policy.backoff(30), MAX_RETRY_DELAY);
+    }
+
+    #[tokio::test]
+    async fn test_retry_on_transient_errors() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        let mock_response = serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": "fix: retry" } }]
+        });
+
+        // Rate limited once, then a server error, then success
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(mock_response))
+            .mount(&mock_server)
+            .await;
+
+        let policy = RetryPolicy {
+            max_retries: 2,
+            base_delay: Duration::from_millis(1),
+            ..RetryPolicy::default()
+        };
+        let message = LlmClient::new("test_token", &mock_server.uri(), "gpt-4o")
+            .with_retry_policy(policy)
+            .complete("system", "user")
+            .await?;
+        assert_eq!(message, "fix: retry");
 
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_retry_gives_up() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500).set_body_string("Internal error"))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let policy = RetryPolicy {
+            max_retries: 1,
+            base_delay: Duration::from_millis(1),
+            ..RetryPolicy::default()
+        };
+        let result = LlmClient::new("test_token", &mock_server.uri(), "gpt-4o")
+            .with_retry_policy(policy)
+            .complete("system", "user")
+            .await;
+
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("500"));
+        assert!(err.contains("Internal error"));
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(500)))
+            .mount(&mock_server)
+            .await;
+
+        let policy = RetryPolicy {
+            max_retries: 0,
+            base_delay: Duration::from_millis(1),
+            ..RetryPolicy::default()
+        };
+        let result = LlmClient::new("test_token", &mock_server.uri(), "gpt-4o")
+            .with_timeout(Duration::from_millis(50))
+            .with_retry_policy(policy)
+            .complete("system", "user")
+            .await;
+
+        let err = result.unwrap_err();
+        assert!(err.to_string().contains("Failed to send request"));
+        assert!(is_unreachable(&err));
+        assert!(!is_unreachable(&anyhow::anyhow!(
+            "API request failed (401)"
+        )));
+    }
+
+    #[test]
+    fn test_retry_after_header() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert(reqwest::header::RETRY_AFTER, "2".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(2)));
+
+        // HTTP dates are not supported and fall back to backoff
+        headers.insert(
+            reqwest::header::RETRY_AFTER,
+            "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+        );
+        assert_eq!(retry_after(&headers), None);
+
+        // The limit that ran out decides how long to wait
+        headers.insert("x-ratelimit-remaining-requests", "0".parse().unwrap());
+        headers.insert("x-ratelimit-reset-requests", "1m30s".parse().unwrap());
+        headers.insert("x-ratelimit-remaining-tokens", "1200".parse().unwrap());
+        headers.insert("x-ratelimit-reset-tokens", "6m0s".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(90)));
+
+        headers.insert("retry-after-ms", "250".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_millis(250)));
+    }
+
+    #[test]
+    fn test_parse_reset() {
+        assert_eq!(parse_reset("1s"), Some(Duration::from_secs(1)));
+        assert_eq!(parse_reset("6m0s"), Some(Duration::from_secs(360)));
+        assert_eq!(
+            parse_reset("1h2m3.5s"),
+            Some(Duration::from_millis(3_723_500))
+        );
+        assert_eq!(parse_reset("20ms"), Some(Duration::from_millis(20)));
+        assert_eq!(parse_reset("12"), Some(Duration::from_secs(12)));
+        assert_eq!(parse_reset(""), None);
+        assert_eq!(parse_reset("soon"), None);
+        assert_eq!(parse_reset("5d"), None);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_wait_ceiling() {
+        let mock_server = MockServer::start().await;
+
+        // Asked to wait ten minutes, more than allowed: fail without retrying
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(429)
+                    .insert_header("Retry-After", "600")
+                    .set_body_string("Rate limit reached"),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let policy = RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(1),
+            max_rate_limit_wait: Duration::from_secs(120),
+        };
+        let err = LlmClient::new("test_token", &mock_server.uri(), "gpt-4o")
+            .with_retry_policy(policy)
+            .complete("system", "user")
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("wait 600s"), "{err}");
+        assert!(err.contains("max_rate_limit_wait_secs (120s)"), "{err}");
+        assert!(err.contains("Rate limit reached"), "{err}");
+        assert_eq!(retry_wait(), None);
+    }
+
     #[tokio::test]
     async fn test_generate_commit_message_api_error() -> Result<()> {
         // Start a mock server
@@ -177,15 +3093,9 @@ mod tests {
             .await;
 
         // Attempt to generate a commit message
-        let result = generate_commit_message(
-            "some diff",
-            "system prompt",
-            "user prompt",
-            "invalid_token",
-            &mock_server.uri(),
-            "gpt-3.5-turbo",
-        )
-        .await;
+        let result = LlmClient::new("invalid_token", &mock_server.uri(), "gpt-3.5-turbo")
+            .generate_commit_messages("some diff", "system prompt", "user prompt", 1)
+            .await;
 
         // Verify that we get an error
         assert!(result.is_err());
@@ -196,4 +3106,57 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_parse_stream_line() {
+        assert_eq!(
+            parse_stream_line(r#"data: {"choices":[{"delta":{"content":"feat"}}]}"#),
+            Some("feat".to_string())
+        );
+        assert_eq!(
+            parse_stream_line(r#"data: {"choices":[{"delta":{"role":"assistant"}}]}"#),
+            None
+        );
+        assert_eq!(parse_stream_line("data: [DONE]"), None);
+        assert_eq!(parse_stream_line(": keep-alive"), None);
+        assert_eq!(parse_stream_line(""), None);
+    }
+
+    #[tokio::test]
+    async fn test_stream_commit_message() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        let events = ["feat", "(auth)", ": add login"]
+            .iter()
+            .map(|piece| {
+                format!(
+                    "data: {}\n\n",
+                    serde_json::json!({ "choices": [{ "delta": { "content": piece } }] })
+                )
+            })
+            .collect::<String>()
+            + "data: [DONE]\n\n";
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(serde_json::json!({ "stream": true })))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Content-Type", "text/event-stream")
+                    .set_body_string(events),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let client = LlmClient::new("test_token", &mock_server.uri(), "test-model");
+        let mut deltas = Vec::new();
+        let message = client
+            .stream_commit_message("diff", "system", "{}", |delta| {
+                deltas.push(delta.to_string())
+            })
+            .await?;
+
+        assert_eq!(message, "feat(auth): add login");
+        assert_eq!(deltas, vec!["feat", "(auth)", ": add login"]);
+        Ok(())
+    }
 }
@@ -0,0 +1,175 @@
+use crate::git;
+use anyhow::{Context, Result};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use uuid::Uuid;
+
+/// Lock file inside `.git/aic`
+const LOCK_FILE: &str = "run.lock";
+
+/// Exclusive lock for runs that stage or commit, held until dropped
+///
+/// Keeps a hook and a manual run from interleaving their `git add` and
+/// `git commit`. The lock file holds the process id of its owner, so a lock
+/// left behind by a crashed run is taken over.
+#[derive(Debug)]
+pub struct RunLock {
+    path: PathBuf,
+
+    /// Contents of the lock file, so an overridden run leaves the new lock alone
+    owner: String,
+}
+
+impl RunLock {
+    /// Lock the current repository, `None` outside a repository
+    pub fn acquire_current(ignore_lock: bool) -> Result<Option<Self>> {
+        match git::aic_dir() {
+            Ok(aic_dir) => Self::acquire(&aic_dir, ignore_lock).map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Lock the repository owning `aic_dir`, taking over the lock of another run with `ignore_lock`
+    pub fn acquire(aic_dir: &Path, ignore_lock: bool) -> Result<Self> {
+        let path = aic_dir.join(LOCK_FILE);
+        if let Some(lock) = Self::try_create(&path)? {
+            return Ok(lock);
+        }
+
+        let owner = fs::read_to_string(&path).ok().and_then(|contents| {
+            contents
+                .split_whitespace()
+                .next()
+                .and_then(|pid| pid.parse::<u32>().ok())
+        });
+        let active_owner = owner.filter(|&pid| is_running(pid));
+        if let (Some(pid), false) = (active_owner, ignore_lock) {
+            anyhow::bail!(
+                "Another aic run is active in this repository (pid {}). Wait for it to finish, \
+                 or pass --ignore-lock if it is stuck",
+                pid
+            );
+        }
+
+        // Stale or overridden, another run may still win the race for a new lock
+        fs::remove_file(&path)
+            .or_else(|err| match err.kind() {
+                ErrorKind::NotFound => Ok(()),
+                _ => Err(err),
+            })
+            .context(format!("Failed to remove {}", path.display()))?;
+        Self::try_create(&path)?
+            .context("Another aic run took the lock at the same time, try again")
+    }
+
+    // Create the lock file, `None` if it already exists
+    fn try_create(path: &Path) -> Result<Option<Self>> {
+        match OpenOptions::new().write(true).create_new(true).open(path) {
+            Ok(mut file) => {
+                let owner = format!("{} {}", process::id(), Uuid::new_v4());
+                writeln!(file, "{owner}").context(format!("Failed to write {}", path.display()))?;
+                Ok(Some(Self {
+                    path: path.to_path_buf(),
+                    owner,
+                }))
+            }
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => Ok(None),
+            Err(err) => Err(err).context(format!("Failed to create {}", path.display())),
+        }
+    }
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        let owned =
+            fs::read_to_string(&self.path).is_ok_and(|contents| contents.trim() == self.owner);
+        if owned {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Whether a process is still running
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    process::Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+/// Whether a process is still running
+#[cfg(windows)]
+fn is_running(pid: u32) -> bool {
+    use windows_sys::Win32::Foundation::{
+        CloseHandle, GetLastError, ERROR_ACCESS_DENIED, STILL_ACTIVE,
+    };
+    use windows_sys::Win32::System::Threading::{
+        GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    // SAFETY: the handle is checked before use and closed right after
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if handle.is_null() {
+            // Access is only denied to a process that exists
+            return GetLastError() == ERROR_ACCESS_DENIED;
+        }
+        let mut exit_code = 0;
+        let queried = GetExitCodeProcess(handle, &mut exit_code) != 0;
+        CloseHandle(handle);
+        !queried || exit_code == STILL_ACTIVE as u32
+    }
+}
+
+/// Whether a process is still running, assumed on platforms that can't tell
+#[cfg(not(any(unix, windows)))]
+fn is_running(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_run_lock() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock_path = temp_dir.path().join(LOCK_FILE);
+
+        let lock = RunLock::acquire(temp_dir.path(), false).unwrap();
+        assert!(fs::read_to_string(&lock_path)
+            .unwrap()
+            .starts_with(&format!("{} ", process::id())));
+
+        // A second run is refused unless it overrides the lock
+        let err = RunLock::acquire(temp_dir.path(), false).unwrap_err();
+        assert!(err.to_string().starts_with("Another aic run is active"));
+        let overridden = RunLock::acquire(temp_dir.path(), true).unwrap();
+
+        // The overridden run leaves the new lock in place
+        drop(lock);
+        assert!(lock_path.exists());
+        drop(overridden);
+        assert!(!lock_path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stale_lock_is_taken_over() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock_path = temp_dir.path().join(LOCK_FILE);
+
+        // The pid of a process that has exited
+        let mut child = process::Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+        fs::write(&lock_path, format!("{pid}\n")).unwrap();
+
+        let lock = RunLock::acquire(temp_dir.path(), false).unwrap();
+        assert_eq!(fs::read_to_string(&lock_path).unwrap().trim(), lock.owner);
+    }
+}
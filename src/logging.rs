@@ -0,0 +1,105 @@
+use anyhow::{Context, Result};
+use std::io::{self, IsTerminal};
+use tracing::level_filters::LevelFilter;
+use tracing::Subscriber;
+use tracing_subscriber::fmt::{self, MakeWriter};
+use tracing_subscriber::EnvFilter;
+
+/// Which events to log for `-v` repeated `verbose` times or `--log-level`,
+/// falling back to `RUST_LOG` when neither is given
+///
+/// Nothing is logged by default; `-v` shows requests and retries, `-vv`
+/// their details and `-vvv` everything, including the HTTP client's events.
+pub fn filter_for(verbose: u8, log_level: Option<LevelFilter>) -> EnvFilter {
+    let level = log_level.or(match verbose {
+        0 => None,
+        1 => Some(LevelFilter::INFO),
+        2 => Some(LevelFilter::DEBUG),
+        _ => Some(LevelFilter::TRACE),
+    });
+    match level {
+        // Dependencies are only heard at the most verbose level
+        Some(LevelFilter::TRACE) => EnvFilter::new("trace"),
+        Some(level) => EnvFilter::new(format!("aic={level},aic_core={level}")),
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("off")),
+    }
+}
+
+/// Log the events `filter` lets through to stderr, so stdout stays clean for
+/// messages and JSON
+pub fn init(filter: EnvFilter) -> Result<()> {
+    tracing::subscriber::set_global_default(subscriber(
+        filter,
+        io::stderr,
+        io::stderr().is_terminal(),
+    ))
+    .context("Failed to set up logging")
+}
+
+/// Writes events as `0.153s DEBUG llm{model="gpt-4o"}: target: message key=value` lines
+fn subscriber<W>(filter: EnvFilter, writer: W, ansi: bool) -> impl Subscriber + Send + Sync
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    fmt::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .with_ansi(ansi)
+        .with_timer(fmt::time::uptime())
+        .finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(bytes)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_filter_for() {
+        let hint = |verbose, log_level| filter_for(verbose, log_level).max_level_hint();
+        assert_eq!(hint(1, None), Some(LevelFilter::INFO));
+        assert_eq!(hint(2, None), Some(LevelFilter::DEBUG));
+        assert_eq!(hint(5, None), Some(LevelFilter::TRACE));
+        assert_eq!(hint(2, Some(LevelFilter::WARN)), Some(LevelFilter::WARN));
+    }
+
+    #[test]
+    fn test_subscriber() {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = subscriber(filter_for(2, None), move || writer.clone(), false);
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("request", model = "gpt-4o");
+            let _entered = span.enter();
+            tracing::debug!(status = 200, "response received");
+            tracing::trace!("too detailed");
+            tracing::info!(target: "hyper", "not ours");
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 1, "{output}");
+        assert!(
+            lines[0].contains("DEBUG request{model=\"gpt-4o\"}"),
+            "{output}"
+        );
+        assert!(
+            lines[0].ends_with("response received status=200"),
+            "{output}"
+        );
+    }
+}
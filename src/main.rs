@@ -1,31 +1,136 @@
-mod cli;
-mod commands;
-mod config;
-mod git;
-mod llm;
-mod ui;
-
+use aic_core::cli::{parse_args, Commands, ConfigCommands};
+use aic_core::{commands, git, lock, logging, Config};
 use anyhow::Result;
-use cli::parse_args;
-use config::Config;
+use std::io::{self, IsTerminal};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Load configuration
-    let config = Config::load()?;
+    match run().await {
+        // The cancellation message has already been printed
+        Err(err) if err.is::<commands::Cancelled>() => std::process::exit(130),
+        result => result,
+    }
+}
 
+async fn run() -> Result<()> {
     // Parse CLI arguments
     let cli = parse_args();
+    logging::init(logging::filter_for(cli.verbose, cli.log_level))?;
+
+    // Load configuration, `config validate` and `config edit` still work on one that fails to load
+    let config = match (&cli.command, Config::load()) {
+        (
+            Some(Commands::Config(ConfigCommands::Validate { .. } | ConfigCommands::Edit { .. })),
+            Err(_),
+        ) => Config::default(),
+        (_, config) => config?,
+    };
+
+    // Config subcommands work on the files as written, everything else on the selected profile
+    let config = match &cli.command {
+        Some(Commands::Config(_)) => config,
+        _ => config
+            .with_profile(cli.profile.as_deref())?
+            .with_exclude_patterns(&cli.exclude)
+            .with_sampling_overrides(cli.temperature, cli.top_p, cli.max_tokens)?
+            .with_trailer_overrides(&cli.trailer)?
+            .with_commit_flag_overrides(cli.signoff, cli.gpg_sign.as_deref(), cli.no_verify)
+            .with_show_prompt(cli.show_prompt),
+    };
+
+    // Keep concurrent runs from interleaving staging and committing
+    let _lock = match &cli.command {
+        None if !cli.sandbox && !cli.prints_only() => {
+            lock::RunLock::acquire_current(cli.ignore_lock)?
+        }
+        Some(
+            Commands::Tag { .. }
+            | Commands::Split
+            | Commands::Branch { .. }
+            | Commands::Bump { apply: true, .. }
+            | Commands::Reword { .. }
+            | Commands::Translate { apply: true, .. }
+            | Commands::Translate { pending: true, .. }
+            | Commands::History {
+                command: Some(_), ..
+            },
+        ) => lock::RunLock::acquire_current(cli.ignore_lock)?,
+        _ => None,
+    };
 
     // Process commands or default behavior
     match &cli.command {
         Some(command) => {
-            commands::handle_commands(command, &config).await?;
+            commands::handle_commands(command, &config, cli.output).await?;
+        }
+        None if cli.prints_only() => {
+            // --print conflicts with these in clap, JSON output is only checked here
+            let interactive = cli.auto_add
+                || cli.all
+                || cli.auto_commit
+                || cli.auto_push
+                || cli.sandbox
+                || cli.amend
+                || cli.candidates > 1;
+            if interactive {
+                anyhow::bail!(
+                    "--output json only prints the message and can't be combined with \
+                     -a, -A, -c, -p, -n, --sandbox or --amend"
+                );
+            }
+            let source = if cli.stdin {
+                if io::stdin().is_terminal() {
+                    anyhow::bail!("--stdin expects a diff on stdin, e.g. git diff | aic --stdin");
+                }
+                commands::DiffSource::Stdin(io::read_to_string(io::stdin())?)
+            } else if let Some(rev) = cli.diff_ref.as_ref().or(cli.range.as_ref()) {
+                commands::DiffSource::Revision(rev.clone())
+            } else {
+                commands::DiffSource::Staged
+            };
+            // --output-file without a path stands for the repository's COMMIT_EDITMSG
+            let output_file = match &cli.output_file {
+                Some(None) => Some(git::git_path("COMMIT_EDITMSG")?),
+                Some(path) => path.clone(),
+                None => None,
+            };
+            commands::print_commit_message(
+                &config,
+                cli.offline,
+                cli.no_cache,
+                cli.output,
+                source,
+                output_file.as_deref(),
+                cli.print,
+            )
+            .await?;
         }
         None => {
             // No subcommand provided, default to generate behavior using cli directly
-            commands::generate_commit(&config, cli.auto_add, cli.auto_commit, cli.auto_push)
-                .await?;
+            let mode = if cli.amend {
+                commands::CommitMode::Amend {
+                    force: cli.force_amend,
+                }
+            } else {
+                commands::CommitMode::New
+            };
+            let options = commands::GenerateOptions {
+                auto_add: cli.auto_add,
+                all: cli.all,
+                auto_commit: cli.auto_commit,
+                auto_push: cli.auto_push,
+                push_target: git::PushTarget {
+                    remote: cli.remote.clone(),
+                    branch: cli.push_branch.clone(),
+                },
+                candidates: cli.candidates,
+                sandbox: cli.sandbox,
+                offline: cli.offline,
+                no_cache: cli.no_cache,
+                suggest_split: true,
+                mode,
+            };
+            commands::generate_commit(&config, options).await?;
         }
     }
 
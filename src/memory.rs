@@ -0,0 +1,166 @@
+use crate::git;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Number of feedback entries kept per branch
+const MAX_ENTRIES: usize = 5;
+
+/// How the user reacted to a generated message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Feedback {
+    /// The message was discarded
+    Rejected { message: String },
+
+    /// The message was edited before committing
+    Edited { message: String, preferred: String },
+}
+
+/// Feedback on generated messages for a single branch, stored in `.git/aic/memory/`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BranchMemory {
+    #[serde(default)]
+    entries: Vec<Feedback>,
+}
+
+impl BranchMemory {
+    // Memory file for a branch, with path separators flattened
+    fn path_for(aic_dir: &Path, branch: &str) -> PathBuf {
+        let file_name = branch.replace(['/', '\\'], "__");
+        aic_dir.join("memory").join(format!("{file_name}.json"))
+    }
+
+    // Memory file for the checked out branch, `None` outside a branch
+    fn current_path() -> Result<Option<PathBuf>> {
+        match git::current_branch()? {
+            Some(branch) => Ok(Some(Self::path_for(&git::aic_dir()?, &branch))),
+            None => Ok(None),
+        }
+    }
+
+    fn load_from(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(path).context("Could not read branch memory")?;
+        serde_json::from_str(&contents).context("Failed to parse branch memory")
+    }
+
+    fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context("Failed to create branch memory directory")?;
+        }
+
+        let json =
+            serde_json::to_string_pretty(self).context("Failed to serialize branch memory")?;
+        fs::write(path, json).context("Failed to write branch memory")
+    }
+
+    /// Load the memory of the checked out branch
+    pub fn load_current() -> Result<Self> {
+        match Self::current_path()? {
+            Some(path) => Self::load_from(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Remember feedback for the checked out branch, keeping the newest entries
+    pub fn record(feedback: Feedback) -> Result<()> {
+        let Some(path) = Self::current_path()? else {
+            return Ok(());
+        };
+
+        let mut memory = Self::load_from(&path)?;
+        memory.push(feedback);
+        memory.save_to(&path)
+    }
+
+    fn push(&mut self, feedback: Feedback) {
+        self.entries.push(feedback);
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.drain(..self.entries.len() - MAX_ENTRIES);
+        }
+    }
+
+    /// Append the remembered feedback to a system prompt
+    pub fn augment_prompt(&self, system_prompt: &str) -> String {
+        if self.entries.is_empty() {
+            return system_prompt.to_string();
+        }
+
+        let mut prompt = format!(
+            "{system_prompt}\n\n\
+            Feedback from earlier attempts on this branch. Avoid repeating rejected \
+            messages and follow the style of the preferred ones:"
+        );
+        for entry in &self.entries {
+            match entry {
+                Feedback::Rejected { message } => {
+                    prompt.push_str(&format!("\n- The user rejected:\n{}", message.trim()));
+                }
+                Feedback::Edited { message, preferred } => {
+                    prompt.push_str(&format!(
+                        "\n- The user rejected:\n{}\n  and preferred:\n{}",
+                        message.trim(),
+                        preferred.trim()
+                    ));
+                }
+            }
+        }
+
+        prompt
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_memory_roundtrip_and_limit() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let path = BranchMemory::path_for(temp_dir.path(), "feature/login");
+        assert!(path.ends_with("memory/feature__login.json"));
+
+        let mut memory = BranchMemory::load_from(&path).unwrap();
+        for index in 0..=MAX_ENTRIES {
+            memory.push(Feedback::Rejected {
+                message: format!("fix: attempt {index}"),
+            });
+        }
+        memory.save_to(&path).unwrap();
+
+        // Only the newest entries are kept
+        let loaded = BranchMemory::load_from(&path).unwrap();
+        assert_eq!(loaded.entries.len(), MAX_ENTRIES);
+        assert_eq!(
+            loaded.entries[0],
+            Feedback::Rejected {
+                message: "fix: attempt 1".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_augment_prompt() {
+        let mut memory = BranchMemory::default();
+        assert_eq!(memory.augment_prompt("system"), "system");
+
+        memory.push(Feedback::Rejected {
+            message: "chore: stuff".to_string(),
+        });
+        memory.push(Feedback::Edited {
+            message: "feat: add login".to_string(),
+            preferred: "feat(auth): add login form".to_string(),
+        });
+
+        let prompt = memory.augment_prompt("system");
+        assert!(prompt.starts_with("system\n\nFeedback from earlier attempts"));
+        assert!(prompt.contains("The user rejected:\nchore: stuff"));
+        assert!(prompt.contains("and preferred:\nfeat(auth): add login form"));
+    }
+}
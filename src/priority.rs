@@ -0,0 +1,317 @@
+use crate::generated;
+use crate::git::{self, FileDiff};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// What a file of the diff is, for deciding what to keep when it is truncated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileRole {
+    Source,
+    Tests,
+    Config,
+    Docs,
+    Generated,
+}
+
+impl FileRole {
+    pub const ALL: [FileRole; 5] = [
+        FileRole::Source,
+        FileRole::Tests,
+        FileRole::Config,
+        FileRole::Docs,
+        FileRole::Generated,
+    ];
+
+    /// Weight used without a `diff_priority` entry, higher is kept first
+    pub fn default_weight(self) -> u32 {
+        match self {
+            FileRole::Source => 5,
+            FileRole::Tests => 4,
+            FileRole::Config => 3,
+            FileRole::Docs => 2,
+            FileRole::Generated => 1,
+        }
+    }
+
+    /// Classify a file of the diff by its path, and its content for
+    /// binaries and minified files
+    pub fn of(file: &FileDiff) -> Self {
+        if generated::classify(file, generated::DEFAULT_GENERATED_PATTERNS).is_some() {
+            return FileRole::Generated;
+        }
+
+        let path = file.path.as_str();
+        let name = path.rsplit('/').next().unwrap_or(path);
+        let extension = name.rsplit_once('.').map_or("", |(_, extension)| extension);
+        let in_dir =
+            |dir: &str| path.starts_with(&format!("{dir}/")) || path.contains(&format!("/{dir}/"));
+
+        if ["vendor", "node_modules", "dist", "generated"]
+            .iter()
+            .any(|dir| in_dir(dir))
+        {
+            FileRole::Generated
+        } else if ["tests", "test", "__tests__", "spec"]
+            .iter()
+            .any(|dir| in_dir(dir))
+            || name.starts_with("test_")
+            || ["_test.", ".test.", ".spec.", "_spec."]
+                .iter()
+                .any(|marker| name.contains(marker))
+        {
+            FileRole::Tests
+        } else if ["md", "rst", "adoc", "txt"].contains(&extension)
+            || in_dir("docs")
+            || in_dir("doc")
+            || name.starts_with("LICENSE")
+        {
+            FileRole::Docs
+        } else if [
+            "toml",
+            "yaml",
+            "yml",
+            "json",
+            "ini",
+            "cfg",
+            "conf",
+            "xml",
+            "properties",
+            "lock",
+        ]
+        .contains(&extension)
+            || name.starts_with('.')
+            || ["Makefile", "Dockerfile", "Containerfile"].contains(&name)
+            || path.starts_with(".github/")
+        {
+            FileRole::Config
+        } else {
+            FileRole::Source
+        }
+    }
+}
+
+impl FromStr for FileRole {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "source" => Ok(FileRole::Source),
+            "tests" => Ok(FileRole::Tests),
+            "config" => Ok(FileRole::Config),
+            "docs" => Ok(FileRole::Docs),
+            "generated" => Ok(FileRole::Generated),
+            _ => Err(anyhow::anyhow!(
+                "Unknown file kind '{}': expected source, tests, config, docs or generated",
+                value.trim()
+            )),
+        }
+    }
+}
+
+impl fmt::Display for FileRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FileRole::Source => "source",
+            FileRole::Tests => "tests",
+            FileRole::Config => "config",
+            FileRole::Docs => "docs",
+            FileRole::Generated => "generated",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Weights of the kinds of files, see `diff_priority`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiffPriority {
+    weights: BTreeMap<FileRole, u32>,
+}
+
+impl DiffPriority {
+    /// Weights overriding the defaults for some kinds of files
+    pub fn new(weights: BTreeMap<FileRole, u32>) -> Self {
+        Self { weights }
+    }
+
+    pub fn weight(&self, role: FileRole) -> u32 {
+        self.weights
+            .get(&role)
+            .copied()
+            .unwrap_or_else(|| role.default_weight())
+    }
+}
+
+/// The hunks that fit into a byte budget, most valuable files first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selection {
+    /// The kept files, each with its header and the hunks that fit
+    pub diff: String,
+
+    /// Files some or all of whose hunks were left out, with the number left
+    /// out and the number they had
+    pub omitted: Vec<(String, usize, usize)>,
+}
+
+/// Keep the highest-value hunks of a diff within `max_bytes`
+///
+/// Files are ranked by the weight of their kind and keep their order within
+/// a weight. Each file gets its header and then every hunk that still fits,
+/// so a large hunk is skipped rather than cutting the diff in the middle of
+/// it. Returns `None` when the text isn't a diff or not even one hunk fits.
+pub fn select_hunks(diff: &str, max_bytes: usize, priority: &DiffPriority) -> Option<Selection> {
+    let mut files = git::split_diff_by_file(diff);
+    // Stable, so files of the same weight stay in diff order
+    files.sort_by_key(|file| std::cmp::Reverse(priority.weight(FileRole::of(file))));
+
+    let mut kept = String::new();
+    let mut omitted = Vec::new();
+    let mut kept_hunk = false;
+    for file in &files {
+        let (header, hunks) = split_hunks(&file.diff);
+        if kept.len() + header.len() > max_bytes {
+            if !hunks.is_empty() {
+                omitted.push((file.path.clone(), hunks.len(), hunks.len()));
+            }
+            continue;
+        }
+
+        let mut file_diff = header.to_string();
+        let mut left_out = 0;
+        for hunk in &hunks {
+            if kept.len() + file_diff.len() + hunk.len() <= max_bytes {
+                file_diff.push_str(hunk);
+            } else {
+                left_out += 1;
+            }
+        }
+        if left_out > 0 {
+            omitted.push((file.path.clone(), left_out, hunks.len()));
+        }
+        // A header without any of its hunks says nothing about the change
+        if hunks.is_empty() || left_out < hunks.len() {
+            kept.push_str(&file_diff);
+            kept_hunk |= left_out < hunks.len();
+        }
+    }
+
+    kept_hunk.then_some(Selection {
+        diff: kept,
+        omitted,
+    })
+}
+
+/// Split the diff of one file into its header and its `@@` hunks
+fn split_hunks(file_diff: &str) -> (&str, Vec<&str>) {
+    let mut starts = Vec::new();
+    let mut offset = 0;
+    for line in file_diff.split_inclusive('\n') {
+        if line.starts_with("@@") {
+            starts.push(offset);
+        }
+        offset += line.len();
+    }
+
+    let Some(&first) = starts.first() else {
+        return (file_diff, Vec::new());
+    };
+    let ends = starts.iter().skip(1).copied().chain([file_diff.len()]);
+    let hunks = starts
+        .iter()
+        .zip(ends)
+        .map(|(&start, end)| &file_diff[start..end])
+        .collect();
+    (&file_diff[..first], hunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, hunks: &[&str]) -> String {
+        let mut diff = format!("diff --git a/{path} b/{path}\n--- a/{path}\n+++ b/{path}\n");
+        for (index, body) in hunks.iter().enumerate() {
+            diff.push_str(&format!("@@ -{0},1 +{0},1 @@\n{body}", index + 1));
+        }
+        diff
+    }
+
+    fn role(path: &str) -> FileRole {
+        FileRole::of(&FileDiff {
+            path: path.to_string(),
+            diff: String::new(),
+        })
+    }
+
+    #[test]
+    fn test_file_roles() {
+        assert_eq!(role("src/main.rs"), FileRole::Source);
+        assert_eq!(role("tests/cli.rs"), FileRole::Tests);
+        assert_eq!(role("pkg/server_test.go"), FileRole::Tests);
+        assert_eq!(role("web/app.spec.ts"), FileRole::Tests);
+        assert_eq!(role("Cargo.toml"), FileRole::Config);
+        assert_eq!(role(".github/workflows/ci.yml"), FileRole::Config);
+        assert_eq!(role("README.md"), FileRole::Docs);
+        assert_eq!(role("docs/guide.html"), FileRole::Docs);
+        assert_eq!(role("Cargo.lock"), FileRole::Generated);
+        assert_eq!(role("vendor/github.com/x/y.go"), FileRole::Generated);
+    }
+
+    #[test]
+    fn test_split_hunks() {
+        let diff = file("a.rs", &["+one\n", "+two\n"]);
+        let (header, hunks) = split_hunks(&diff);
+        assert!(header.ends_with("+++ b/a.rs\n"));
+        assert_eq!(
+            hunks,
+            vec!["@@ -1,1 +1,1 @@\n+one\n", "@@ -2,1 +2,1 @@\n+two\n"]
+        );
+        assert_eq!(split_hunks("Binary files differ\n").1.len(), 0);
+    }
+
+    #[test]
+    fn test_select_hunks_keeps_source_first() {
+        let lockfile = file("Cargo.lock", &["+lock line\n"]);
+        let readme = file("README.md", &["+docs line\n"]);
+        let source = file("src/lib.rs", &["+small\n", &"+big\n".repeat(50), "+tail\n"]);
+        let diff = format!("{lockfile}{readme}{source}");
+
+        let priority = DiffPriority::default();
+        let budget = split_hunks(&source).0.len() + 60;
+        let selection = select_hunks(&diff, budget, &priority).unwrap();
+        assert!(selection.diff.starts_with("diff --git a/src/lib.rs"));
+        assert!(selection.diff.contains("+small\n") && selection.diff.contains("+tail\n"));
+        assert!(!selection.diff.contains("+big\n"));
+        assert!(selection.diff.len() <= budget);
+        assert_eq!(
+            selection.omitted,
+            vec![
+                ("src/lib.rs".to_string(), 1, 3),
+                ("README.md".to_string(), 1, 1),
+                ("Cargo.lock".to_string(), 1, 1),
+            ]
+        );
+
+        // Weights put the docs first
+        let priority = DiffPriority::new(BTreeMap::from([(FileRole::Docs, 9)]));
+        let selection = select_hunks(&diff, diff.len(), &priority).unwrap();
+        assert!(selection.diff.starts_with("diff --git a/README.md"));
+        assert!(selection.omitted.is_empty());
+
+        // Nothing fits
+        assert!(select_hunks(&diff, 10, &DiffPriority::default()).is_none());
+        assert!(select_hunks("+not a diff\n", 100, &DiffPriority::default()).is_none());
+    }
+
+    #[test]
+    fn test_parse_file_role() {
+        assert_eq!("Tests".parse::<FileRole>().unwrap(), FileRole::Tests);
+        assert!("vendor".parse::<FileRole>().is_err());
+        for role in FileRole::ALL {
+            assert_eq!(role.to_string().parse::<FileRole>().unwrap(), role);
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use crate::config;
+use crate::generated;
+use crate::git;
+use crate::glob;
+use anyhow::Result;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::LazyLock;
+
+/// Keys accepted in the `[redact]` table
+pub const KEYS: &[&str] = &["paths", "emails", "ips", "string_literals"];
+
+static EMAIL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}")
+        .expect("valid email pattern")
+});
+static IPV4: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b\d{1,3}(\.\d{1,3}){3}\b").expect("valid IPv4 pattern"));
+static IPV6: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"[0-9A-Fa-f]{0,4}(:[0-9A-Fa-f]{0,4}){2,7}").expect("valid IPv6 pattern")
+});
+static DOUBLE_QUOTED: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#""(?:[^"\\\n]|\\.)+""#).expect("valid string pattern"));
+static SINGLE_QUOTED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(^|[^\w'])'(?:[^'\\\n]|\\.)+'(\W|$)").expect("valid string pattern")
+});
+
+/// What to strip from the diff before it is sent, the `[redact]` table
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rules {
+    /// Globs of files sent as their name and line counts only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paths: Option<Vec<String>>,
+
+    /// Replace email addresses with `[email]`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emails: Option<bool>,
+
+    /// Replace IPv4 and IPv6 addresses with `[ip]`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ips: Option<bool>,
+
+    /// Replace the content of quoted strings with `[string]`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub string_literals: Option<bool>,
+}
+
+impl Rules {
+    /// Rules of `self` where set, the ones of `base` otherwise
+    pub fn or(self, base: Self) -> Self {
+        Self {
+            paths: self.paths.or(base.paths),
+            emails: self.emails.or(base.emails),
+            ips: self.ips.or(base.ips),
+            string_literals: self.string_literals.or(base.string_literals),
+        }
+    }
+
+    pub(crate) fn set(&mut self, key: &str, value: Option<String>) -> Result<()> {
+        let flag = |value: Option<String>| {
+            value
+                .map(|v| config::parse_bool(&format!("redact.{key}"), &v))
+                .transpose()
+        };
+        match key {
+            "paths" => self.paths = value.map(|v| config::parse_list(&v)),
+            "emails" => self.emails = flag(value)?,
+            "ips" => self.ips = flag(value)?,
+            "string_literals" => self.string_literals = flag(value)?,
+            _ => anyhow::bail!("Unknown redact key: {} (expected {})", key, KEYS.join(", ")),
+        }
+        Ok(())
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<String> {
+        match key {
+            "paths" => self.paths.as_ref().map(|v| v.join(", ")),
+            "emails" => self.emails.map(|v| v.to_string()),
+            "ips" => self.ips.map(|v| v.to_string()),
+            "string_literals" => self.string_literals.map(|v| v.to_string()),
+            _ => None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// The rules that are on, e.g. `paths (secrets/**), emails`
+    pub fn describe(&self) -> String {
+        let mut rules = Vec::new();
+        if let Some(paths) = self.paths.as_ref().filter(|paths| !paths.is_empty()) {
+            rules.push(format!("paths ({})", paths.join(", ")));
+        }
+        for (name, on) in [
+            ("emails", self.emails),
+            ("ips", self.ips),
+            ("string_literals", self.string_literals),
+        ] {
+            if on == Some(true) {
+                rules.push(name.to_string());
+            }
+        }
+        rules.join(", ")
+    }
+
+    /// The diff with the files matching `paths` summarized in one line and
+    /// emails, IP addresses and string literals masked as configured
+    ///
+    /// File headers are left alone so the model still sees which files changed.
+    pub fn apply(&self, diff: &str) -> String {
+        let paths = self.paths.as_deref().unwrap_or_default();
+        let maskers = self.maskers();
+        if paths.is_empty() && maskers.is_empty() {
+            return diff.to_string();
+        }
+
+        let mask = |text: &str| -> String {
+            text.split_inclusive('\n')
+                .map(|line| {
+                    if is_file_header(line) {
+                        line.to_string()
+                    } else {
+                        maskers
+                            .iter()
+                            .fold(line.to_string(), |line, mask| mask(&line))
+                    }
+                })
+                .collect()
+        };
+
+        // A diff without `diff --git` headers, e.g. from stdin, has no paths to match
+        let files = git::split_diff_by_file(diff);
+        if files.is_empty() {
+            return mask(diff);
+        }
+        files
+            .iter()
+            .map(|file| {
+                if glob::matches_any(paths, &file.path) {
+                    let summary = generated::summarize_text(file, "file");
+                    format!("[{summary}, content redacted]\n")
+                } else {
+                    mask(&file.diff)
+                }
+            })
+            .collect()
+    }
+
+    fn maskers(&self) -> Vec<fn(&str) -> String> {
+        let mut maskers: Vec<fn(&str) -> String> = Vec::new();
+        // Strings first, so addresses inside them go with the string
+        if self.string_literals == Some(true) {
+            maskers.push(mask_string_literals);
+        }
+        if self.emails == Some(true) {
+            maskers.push(mask_emails);
+        }
+        if self.ips == Some(true) {
+            maskers.push(mask_ips);
+        }
+        maskers
+    }
+}
+
+fn is_file_header(line: &str) -> bool {
+    [
+        "diff --git ",
+        "index ",
+        "--- ",
+        "+++ ",
+        "new file mode",
+        "deleted file mode",
+    ]
+    .iter()
+    .any(|prefix| line.starts_with(prefix))
+        || line.starts_with("rename ")
+        || line.starts_with("similarity index")
+}
+
+fn mask_emails(line: &str) -> String {
+    EMAIL.replace_all(line, "[email]").into_owned()
+}
+
+// Candidates are parsed, so version numbers like 1.2.3 and Rust paths like
+// `de::Deserialize` are left alone
+fn mask_ips(line: &str) -> String {
+    let line = IPV4.replace_all(line, |caps: &Captures| {
+        if caps[0].parse::<Ipv4Addr>().is_ok() {
+            "[ip]".to_string()
+        } else {
+            caps[0].to_string()
+        }
+    });
+    IPV6.replace_all(&line, |caps: &Captures| {
+        let candidate = &caps[0];
+        if candidate.chars().any(|c| c.is_ascii_digit()) && candidate.parse::<Ipv6Addr>().is_ok() {
+            "[ip]".to_string()
+        } else {
+            candidate.to_string()
+        }
+    })
+    .into_owned()
+}
+
+// Double-quoted strings and single-quoted ones that stand on their own, so
+// apostrophes in comments and Rust lifetimes stay as they are
+fn mask_string_literals(line: &str) -> String {
+    let line = DOUBLE_QUOTED.replace_all(line, "\"[string]\"");
+    SINGLE_QUOTED
+        .replace_all(&line, "$1'[string]'$2")
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "diff --git a/deploy/prod.env b/deploy/prod.env\n\
+        index 83db48f..bf269f4 100644\n\
+        --- a/deploy/prod.env\n\
+        +++ b/deploy/prod.env\n\
+        @@ -1,2 +1,3 @@\n\
+        -DB_HOST=old-host\n\
+        +DB_HOST=10.0.3.17\n\
+        +DB_NAME=orders\n\
+        diff --git a/src/notify.py b/src/notify.py\n\
+        index 1111111..2222222 100644\n\
+        --- a/src/notify.py\n\
+        +++ b/src/notify.py\n\
+        @@ -3,1 +3,2 @@ def notify(user):\n\
+        +    send(\"ops@example.com\", 'Disk full on db-1', host=\"fe80::1\")\n\
+        +    # don't retry, version 1.2.3.4000 talks to 192.168.0.1 and admin@example.org\n";
+
+    fn rules(paths: &[&str], emails: bool, ips: bool, string_literals: bool) -> Rules {
+        Rules {
+            paths: Some(paths.iter().map(|path| path.to_string()).collect()),
+            emails: Some(emails),
+            ips: Some(ips),
+            string_literals: Some(string_literals),
+        }
+    }
+
+    #[test]
+    fn test_apply_paths() {
+        let redacted = rules(&["deploy/**"], false, false, false).apply(DIFF);
+        assert!(redacted.starts_with(
+            "[modified file deploy/prod.env, +2 -1 lines, content redacted]\n\
+             diff --git a/src/notify.py b/src/notify.py\n"
+        ));
+        assert!(!redacted.contains("10.0.3.17"));
+        assert!(redacted.contains("ops@example.com"));
+
+        assert_eq!(Rules::default().apply(DIFF), DIFF);
+
+        let bare = "+send(\"ops@example.com\")\n";
+        assert_eq!(
+            rules(&[], true, false, false).apply(bare),
+            "+send(\"[email]\")\n"
+        );
+    }
+
+    #[test]
+    fn test_apply_masks() {
+        let redacted = rules(&[], true, true, false).apply(DIFF);
+        assert!(redacted.contains("+DB_HOST=[ip]\n"));
+        assert!(redacted.contains("send(\"[email]\", 'Disk full on db-1', host=\"[ip]\")"));
+        assert!(redacted.contains("version 1.2.3.4000 talks to [ip] and [email]\n"));
+        assert!(redacted.contains("+++ b/src/notify.py\n"));
+
+        let redacted = rules(&[], false, false, true).apply(DIFF);
+        assert!(redacted.contains("send(\"[string]\", '[string]', host=\"[string]\")"));
+        assert!(redacted.contains("# don't retry"));
+        assert_eq!(
+            mask_string_literals("fn first<'a>(x: &'a str, y: &'a str) -> char { 'x' }"),
+            "fn first<'a>(x: &'a str, y: &'a str) -> char { '[string]' }"
+        );
+    }
+
+    #[test]
+    fn test_mask_ips() {
+        assert_eq!(
+            mask_ips("use serde::de::Deserialize;"),
+            "use serde::de::Deserialize;"
+        );
+        assert_eq!(
+            mask_ips("listen on ::1 and 2001:db8::8a2e:370:7334"),
+            "listen on [ip] and [ip]"
+        );
+        assert_eq!(mask_ips("999.1.1.1"), "999.1.1.1");
+    }
+
+    #[test]
+    fn test_set_and_describe() {
+        let mut rules = Rules::default();
+        rules
+            .set("paths", Some("secrets/**, *.pem".to_string()))
+            .unwrap();
+        rules.set("emails", Some("yes".to_string())).unwrap();
+        assert!(rules.set("emails", Some("maybe".to_string())).is_err());
+        assert!(rules.set("names", Some("true".to_string())).is_err());
+        assert_eq!(rules.get("paths").unwrap(), "secrets/**, *.pem");
+        assert_eq!(rules.describe(), "paths (secrets/**, *.pem), emails");
+
+        let merged = Rules {
+            emails: Some(false),
+            ..Rules::default()
+        }
+        .or(rules);
+        assert_eq!(merged.emails, Some(false));
+        assert_eq!(merged.paths.unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,167 @@
+use crate::ui;
+use anyhow::Result;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Attempts per item, on top of the retries the API client already performs
+const DEFAULT_ITEM_ATTEMPTS: usize = 2;
+
+/// Result of one item in a batch
+pub struct ItemOutcome<T> {
+    pub label: String,
+    pub result: Result<T>,
+}
+
+/// Per-item results of a batch, in input order
+pub struct BatchReport<T> {
+    pub outcomes: Vec<ItemOutcome<T>>,
+}
+
+impl<T> BatchReport<T> {
+    pub fn succeeded(&self) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|outcome| outcome.result.is_ok())
+            .count()
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = (&str, &anyhow::Error)> {
+        self.outcomes.iter().filter_map(|outcome| {
+            outcome
+                .result
+                .as_ref()
+                .err()
+                .map(|err| (outcome.label.as_str(), err))
+        })
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.failed().next().is_some()
+    }
+}
+
+/// Runs many LLM calls one after another within the provider's rate limit
+///
+/// Used by commands that issue a request per item (chunk summaries,
+/// changelog entries, rewording commits). A failing item is retried and then
+/// recorded in the report instead of aborting the whole batch.
+pub struct Scheduler {
+    min_interval: Duration,
+    item_attempts: usize,
+    last_start: Option<Instant>,
+}
+
+impl Scheduler {
+    /// Create a scheduler allowing at most `requests_per_minute` requests, unlimited when `None`
+    pub fn new(requests_per_minute: Option<u32>) -> Self {
+        let min_interval = requests_per_minute
+            .filter(|&rpm| rpm > 0)
+            .map_or(Duration::ZERO, |rpm| Duration::from_secs(60) / rpm);
+
+        Self {
+            min_interval,
+            item_attempts: DEFAULT_ITEM_ATTEMPTS,
+            last_start: None,
+        }
+    }
+
+    /// Wait until the next request is allowed by the rate limit
+    async fn throttle(&mut self) {
+        if let Some(last_start) = self.last_start {
+            tokio::time::sleep_until(last_start + self.min_interval).await;
+        }
+        self.last_start = Some(Instant::now());
+    }
+
+    /// Run `task` for every item, showing progress under `progress_label`
+    pub async fn run<I, T>(
+        &mut self,
+        progress_label: &str,
+        items: Vec<(String, I)>,
+        task: impl AsyncFn(&I) -> Result<T>,
+    ) -> BatchReport<T> {
+        let total = items.len();
+        let mut outcomes = Vec::with_capacity(total);
+
+        ui::print_progress(progress_label, 0, total);
+        for (index, (label, item)) in items.into_iter().enumerate() {
+            let mut attempt = 1;
+            let result = loop {
+                self.throttle().await;
+                match task(&item).await {
+                    Err(_) if attempt < self.item_attempts => attempt += 1,
+                    result => break result,
+                }
+            };
+
+            outcomes.push(ItemOutcome { label, result });
+            ui::print_progress(progress_label, index + 1, total);
+        }
+
+        BatchReport { outcomes }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_min_interval() {
+        assert_eq!(Scheduler::new(None).min_interval, Duration::ZERO);
+        assert_eq!(Scheduler::new(Some(0)).min_interval, Duration::ZERO);
+        assert_eq!(
+            Scheduler::new(Some(120)).min_interval,
+            Duration::from_millis(500)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_reports_per_item() {
+        let calls = Cell::new(0);
+        let items = vec![
+            ("ok".to_string(), 1),
+            ("flaky".to_string(), 2),
+            ("broken".to_string(), 3),
+        ];
+
+        let mut scheduler = Scheduler::new(None);
+        let flaky_failed = Cell::new(false);
+        let report = scheduler
+            .run("Testing", items, async |&item: &i32| {
+                calls.set(calls.get() + 1);
+                match item {
+                    // Fails on the first attempt only
+                    2 if !flaky_failed.replace(true) => Err(anyhow::anyhow!("transient")),
+                    3 => Err(anyhow::anyhow!("permanent")),
+                    _ => Ok(item * 10),
+                }
+            })
+            .await;
+
+        assert_eq!(report.succeeded(), 2);
+        assert!(report.has_failures());
+        let failed: Vec<&str> = report.failed().map(|(label, _)| label).collect();
+        assert_eq!(failed, vec!["broken"]);
+        assert_eq!(*report.outcomes[1].result.as_ref().unwrap(), 20);
+
+        // ok once, flaky twice, broken for every attempt
+        assert_eq!(calls.get(), 1 + 2 + DEFAULT_ITEM_ATTEMPTS);
+    }
+
+    #[tokio::test]
+    async fn test_run_respects_rate_limit() {
+        let items = (0..3).map(|i| (i.to_string(), i)).collect();
+        let start = Instant::now();
+
+        let mut scheduler = Scheduler::new(Some(600));
+        let report = scheduler
+            .run("Testing", items, async |&item: &i32| Ok(item))
+            .await;
+
+        assert_eq!(report.succeeded(), 3);
+        // Three requests at one per 100ms need two full intervals
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+}
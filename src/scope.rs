@@ -0,0 +1,165 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Files that mark the root of a package in a monorepo
+const PACKAGE_MANIFESTS: &[&str] = &["Cargo.toml", "package.json", "pyproject.toml", "go.mod"];
+
+/// Source directories whose children name the scope rather than the directory itself
+const SOURCE_DIRS: &[&str] = &["src", "lib", "source", "app"];
+
+/// File stems that say nothing about the area of a change
+const GENERIC_STEMS: &[&str] = &["main", "lib", "mod", "index"];
+
+/// Infer commit scope candidates from staged paths, most touched first
+///
+/// A file belongs to the nearest package below the repository root, the
+/// directory of its manifest. Other files fall back to their top-level
+/// directory, or the module below a source directory like `src/`. With an
+/// allowlist, only allowed scopes are returned.
+pub fn infer_scopes(
+    files: &[String],
+    is_package_dir: impl Fn(&str) -> bool,
+    allowed: &[String],
+) -> Vec<String> {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for file in files {
+        if let Some(scope) = scope_of(file, &is_package_dir) {
+            *counts.entry(scope).or_default() += 1;
+        }
+    }
+
+    let mut scopes: Vec<(String, usize)> = counts
+        .into_iter()
+        .filter(|(scope, _)| allowed.is_empty() || allowed.contains(scope))
+        .collect();
+    // Stable sort keeps ties in alphabetical order
+    scopes.sort_by_key(|(_, count)| Reverse(*count));
+    scopes.into_iter().map(|(scope, _)| scope).collect()
+}
+
+/// Whether a repository-relative directory holds a package manifest
+pub fn has_manifest(repo_root: &Path, dir: &str) -> bool {
+    PACKAGE_MANIFESTS
+        .iter()
+        .any(|manifest| repo_root.join(dir).join(manifest).is_file())
+}
+
+// Scope of a single file, `None` for files at the root
+fn scope_of(file: &str, is_package_dir: &impl Fn(&str) -> bool) -> Option<String> {
+    let parts: Vec<&str> = file.split('/').collect();
+    let dirs = &parts[..parts.len() - 1];
+
+    // Nearest enclosing package, named after its directory
+    if let Some(depth) = (1..=dirs.len())
+        .rev()
+        .find(|&depth| is_package_dir(&dirs[..depth].join("/")))
+    {
+        return Some(dirs[depth - 1].to_string());
+    }
+
+    match parts.as_slice() {
+        [] | [_] => None,
+        [dir, rest @ ..] if SOURCE_DIRS.contains(dir) => {
+            let name = rest[0].split('.').next().unwrap_or_default();
+            (!name.is_empty() && !GENERIC_STEMS.contains(&name)).then(|| name.to_string())
+        }
+        [dir, ..] => Some(dir.to_string()),
+    }
+}
+
+/// Instructions for the system prompt about which scope to use
+pub fn scope_hint(candidates: &[String], allowed: &[String]) -> Option<String> {
+    let allowlist = (!allowed.is_empty()).then(|| {
+        format!(
+            " Only these scopes are allowed: {}. Omit the scope if none fits.",
+            allowed.join(", ")
+        )
+    });
+
+    let hint = match candidates {
+        [] => return allowlist.map(|allowlist| format!("Scope:{allowlist}")),
+        [scope] => format!("Scope: the change is confined to `{scope}`, use it as the scope."),
+        _ => format!(
+            "Scope: the change touches {}. Use the main one as the scope, or omit the scope \
+             if the change is spread evenly. Do not invent other scopes.",
+            candidates
+                .iter()
+                .map(|scope| format!("`{scope}`"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+
+    Some(hint + allowlist.as_deref().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn paths(files: &[&str]) -> Vec<String> {
+        files.iter().map(|file| file.to_string()).collect()
+    }
+
+    fn packages(dir: &str) -> bool {
+        ["crates/core", "crates/cli", "web"].contains(&dir)
+    }
+
+    #[test]
+    fn test_infer_from_packages() {
+        let files = paths(&[
+            "crates/core/src/lib.rs",
+            "crates/core/src/parser/mod.rs",
+            "crates/cli/src/main.rs",
+        ]);
+        assert_eq!(infer_scopes(&files, packages, &[]), vec!["core", "cli"]);
+
+        let files = paths(&["web/src/App.tsx", "web/package.json"]);
+        assert_eq!(infer_scopes(&files, packages, &[]), vec!["web"]);
+    }
+
+    #[test]
+    fn test_infer_without_packages() {
+        let files = paths(&["src/llm.rs", "src/llm/stream.rs", "docs/guide.md"]);
+        assert_eq!(infer_scopes(&files, |_| false, &[]), vec!["llm", "docs"]);
+
+        // Root files and generic entry points have no scope
+        let files = paths(&["README.md", "src/main.rs"]);
+        assert!(infer_scopes(&files, |_| false, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_infer_with_allowlist() {
+        let files = paths(&["crates/core/src/lib.rs", "docs/guide.md"]);
+        let allowed = paths(&["core", "cli"]);
+        assert_eq!(infer_scopes(&files, packages, &allowed), vec!["core"]);
+    }
+
+    #[test]
+    fn test_has_manifest() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("crates/core")).unwrap();
+        std::fs::write(temp_dir.path().join("crates/core/Cargo.toml"), "").unwrap();
+
+        assert!(has_manifest(temp_dir.path(), "crates/core"));
+        assert!(!has_manifest(temp_dir.path(), "crates"));
+    }
+
+    #[test]
+    fn test_scope_hint() {
+        assert_eq!(scope_hint(&[], &[]), None);
+        assert_eq!(
+            scope_hint(&paths(&["core"]), &[]).unwrap(),
+            "Scope: the change is confined to `core`, use it as the scope."
+        );
+        assert!(scope_hint(&paths(&["core", "cli"]), &[])
+            .unwrap()
+            .contains("touches `core`, `cli`"));
+        assert_eq!(
+            scope_hint(&[], &paths(&["core", "cli"])).unwrap(),
+            "Scope: Only these scopes are allowed: core, cli. Omit the scope if none fits."
+        );
+    }
+}
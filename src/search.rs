@@ -0,0 +1,264 @@
+use crate::git::LogEntry;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// `embedding_model` that embeds on this machine instead of calling the API
+pub const LOCAL_MODEL: &str = "local";
+
+/// Length of the vectors of the local embedding
+const LOCAL_DIMENSIONS: usize = 512;
+
+/// Longest part of a commit message that is embedded, in bytes
+const MAX_EMBEDDED_BYTES: usize = 8000;
+
+/// Commit messages embedded for `aic search`, stored in `.git/aic/search-index.json`
+///
+/// The vectors of different models can't be compared, so an index built
+/// with another `embedding_model` is started over.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SearchIndex {
+    pub model: String,
+    #[serde(default)]
+    pub entries: Vec<IndexEntry>,
+}
+
+/// One embedded commit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub commit: String,
+    pub date: String,
+    pub subject: String,
+    pub vector: Vec<f32>,
+}
+
+impl SearchIndex {
+    /// Index file in the `.git/aic` directory
+    pub fn path(aic_dir: &Path) -> PathBuf {
+        aic_dir.join("search-index.json")
+    }
+
+    /// Load the index, or start an empty one when there is none yet or it
+    /// was built with another model
+    pub fn load_from(path: &Path, model: &str) -> Result<Self> {
+        let empty = Self {
+            model: model.to_string(),
+            entries: Vec::new(),
+        };
+        if !path.exists() {
+            return Ok(empty);
+        }
+
+        let contents = fs::read_to_string(path).context("Could not read the search index")?;
+        let index: Self =
+            serde_json::from_str(&contents).context("Failed to parse the search index")?;
+        Ok(if index.model == model { index } else { empty })
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string(self).context("Failed to serialize the search index")?;
+        fs::write(path, json).context("Failed to write the search index")
+    }
+
+    /// Drop commits that are no longer on the branch, e.g. after a rebase,
+    /// and return the ones that still need embedding
+    pub fn sync<'a>(&mut self, commits: &'a [LogEntry]) -> Vec<&'a LogEntry> {
+        let current: HashSet<&str> = commits.iter().map(|commit| commit.id.as_str()).collect();
+        self.entries
+            .retain(|entry| current.contains(entry.commit.as_str()));
+
+        let indexed: HashSet<&str> = self
+            .entries
+            .iter()
+            .map(|entry| entry.commit.as_str())
+            .collect();
+        commits
+            .iter()
+            .filter(|commit| !indexed.contains(commit.id.as_str()))
+            .collect()
+    }
+
+    pub fn add(&mut self, commit: &LogEntry, vector: Vec<f32>) {
+        self.entries.push(IndexEntry {
+            commit: commit.id.clone(),
+            date: commit.date.clone(),
+            subject: commit
+                .message
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            vector,
+        });
+    }
+
+    /// The `limit` commits most similar to the query, most similar first,
+    /// with their cosine similarity
+    pub fn search(&self, query: &[f32], limit: usize) -> Vec<(f32, &IndexEntry)> {
+        let mut scored: Vec<(f32, &IndexEntry)> = self
+            .entries
+            .iter()
+            .map(|entry| (cosine(query, &entry.vector), entry))
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored.truncate(limit);
+        scored
+    }
+}
+
+/// The text of a commit that is embedded, its message cut to a size every
+/// embedding model accepts
+pub fn embedded_text(commit: &LogEntry) -> String {
+    let mut cut = commit.message.len().min(MAX_EMBEDDED_BYTES);
+    while !commit.message.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    commit.message[..cut].to_string()
+}
+
+/// Embed a text without a model: its words and word pairs hashed into a
+/// fixed number of buckets
+///
+/// Much weaker than a real embedding, it finds commits sharing words with the
+/// question ("retry" finds "retries" too), but works offline and for free.
+pub fn local_embedding(text: &str) -> Vec<f32> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.len() > 1)
+        .map(str::to_lowercase)
+        .filter(|word| !STOP_WORDS.contains(&word.as_str()))
+        .map(|word| stem(&word))
+        .collect();
+
+    let mut vector = vec![0.0; LOCAL_DIMENSIONS];
+    for word in &words {
+        vector[bucket(word)] += 1.0;
+    }
+    for pair in words.windows(2) {
+        vector[bucket(&format!("{} {}", pair[0], pair[1]))] += 0.5;
+    }
+    vector
+}
+
+/// Cosine similarity of two vectors, 0 when either is all zeros
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    match norm(a) * norm(b) {
+        0.0 => 0.0,
+        norms => dot / norms,
+    }
+}
+
+/// Words too common to say anything about a commit
+const STOP_WORDS: &[&str] = &[
+    "the", "and", "for", "to", "of", "in", "on", "is", "it", "we", "did", "do", "when", "what",
+    "which", "where", "how", "why", "was", "with", "from", "this", "that", "be", "by", "an", "or",
+];
+
+/// Strip the most common English suffixes so that word forms match
+fn stem(word: &str) -> String {
+    for suffix in ["ing", "ies", "ed", "es", "s", "e"] {
+        if let Some(stem) = word.strip_suffix(suffix) {
+            if stem.len() >= 3 {
+                return match suffix {
+                    "ies" => format!("{stem}y"),
+                    _ => stem.to_string(),
+                };
+            }
+        }
+    }
+    word.to_string()
+}
+
+/// FNV-1a hash of a word, stable across builds so saved indexes stay valid
+fn bucket(word: &str) -> usize {
+    let hash = word.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    (hash % LOCAL_DIMENSIONS as u64) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn commit(id: &str, message: &str) -> LogEntry {
+        LogEntry {
+            id: id.to_string(),
+            date: "2024-05-01".to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_local_search() {
+        let commits = vec![
+            commit("a", "docs: describe the config file"),
+            commit(
+                "b",
+                "fix(llm): retry requests after rate limits\n\nBack off exponentially.",
+            ),
+            commit("c", "feat: add a spinner"),
+        ];
+        let mut index = SearchIndex {
+            model: LOCAL_MODEL.to_string(),
+            entries: Vec::new(),
+        };
+        for commit in &commits {
+            index.add(commit, local_embedding(&embedded_text(commit)));
+        }
+
+        let results = index.search(&local_embedding("when did we change the retry logic?"), 2);
+        assert_eq!(results[0].1.commit, "b");
+        assert_eq!(
+            results[0].1.subject,
+            "fix(llm): retry requests after rate limits"
+        );
+        assert!(results[0].0 > results[1].0);
+        assert_eq!(results.len(), 2);
+    }
+
+    #[test]
+    fn test_index_sync_and_reload() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = SearchIndex::path(temp_dir.path());
+        let mut index = SearchIndex::load_from(&path, "text-embedding-3-small").unwrap();
+        assert!(index.entries.is_empty());
+
+        let commits = vec![commit("a", "feat: one"), commit("b", "fix: two")];
+        assert_eq!(index.sync(&commits).len(), 2);
+        index.add(&commits[0], vec![1.0, 0.0]);
+        index.add(&commit("gone", "chore: rebased away"), vec![0.0, 1.0]);
+        index.save_to(&path).unwrap();
+
+        let mut reloaded = SearchIndex::load_from(&path, "text-embedding-3-small").unwrap();
+        let missing = reloaded.sync(&commits);
+        assert_eq!(missing, vec![&commits[1]]);
+        assert_eq!(reloaded.entries.len(), 1);
+
+        // Vectors of another model are not reused
+        assert!(SearchIndex::load_from(&path, LOCAL_MODEL)
+            .unwrap()
+            .entries
+            .is_empty());
+    }
+
+    #[test]
+    fn test_cosine() {
+        assert_eq!(cosine(&[1.0, 0.0], &[1.0, 0.0]), 1.0);
+        assert_eq!(cosine(&[1.0, 0.0], &[0.0, 1.0]), 0.0);
+        assert_eq!(cosine(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn test_stem() {
+        assert_eq!(stem("retries"), "retry");
+        assert_eq!(stem("retrying"), "retry");
+        assert_eq!(stem("changed"), stem("change"));
+        assert_eq!(stem("is"), "is");
+    }
+}
@@ -0,0 +1,406 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Language of commit messages, for the `spell_check` pass
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    En,
+}
+
+impl Language {
+    /// Common misspellings and their corrections, all lowercase
+    fn misspellings(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Language::En => EN_MISSPELLINGS,
+        }
+    }
+
+    /// Words that may legitimately follow themselves, e.g. "that that"
+    fn repeatable(self) -> &'static [&'static str] {
+        match self {
+            Language::En => &["that", "had", "is", "do"],
+        }
+    }
+}
+
+impl FromStr for Language {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "en" | "en-us" | "en-gb" | "english" => Ok(Language::En),
+            _ => Err(anyhow::anyhow!(
+                "Invalid value for spell_check: expected a supported language (en), got '{}'",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Language::En => write!(f, "en"),
+        }
+    }
+}
+
+/// A word of the message that looks wrong, with the suggested correction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Misspelling {
+    /// 1-based line of the message
+    pub line: usize,
+    pub word: String,
+    pub suggestion: String,
+}
+
+impl fmt::Display for Misspelling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {} → {}", self.line, self.word, self.suggestion)
+    }
+}
+
+/// Find misspelled and doubled words in a commit message
+///
+/// This is a lookup of well-known misspellings rather than a dictionary
+/// check, so names, identifiers and jargon are never flagged. Code in
+/// backticks, paths, URLs and identifiers are skipped.
+pub fn check(message: &str, language: Language) -> Vec<Misspelling> {
+    correct(message, language).1
+}
+
+/// The message with every suggestion of [`check`] applied
+pub fn apply(message: &str, language: Language) -> String {
+    correct(message, language).0
+}
+
+fn correct(message: &str, language: Language) -> (String, Vec<Misspelling>) {
+    let mut corrected = Vec::new();
+    let mut misspellings = Vec::new();
+    for (index, line) in message.split('\n').enumerate() {
+        let mut fixed = String::with_capacity(line.len());
+        let mut last = 0;
+        let mut previous: Option<&str> = None;
+        for (start, end) in words(line) {
+            let word = &line[start..end];
+            let lower = word.to_lowercase();
+            if previous.is_some_and(|previous| previous.eq_ignore_ascii_case(word))
+                && line[last..start].chars().all(|c| c == ' ')
+                && !language.repeatable().contains(&lower.as_str())
+            {
+                misspellings.push(Misspelling {
+                    line: index + 1,
+                    word: format!("{} {word}", previous.unwrap_or_default()),
+                    suggestion: previous.unwrap_or_default().to_string(),
+                });
+                // Drop the repeated word and the space before it
+                last = end;
+                continue;
+            }
+
+            fixed.push_str(&line[last..start]);
+            match language
+                .misspellings()
+                .iter()
+                .find(|(wrong, _)| *wrong == lower)
+            {
+                Some((_, right)) => {
+                    let suggestion = match_case(word, right);
+                    misspellings.push(Misspelling {
+                        line: index + 1,
+                        word: word.to_string(),
+                        suggestion: suggestion.clone(),
+                    });
+                    fixed.push_str(&suggestion);
+                }
+                None => fixed.push_str(word),
+            }
+            last = end;
+            previous = Some(word);
+        }
+        fixed.push_str(&line[last..]);
+        corrected.push(fixed);
+    }
+    (corrected.join("\n"), misspellings)
+}
+
+/// Byte ranges of the plain words of a line, leaving out code in backticks
+/// and tokens that look like paths, URLs or identifiers
+fn words(line: &str) -> Vec<(usize, usize)> {
+    let mut words = Vec::new();
+    let mut in_code = false;
+    let mut token_start = None;
+    for (index, c) in line.char_indices().chain([(line.len(), ' ')]) {
+        if c == '`' {
+            in_code = !in_code;
+            token_start = None;
+        } else if c.is_whitespace() {
+            if let Some(start) = token_start.take() {
+                words.extend(plain_word(line, start, index));
+            }
+        } else if !in_code && token_start.is_none() {
+            token_start = Some(index);
+        }
+    }
+    words
+}
+
+/// The word in a whitespace-separated token, without surrounding punctuation,
+/// or `None` when the token is more than a word
+fn plain_word(line: &str, start: usize, end: usize) -> Option<(usize, usize)> {
+    let token = &line[start..end];
+    let trimmed = token.trim_start_matches(|c: char| !c.is_alphanumeric());
+    let word_start = start + token.len() - trimmed.len();
+    let word = trimmed.trim_end_matches(|c: char| !c.is_alphanumeric());
+    let word_end = word_start + word.len();
+
+    let looks_like_word = !word.is_empty()
+        && word.chars().all(|c| c.is_alphabetic() || c == '\'')
+        && !word.chars().skip(1).any(char::is_uppercase);
+    looks_like_word.then_some((word_start, word_end))
+}
+
+/// `correction` with the capitalization of `word`
+fn match_case(word: &str, correction: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) if first.is_uppercase() => {
+            let mut correction_chars = correction.chars();
+            correction_chars
+                .next()
+                .map(|c| c.to_uppercase().chain(correction_chars).collect())
+                .unwrap_or_default()
+        }
+        _ => correction.to_string(),
+    }
+}
+
+/// Misspellings common in commit messages and code review, after the lists
+/// of codespell and Wikipedia
+const EN_MISSPELLINGS: &[(&str, &str)] = &[
+    ("accomodate", "accommodate"),
+    ("accross", "across"),
+    ("acheive", "achieve"),
+    ("adress", "address"),
+    ("adressed", "addressed"),
+    ("agressive", "aggressive"),
+    ("algorith", "algorithm"),
+    ("algoritm", "algorithm"),
+    ("alot", "a lot"),
+    ("alredy", "already"),
+    ("ammount", "amount"),
+    ("aproach", "approach"),
+    ("arguement", "argument"),
+    ("arguements", "arguments"),
+    ("asynchonous", "asynchronous"),
+    ("asyncronous", "asynchronous"),
+    ("atribute", "attribute"),
+    ("attribue", "attribute"),
+    ("availabe", "available"),
+    ("availible", "available"),
+    ("begining", "beginning"),
+    ("beleive", "believe"),
+    ("behaviuor", "behaviour"),
+    ("boundry", "boundary"),
+    ("buisness", "business"),
+    ("charachter", "character"),
+    ("charater", "character"),
+    ("collapsable", "collapsible"),
+    ("comand", "command"),
+    ("comit", "commit"),
+    ("commited", "committed"),
+    ("commiting", "committing"),
+    ("comparision", "comparison"),
+    ("compatability", "compatibility"),
+    ("compatable", "compatible"),
+    ("compatibilty", "compatibility"),
+    ("completly", "completely"),
+    ("conection", "connection"),
+    ("configration", "configuration"),
+    ("configuraiton", "configuration"),
+    ("consistant", "consistent"),
+    ("containg", "containing"),
+    ("convertion", "conversion"),
+    ("corect", "correct"),
+    ("curently", "currently"),
+    ("deafult", "default"),
+    ("defautl", "default"),
+    ("definately", "definitely"),
+    ("definiton", "definition"),
+    ("dependancy", "dependency"),
+    ("dependancies", "dependencies"),
+    ("dependecy", "dependency"),
+    ("depricated", "deprecated"),
+    ("desciption", "description"),
+    ("destory", "destroy"),
+    ("diffrent", "different"),
+    ("directoy", "directory"),
+    ("dissapear", "disappear"),
+    ("doesnt", "doesn't"),
+    ("dont", "don't"),
+    ("duplicatd", "duplicated"),
+    ("enviroment", "environment"),
+    ("enviornment", "environment"),
+    ("existant", "existent"),
+    ("exising", "existing"),
+    ("explicitely", "explicitly"),
+    ("extention", "extension"),
+    ("failuer", "failure"),
+    ("fucntion", "function"),
+    ("funciton", "function"),
+    ("functon", "function"),
+    ("garantee", "guarantee"),
+    ("handeling", "handling"),
+    ("hierachy", "hierarchy"),
+    ("ignor", "ignore"),
+    ("immediatly", "immediately"),
+    ("implemenation", "implementation"),
+    ("implmentation", "implementation"),
+    ("incompatable", "incompatible"),
+    ("inconsistant", "inconsistent"),
+    ("independant", "independent"),
+    ("infomation", "information"),
+    ("initalize", "initialize"),
+    ("initalise", "initialise"),
+    ("intial", "initial"),
+    ("interupt", "interrupt"),
+    ("isnt", "isn't"),
+    ("lenght", "length"),
+    ("libary", "library"),
+    ("maintainance", "maintenance"),
+    ("maintenence", "maintenance"),
+    ("mesage", "message"),
+    ("messsage", "message"),
+    ("millisecons", "milliseconds"),
+    ("mispell", "misspell"),
+    ("mispelled", "misspelled"),
+    ("neccessary", "necessary"),
+    ("necesary", "necessary"),
+    ("occured", "occurred"),
+    ("occurence", "occurrence"),
+    ("occurrance", "occurrence"),
+    ("ommit", "omit"),
+    ("optinal", "optional"),
+    ("overriden", "overridden"),
+    ("paramater", "parameter"),
+    ("paramter", "parameter"),
+    ("parrallel", "parallel"),
+    ("paralell", "parallel"),
+    ("performace", "performance"),
+    ("persistant", "persistent"),
+    ("posible", "possible"),
+    ("preceed", "precede"),
+    ("prefered", "preferred"),
+    ("previosly", "previously"),
+    ("priviledge", "privilege"),
+    ("proccess", "process"),
+    ("propery", "property"),
+    ("recieve", "receive"),
+    ("recieved", "received"),
+    ("recomend", "recommend"),
+    ("recursivly", "recursively"),
+    ("refered", "referred"),
+    ("reponse", "response"),
+    ("repositry", "repository"),
+    ("resouce", "resource"),
+    ("retreive", "retrieve"),
+    ("seperat", "separate"),
+    ("seperate", "separate"),
+    ("seperated", "separated"),
+    ("seperately", "separately"),
+    ("seperator", "separator"),
+    ("similiar", "similar"),
+    ("sucess", "success"),
+    ("succesful", "successful"),
+    ("successfull", "successful"),
+    ("suport", "support"),
+    ("supress", "suppress"),
+    ("syncronous", "synchronous"),
+    ("teh", "the"),
+    ("threshhold", "threshold"),
+    ("transfered", "transferred"),
+    ("truely", "truly"),
+    ("unecessary", "unnecessary"),
+    ("unneccessary", "unnecessary"),
+    ("untill", "until"),
+    ("upate", "update"),
+    ("usefull", "useful"),
+    ("valiation", "validation"),
+    ("varaible", "variable"),
+    ("visibile", "visible"),
+    ("wich", "which"),
+    ("wierd", "weird"),
+    ("writting", "writing"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_finds_misspellings() {
+        let message =
+            "fix: seperat parsing from teh Lexer\n\nRecieve `seperat` and src/seperat.rs untouched";
+        let found = check(message, Language::En);
+        assert_eq!(
+            found,
+            vec![
+                Misspelling {
+                    line: 1,
+                    word: "seperat".to_string(),
+                    suggestion: "separate".to_string(),
+                },
+                Misspelling {
+                    line: 1,
+                    word: "teh".to_string(),
+                    suggestion: "the".to_string(),
+                },
+                Misspelling {
+                    line: 3,
+                    word: "Recieve".to_string(),
+                    suggestion: "Receive".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            apply(message, Language::En),
+            "fix: separate parsing from the Lexer\n\nReceive `seperat` and src/seperat.rs untouched"
+        );
+    }
+
+    #[test]
+    fn test_check_finds_repeated_words() {
+        let message = "docs: explain the the cache, and that that is fine";
+        assert_eq!(
+            check(message, Language::En),
+            vec![Misspelling {
+                line: 1,
+                word: "the the".to_string(),
+                suggestion: "the".to_string(),
+            }]
+        );
+        assert_eq!(
+            apply(message, Language::En),
+            "docs: explain the cache, and that that is fine"
+        );
+    }
+
+    #[test]
+    fn test_identifiers_are_skipped() {
+        assert!(check(
+            "refactor: rename seperatValue and seperat_value",
+            Language::En
+        )
+        .is_empty());
+        assert!(check("feat: support https://teh.example.com", Language::En).is_empty());
+    }
+
+    #[test]
+    fn test_parse_language() {
+        assert_eq!("EN".parse::<Language>().unwrap(), Language::En);
+        assert!("klingon".parse::<Language>().is_err());
+    }
+}
@@ -0,0 +1,224 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Number of distinct areas at which a staged change is considered oversized
+pub const SPLIT_SUGGESTION_THRESHOLD: usize = 3;
+
+/// Name used for files that sit directly in the common directory
+const ROOT_GROUP_NAME: &str = "(root)";
+
+/// Name of the group collecting the files the model left out
+const REMAINING_GROUP_NAME: &str = "Remaining changes";
+
+/// A set of staged files that belong to the same area of the repository
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeGroup {
+    pub name: String,
+    pub files: Vec<String>,
+}
+
+/// Group staged file paths by the area of the repository they touch
+///
+/// The directory shared by all files is ignored, so a change confined to
+/// `src/` is grouped by the directories below it rather than collapsing
+/// into a single `src` group.
+pub fn group_by_area(files: &[String]) -> Vec<ChangeGroup> {
+    let components: Vec<Vec<&str>> = files.iter().map(|file| file.split('/').collect()).collect();
+
+    // Length of the directory prefix shared by every file
+    let common = components
+        .iter()
+        .map(|parts| &parts[..parts.len().saturating_sub(1)])
+        .reduce(|prefix, dirs| {
+            let shared = prefix
+                .iter()
+                .zip(dirs.iter())
+                .take_while(|(a, b)| a == b)
+                .count();
+            &prefix[..shared]
+        })
+        .map_or(0, |prefix| prefix.len());
+
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (file, parts) in files.iter().zip(components.iter()) {
+        let name = if parts.len() > common + 1 {
+            parts[common].to_string()
+        } else {
+            ROOT_GROUP_NAME.to_string()
+        };
+        groups.entry(name).or_default().push(file.clone());
+    }
+
+    groups
+        .into_iter()
+        .map(|(name, files)| ChangeGroup { name, files })
+        .collect()
+}
+
+/// A group as the model describes it
+#[derive(Deserialize)]
+struct ProposedGroup {
+    name: String,
+    files: Vec<String>,
+}
+
+/// Parse the model's grouping of the staged files into commit groups
+///
+/// Only staged files are kept and each goes into the first group that names
+/// it. Files the model left out are collected in a last group, so every file
+/// ends up in exactly one group.
+pub fn parse_groups(response: &str, files: &[String]) -> Result<Vec<ChangeGroup>> {
+    // The array may be wrapped in a code block or some text
+    let json = match (response.find('['), response.rfind(']')) {
+        (Some(start), Some(end)) if start < end => &response[start..=end],
+        _ => anyhow::bail!("The model did not reply with a list of groups"),
+    };
+    let proposed: Vec<ProposedGroup> =
+        serde_json::from_str(json).context("The model did not reply with a list of groups")?;
+
+    let mut assigned: Vec<&String> = Vec::new();
+    let mut groups = Vec::new();
+    for group in proposed {
+        let group_files: Vec<String> = group
+            .files
+            .iter()
+            .map(|file| file.trim().trim_start_matches("./"))
+            .filter_map(|file| files.iter().find(|staged| staged.as_str() == file))
+            .filter(|staged| !assigned.contains(staged))
+            .cloned()
+            .collect();
+        if group_files.is_empty() {
+            continue;
+        }
+        assigned.extend(files.iter().filter(|file| group_files.contains(file)));
+        groups.push(ChangeGroup {
+            name: group.name.trim().to_string(),
+            files: group_files,
+        });
+    }
+
+    let remaining: Vec<String> = files
+        .iter()
+        .filter(|file| !assigned.contains(file))
+        .cloned()
+        .collect();
+    if !remaining.is_empty() {
+        groups.push(ChangeGroup {
+            name: REMAINING_GROUP_NAME.to_string(),
+            files: remaining,
+        });
+    }
+
+    Ok(groups)
+}
+
+/// Whether the groups look like several unrelated changes
+pub fn is_oversized(groups: &[ChangeGroup]) -> bool {
+    groups.len() >= SPLIT_SUGGESTION_THRESHOLD
+}
+
+/// Parse a selection like `1, 3` into zero-based group indices
+///
+/// Returns `None` when any entry is not a valid group number.
+pub fn parse_group_selection(input: &str, count: usize) -> Option<Vec<usize>> {
+    let mut selected = Vec::new();
+
+    for part in input
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|part| !part.is_empty())
+    {
+        match part.parse::<usize>() {
+            Ok(choice) if (1..=count).contains(&choice) => {
+                if !selected.contains(&(choice - 1)) {
+                    selected.push(choice - 1);
+                }
+            }
+            _ => return None,
+        }
+    }
+
+    Some(selected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(files: &[&str]) -> Vec<String> {
+        files.iter().map(|file| file.to_string()).collect()
+    }
+
+    #[test]
+    fn test_group_by_top_level_directory() {
+        let groups = group_by_area(&paths(&[
+            "docs/guide.md",
+            "src/main.rs",
+            "src/cli.rs",
+            "README.md",
+            "tests/cli.rs",
+        ]));
+
+        let names: Vec<&str> = groups.iter().map(|group| group.name.as_str()).collect();
+        assert_eq!(names, vec!["(root)", "docs", "src", "tests"]);
+        assert_eq!(groups[2].files, paths(&["src/main.rs", "src/cli.rs"]));
+        assert!(is_oversized(&groups));
+    }
+
+    #[test]
+    fn test_group_ignores_common_prefix() {
+        let groups = group_by_area(&paths(&[
+            "crates/core/src/lib.rs",
+            "crates/core/Cargo.toml",
+            "crates/cli/src/main.rs",
+        ]));
+
+        let names: Vec<&str> = groups.iter().map(|group| group.name.as_str()).collect();
+        assert_eq!(names, vec!["cli", "core"]);
+        assert!(!is_oversized(&groups));
+    }
+
+    #[test]
+    fn test_group_single_file() {
+        let groups = group_by_area(&paths(&["src/main.rs"]));
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].name, "(root)");
+    }
+
+    #[test]
+    fn test_parse_groups() {
+        let files = paths(&["src/db.rs", "src/api.rs", "README.md", "Cargo.toml"]);
+        let response = "```json\n[\
+            {\"name\": \"Add the database layer\", \"files\": [\"src/db.rs\", \"Cargo.toml\"]},\
+            {\"name\": \"Expose it in the API\", \"files\": [\"./src/api.rs\", \"src/db.rs\", \"src/gone.rs\"]},\
+            {\"name\": \"Nothing staged\", \"files\": [\"src/gone.rs\"]}\
+        ]\n```";
+
+        let groups = parse_groups(response, &files).unwrap();
+        let names: Vec<&str> = groups.iter().map(|group| group.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "Add the database layer",
+                "Expose it in the API",
+                "Remaining changes"
+            ]
+        );
+        assert_eq!(groups[0].files, paths(&["src/db.rs", "Cargo.toml"]));
+        assert_eq!(groups[1].files, paths(&["src/api.rs"]));
+        assert_eq!(groups[2].files, paths(&["README.md"]));
+
+        assert!(parse_groups("Sorry, I can't do that.", &files).is_err());
+        assert!(parse_groups("[{\"name\": 1}]", &files).is_err());
+    }
+
+    #[test]
+    fn test_parse_group_selection() {
+        assert_eq!(parse_group_selection("", 3), Some(vec![]));
+        assert_eq!(parse_group_selection("2", 3), Some(vec![1]));
+        assert_eq!(parse_group_selection("1, 3", 3), Some(vec![0, 2]));
+        assert_eq!(parse_group_selection("3 3 1", 3), Some(vec![2, 0]));
+        assert_eq!(parse_group_selection("4", 3), None);
+        assert_eq!(parse_group_selection("x", 3), None);
+    }
+}
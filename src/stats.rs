@@ -0,0 +1,137 @@
+use crate::lint::{self, LintRules};
+use std::collections::BTreeMap;
+
+/// Scopes listed by `aic stats`, most active first
+pub const TOP_SCOPES: usize = 10;
+
+/// How well a set of commit messages follows the commit rules
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommitStats {
+    pub commits: usize,
+
+    /// Messages whose header is `type(scope): subject` with an allowed type
+    pub conventional: usize,
+
+    /// Messages breaking none of the rules `aic lint` checks
+    pub lint_clean: usize,
+
+    /// Mean length of the first line, in characters
+    pub average_subject_length: f64,
+
+    /// Commits per type of the conventional messages, most used first
+    pub types: Vec<(String, usize)>,
+
+    /// Commits per scope of the conventional messages, most used first
+    pub scopes: Vec<(String, usize)>,
+}
+
+impl CommitStats {
+    /// Analyze commit messages against `rules`
+    pub fn compute<'a>(messages: impl IntoIterator<Item = &'a str>, rules: &LintRules) -> Self {
+        let mut stats = Self::default();
+        let mut subject_chars = 0;
+        let mut types: BTreeMap<String, usize> = BTreeMap::new();
+        let mut scopes: BTreeMap<String, usize> = BTreeMap::new();
+        for message in messages {
+            stats.commits += 1;
+            let header = message.lines().next().unwrap_or_default().trim();
+            subject_chars += header.chars().count();
+            if lint::lint_message(message, rules).is_empty() {
+                stats.lint_clean += 1;
+            }
+
+            let Some(parsed) = lint::parse_header(header) else {
+                continue;
+            };
+            if !rules
+                .types
+                .iter()
+                .any(|allowed| allowed == parsed.commit_type)
+            {
+                continue;
+            }
+            stats.conventional += 1;
+            *types.entry(parsed.commit_type.to_string()).or_default() += 1;
+            if let Some(scope) = parsed.scope {
+                *scopes.entry(scope.to_string()).or_default() += 1;
+            }
+        }
+
+        if stats.commits > 0 {
+            stats.average_subject_length = subject_chars as f64 / stats.commits as f64;
+        }
+        stats.types = by_count(types);
+        stats.scopes = by_count(scopes);
+        stats
+    }
+
+    /// Share of conventional messages, from 0 to 1
+    pub fn conventional_rate(&self) -> f64 {
+        rate(self.conventional, self.commits)
+    }
+
+    /// Share of messages breaking no rule, from 0 to 1
+    pub fn lint_clean_rate(&self) -> f64 {
+        rate(self.lint_clean, self.commits)
+    }
+}
+
+fn rate(count: usize, total: usize) -> f64 {
+    match total {
+        0 => 0.0,
+        _ => count as f64 / total as f64,
+    }
+}
+
+/// Counts sorted from the highest down, names breaking ties
+fn by_count(counts: BTreeMap<String, usize>) -> Vec<(String, usize)> {
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    // Stable, so equal counts stay in name order
+    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute() {
+        let messages = [
+            "feat(cli): add stats command",
+            "fix(cli): handle empty history",
+            "fix(llm)!: retry on timeouts",
+            "docs: describe stats",
+            "wip: try something",
+            "Update README.md",
+        ];
+        let stats = CommitStats::compute(messages, &LintRules::default());
+
+        assert_eq!(stats.commits, 6);
+        assert_eq!(stats.conventional, 4);
+        assert_eq!(stats.lint_clean, 4);
+        assert_eq!(stats.conventional_rate(), 4.0 / 6.0);
+        let total: usize = messages.iter().map(|message| message.len()).sum();
+        assert_eq!(stats.average_subject_length, total as f64 / 6.0);
+        assert_eq!(
+            stats.types,
+            vec![
+                ("fix".to_string(), 2),
+                ("docs".to_string(), 1),
+                ("feat".to_string(), 1)
+            ]
+        );
+        assert_eq!(
+            stats.scopes,
+            vec![("cli".to_string(), 2), ("llm".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn test_compute_without_commits() {
+        let stats = CommitStats::compute([], &LintRules::default());
+        assert_eq!(stats.commits, 0);
+        assert_eq!(stats.conventional_rate(), 0.0);
+        assert_eq!(stats.average_subject_length, 0.0);
+    }
+}
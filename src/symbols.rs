@@ -0,0 +1,646 @@
+use crate::git::{self, FileDiff};
+use crate::priority::FileRole;
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::RangeInclusive;
+use tree_sitter::{Node, Parser};
+
+/// A language whose declarations are found with its tree-sitter grammar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Language {
+    Rust,
+    Python,
+    JavaScript,
+    TypeScript,
+    Tsx,
+    Go,
+    Java,
+}
+
+impl Language {
+    /// Language of a file from its extension
+    fn of(path: &str) -> Option<Self> {
+        let (_, extension) = path.rsplit_once('.')?;
+        Some(match extension {
+            "rs" => Self::Rust,
+            "py" | "pyi" => Self::Python,
+            "js" | "jsx" | "mjs" | "cjs" => Self::JavaScript,
+            "ts" | "mts" | "cts" => Self::TypeScript,
+            "tsx" => Self::Tsx,
+            "go" => Self::Go,
+            "java" => Self::Java,
+            _ => return None,
+        })
+    }
+
+    fn grammar(self) -> tree_sitter::Language {
+        match self {
+            Self::Rust => tree_sitter_rust::LANGUAGE.into(),
+            Self::Python => tree_sitter_python::LANGUAGE.into(),
+            Self::JavaScript => tree_sitter_javascript::LANGUAGE.into(),
+            Self::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            Self::Tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
+            Self::Go => tree_sitter_go::LANGUAGE.into(),
+            Self::Java => tree_sitter_java::LANGUAGE.into(),
+        }
+    }
+
+    /// Keyword shown for a node of `kind`, `None` when it declares nothing
+    fn keyword(self, kind: &str) -> Option<&'static str> {
+        Some(match (self, kind) {
+            (Self::Rust, "function_item" | "function_signature_item") => "fn",
+            (Self::Rust, "struct_item") => "struct",
+            (Self::Rust, "enum_item") => "enum",
+            (Self::Rust, "union_item") => "union",
+            (Self::Rust, "trait_item") => "trait",
+            (Self::Rust, "impl_item") => "impl",
+            (Self::Rust, "type_item") => "type",
+            (Self::Rust, "mod_item") => "mod",
+            (Self::Rust, "const_item") => "const",
+            (Self::Rust, "static_item") => "static",
+            (Self::Rust, "macro_definition") => "macro_rules!",
+            (Self::Python, "function_definition") => "def",
+            (Self::Python, "class_definition") => "class",
+            (
+                Self::JavaScript | Self::TypeScript | Self::Tsx,
+                "function_declaration" | "generator_function_declaration" | "variable_declarator",
+            ) => "function",
+            (
+                Self::JavaScript | Self::TypeScript | Self::Tsx,
+                "class_declaration" | "abstract_class_declaration",
+            ) => "class",
+            (Self::JavaScript | Self::TypeScript | Self::Tsx, "method_definition") => "method",
+            (Self::TypeScript | Self::Tsx, "interface_declaration") => "interface",
+            (Self::TypeScript | Self::Tsx, "type_alias_declaration") => "type",
+            (Self::TypeScript | Self::Tsx, "enum_declaration") => "enum",
+            (Self::TypeScript | Self::Tsx, "internal_module") => "namespace",
+            (Self::Go, "function_declaration" | "method_declaration") => "func",
+            (Self::Go, "type_spec") => "type",
+            (Self::Java, "class_declaration") => "class",
+            (Self::Java, "interface_declaration") => "interface",
+            (Self::Java, "enum_declaration") => "enum",
+            (Self::Java, "record_declaration") => "record",
+            (Self::Java, "annotation_type_declaration") => "@interface",
+            (Self::Java, "method_declaration") => "method",
+            (Self::Java, "constructor_declaration") => "constructor",
+            _ => return None,
+        })
+    }
+}
+
+/// A function, type, method or other item declared in a source file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Declaration {
+    /// Keyword and name, e.g. `fn load` or `class Parser`
+    pub symbol: String,
+
+    /// Symbols of the declarations it is nested in, outermost first, e.g.
+    /// `impl Config` for a method
+    pub scope: Vec<String>,
+
+    /// Bare name, e.g. `load`
+    pub name: String,
+
+    /// Text up to the body with whitespace collapsed, e.g.
+    /// `pub fn load(path: &Path) -> Result<Self>`
+    pub signature: String,
+
+    /// Visibility modifier of a Rust item, e.g. `pub` or `pub(crate)`
+    pub visibility: Option<String>,
+
+    /// Lines it spans, counted from 1
+    pub lines: RangeInclusive<usize>,
+}
+
+impl Declaration {
+    /// Scope and symbol, which tells apart methods of the same name
+    pub fn key(&self) -> String {
+        self.scope
+            .iter()
+            .chain([&self.symbol])
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" > ")
+    }
+}
+
+/// Parse `source` with the grammar of the language of `path`
+///
+/// `None` for languages without a grammar. Code that doesn't parse still gives
+/// the declarations tree-sitter recovers around the errors.
+pub fn declarations(path: &str, source: &str) -> Option<Vec<Declaration>> {
+    let language = Language::of(path)?;
+    let mut parser = Parser::new();
+    parser.set_language(&language.grammar()).ok()?;
+    let tree = parser.parse(source, None)?;
+
+    let mut found = Vec::new();
+    collect(
+        language,
+        tree.root_node(),
+        source.as_bytes(),
+        &mut Vec::new(),
+        &mut found,
+    );
+    Some(found)
+}
+
+fn collect(
+    language: Language,
+    node: Node,
+    source: &[u8],
+    scope: &mut Vec<String>,
+    found: &mut Vec<Declaration>,
+) {
+    let declaration = language
+        .keyword(node.kind())
+        .and_then(|keyword| declaration(language, keyword, node, source, scope));
+    if let Some(declaration) = &declaration {
+        scope.push(declaration.symbol.clone());
+    }
+
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect(language, child, source, scope, found);
+    }
+
+    if let Some(declaration) = declaration {
+        scope.pop();
+        // Parents before the declarations nested in them
+        let position = found
+            .iter()
+            .position(|nested: &Declaration| nested.lines.start() >= declaration.lines.start())
+            .unwrap_or(found.len());
+        found.insert(position, declaration);
+    }
+}
+
+fn declaration(
+    language: Language,
+    keyword: &str,
+    node: Node,
+    source: &[u8],
+    scope: &[String],
+) -> Option<Declaration> {
+    let text = |node: Node| node.utf8_text(source).ok().map(str::to_string);
+    let name = match (language, node.kind()) {
+        (Language::Rust, "impl_item") => {
+            let implemented = text(node.child_by_field_name("type")?)?;
+            match node.child_by_field_name("trait") {
+                Some(implemented_trait) => {
+                    format!("{} for {implemented}", text(implemented_trait)?)
+                }
+                None => implemented,
+            }
+        }
+        // `const load = async () => {}` declares a function, other values don't
+        (_, "variable_declarator") => {
+            let value = node.child_by_field_name("value")?;
+            if !matches!(value.kind(), "arrow_function" | "function_expression") {
+                return None;
+            }
+            let name = node.child_by_field_name("name")?;
+            if name.kind() != "identifier" {
+                return None;
+            }
+            text(name)?
+        }
+        _ => text(node.child_by_field_name("name")?)?,
+    };
+
+    let body_start = node
+        .child_by_field_name("body")
+        .map_or(node.end_byte(), |body| body.start_byte());
+    let header = String::from_utf8_lossy(&source[node.start_byte()..body_start]);
+    let signature = header
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_end_matches(['{', ';', ':', ' '])
+        .to_string();
+
+    let mut cursor = node.walk();
+    let visibility = node
+        .children(&mut cursor)
+        .find(|child| child.kind() == "visibility_modifier")
+        .and_then(text);
+
+    Some(Declaration {
+        symbol: format!("{keyword} {name}"),
+        scope: scope.to_vec(),
+        name,
+        signature,
+        visibility,
+        lines: node.start_position().row + 1..=node.end_position().row + 1,
+    })
+}
+
+/// Both sides of one file of a diff
+pub struct FileSides {
+    pub old_path: Option<String>,
+    pub new_path: Option<String>,
+
+    /// Full contents before and after the change, empty for a side that
+    /// doesn't exist
+    pub old_source: String,
+    pub new_source: String,
+
+    /// Non-blank lines removed from the old side and added to the new one,
+    /// counted from 1
+    pub removed: Vec<usize>,
+    pub added: Vec<usize>,
+}
+
+impl FileSides {
+    /// Read both sides of a file of a diff
+    ///
+    /// `blob` looks up a blob on the diff's `index <old>..<new>` line by its id
+    /// and path, [`git::blob_text`] for the current repository. A side it can't
+    /// find is pieced together from the hunks, which is enough for the
+    /// declarations around the changed lines.
+    pub fn read(file: &FileDiff, blob: impl Fn(&str, &str) -> Option<String>) -> Self {
+        let mut old_path = Some(file.path.clone());
+        let mut new_path = Some(file.path.clone());
+        let mut ids = None;
+        let mut old_lines: Vec<&str> = Vec::new();
+        let mut new_lines: Vec<&str> = Vec::new();
+        let mut removed = Vec::new();
+        let mut added = Vec::new();
+        let (mut old_line, mut new_line) = (0, 0);
+        let mut in_hunk = false;
+
+        for line in file.diff.lines() {
+            if let Some(range) = line.strip_prefix("@@ ") {
+                let mut starts = range.split_whitespace().take(2).map(|side| {
+                    side[1..]
+                        .split(',')
+                        .next()
+                        .and_then(|start| start.parse::<usize>().ok())
+                        .unwrap_or(1)
+                });
+                (old_line, new_line) = (starts.next().unwrap_or(1), starts.next().unwrap_or(1));
+                in_hunk = true;
+                continue;
+            }
+            if !in_hunk {
+                if let Some(path) = line.strip_prefix("--- ") {
+                    old_path = path.strip_prefix("a/").map(str::to_string);
+                } else if let Some(path) = line.strip_prefix("+++ ") {
+                    new_path = path.strip_prefix("b/").map(str::to_string);
+                } else if let Some(index) = line.strip_prefix("index ") {
+                    ids = index
+                        .split_whitespace()
+                        .next()
+                        .and_then(|ids| ids.split_once(".."));
+                }
+                continue;
+            }
+
+            let (marker, code) = line.split_at(line.len().min(1));
+            match marker {
+                "-" => {
+                    place(&mut old_lines, old_line, code);
+                    if !code.trim().is_empty() {
+                        removed.push(old_line);
+                    }
+                    old_line += 1;
+                }
+                "+" => {
+                    place(&mut new_lines, new_line, code);
+                    if !code.trim().is_empty() {
+                        added.push(new_line);
+                    }
+                    new_line += 1;
+                }
+                " " | "" => {
+                    place(&mut old_lines, old_line, code);
+                    place(&mut new_lines, new_line, code);
+                    old_line += 1;
+                    new_line += 1;
+                }
+                // `\ No newline at end of file`
+                _ => {}
+            }
+        }
+
+        let source = |path: &Option<String>, id: Option<&str>, pieces: Vec<&str>| match path {
+            None => String::new(),
+            Some(path) => id
+                .and_then(|id| blob(id, path))
+                .unwrap_or_else(|| pieces.join("\n")),
+        };
+        Self {
+            old_source: source(&old_path, ids.map(|(old, _)| old), old_lines),
+            new_source: source(&new_path, ids.map(|(_, new)| new), new_lines),
+            old_path,
+            new_path,
+            removed,
+            added,
+        }
+    }
+}
+
+// Put a line of a hunk at its number, the lines between hunks left blank
+fn place<'a>(lines: &mut Vec<&'a str>, number: usize, line: &'a str) {
+    if number == 0 {
+        return;
+    }
+    if lines.len() < number {
+        lines.resize(number, "");
+    }
+    lines[number - 1] = line;
+}
+
+/// What happened to a declaration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SymbolStatus {
+    Added,
+    Removed,
+    Modified,
+}
+
+impl fmt::Display for SymbolStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            SymbolStatus::Added => "added",
+            SymbolStatus::Removed => "removed",
+            SymbolStatus::Modified => "modified",
+        };
+        f.write_str(status)
+    }
+}
+
+/// A function, type or method the diff adds, removes or changes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolChange {
+    pub path: String,
+    pub status: SymbolStatus,
+
+    /// Keyword and name, e.g. `fn load` or `class Parser`
+    pub symbol: String,
+}
+
+impl fmt::Display for SymbolChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} {}", self.path, self.status, self.symbol)
+    }
+}
+
+/// The declarations the diff adds, removes or modifies, file by file
+///
+/// Both sides of each source and test file are parsed, see [`FileSides::read`]
+/// for where they come from. A declaration is modified when a changed line
+/// falls in it rather than in a declaration nested inside it, so adding a
+/// method doesn't also modify its class.
+pub fn changed_symbols(
+    diff: &str,
+    blob: impl Fn(&str, &str) -> Option<String>,
+) -> Vec<SymbolChange> {
+    let mut changes = Vec::new();
+    for file in git::split_diff_by_file(diff) {
+        if !matches!(FileRole::of(&file), FileRole::Source | FileRole::Tests) {
+            continue;
+        }
+
+        let sides = FileSides::read(&file, &blob);
+        let old_path = sides.old_path.as_deref().unwrap_or(&file.path);
+        let (Some(old), Some(new)) = (
+            declarations(old_path, &sides.old_source),
+            declarations(&file.path, &sides.new_source),
+        ) else {
+            continue;
+        };
+
+        let old_keys: HashSet<String> = old.iter().map(Declaration::key).collect();
+        let new_keys: HashSet<String> = new.iter().map(Declaration::key).collect();
+        let touched: HashSet<String> = sides
+            .removed
+            .iter()
+            .filter_map(|&line| innermost(&old, line))
+            .chain(sides.added.iter().filter_map(|&line| innermost(&new, line)))
+            .map(Declaration::key)
+            .collect();
+
+        let mut listed = HashSet::new();
+        let mut push = |status, declaration: &Declaration| {
+            if listed.insert((status, declaration.symbol.clone())) {
+                changes.push(SymbolChange {
+                    path: file.path.clone(),
+                    status,
+                    symbol: declaration.symbol.clone(),
+                });
+            }
+        };
+        for declaration in &new {
+            let key = declaration.key();
+            if !old_keys.contains(&key) {
+                push(SymbolStatus::Added, declaration);
+            } else if touched.contains(&key) {
+                push(SymbolStatus::Modified, declaration);
+            }
+        }
+        for declaration in old
+            .iter()
+            .filter(|declaration| !new_keys.contains(&declaration.key()))
+        {
+            push(SymbolStatus::Removed, declaration);
+        }
+    }
+    changes
+}
+
+/// The most deeply nested declaration around a line
+fn innermost(declarations: &[Declaration], line: usize) -> Option<&Declaration> {
+    declarations
+        .iter()
+        .filter(|declaration| declaration.lines.contains(&line))
+        .min_by_key(|declaration| declaration.lines.end() - declaration.lines.start())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD_CONFIG: &str = "use std::fs;
+
+pub struct Config {
+    strict: bool,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)?;
+        toml::from_str(&text)
+    }
+
+    fn merge(base: Self, other: Self) -> Self {
+        Self {
+            strict: other.strict,
+        }
+    }
+}
+
+fn legacy() {}
+";
+
+    const NEW_CONFIG: &str = "use std::fs;
+
+pub struct Config {
+    strict: bool,
+}
+
+impl Config {
+    pub fn load(path: &Path, strict: bool) -> Result<Self> {
+        let text = fs::read_to_string(path)?;
+        toml::from_str(&text)
+    }
+
+    fn merge(base: Self, other: Self) -> Self {
+        let base = base.normalized();
+        Self {
+            strict: other.strict,
+        }
+    }
+}
+
+pub(crate) struct Strictness;
+";
+
+    const DIFF: &str = concat!(
+        "diff --git a/src/config.rs b/src/config.rs\n",
+        "index 1111111..2222222 100644\n",
+        "--- a/src/config.rs\n",
+        "+++ b/src/config.rs\n",
+        "@@ -8 +8 @@ impl Config {\n",
+        "-    pub fn load(path: &Path) -> Result<Self> {\n",
+        "+    pub fn load(path: &Path, strict: bool) -> Result<Self> {\n",
+        "@@ -13,0 +14 @@ impl Config {\n",
+        "+        let base = base.normalized();\n",
+        "@@ -20 +21 @@ impl Config {\n",
+        "-fn legacy() {}\n",
+        "+pub(crate) struct Strictness;\n",
+        "diff --git a/web/api.ts b/web/api.ts\n",
+        "new file mode 100644\n",
+        "index 0000000..3333333\n",
+        "--- /dev/null\n",
+        "+++ b/web/api.ts\n",
+        "@@ -0,0 +1,3 @@\n",
+        "+export async function fetchUser(id: string) {\n",
+        "+  return get(`/users/${id}`);\n",
+        "+}\n",
+        "diff --git a/src/app.py b/src/app.py\n",
+        "--- a/src/app.py\n",
+        "+++ b/src/app.py\n",
+        "@@ -1,3 +1,6 @@\n",
+        " class App:\n",
+        "     def run(self):\n",
+        "         pass\n",
+        "+\n",
+        "+    def stop(self):\n",
+        "+        pass\n",
+        "diff --git a/README.md b/README.md\n",
+        "--- a/README.md\n",
+        "+++ b/README.md\n",
+        "@@ -1 +1 @@\n",
+        "+class names are documented here\n",
+    );
+
+    fn blob(id: &str, path: &str) -> Option<String> {
+        match (id, path) {
+            ("1111111", "src/config.rs") => Some(OLD_CONFIG.to_string()),
+            ("2222222", "src/config.rs") => Some(NEW_CONFIG.to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_changed_symbols() {
+        let changes: Vec<String> = changed_symbols(DIFF, blob)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                "src/config.rs: modified fn load",
+                "src/config.rs: modified fn merge",
+                "src/config.rs: added struct Strictness",
+                "src/config.rs: removed fn legacy",
+                "web/api.ts: added function fetchUser",
+                "src/app.py: added def stop",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_file_sides() {
+        let files = git::split_diff_by_file(DIFF);
+        let sides = FileSides::read(&files[0], blob);
+        assert_eq!(sides.old_source, OLD_CONFIG);
+        assert_eq!(sides.removed, vec![8, 20]);
+        assert_eq!(sides.added, vec![8, 14, 21]);
+
+        // Without the blobs, the sides are pieced together from the hunks
+        let sides = FileSides::read(&files[2], blob);
+        assert_eq!(
+            sides.old_source,
+            "class App:\n    def run(self):\n        pass"
+        );
+        assert_eq!(sides.added, vec![5, 6]);
+        let sides = FileSides::read(&files[1], blob);
+        assert_eq!((sides.old_path, sides.old_source.as_str()), (None, ""));
+    }
+
+    #[test]
+    fn test_declarations() {
+        let source = "pub(crate) fn load(\n    path: &Path,\n) -> Result<Config> {\n    todo!()\n}\n\n\
+            impl fmt::Display for Config {\n    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {\n        Ok(())\n    }\n}\n";
+        let found = declarations("src/config.rs", source).unwrap();
+        let symbols: Vec<&str> = found.iter().map(|found| found.symbol.as_str()).collect();
+        assert_eq!(
+            symbols,
+            vec!["fn load", "impl fmt::Display for Config", "fn fmt"]
+        );
+        assert_eq!(
+            found[0].signature,
+            "pub(crate) fn load( path: &Path, ) -> Result<Config>"
+        );
+        assert_eq!(found[0].visibility.as_deref(), Some("pub(crate)"));
+        assert_eq!(found[0].lines, 1..=5);
+        assert_eq!(found[2].key(), "impl fmt::Display for Config > fn fmt");
+
+        let symbols = |path: &str, source: &str| -> Vec<String> {
+            declarations(path, source)
+                .unwrap()
+                .into_iter()
+                .map(|found| found.symbol)
+                .collect()
+        };
+        assert_eq!(
+            symbols("server.go", "package main\n\nfunc (s *Server) Start(ctx context.Context) error {\n\treturn nil\n}\n"),
+            vec!["func Start"]
+        );
+        assert_eq!(
+            symbols(
+                "app.py",
+                "class App:\n    async def handle(self, request):\n        pass\n"
+            ),
+            vec!["class App", "def handle"]
+        );
+        assert_eq!(
+            symbols(
+                "parser.js",
+                "export default class Parser {}\nconst load = async () => {};\nlet fn_name = 1;\n// the function below\n"
+            ),
+            vec!["class Parser", "function load"]
+        );
+        assert_eq!(
+            symbols(
+                "Main.java",
+                "public class Main {\n  public static void main(String[] args) {}\n}\n"
+            ),
+            vec!["class Main", "method main"]
+        );
+        assert!(declarations("README.md", "fn load() {}").is_none());
+    }
+}
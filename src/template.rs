@@ -0,0 +1,77 @@
+/// Replace the `{{name}}` placeholders of a template, spaces inside the braces
+/// are allowed
+///
+/// Placeholders for which `lookup` has no value are kept as written, so a
+/// prompt can still contain literal double braces.
+pub fn render(template: &str, mut lookup: impl FnMut(&str) -> Option<String>) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            rest = &rest[start..];
+            break;
+        };
+
+        let name = after[..end].trim();
+        match is_name(name).then(|| lookup(name)).flatten() {
+            Some(value) => rendered.push_str(&value),
+            None => rendered.push_str(&rest[start..start + 2 + end + 2]),
+        }
+        rest = &after[end + 2..];
+    }
+
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Names of the placeholders used in a template, in order of appearance
+pub fn variables(template: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    render(template, |name| {
+        names.push(name.to_string());
+        None
+    });
+    names
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "branch" => Some("feature/login".to_string()),
+            "ticket" => Some("JIRA-123".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            render("On {{branch}} for {{ ticket }}.", lookup),
+            "On feature/login for JIRA-123."
+        );
+
+        // Unknown, invalid and unterminated placeholders stay as written
+        assert_eq!(render("{{nope}} {{a b}}", lookup), "{{nope}} {{a b}}");
+        assert_eq!(render("{{branch", lookup), "{{branch");
+        assert_eq!(render("{single} {}", lookup), "{single} {}");
+    }
+
+    #[test]
+    fn test_variables() {
+        assert_eq!(
+            variables("{{diff}} and {{ files }}, not {date}"),
+            vec!["diff", "files"]
+        );
+        assert!(variables("plain").is_empty());
+    }
+}
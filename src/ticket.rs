@@ -0,0 +1,89 @@
+use regex::Regex;
+
+/// Jira-style keys such as `ABC-123`, as in `feature/ABC-123-add-login`
+pub const DEFAULT_TICKET_PATTERN: &str = r"[A-Z][A-Z0-9]+-\d+";
+
+/// Footer added for the ticket of the branch
+pub const DEFAULT_TICKET_FOOTER: &str = "Refs: {ticket}";
+
+/// GitHub issue numbers such as `#123` or `issues/123`, the default with
+/// `ticket_tracker = "github"`
+pub const GITHUB_TICKET_PATTERN: &str = r"(?:#|issues/)(\d+)";
+
+/// Footer for GitHub issues, which GitHub links to the issue
+pub const GITHUB_TICKET_FOOTER: &str = "Refs #{ticket}";
+
+/// Extract the ticket ID from a branch name
+///
+/// The first capture group is used when the pattern has one, otherwise the
+/// whole match.
+pub fn extract_ticket(pattern: &Regex, branch: &str) -> Option<String> {
+    let captures = pattern.captures(branch)?;
+    captures
+        .get(1)
+        .or_else(|| captures.get(0))
+        .map(|ticket| ticket.as_str().to_string())
+        .filter(|ticket| !ticket.is_empty())
+}
+
+/// Footer for the ticket, `None` when the message already mentions it
+pub fn ticket_footer(template: &str, ticket: &str, message: &str) -> Option<String> {
+    if message.contains(ticket) {
+        return None;
+    }
+
+    Some(template.replace("{ticket}", ticket))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_ticket() {
+        let pattern = Regex::new(DEFAULT_TICKET_PATTERN).unwrap();
+        assert_eq!(
+            extract_ticket(&pattern, "feature/JIRA-123-add-login"),
+            Some("JIRA-123".to_string())
+        );
+        assert_eq!(extract_ticket(&pattern, "fix/login-redirect"), None);
+
+        let pattern = Regex::new(GITHUB_TICKET_PATTERN).unwrap();
+        assert_eq!(
+            extract_ticket(&pattern, "fix/#42-crash"),
+            Some("42".to_string())
+        );
+        assert_eq!(
+            extract_ticket(&pattern, "fix/issues/42"),
+            Some("42".to_string())
+        );
+        assert_eq!(extract_ticket(&pattern, "release/1.2"), None);
+
+        // A capture group picks part of the match
+        let pattern = Regex::new(r"issue-(\d+)").unwrap();
+        assert_eq!(
+            extract_ticket(&pattern, "issue-42-crash"),
+            Some("42".to_string())
+        );
+    }
+
+    #[test]
+    fn test_ticket_footer() {
+        assert_eq!(
+            ticket_footer(DEFAULT_TICKET_FOOTER, "ABC-1", "feat: add login"),
+            Some("Refs: ABC-1".to_string())
+        );
+        assert_eq!(
+            ticket_footer("Closes #{ticket}", "42", "fix: crash"),
+            Some("Closes #42".to_string())
+        );
+        assert_eq!(
+            ticket_footer(
+                DEFAULT_TICKET_FOOTER,
+                "ABC-1",
+                "feat: add login\n\nRefs: ABC-1"
+            ),
+            None
+        );
+    }
+}
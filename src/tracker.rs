@@ -0,0 +1,465 @@
+use crate::config::Config;
+use crate::git;
+use anyhow::{Context, Result};
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::process::Command;
+
+/// Linear's API, used when `ticket_api_base_url` is not set
+pub const DEFAULT_LINEAR_API_URL: &str = "https://api.linear.app";
+
+/// GitHub's REST API, used when `ticket_api_base_url` is not set
+pub const DEFAULT_GITHUB_API_URL: &str = "https://api.github.com";
+
+/// Longest ticket description put into the prompt, in characters
+const MAX_DESCRIPTION_CHARS: usize = 2000;
+
+/// Ticket lookups give up after this long, so a slow tracker doesn't hold up the commit
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Issue tracker the tickets named in branches are fetched from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrackerKind {
+    Jira,
+    Linear,
+    Github,
+}
+
+impl FromStr for TrackerKind {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "jira" => Ok(Self::Jira),
+            "linear" => Ok(Self::Linear),
+            "github" => Ok(Self::Github),
+            _ => Err(anyhow::anyhow!(
+                "Invalid value for ticket_tracker: expected 'jira', 'linear' or 'github', got '{}'",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for TrackerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Jira => write!(f, "jira"),
+            Self::Linear => write!(f, "linear"),
+            Self::Github => write!(f, "github"),
+        }
+    }
+}
+
+/// Title and description of a ticket
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ticket {
+    pub id: String,
+    pub title: String,
+    pub description: Option<String>,
+}
+
+/// Future returned by [`TicketSource::fetch`]
+pub type TicketFuture<'a> = Pin<Box<dyn Future<Output = Result<Ticket>> + Send + 'a>>;
+
+/// An issue tracker tickets can be fetched from
+///
+/// [`fetch_ticket`] fetches through a source and shortens the description.
+pub trait TicketSource: Send + Sync {
+    /// Fetch the ticket with the given ID
+    fn fetch<'a>(&'a self, id: &'a str) -> TicketFuture<'a>;
+}
+
+// Send a request and read its JSON response
+async fn get_json(request: RequestBuilder, id: &str) -> Result<serde_json::Value> {
+    let response = request
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await
+        .with_context(|| format!("Failed to fetch ticket {id}"))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        anyhow::bail!("Failed to fetch ticket {} ({})", id, status);
+    }
+    response
+        .json()
+        .await
+        .with_context(|| format!("Failed to read ticket {id}"))
+}
+
+/// Jira's REST API, with an email and API token on Jira Cloud or a personal
+/// access token on Jira Data Center
+pub struct Jira {
+    pub base_url: String,
+    pub token: String,
+    pub user: Option<String>,
+}
+
+impl TicketSource for Jira {
+    fn fetch<'a>(&'a self, id: &'a str) -> TicketFuture<'a> {
+        let url = format!(
+            "{}/rest/api/2/issue/{id}?fields=summary,description",
+            self.base_url.trim_end_matches('/')
+        );
+        let request = Client::new().get(url).header("Accept", "application/json");
+        let request = match &self.user {
+            Some(user) => request.basic_auth(user, Some(&self.token)),
+            None => request.bearer_auth(&self.token),
+        };
+        Box::pin(async move { Self::parse(id, &get_json(request, id).await?) })
+    }
+}
+
+impl Jira {
+    fn parse(id: &str, body: &serde_json::Value) -> Result<Ticket> {
+        let fields = &body["fields"];
+        let title = fields["summary"]
+            .as_str()
+            .with_context(|| format!("Jira returned no summary for {id}"))?;
+        Ok(Ticket {
+            id: body["key"].as_str().unwrap_or(id).to_string(),
+            title: title.to_string(),
+            description: fields["description"].as_str().map(str::to_string),
+        })
+    }
+}
+
+/// Linear's GraphQL API, with a personal API key
+pub struct Linear {
+    pub base_url: String,
+    pub token: String,
+}
+
+impl TicketSource for Linear {
+    fn fetch<'a>(&'a self, id: &'a str) -> TicketFuture<'a> {
+        let url = format!("{}/graphql", self.base_url.trim_end_matches('/'));
+        let request = Client::new()
+            .post(url)
+            .header("Authorization", &self.token)
+            .json(&serde_json::json!({
+                "query": "query Issue($id: String!) { issue(id: $id) { identifier title description } }",
+                "variables": { "id": id },
+            }));
+        Box::pin(async move { Self::parse(id, &get_json(request, id).await?) })
+    }
+}
+
+impl Linear {
+    fn parse(id: &str, body: &serde_json::Value) -> Result<Ticket> {
+        if let Some(message) = body["errors"][0]["message"].as_str() {
+            anyhow::bail!("Linear could not find {}: {}", id, message);
+        }
+        let issue = &body["data"]["issue"];
+        let title = issue["title"]
+            .as_str()
+            .with_context(|| format!("Linear returned no title for {id}"))?;
+        Ok(Ticket {
+            id: issue["identifier"].as_str().unwrap_or(id).to_string(),
+            title: title.to_string(),
+            description: issue["description"].as_str().map(str::to_string),
+        })
+    }
+}
+
+/// GitHub's REST API for the issues of `repo` (`owner/name`), with an optional token
+pub struct GitHub {
+    pub base_url: String,
+    pub token: Option<String>,
+    pub repo: String,
+}
+
+impl TicketSource for GitHub {
+    fn fetch<'a>(&'a self, id: &'a str) -> TicketFuture<'a> {
+        let url = format!(
+            "{}/repos/{}/issues/{}",
+            self.base_url.trim_end_matches('/'),
+            self.repo,
+            id.trim_start_matches('#')
+        );
+        let request = Client::new()
+            .get(url)
+            .header("Accept", "application/vnd.github+json")
+            .header("User-Agent", "aic");
+        let request = match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        };
+        Box::pin(async move { parse_github_issue(id, &get_json(request, id).await?) })
+    }
+}
+
+/// GitHub issues fetched with the `gh` CLI, signed in with `gh auth login`
+pub struct GhCli;
+
+impl TicketSource for GhCli {
+    fn fetch<'a>(&'a self, id: &'a str) -> TicketFuture<'a> {
+        Box::pin(async move {
+            // gh fills in `{owner}/{repo}` from the repository's remotes
+            let endpoint = format!(
+                "repos/{{owner}}/{{repo}}/issues/{}",
+                id.trim_start_matches('#')
+            );
+            let output = Command::new("gh")
+                .args(["api", &endpoint])
+                .output()
+                .await
+                .context("Failed to run gh, install the GitHub CLI or set ticket_api_token")?;
+            if !output.status.success() {
+                anyhow::bail!(
+                    "gh could not fetch issue {}: {}",
+                    id,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+            let body = serde_json::from_slice(&output.stdout)
+                .with_context(|| format!("Failed to read issue {id} from gh"))?;
+            parse_github_issue(id, &body)
+        })
+    }
+}
+
+fn parse_github_issue(id: &str, body: &serde_json::Value) -> Result<Ticket> {
+    let title = body["title"]
+        .as_str()
+        .with_context(|| format!("GitHub returned no title for issue {id}"))?;
+    Ok(Ticket {
+        id: body["number"]
+            .as_u64()
+            .map_or_else(|| id.to_string(), |number| format!("#{number}")),
+        title: title.to_string(),
+        description: body["body"].as_str().map(str::to_string),
+    })
+}
+
+/// `owner/name` of a GitHub remote URL, over HTTPS or SSH
+pub fn github_repo(remote_url: &str) -> Option<String> {
+    let path = remote_url
+        .trim()
+        .trim_end_matches('/')
+        .trim_end_matches(".git");
+    let path = match path.split_once("://") {
+        Some((_, rest)) => rest.split_once('/')?.1,
+        None => path.split_once(':')?.1,
+    };
+    let mut parts = path.rsplit('/');
+    let name = parts.next().filter(|name| !name.is_empty())?;
+    let owner = parts.next().filter(|owner| !owner.is_empty())?;
+    Some(format!("{owner}/{name}"))
+}
+
+/// The ticket source configured with `ticket_tracker`, `None` when there is none
+pub fn from_config(config: &Config) -> Result<Option<Box<dyn TicketSource>>> {
+    let Some(kind) = config.get_ticket_tracker() else {
+        return Ok(None);
+    };
+    let token = || {
+        config.get_ticket_api_token().with_context(|| {
+            format!(
+                "ticket_tracker is {kind}, but ticket_api_token is not set (or AIC_TICKET_API_TOKEN)"
+            )
+        })
+    };
+
+    let source: Box<dyn TicketSource> = match kind {
+        TrackerKind::Jira => Box::new(Jira {
+            base_url: config
+                .get_ticket_api_base_url()
+                .context("ticket_tracker is jira, but ticket_api_base_url is not set")?
+                .to_string(),
+            token: token()?,
+            user: config.get_ticket_api_user().map(str::to_string),
+        }),
+        TrackerKind::Linear => Box::new(Linear {
+            base_url: config
+                .get_ticket_api_base_url()
+                .unwrap_or(DEFAULT_LINEAR_API_URL)
+                .to_string(),
+            token: token()?,
+        }),
+        // Without a token, gh signs in on its own
+        TrackerKind::Github => match config.get_ticket_api_token() {
+            None => Box::new(GhCli),
+            Some(token) => {
+                let remote = git::remote_url("origin")?
+                    .context("ticket_tracker is github, but there is no origin remote")?;
+                Box::new(GitHub {
+                    base_url: config
+                        .get_ticket_api_base_url()
+                        .unwrap_or(DEFAULT_GITHUB_API_URL)
+                        .to_string(),
+                    token: Some(token),
+                    repo: github_repo(&remote).with_context(|| {
+                        format!("Can't tell the GitHub repository of the origin remote {remote}")
+                    })?,
+                })
+            }
+        },
+    };
+    Ok(Some(source))
+}
+
+/// Fetch a ticket, with its description shortened to fit into a prompt
+pub async fn fetch_ticket(source: &dyn TicketSource, id: &str) -> Result<Ticket> {
+    let mut ticket = source.fetch(id).await?;
+    ticket.description = ticket
+        .description
+        .map(|description| shorten(description.trim(), MAX_DESCRIPTION_CHARS))
+        .filter(|description| !description.is_empty());
+    Ok(ticket)
+}
+
+// Cut text to at most `max` characters, marking the cut
+fn shorten(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((end, _)) => format!("{}…", text[..end].trim_end()),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_partial_json, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_fetch_jira_ticket() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/rest/api/2/issue/PROJ-12"))
+            .and(query_param("fields", "summary,description"))
+            .and(header("Authorization", "Bearer jira-token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "key": "PROJ-12",
+                "fields": {
+                    "summary": "Let users sign in with OAuth",
+                    "description": format!("  {}  ", "x".repeat(3000)),
+                }
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let jira = Jira {
+            base_url: format!("{}/", mock_server.uri()),
+            token: "jira-token".to_string(),
+            user: None,
+        };
+        let ticket = fetch_ticket(&jira, "PROJ-12").await?;
+        assert_eq!(ticket.title, "Let users sign in with OAuth");
+        let description = ticket.description.unwrap();
+        assert_eq!(description.chars().count(), MAX_DESCRIPTION_CHARS + 1);
+        assert!(description.ends_with('…'));
+
+        assert!(fetch_ticket(&jira, "PROJ-13").await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetch_linear_ticket() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .and(header("Authorization", "lin_api_key"))
+            .and(body_partial_json(serde_json::json!({
+                "variables": { "id": "ENG-7" }
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "data": {
+                    "issue": { "identifier": "ENG-7", "title": "Cache lookups", "description": null }
+                }
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let linear = Linear {
+            base_url: mock_server.uri(),
+            token: "lin_api_key".to_string(),
+        };
+        assert_eq!(
+            fetch_ticket(&linear, "ENG-7").await?,
+            Ticket {
+                id: "ENG-7".to_string(),
+                title: "Cache lookups".to_string(),
+                description: None,
+            }
+        );
+
+        let not_found = serde_json::json!({ "errors": [{ "message": "Entity not found" }] });
+        assert!(Linear::parse("ENG-8", &not_found).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetch_github_issue() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/octo/app/issues/42"))
+            .and(header("Authorization", "Bearer ghp_token"))
+            .and(header("User-Agent", "aic"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "number": 42,
+                "title": "Crash on empty config",
+                "body": "Steps to reproduce",
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let github = GitHub {
+            base_url: mock_server.uri(),
+            token: Some("ghp_token".to_string()),
+            repo: "octo/app".to_string(),
+        };
+        assert_eq!(
+            fetch_ticket(&github, "42").await?,
+            Ticket {
+                id: "#42".to_string(),
+                title: "Crash on empty config".to_string(),
+                description: Some("Steps to reproduce".to_string()),
+            }
+        );
+        assert!(fetch_ticket(&github, "43").await.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_github_repo() {
+        for url in [
+            "https://github.com/octo/app.git",
+            "https://github.com/octo/app/",
+            "git@github.com:octo/app.git",
+            "ssh://git@github.example.com:22/octo/app",
+        ] {
+            assert_eq!(github_repo(url), Some("octo/app".to_string()), "{url}");
+        }
+        assert_eq!(github_repo("https://github.com/app"), None);
+        assert_eq!(github_repo("/srv/git/app.git"), None);
+    }
+
+    #[test]
+    fn test_from_config() {
+        let mut config = Config::default();
+        assert!(from_config(&config).unwrap().is_none());
+
+        config.ticket_tracker = Some("Jira".parse().unwrap());
+        config.ticket_api_token = Some("token".to_string());
+        // Jira has no default URL
+        assert!(from_config(&config).is_err());
+        config.ticket_api_base_url = Some("https://example.atlassian.net".to_string());
+        assert!(from_config(&config).unwrap().is_some());
+
+        // GitHub falls back to the gh CLI without a token
+        config.ticket_tracker = Some("github".parse().unwrap());
+        config.ticket_api_token = None;
+        assert!(from_config(&config).unwrap().is_some());
+
+        assert!("trello".parse::<TrackerKind>().is_err());
+    }
+}
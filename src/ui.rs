@@ -1,7 +1,69 @@
-use crate::config::Config;
+use crate::breaking::ApiChange;
+use crate::bump::{Level, Version};
+use crate::capabilities::ModelCapabilities;
+use crate::config::{self, Config};
+use crate::context;
+use crate::doctor;
+use crate::experiment::VariantScore;
+use crate::git::{FileStat, PreflightIssue};
+use crate::history;
+use crate::lint::Violation;
+use crate::llm;
+use crate::priority::FileRole;
+use crate::scheduler::BatchReport;
+use crate::spell::Misspelling;
+use crate::split::ChangeGroup;
+use crate::stats::{self, CommitStats};
+use crate::ticket;
+use crate::usage;
+use crate::validate::{Problem, Severity};
 use colored::Colorize;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressState, ProgressStyle};
 use prettytable::{row, Table};
+use std::fmt;
+use std::future::Future;
+use std::io::{self, IsTerminal, Write};
 use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+// The last character is drawn once the spinner finishes
+const SPINNER_FRAMES: &str = "⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏ ";
+
+/// How results are written to stdout
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Decorated output for people
+    #[default]
+    Text,
+
+    /// One JSON object, for editor integrations and CI
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(anyhow::anyhow!(
+                "Invalid output format '{}': expected 'text' or 'json'",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Text => write!(f, "text"),
+            Self::Json => write!(f, "json"),
+        }
+    }
+}
 
 /// Print the application header
 pub fn print_header() {
@@ -14,18 +76,680 @@ pub fn print_header() {
     );
 }
 
+/// Print a single-line progress indicator, overwritten on each call
+pub fn print_progress(label: &str, done: usize, total: usize) {
+    const BAR_WIDTH: usize = 20;
+    let filled = (done * BAR_WIDTH).checked_div(total).unwrap_or(BAR_WIDTH);
+
+    print!(
+        "\r{} [{}{}] {done}/{total}",
+        label.blue(),
+        "█".repeat(filled).green(),
+        "░".repeat(BAR_WIDTH - filled).dimmed()
+    );
+    if done >= total {
+        println!();
+    }
+    let _ = io::stdout().flush();
+}
+
+/// Await `future` behind an animated spinner showing `label` and the elapsed
+/// time, cleared once the future completes
+///
+/// The spinner is only drawn when stdout is a terminal.
+pub async fn with_spinner<T>(label: &str, future: impl Future<Output = T>) -> T {
+    if !io::stdout().is_terminal() {
+        return future.await;
+    }
+
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_draw_target(ProgressDrawTarget::stdout());
+    spinner.set_style(spinner_style());
+    spinner.set_message(label.to_string());
+    spinner.enable_steady_tick(Duration::from_millis(100));
+    let output = future.await;
+    spinner.finish_and_clear();
+    output
+}
+
+fn spinner_style() -> ProgressStyle {
+    ProgressStyle::with_template("{spinner:.cyan} {msg:.blue} {seconds:.dim}{retry:.yellow}")
+        .expect("the spinner template is valid")
+        .tick_chars(SPINNER_FRAMES)
+        .with_key(
+            "seconds",
+            |state: &ProgressState, out: &mut dyn fmt::Write| {
+                let _ = write!(out, "{:.1}s", state.elapsed().as_secs_f32());
+            },
+        )
+        .with_key("retry", |_: &ProgressState, out: &mut dyn fmt::Write| {
+            let _ = out.write_str(&retry_note(llm::retry_wait()));
+        })
+}
+
+/// Countdown shown after the spinner while a request waits to be retried
+fn retry_note(retry_wait: Option<Duration>) -> String {
+    match retry_wait {
+        Some(wait) => format!(" ⏳ retrying in {}s", wait.as_secs_f64().ceil()),
+        None => String::new(),
+    }
+}
+
+/// Print the per-item outcome summary of a batch
+pub fn print_batch_report<T>(report: &BatchReport<T>) {
+    let failed: Vec<_> = report.failed().collect();
+    if failed.is_empty() {
+        println!(
+            "{}",
+            format!("✅ All {} item(s) succeeded.", report.succeeded()).green()
+        );
+        return;
+    }
+
+    println!(
+        "{}",
+        format!(
+            "⚠️  {} item(s) succeeded, {} failed:",
+            report.succeeded(),
+            failed.len()
+        )
+        .yellow()
+    );
+    for (label, err) in failed {
+        println!(
+            "   {} {}: {}",
+            "✗".red(),
+            label,
+            format!("{err:#}").dimmed()
+        );
+    }
+}
+
+/// Print numbered candidate commit messages for interactive selection
+pub fn print_candidates(candidates: &[String]) {
+    println!("{}", "📝 Candidate commit messages:".green().bold());
+    for (index, candidate) in candidates.iter().enumerate() {
+        println!();
+        let mut lines = candidate.trim().lines();
+        let subject = lines.next().unwrap_or_default();
+        println!(
+            "{} {}",
+            format!("[{}]", index + 1).yellow().bold(),
+            subject.bright_white()
+        );
+        for line in lines {
+            println!("    {}", line.dimmed());
+        }
+    }
+}
+
+/// Print which optional request features the model supports
+pub fn print_capabilities(capabilities: &ModelCapabilities) {
+    let mark = |supported: bool| {
+        if supported {
+            "✓".green()
+        } else {
+            "✗".red()
+        }
+    };
+
+    println!("{}", "🔬 Model capabilities:".blue());
+    println!("   {} System role", mark(capabilities.system_role));
+    println!("   {} JSON mode", mark(capabilities.json_mode));
+    println!("   {} JSON schema", mark(capabilities.json_schema));
+    println!("   {} Streaming", mark(capabilities.streaming));
+    println!("   {} Temperature and top_p", mark(capabilities.sampling));
+    println!(
+        "   {} max_tokens (otherwise max_completion_tokens)",
+        mark(!capabilities.max_completion_tokens)
+    );
+}
+
+/// Print the areas a staged change spans when it looks like several commits
+pub fn print_change_groups(groups: &[ChangeGroup]) {
+    println!(
+        "{}",
+        format!("✂️  This looks like {} separate changes:", groups.len())
+            .yellow()
+            .bold()
+    );
+    for (index, group) in groups.iter().enumerate() {
+        println!(
+            "{} {} ({} file{})",
+            format!("[{}]", index + 1).yellow().bold(),
+            group.name.bright_white(),
+            group.files.len(),
+            if group.files.len() == 1 { "" } else { "s" }
+        );
+        for file in &group.files {
+            println!("    {}", file.dimmed());
+        }
+    }
+}
+
+/// Print the commit rules a message breaks
+pub fn print_lint_violations(violations: &[Violation]) {
+    println!(
+        "{}",
+        "⚠️  The commit message breaks the commit rules:"
+            .yellow()
+            .bold()
+    );
+    for violation in violations {
+        println!("   {} {}", "✗".red(), violation);
+    }
+}
+
+/// Print the header of a checked commit and the rules its message breaks
+pub fn print_commit_lint(label: &str, message: &str, violations: &[Violation]) {
+    let header = message.lines().next().unwrap_or_default();
+    if violations.is_empty() {
+        println!("{} {} {}", "✓".green(), label.yellow(), header);
+        return;
+    }
+
+    println!("{} {} {}", "✗".red(), label.yellow(), header.bright_white());
+    for violation in violations {
+        println!("    {}", violation.to_string().dimmed());
+    }
+}
+
+/// Print the scores of an experiment, one row per prompt variant
+pub fn print_experiment_scores(scores: &[VariantScore]) {
+    let percent = |value: f64| format!("{:.0}%", value * 100.0);
+
+    let mut table = Table::new();
+    table.add_row(row![
+        "Variant",
+        "Model",
+        "Generated",
+        "Failed",
+        "Compliance",
+        "Similarity"
+    ]);
+    for score in scores {
+        table.add_row(row![
+            score.name,
+            score.model,
+            score.generated,
+            score.failed,
+            percent(score.compliance()),
+            percent(score.similarity())
+        ]);
+    }
+
+    println!("{}", "📊 Experiment results:".green().bold());
+    table.printstd();
+    println!(
+        "{}",
+        "Compliance: messages without commit rule violations. \
+         Similarity: word overlap with the original headers."
+            .dimmed()
+    );
+}
+
+/// Print generated messages from the history, one row per message
+pub fn print_history(entries: &[history::Entry]) {
+    let mut table = Table::new();
+    table.add_row(row!["Id", "Date (UTC)", "Model", "Committed", "Subject"]);
+    for entry in entries {
+        let days = (entry.timestamp / 86_400) as i64;
+        let minutes = entry.timestamp % 86_400 / 60;
+        let date = format!(
+            "{} {:02}:{:02}",
+            context::format_date(days, context::DEFAULT_DATE_FORMAT),
+            minutes / 60,
+            minutes % 60
+        );
+        let committed = if entry.committed { "✓" } else { "" };
+        table.add_row(row![
+            entry.id,
+            date,
+            entry.model,
+            committed,
+            entry.subject()
+        ]);
+    }
+
+    println!("{}", "📜 Generated messages:".green().bold());
+    table.printstd();
+    println!(
+        "{}",
+        "Commit the staged changes with one of them: aic history reuse <id>".dimmed()
+    );
+}
+
+/// Print the tokens used per model and their cost, with a total row
+pub fn print_usage(totals: &[usage::Totals], days: Option<u32>) {
+    // Records without a price are left out of the cost, flag them
+    let cost = |cost: f64, unpriced: usize, requests: usize| match unpriced {
+        0 => format!("${cost:.4}"),
+        _ if unpriced == requests => "unknown".to_string(),
+        _ => format!("${cost:.4} + unknown"),
+    };
+
+    let mut table = Table::new();
+    table.add_row(row!["Model", "Requests", "Prompt", "Completion", "Cost"]);
+    let mut all = usage::Totals::default();
+    for total in totals {
+        table.add_row(row![
+            total.model,
+            r->total.requests,
+            r->total.usage.prompt_tokens,
+            r->total.usage.completion_tokens,
+            r->cost(total.cost, total.unpriced, total.requests)
+        ]);
+        all.requests += total.requests;
+        all.usage.add(total.usage);
+        all.cost += total.cost;
+        all.unpriced += total.unpriced;
+    }
+    if totals.len() > 1 {
+        table.add_row(row![
+            b->"Total",
+            r->all.requests,
+            r->all.usage.prompt_tokens,
+            r->all.usage.completion_tokens,
+            r->cost(all.cost, all.unpriced, all.requests)
+        ]);
+    }
+
+    let title = match days {
+        Some(days) => format!("🔢 Usage over the last {days} day(s):"),
+        None => "🔢 Usage:".to_string(),
+    };
+    println!("{}", title.green().bold());
+    table.printstd();
+    println!(
+        "{}",
+        "Costs are estimates, set model_prices for models without list prices.".dimmed()
+    );
+}
+
+/// Print the commit quality report of `aic stats`
+pub fn print_stats(stats: &CommitStats) {
+    let percent = |rate: f64| format!("{:.1}%", rate * 100.0);
+
+    let mut table = Table::new();
+    table.add_row(row!["Commits", r->stats.commits]);
+    table.add_row(row![
+        "Conventional commits",
+        r->format!("{} ({})", stats.conventional, percent(stats.conventional_rate()))
+    ]);
+    table.add_row(row![
+        "Passing aic lint",
+        r->format!("{} ({})", stats.lint_clean, percent(stats.lint_clean_rate()))
+    ]);
+    table.add_row(row![
+        "Average subject length",
+        r->format!("{:.1}", stats.average_subject_length)
+    ]);
+    println!("{}", "📊 Commit quality:".green().bold());
+    table.printstd();
+
+    if !stats.types.is_empty() {
+        let mut table = Table::new();
+        table.add_row(row!["Type", "Commits", "Share"]);
+        for (commit_type, count) in &stats.types {
+            let share = *count as f64 / stats.conventional as f64;
+            table.add_row(row![commit_type, r->count, r->percent(share)]);
+        }
+        println!("{}", "🏷️  Commit types:".green().bold());
+        table.printstd();
+    }
+
+    if !stats.scopes.is_empty() {
+        let mut table = Table::new();
+        table.add_row(row!["Scope", "Commits"]);
+        for (scope, count) in stats.scopes.iter().take(stats::TOP_SCOPES) {
+            table.add_row(row![scope, r->count]);
+        }
+        println!("{}", "🔥 Most active scopes:".green().bold());
+        table.printstd();
+    }
+}
+
+/// Print the `aic doctor` checklist, one line per check
+pub fn print_checks(checks: &[doctor::Check]) {
+    println!("{}", "🩺 Diagnostics:".blue().bold());
+    let width = checks
+        .iter()
+        .map(|check| check.name.len())
+        .max()
+        .unwrap_or(0);
+    for check in checks {
+        let mark = match check.status {
+            doctor::CheckStatus::Pass => "✓".green(),
+            doctor::CheckStatus::Warn => "!".yellow(),
+            doctor::CheckStatus::Fail => "✗".red(),
+            doctor::CheckStatus::Skip => "-".dimmed(),
+        };
+        let detail = match check.status {
+            doctor::CheckStatus::Skip => check.detail.dimmed(),
+            _ => check.detail.normal(),
+        };
+        println!("  {mark} {:width$}  {detail}", check.name, width = width);
+    }
+}
+
+/// Print the problems found by `aic config validate`
+pub fn print_config_problems(problems: &[Problem]) {
+    if problems.is_empty() {
+        println!("{}", "✓ The configuration looks good.".green().bold());
+        return;
+    }
+
+    let mut table = Table::new();
+    table.add_row(row!["Severity", "Key", "Source", "Problem"]);
+    for problem in problems {
+        let severity = match problem.severity {
+            Severity::Error => problem.severity.to_string().red(),
+            Severity::Warning => problem.severity.to_string().yellow(),
+        };
+        table.add_row(row![severity, problem.key, problem.source, problem.message]);
+    }
+
+    println!("{}", "🩺 Configuration problems:".yellow().bold());
+    table.printstd();
+}
+
+/// Print the model ids an API offers, marking the configured one
+pub fn print_models(models: &[&String], configured: &str) {
+    if models.is_empty() {
+        println!("{}", "ℹ️ No models found.".blue());
+        return;
+    }
+
+    let mut table = Table::new();
+    table.add_row(row!["Model", "Configured"]);
+    for &model in models {
+        let mark = if model == configured { "✓" } else { "" };
+        table.add_row(row![model, mark]);
+    }
+
+    println!(
+        "{}",
+        format!("🤖 Available models ({}):", models.len())
+            .green()
+            .bold()
+    );
+    table.printstd();
+}
+
+/// Print the release level of each change and the version they call for
+pub fn print_bump(
+    changes: &[(String, Option<Level>)],
+    since: &str,
+    current: Version,
+    next: Option<Version>,
+    level: Option<Level>,
+) {
+    println!(
+        "{}",
+        format!("📦 {} change(s) since {since}:", changes.len())
+            .green()
+            .bold()
+    );
+    for (subject, level) in changes {
+        let label = match level {
+            Some(Level::Major) => "major".red().bold(),
+            Some(Level::Minor) => "minor".yellow(),
+            Some(Level::Patch) => "patch".normal(),
+            None => "-".dimmed(),
+        };
+        println!("   {:>5}  {}", label, subject);
+    }
+
+    match (next, level) {
+        (Some(next), Some(level)) => println!(
+            "\n{} {} → {} ({level})",
+            "📈 Next version:".green().bold(),
+            current,
+            next.to_string().bright_white().bold()
+        ),
+        _ => println!(
+            "\n{}",
+            format!("ℹ️ No feature, fix or breaking change, {current} needs no release.").blue()
+        ),
+    }
+}
+
+/// Print the old and new message of every reworded commit
+pub fn print_rewords(rewords: &[(String, String, String)]) {
+    println!("{}", "✏️  Reworded messages:".green().bold());
+    for (label, old, new) in rewords {
+        println!(
+            "\n{} {}",
+            label.yellow(),
+            old.lines().next().unwrap_or_default().dimmed()
+        );
+        for line in new.lines() {
+            println!("   {}", line.bright_white());
+        }
+    }
+}
+
+/// Print public API changes that break compatibility
+pub fn print_api_changes(changes: &[ApiChange]) {
+    println!(
+        "{}",
+        "💥 The diff looks like a breaking change:".yellow().bold()
+    );
+    for change in changes {
+        println!("   {} {}", "✗".red(), change);
+    }
+}
+
+/// Print the words of a message the spell check would correct
+pub fn print_misspellings(misspellings: &[Misspelling]) {
+    println!("{}", "🔤 Possible typos in the message:".yellow().bold());
+    for misspelling in misspellings {
+        println!("   {} {}", "✗".red(), misspelling);
+    }
+}
+
+/// Print the repository states found before generating, blocking ones in red
+pub fn print_preflight_issues(issues: &[PreflightIssue]) {
+    for issue in issues {
+        if issue.is_blocking() {
+            println!("{} {}", "✗".red().bold(), issue.to_string().red());
+        } else {
+            println!("{}", format!("⚠️  {issue}").yellow());
+        }
+    }
+}
+
+/// Widest `+`/`-` bar of a diff stat, longer changes are scaled down
+const DIFF_STAT_BAR_WIDTH: usize = 40;
+
+/// Print a `git diff --stat` style summary of the changes about to be
+/// described, with the estimated size of the diff in tokens
+pub fn print_diff_stat(stats: &[FileStat], tokens: usize) {
+    let path_width = stats
+        .iter()
+        .map(|stat| stat.path.chars().count())
+        .max()
+        .unwrap_or_default();
+    let largest = stats
+        .iter()
+        .map(|stat| stat.insertions + stat.deletions)
+        .max()
+        .unwrap_or_default();
+    let count_width = largest.to_string().len();
+
+    println!();
+    for stat in stats {
+        let changed = stat.insertions + stat.deletions;
+        let line = if stat.binary {
+            "Bin".to_string()
+        } else {
+            let (plus, minus) = diff_stat_bar(stat.insertions, stat.deletions, largest);
+            format!(
+                "{changed:>count_width$} {}{}",
+                "+".repeat(plus).green(),
+                "-".repeat(minus).red()
+            )
+        };
+        println!(" {:<path_width$} | {line}", stat.path);
+    }
+
+    let insertions: usize = stats.iter().map(|stat| stat.insertions).sum();
+    let deletions: usize = stats.iter().map(|stat| stat.deletions).sum();
+    println!(
+        " {} file{} changed, {} {}, {} {}, {}",
+        stats.len(),
+        if stats.len() == 1 { "" } else { "s" },
+        insertions,
+        if insertions == 1 {
+            "insertion(+)"
+        } else {
+            "insertions(+)"
+        },
+        deletions,
+        if deletions == 1 {
+            "deletion(-)"
+        } else {
+            "deletions(-)"
+        },
+        format!("~{tokens} tokens").dimmed()
+    );
+}
+
+/// Lengths of the `+` and `-` parts of a stat bar, scaled like git so the
+/// largest change fits and every change shows at least one mark
+fn diff_stat_bar(insertions: usize, deletions: usize, largest: usize) -> (usize, usize) {
+    if largest <= DIFF_STAT_BAR_WIDTH {
+        return (insertions, deletions);
+    }
+    let scale = |count: usize| match count {
+        0 => 0,
+        count => (count * DIFF_STAT_BAR_WIDTH / largest).max(1),
+    };
+    (scale(insertions), scale(deletions))
+}
+
+/// A byte count the way people read it, e.g. `512 B`, `12.3 KB` or `5.0 MB`
+pub fn format_bytes(bytes: usize) -> String {
+    const KB: f64 = 1024.0;
+    let size = bytes as f64;
+    if size >= KB * KB {
+        format!("{:.1} MB", size / (KB * KB))
+    } else if size >= KB {
+        format!("{:.1} KB", size / KB)
+    } else {
+        format!("{bytes} B")
+    }
+}
+
+/// Color a unified diff like `git diff`: file headers bold, hunk headers cyan,
+/// added lines green and removed lines red
+pub fn colorize_diff(diff: &str) -> String {
+    let mut colored = String::with_capacity(diff.len());
+    for line in diff.lines() {
+        let line = if line.starts_with("diff --git ")
+            || line.starts_with("+++ ")
+            || line.starts_with("--- ")
+        {
+            line.bold().to_string()
+        } else if line.starts_with("@@") {
+            line.cyan().to_string()
+        } else if line.starts_with('+') {
+            line.green().to_string()
+        } else if line.starts_with('-') {
+            line.red().to_string()
+        } else {
+            line.to_string()
+        };
+        colored.push_str(&line);
+        colored.push('\n');
+    }
+    colored
+}
+
+/// Print the messages of a request as the model will read them, one section per role
+pub fn print_prompt(messages: &[(String, String)]) {
+    println!("\n{}", "📨 Prompt to be sent:".green().bold());
+    for (role, content) in messages {
+        println!("\n{}", format!("── {role} ──").yellow().bold());
+        println!("{}", content.trim_end());
+    }
+    println!("\n{}", "── end of prompt ──".yellow().bold());
+}
+
+/// Print the git commands a sandbox run would have executed
+pub fn print_sandbox_commands(commands: &[String]) {
+    println!(
+        "\n{}",
+        "🧪 Sandbox mode, the repository was not changed. These commands would run:"
+            .yellow()
+            .bold()
+    );
+    for command in commands {
+        println!("   {}", command.bright_white());
+    }
+}
+
+/// Mask an API token so only its first characters are shown
+pub fn mask_token(token: &str) -> String {
+    if token.len() > 8 {
+        format!("{}•••••", &token[0..4])
+    } else {
+        "•••••••".to_string()
+    }
+}
+
+/// The active configuration as JSON, with API tokens masked like in the table
+pub fn config_json(
+    config: &Config,
+    global_config_path: &Path,
+    project_config_path: Option<&Path>,
+) -> serde_json::Value {
+    let mut settings = serde_json::to_value(config).unwrap_or_default();
+    // Effective values, like in the table, even when the files leave them unset
+    settings["api_base_url"] = config.get_api_base_url().into();
+    settings["model"] = config.get_model().into();
+    let mask = |settings: &mut serde_json::Value| {
+        for key in ["api_token", "ticket_api_token"] {
+            if let Some(token) = settings.get_mut(key) {
+                *token = mask_token(token.as_str().unwrap_or_default()).into();
+            }
+        }
+    };
+    mask(&mut settings);
+    if let Some(profiles) = settings
+        .get_mut("profiles")
+        .and_then(serde_json::Value::as_object_mut)
+    {
+        profiles.values_mut().for_each(mask);
+    }
+
+    // The environment takes precedence over the config file
+    let token_source = match Config::api_token_from_env() {
+        Some((name, token)) => {
+            settings["api_token"] = mask_token(&token).into();
+            Some(name.to_string())
+        }
+        None => config.api_token.as_ref().map(|_| "config".to_string()),
+    };
+
+    serde_json::json!({
+        "global_config": global_config_path.display().to_string(),
+        "project_config": project_config_path.map(|path| path.display().to_string()),
+        "api_token_source": token_source,
+        "settings": settings,
+    })
+}
+
 /// Print configuration in a formatted table
 pub fn print_config_table(config: &Config) {
     let mut table = Table::new();
     table.add_row(row!["Setting", "Value"]);
 
-    // API Token (with masking for security)
-    let token_display = if let Some(token) = config.get("api_token") {
-        if token.len() > 8 {
-            format!("{}•••••", &token[0..4])
-        } else {
-            "•••••••".to_string()
-        }
+    // API Token (with masking for security), the environment takes precedence
+    let token_display = if let Some((name, token)) = Config::api_token_from_env() {
+        format!("{} (from {name})", mask_token(&token))
+    } else if let Some(token) = config.get("api_token") {
+        mask_token(&token)
     } else {
         "<not set>".to_string()
     };
@@ -34,6 +758,180 @@ pub fn print_config_table(config: &Config) {
     // Other settings
     table.add_row(row!["api_base_url", config.get_api_base_url()]);
     table.add_row(row!["model", config.get_model()]);
+    let profile_names = config.profile_names();
+    if !profile_names.is_empty() {
+        table.add_row(row!["profiles", profile_names.join(", ")]);
+        table.add_row(row![
+            "default_profile",
+            config
+                .get("default_profile")
+                .unwrap_or_else(|| "<none>".to_string())
+        ]);
+    }
+    table.add_row(row![
+        "requests_per_minute",
+        config
+            .get("requests_per_minute")
+            .unwrap_or_else(|| "<unlimited>".to_string())
+    ]);
+    table.add_row(row![
+        "max_generation_seconds",
+        config
+            .get("max_generation_seconds")
+            .unwrap_or_else(|| "<unlimited>".to_string())
+    ]);
+    table.add_row(row![
+        "cost_confirmation_threshold",
+        config
+            .get("cost_confirmation_threshold")
+            .map_or_else(|| "<never ask>".to_string(), |usd| format!("${usd}"))
+    ]);
+    table.add_row(row!["confirm_send", config.get_confirm_send().to_string()]);
+    table.add_row(row![
+        "fallback_model",
+        config
+            .get("fallback_model")
+            .unwrap_or_else(|| "<not set>".to_string())
+    ]);
+    for key in ["temperature", "top_p", "max_tokens"] {
+        table.add_row(row![
+            key,
+            config
+                .get(key)
+                .unwrap_or_else(|| "<API default>".to_string())
+        ]);
+    }
+    table.add_row(row![
+        "request_timeout_secs",
+        config.get_request_timeout().as_secs()
+    ]);
+    let retry_policy = config.get_retry_policy();
+    table.add_row(row!["max_retries", retry_policy.max_retries]);
+    table.add_row(row![
+        "retry_base_delay_ms",
+        retry_policy.base_delay.as_millis()
+    ]);
+    table.add_row(row![
+        "max_rate_limit_wait_secs",
+        retry_policy.max_rate_limit_wait.as_secs()
+    ]);
+    table.add_row(row![
+        "snapshot_patterns",
+        config.get_snapshot_patterns().join(", ")
+    ]);
+    table.add_row(row![
+        "generated_patterns",
+        config.get_generated_patterns().join(", ")
+    ]);
+    table.add_row(row!["secret_scan", config.get_secret_scan().to_string()]);
+    let redaction = config.get_redaction().describe();
+    table.add_row(row![
+        "redact",
+        if redaction.is_empty() {
+            "<nothing>".to_string()
+        } else {
+            redaction
+        }
+    ]);
+    table.add_row(row![
+        "secret_patterns",
+        config
+            .secret_patterns
+            .clone()
+            .unwrap_or_default()
+            .join(", ")
+    ]);
+    table.add_row(row![
+        "exclude_patterns",
+        config.get_exclude_patterns().join(", ")
+    ]);
+    table.add_row(row![
+        "commit_validation",
+        config.get_commit_validation().to_string()
+    ]);
+    table.add_row(row![
+        "max_repair_attempts",
+        config.get_max_repair_attempts()
+    ]);
+    table.add_row(row!["history_context", config.get_history_context()]);
+    table.add_row(row!["symbol_summary", config.get_symbol_summary()]);
+    table.add_row(row!["embedding_model", config.get_embedding_model()]);
+    table.add_row(row!["scopes", config.get_scopes().join(", ")]);
+    table.add_row(row!["commit_types", config.get_commit_types().join(", ")]);
+    let templates = config.get_templates();
+    table.add_row(row![
+        "templates",
+        if templates.is_empty() {
+            "<none>".to_string()
+        } else {
+            templates.keys().cloned().collect::<Vec<_>>().join(", ")
+        }
+    ]);
+    table.add_row(row![
+        "max_header_length",
+        config.get_max_header_length().to_string()
+    ]);
+    table.add_row(row!["structured_output", config.get_structured_output()]);
+    table.add_row(row![
+        "spell_check",
+        config
+            .get_spell_check()
+            .map_or_else(|| "off".to_string(), |language| language.to_string())
+    ]);
+    table.add_row(row!["date_format", config.get_date_format()]);
+    table.add_row(row![
+        "milestone",
+        config.get_milestone().unwrap_or("<not set>")
+    ]);
+    table.add_row(row![
+        "ticket_pattern",
+        config
+            .get("ticket_pattern")
+            .unwrap_or_else(|| ticket::DEFAULT_TICKET_PATTERN.to_string())
+    ]);
+    table.add_row(row!["ticket_footer", config.get_ticket_footer()]);
+    if let Some(tracker) = config.get_ticket_tracker() {
+        table.add_row(row!["ticket_tracker", tracker.to_string()]);
+        table.add_row(row![
+            "ticket_api_base_url",
+            config.get_ticket_api_base_url().unwrap_or("<default>")
+        ]);
+        table.add_row(row![
+            "ticket_api_token",
+            config
+                .get_ticket_api_token()
+                .map_or_else(|| "<not set>".to_string(), |token| mask_token(&token))
+        ]);
+    }
+    table.add_row(row![
+        "protected_branches",
+        config.get_protected_branches().join(", ")
+    ]);
+    table.add_row(row![
+        "large_diff_strategy",
+        config.get_large_diff_strategy().to_string()
+    ]);
+    table.add_row(row!["max_diff_bytes", config.get_max_diff_bytes()]);
+    table.add_row(row![
+        "max_input_tokens",
+        config
+            .get("max_input_tokens")
+            .unwrap_or_else(|| "<model default>".to_string())
+    ]);
+    table.add_row(row![
+        "model_prices",
+        config
+            .get("model_prices")
+            .unwrap_or_else(|| "<list prices>".to_string())
+    ]);
+    table.add_row(row![
+        "diff_priority",
+        FileRole::ALL
+            .iter()
+            .map(|&role| format!("{role}={}", config.get_diff_priority().weight(role)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    ]);
 
     // System prompt (truncated if too long)
     let system_prompt = config.get_system_prompt();
@@ -66,6 +964,29 @@ pub fn print_config_table(config: &Config) {
     };
     table.add_row(row!["user_prompt", display_user_prompt]);
 
+    // Review prompt (truncated if too long)
+    let review_prompt = config.get_review_prompt();
+    let display_review_prompt = if review_prompt.chars().count() > MAX_PROMPT_LENGTH {
+        format!(
+            "{}...",
+            review_prompt
+                .chars()
+                .take(MAX_PROMPT_LENGTH)
+                .collect::<String>()
+        )
+    } else {
+        review_prompt.to_string()
+    };
+    table.add_row(row!["review_prompt", display_review_prompt]);
+    for (key, file) in [
+        ("system_prompt_file", &config.system_prompt_file),
+        ("user_prompt_file", &config.user_prompt_file),
+    ] {
+        if let Some(file) = file {
+            table.add_row(row![key, file]);
+        }
+    }
+
     table.printstd();
 }
 
@@ -93,6 +1014,18 @@ pub fn print_config_sources(global_config_path: &Path, project_config_path: &Opt
     } else {
         println!("   Project config: {}", "None".dimmed());
     }
+
+    let overrides: Vec<String> = config::env_overrides()
+        .into_iter()
+        .map(|(var, _, _)| var)
+        .collect();
+    if !overrides.is_empty() {
+        println!("   Environment: {}", overrides.join(", ").bright_blue());
+        println!(
+            "   {} Environment variables override both config files",
+            "ℹ️".blue()
+        );
+    }
     println!();
 
     println!("{}", "⚙️  Settings:".blue());
@@ -102,7 +1035,6 @@ pub fn print_config_sources(global_config_path: &Path, project_config_path: &Opt
 mod tests {
     use super::*;
     use crate::config::Config;
-    use std::env;
     use tempfile::TempDir;
 
     // Helper function to create a test environment
@@ -112,8 +1044,8 @@ mod tests {
         let config_dir = temp_dir.path().join(".config").join("aic");
         std::fs::create_dir_all(&config_dir).expect("Failed to create config directory");
 
-        // Set the HOME environment variable to the temporary directory
-        env::set_var("HOME", temp_dir.path());
+        // Keep the global config in the temporary directory
+        config::use_test_config_base(&temp_dir.path().join(".config"));
 
         // Create a new config instance
         let config = Config::default();
@@ -126,6 +1058,129 @@ mod tests {
         print_header();
     }
 
+    #[test]
+    fn test_print_progress() {
+        print_progress("Summarizing", 0, 3);
+        print_progress("Summarizing", 2, 3);
+        print_progress("Summarizing", 3, 3);
+        // Degenerate total must not divide by zero
+        print_progress("Summarizing", 0, 0);
+    }
+
+    #[test]
+    fn test_print_batch_report() {
+        use crate::scheduler::ItemOutcome;
+
+        let report = BatchReport {
+            outcomes: vec![
+                ItemOutcome {
+                    label: "src/main.rs".to_string(),
+                    result: Ok(()),
+                },
+                ItemOutcome {
+                    label: "src/cli.rs".to_string(),
+                    result: Err(anyhow::anyhow!("API request failed (500)")),
+                },
+            ],
+        };
+        print_batch_report(&report);
+        print_batch_report::<()>(&BatchReport { outcomes: vec![] });
+    }
+
+    #[test]
+    fn test_print_diff_stat() {
+        let stat = |path: &str, insertions: usize, deletions: usize, binary: bool| FileStat {
+            path: path.to_string(),
+            insertions,
+            deletions,
+            binary,
+        };
+        print_diff_stat(
+            &[
+                stat("src/main.rs", 12, 4, false),
+                stat("assets/logo.png", 0, 0, true),
+            ],
+            1234,
+        );
+        print_diff_stat(&[], 0);
+
+        assert_eq!(diff_stat_bar(12, 4, 16), (12, 4));
+        assert_eq!(diff_stat_bar(300, 100, 400), (30, 10));
+        assert_eq!(diff_stat_bar(1, 399, 400), (1, 39));
+    }
+
+    #[test]
+    fn test_print_preflight_issues() {
+        print_preflight_issues(&[
+            PreflightIssue::DetachedHead,
+            PreflightIssue::MissingIdentity(vec!["user.name", "user.email"]),
+        ]);
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(5 * 1024 * 1024), "5.0 MB");
+    }
+
+    #[test]
+    fn test_colorize_diff_keeps_every_line() {
+        let diff = concat!(
+            "diff --git a/src/main.rs b/src/main.rs\n",
+            "--- a/src/main.rs\n",
+            "+++ b/src/main.rs\n",
+            "@@ -1,2 +1,2 @@\n",
+            " fn main() {\n",
+            "-    old();\n",
+            "+    new();\n",
+        );
+        let colored = colorize_diff(diff);
+
+        assert_eq!(colored.lines().count(), diff.lines().count());
+        for (colored, line) in colored.lines().zip(diff.lines()) {
+            assert!(colored.contains(line), "{line:?} lost in {colored:?}");
+        }
+    }
+
+    #[test]
+    fn test_print_candidates() {
+        // Verify multi-line and empty candidates print without panicking
+        print_candidates(&[
+            "feat: add feature".to_string(),
+            "fix(cli): handle flag\n\n1. Parse value\n2. Validate range".to_string(),
+            String::new(),
+        ]);
+    }
+
+    #[test]
+    fn test_print_capabilities() {
+        print_capabilities(&ModelCapabilities::default());
+        print_capabilities(&ModelCapabilities {
+            system_role: false,
+            json_mode: false,
+            json_schema: false,
+            streaming: false,
+            sampling: false,
+            max_completion_tokens: true,
+        });
+    }
+
+    #[test]
+    fn test_print_change_groups() {
+        print_change_groups(&[
+            ChangeGroup {
+                name: "src".to_string(),
+                files: vec!["src/main.rs".to_string(), "src/cli.rs".to_string()],
+            },
+            ChangeGroup {
+                name: "docs".to_string(),
+                files: vec!["docs/guide.md".to_string()],
+            },
+        ]);
+    }
+
     #[test]
     fn test_token_masking() {
         let (_temp_dir, mut config) = setup_test_env();
@@ -145,6 +1200,39 @@ mod tests {
         print_config_table(&config);
     }
 
+    #[test]
+    fn test_config_json() {
+        let (_temp_dir, mut config) = setup_test_env();
+        config
+            .set("api_token", Some("abcd1234567890".to_string()))
+            .unwrap();
+        config
+            .set(
+                "profiles.work.api_token",
+                Some("wxyz1234567890".to_string()),
+            )
+            .unwrap();
+
+        let json = config_json(&config, Path::new("/home/me/.config/aic/config.toml"), None);
+        assert_eq!(json["settings"]["api_token"], "abcd•••••");
+        assert_eq!(
+            json["settings"]["profiles"]["work"]["api_token"],
+            "wxyz•••••"
+        );
+        assert_eq!(json["project_config"], serde_json::Value::Null);
+        assert!(!json.to_string().contains("1234567890"));
+    }
+
+    #[test]
+    fn test_output_format() {
+        assert_eq!("json".parse::<OutputFormat>().unwrap(), OutputFormat::Json);
+        assert_eq!(
+            " Text ".parse::<OutputFormat>().unwrap(),
+            OutputFormat::Text
+        );
+        assert!("yaml".parse::<OutputFormat>().is_err());
+    }
+
     #[test]
     fn test_english_prompts() {
         let (_temp_dir, mut config) = setup_test_env();
@@ -220,4 +1308,24 @@ mod tests {
 
         print_config_table(&config);
     }
+
+    #[test]
+    fn test_spinner_style() {
+        // Panics when the template doesn't parse
+        spinner_style();
+
+        assert_eq!(retry_note(None), "");
+
+        // The countdown to a retry is shown in whole seconds
+        assert_eq!(
+            retry_note(Some(Duration::from_millis(8200))),
+            " ⏳ retrying in 9s"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_with_spinner_returns_output() {
+        // Tests don't run on a terminal, so this is the plain await
+        assert_eq!(with_spinner("Waiting", async { 42 }).await, 42);
+    }
 }
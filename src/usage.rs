@@ -0,0 +1,177 @@
+use crate::history;
+use crate::llm::Usage;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const USAGE_FILENAME: &str = "usage.jsonl";
+
+/// Tokens used by one command, one line of `usage.jsonl`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+
+    pub model: String,
+
+    #[serde(flatten)]
+    pub usage: Usage,
+
+    /// Estimated cost in USD, `None` when the model's prices aren't known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f64>,
+}
+
+impl Record {
+    pub fn new(model: &str, usage: Usage, cost: Option<f64>) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+
+        Self {
+            timestamp,
+            model: model.to_string(),
+            usage,
+            cost,
+        }
+    }
+}
+
+/// Usage of one model added up
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Totals {
+    pub model: String,
+    pub requests: usize,
+
+    #[serde(flatten)]
+    pub usage: Usage,
+
+    /// Cost of the records with known prices
+    pub cost: f64,
+
+    /// Records whose cost isn't known and so isn't part of `cost`
+    pub unpriced: usize,
+}
+
+pub fn usage_path() -> Result<PathBuf> {
+    Ok(history::data_dir()?.join(USAGE_FILENAME))
+}
+
+/// All records, oldest first, skipping lines that can't be parsed
+pub fn load_from(path: &Path) -> Result<Vec<Record>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let contents = fs::read_to_string(path).context("Could not read the usage log")?;
+    Ok(contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Append a record to the usage log
+pub fn append_to(path: &Path, record: &Record) -> Result<()> {
+    let line = serde_json::to_string(record).context("Failed to serialize usage record")?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .context("Could not open the usage log")?;
+    writeln!(file, "{line}").context("Failed to write to the usage log")
+}
+
+/// Totals per model of the records since `since` (seconds since the Unix
+/// epoch), the most expensive first
+pub fn summarize(records: &[Record], since: u64) -> Vec<Totals> {
+    let mut totals: Vec<Totals> = Vec::new();
+    for record in records.iter().filter(|record| record.timestamp >= since) {
+        let index = match totals.iter().position(|total| total.model == record.model) {
+            Some(index) => index,
+            None => {
+                totals.push(Totals {
+                    model: record.model.clone(),
+                    ..Totals::default()
+                });
+                totals.len() - 1
+            }
+        };
+        let total = &mut totals[index];
+        total.requests += 1;
+        total.usage.add(record.usage);
+        match record.cost {
+            Some(cost) => total.cost += cost,
+            None => total.unpriced += 1,
+        }
+    }
+
+    totals.sort_by(|a, b| {
+        b.cost
+            .total_cmp(&a.cost)
+            .then(b.usage.total_tokens.cmp(&a.usage.total_tokens))
+    });
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn record(timestamp: u64, model: &str, tokens: usize, cost: Option<f64>) -> Record {
+        Record {
+            timestamp,
+            model: model.to_string(),
+            usage: Usage {
+                prompt_tokens: tokens,
+                completion_tokens: 10,
+                total_tokens: tokens + 10,
+            },
+            cost,
+        }
+    }
+
+    #[test]
+    fn test_append_and_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join(USAGE_FILENAME);
+        assert!(load_from(&path).unwrap().is_empty());
+
+        let first = record(100, "gpt-4o", 1000, Some(0.0026));
+        let second = record(200, "local-model", 500, None);
+        append_to(&path, &first).unwrap();
+        append_to(&path, &second).unwrap();
+        fs::write(
+            &path,
+            format!("{}not json\n", fs::read_to_string(&path).unwrap()),
+        )
+        .unwrap();
+        assert_eq!(load_from(&path).unwrap(), vec![first, second]);
+    }
+
+    #[test]
+    fn test_summarize() {
+        let records = vec![
+            record(100, "gpt-4o-mini", 1000, Some(0.001)),
+            record(200, "gpt-4o", 1000, Some(0.003)),
+            record(300, "gpt-4o-mini", 2000, Some(0.002)),
+            record(400, "local-model", 9000, None),
+        ];
+
+        let totals = summarize(&records, 0);
+        let models: Vec<&str> = totals.iter().map(|total| total.model.as_str()).collect();
+        assert_eq!(models, vec!["gpt-4o-mini", "gpt-4o", "local-model"]);
+        assert_eq!(totals[0].requests, 2);
+        assert_eq!(totals[0].usage.prompt_tokens, 3000);
+        assert!((totals[0].cost - 0.003).abs() < 1e-9);
+        assert_eq!(totals[2].unpriced, 1);
+
+        // Older records are left out
+        let totals = summarize(&records, 250);
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals[0].usage.prompt_tokens, 2000);
+    }
+}
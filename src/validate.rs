@@ -0,0 +1,402 @@
+use crate::config::{self, Config};
+use crate::lint;
+use crate::redact;
+use crate::template;
+use reqwest::{StatusCode, Url};
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// How serious a configuration problem is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Works, but probably not as intended
+    Warning,
+
+    /// Keeps aic from generating messages
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found in the configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Problem {
+    pub severity: Severity,
+
+    /// Key the problem is about, empty for the whole file
+    pub key: String,
+
+    /// Config file the problem was found in, `merged` for the effective settings
+    pub source: String,
+
+    pub message: String,
+}
+
+impl Problem {
+    pub fn new(severity: Severity, key: &str, source: &str, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            key: key.to_string(),
+            source: source.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Source label of the checks on the merged settings
+pub const MERGED: &str = "merged";
+
+/// Check that a config file is valid TOML without unknown keys
+pub fn check_file(path: &Path) -> Vec<Problem> {
+    let source = path.display().to_string();
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) => {
+            return vec![Problem::new(
+                Severity::Error,
+                "",
+                &source,
+                format!("Could not read the file: {err}"),
+            )]
+        }
+    };
+
+    let table = match contents.parse::<toml::Table>() {
+        Ok(table) => table,
+        Err(err) => {
+            return vec![Problem::new(
+                Severity::Error,
+                "",
+                &source,
+                format!("Invalid TOML: {}", err.message()),
+            )]
+        }
+    };
+
+    unknown_keys(&table)
+        .into_iter()
+        .map(|key| {
+            Problem::new(
+                Severity::Warning,
+                &key,
+                &source,
+                "Unknown key, it is ignored",
+            )
+        })
+        .collect()
+}
+
+/// Keys of a config table that aic doesn't know, profile keys as `profiles.<name>.<key>`
+/// and redaction rules as `redact.<key>`
+pub fn unknown_keys(table: &toml::Table) -> Vec<String> {
+    let mut unknown = Vec::new();
+    for (key, value) in table {
+        if !config::KEYS.contains(&key.as_str()) {
+            unknown.push(key.clone());
+            continue;
+        }
+
+        if let Some(rules) = value.as_table().filter(|_| key == "redact") {
+            unknown.extend(
+                rules
+                    .keys()
+                    .filter(|key| !redact::KEYS.contains(&key.as_str()))
+                    .map(|key| format!("redact.{key}")),
+            );
+            continue;
+        }
+
+        let Some(profiles) = value.as_table().filter(|_| key == "profiles") else {
+            continue;
+        };
+        for (name, profile) in profiles {
+            let Some(profile) = profile.as_table() else {
+                continue;
+            };
+            unknown.extend(
+                profile
+                    .keys()
+                    .filter(|key| !config::PROFILE_KEYS.contains(&key.as_str()))
+                    .map(|key| format!("profiles.{name}.{key}")),
+            );
+        }
+    }
+    unknown
+}
+
+/// Check the merged settings without contacting the API
+pub fn check_config(config: &Config) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    if let Err(message) = check_base_url(config.get_api_base_url()) {
+        problems.push(Problem::new(
+            Severity::Error,
+            "api_base_url",
+            MERGED,
+            message,
+        ));
+    } else if config
+        .get_api_base_url()
+        .trim_end_matches('/')
+        .ends_with("/chat/completions")
+    {
+        problems.push(Problem::new(
+            Severity::Warning,
+            "api_base_url",
+            MERGED,
+            "Should end before /chat/completions, which aic appends",
+        ));
+    }
+
+    let user_prompt = config.get_user_prompt();
+    let has_diff = user_prompt.contains("{}")
+        || template::variables(user_prompt)
+            .iter()
+            .any(|name| name == "diff");
+    if !has_diff {
+        problems.push(Problem::new(
+            Severity::Error,
+            "user_prompt",
+            MERGED,
+            "Has no {} or {{diff}} placeholder, so the diff is never sent",
+        ));
+    }
+
+    if let Err(err) = config.get_ticket_pattern() {
+        problems.push(Problem::new(
+            Severity::Error,
+            "ticket_pattern",
+            MERGED,
+            format!("{err:#}"),
+        ));
+    }
+
+    for trailer in config.get_trailers() {
+        if lint::parse_trailer(&trailer).is_none() {
+            problems.push(Problem::new(
+                Severity::Error,
+                "trailers",
+                MERGED,
+                format!("'{trailer}' is not a 'Key: value' trailer"),
+            ));
+        }
+    }
+
+    let commit_types = config.get_commit_types();
+    for (commit_type, template) in config.get_templates() {
+        let key = format!("templates.{commit_type}");
+        if !commit_types.contains(&commit_type) {
+            problems.push(Problem::new(
+                Severity::Warning,
+                &key,
+                MERGED,
+                format!(
+                    "'{commit_type}' is not one of the commit types, the template is never used"
+                ),
+            ));
+        }
+        if lint::template_sections(&template).is_empty() {
+            problems.push(Problem::new(
+                Severity::Warning,
+                &key,
+                MERGED,
+                "Has no 'Label:' sections, so generated bodies can't be checked against it",
+            ));
+        }
+    }
+
+    if config.get_api_token().is_err() {
+        problems.push(Problem::new(
+            Severity::Error,
+            "api_token",
+            MERGED,
+            "Not set, and neither AIC_API_TOKEN nor OPENAI_API_KEY is",
+        ));
+    }
+
+    problems
+}
+
+/// Check that the API answers at the base URL and accepts the token, by
+/// listing the models
+pub async fn probe_api(config: &Config) -> Vec<Problem> {
+    let (Ok(api_token), Ok(())) = (
+        config.get_api_token(),
+        check_base_url(config.get_api_base_url()),
+    ) else {
+        // Already reported by `check_config`
+        return Vec::new();
+    };
+
+    let endpoint = format!("{}/models", config.get_api_base_url().trim_end_matches('/'));
+    let response = match reqwest::Client::builder()
+        .timeout(config.get_request_timeout())
+        .build()
+    {
+        Ok(client) => client.get(&endpoint).bearer_auth(api_token).send().await,
+        Err(err) => Err(err),
+    };
+
+    match response {
+        Err(err) => vec![Problem::new(
+            Severity::Error,
+            "api_base_url",
+            MERGED,
+            format!("Could not reach the API: {err}"),
+        )],
+        Ok(response)
+            if matches!(
+                response.status(),
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN
+            ) =>
+        {
+            vec![Problem::new(
+                Severity::Error,
+                "api_token",
+                MERGED,
+                format!("Rejected by the API ({})", response.status()),
+            )]
+        }
+        Ok(response) if !response.status().is_success() => vec![Problem::new(
+            Severity::Warning,
+            "api_token",
+            MERGED,
+            format!(
+                "Could not be checked, {endpoint} answered {}",
+                response.status()
+            ),
+        )],
+        Ok(_) => Vec::new(),
+    }
+}
+
+/// An http(s) URL with a host
+fn check_base_url(url: &str) -> Result<(), String> {
+    let parsed = Url::parse(url).map_err(|err| format!("Not a valid URL ({err}): {url}"))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("Must be an http or https URL: {url}"));
+    }
+    if parsed.host_str().is_none_or(str::is_empty) {
+        return Err(format!("Has no host: {url}"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn config_from(contents: &str) -> Config {
+        toml::from_str(contents).unwrap()
+    }
+
+    #[test]
+    fn test_check_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.toml");
+
+        fs::write(
+            &path,
+            "model = \"gpt-4o\"\nmodle = \"typo\"\n\n[redact]\npaths = [\"secrets/**\"]\nemail = true\n\n[profiles.work]\nmodel = \"x\"\ntoken = \"y\"\n",
+        )
+        .unwrap();
+        let keys: Vec<String> = check_file(&path).into_iter().map(|p| p.key).collect();
+        assert_eq!(keys, vec!["modle", "profiles.work.token", "redact.email"]);
+
+        fs::write(&path, "model = \"unterminated\n").unwrap();
+        let problems = check_file(&path);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].severity, Severity::Error);
+        assert!(problems[0].message.starts_with("Invalid TOML"));
+    }
+
+    #[test]
+    fn test_check_config() {
+        let config = config_from(
+            "api_token = \"token\"\n\
+             api_base_url = \"https://api.example.com/v1\"\n\
+             user_prompt = \"Describe {{ diff }}\"\n",
+        );
+        assert!(check_config(&config).is_empty());
+
+        let config = config_from(
+            "api_token = \"token\"\n\
+             api_base_url = \"api.example.com\"\n\
+             user_prompt = \"Describe the change\"\n\
+             ticket_pattern = \"([A-Z]+\"\n\
+             trailers = [\"AI-assisted-by: aic\", \"Signed off\"]\n\n\
+             [templates]\n\
+             fix = \"Root cause:\"\n\
+             chore = \"Keep it short\"\n\
+             wip = \"Next steps:\"\n",
+        );
+        let keys: Vec<String> = check_config(&config).into_iter().map(|p| p.key).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "api_base_url",
+                "user_prompt",
+                "ticket_pattern",
+                "trailers",
+                "templates.chore",
+                "templates.wip"
+            ]
+        );
+
+        let config = config_from(
+            "api_token = \"token\"\n\
+             api_base_url = \"https://api.example.com/v1/chat/completions\"\n",
+        );
+        let problems = check_config(&config);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].severity, Severity::Warning);
+    }
+
+    #[tokio::test]
+    async fn test_probe_api() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/models"))
+            .and(header("Authorization", "Bearer good"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("{\"data\": []}"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/models"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&mock_server)
+            .await;
+
+        let config = config_from(&format!(
+            "api_token = \"good\"\napi_base_url = \"{}\"\n",
+            mock_server.uri()
+        ));
+        assert!(probe_api(&config).await.is_empty());
+
+        let config = config_from(&format!(
+            "api_token = \"bad\"\napi_base_url = \"{}\"\n",
+            mock_server.uri()
+        ));
+        let problems = probe_api(&config).await;
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].key, "api_token");
+        assert_eq!(problems[0].severity, Severity::Error);
+
+        let config = config_from("api_token = \"good\"\napi_base_url = \"http://127.0.0.1:9\"\n");
+        let problems = probe_api(&config).await;
+        assert_eq!(problems[0].key, "api_base_url");
+    }
+}